@@ -0,0 +1,101 @@
+//! Benchmarks for the code that runs on every level or every turn. The
+//! web build is a lot slower than the desktop, so keep an eye on these:
+//!
+//!     cargo bench
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use quicksilver::geom::Vector;
+use rand::{rngs::StdRng, SeedableRng};
+
+use quicksilver_roguelike::{
+    fov::Fov,
+    map::{Map, FLOOR},
+    mapgen::{generate_level, MapType},
+    pathfinding,
+    world::{Entity, World},
+};
+
+/// The size of a typical big roguelike level and a huge one.
+const SIZES: [(i32, i32); 2] = [(80, 50), (200, 200)];
+
+fn floor_tiles(map: &Map) -> Vec<Vector> {
+    map.tiles
+        .iter()
+        .filter(|tile| tile.glyph == FLOOR)
+        .map(|tile| tile.pos)
+        .collect()
+}
+
+/// A world on a generated map with the player on its first floor tile.
+fn world(size: Vector) -> World {
+    let mut rng = StdRng::seed_from_u64(1);
+    let map = generate_level(size, &mut rng);
+    let player = Entity {
+        pos: floor_tiles(&map)[0],
+        hp: 5,
+        max_hp: 5,
+        blocks_tile: true,
+        ..Default::default()
+    };
+    World::from_parts(1, rng, map, vec![], player)
+}
+
+fn map_generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("map generation");
+    for &(width, height) in &SIZES {
+        let size = Vector::new(width, height);
+        for &map_type in &MapType::ALL {
+            let id = BenchmarkId::new(format!("{:?}", map_type), format!("{}x{}", width, height));
+            group.bench_function(id, |b| {
+                let mut rng = StdRng::seed_from_u64(0);
+                b.iter(|| map_type.builder().build(size, &mut rng))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn fov(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fov");
+    for &(width, height) in &SIZES {
+        let world = world(Vector::new(width, height));
+        let id = BenchmarkId::from_parameter(format!("{}x{}", width, height));
+        group.bench_function(id, |b| b.iter(|| Fov::update(&world)));
+    }
+    group.finish();
+}
+
+/// Paths to the furthest tile the search still gets to and, where the
+/// level is big enough, to the furthest one it gives up on. The search
+/// stops once paths cost more than `MAX_COST` in `pathfinding`, so the
+/// second case is as slow as it gets.
+fn pathfinding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pathfinding");
+    for &(width, height) in &SIZES {
+        let world = world(Vector::new(width, height));
+        let start = world.player().pos;
+        let distance = |pos: &Vector| ((pos.x - start.x).abs() + (pos.y - start.y).abs()) as i32;
+        let (found, given_up): (Vec<Vector>, Vec<Vector>) = floor_tiles(&world.map)
+            .into_iter()
+            .filter(|&pos| pos != start)
+            .partition(|&pos| pathfinding::next_step(&world, world.player_id, pos).is_some());
+        let goals = [
+            ("found", found.into_iter().max_by_key(distance)),
+            ("given up", given_up.into_iter().max_by_key(distance)),
+        ];
+        for &(name, goal) in &goals {
+            let goal = match goal {
+                Some(goal) => goal,
+                None => continue,
+            };
+            let id = BenchmarkId::new(name, format!("{}x{}", width, height));
+            group.bench_function(id, |b| {
+                b.iter(|| pathfinding::next_step(&world, world.player_id, goal))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, map_generation, fov, pathfinding);
+criterion_main!(benches);
@@ -0,0 +1,535 @@
+//! What the monsters do on their turns.
+//!
+//! Every monster picks a `Mode` based on its template's `Behavior`, its
+//! health and whatever's happened to it, then acts on it. Monsters go
+//! after the closest entity they're hostile to, see `world::is_hostile`.
+//! Monsters in a pack all go after the same one. When they lose sight of
+//! it they search where they saw it last for a while. Bosses change
+//! tactics as they get hurt, see `Phase`. Sleeping monsters do nothing
+//! until a noise wakes them, see `noise`.
+
+use quicksilver::geom::Vector;
+use rand::Rng;
+
+use crate::{
+    events, locale, noise, pathfinding, spawn,
+    templates::Behavior,
+    world::{capitalize, is_hostile, move_or_attack, with_article, World},
+};
+
+/// Monsters flee when their health drops below this fraction.
+pub const FLEE_HEALTH: f32 = 0.25;
+
+/// Bosses start calling for help when their health drops below this
+/// fraction.
+pub const SUMMON_HEALTH: f32 = 0.6;
+
+/// Bosses fly into a rage when their health drops below this fraction.
+pub const ENRAGE_HEALTH: f32 = 0.25;
+
+/// A boss calls for help every this many turns.
+pub const SUMMON_EVERY: u32 = 4;
+
+/// How many turns a monster looks around where it last saw its target
+/// before giving up.
+pub const SEARCH_TURNS: u32 = 5;
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// What a monster is doing this turn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Standing still: asleep, or a guard that hasn't noticed the
+    /// player yet.
+    Idle,
+    Chase,
+    Flee,
+    /// Stumbling around at random, either confused or looking for the
+    /// player.
+    Wander,
+    /// Going to where the target was last seen and looking around there.
+    Search,
+}
+
+/// How a `Boss` fights. Each phase starts once its health drops low
+/// enough and it never goes back, even if it heals.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub enum Phase {
+    #[default]
+    Melee,
+    /// Calls in its `summons` every `SUMMON_EVERY` turns.
+    Summon,
+    /// Twice as fast, see `schedule::speed`.
+    Enraged,
+}
+
+impl Phase {
+    /// The phase a boss with this much health should be in.
+    pub fn of(hp: i32, max_hp: i32) -> Phase {
+        let health = hp as f32 / max_hp as f32;
+        if health < ENRAGE_HEALTH {
+            Phase::Enraged
+        } else if health < SUMMON_HEALTH {
+            Phase::Summon
+        } else {
+            Phase::Melee
+        }
+    }
+}
+
+/// Let the monster `id` act once. `schedule::run` decides when.
+pub fn act(world: &mut World, id: usize) {
+    update_phase(world, id);
+    take_turn(world, id);
+    events::process(world);
+}
+
+/// Move a boss on to the next phase once it's hurt badly enough.
+fn update_phase(world: &mut World, id: usize) {
+    let entity = &mut world.entities[id];
+    let phase = Phase::of(entity.hp, entity.max_hp);
+    match &mut entity.ai {
+        Some(ai) if ai.behavior == Behavior::Boss && phase > ai.phase => ai.phase = phase,
+        _ => return,
+    }
+    if !world.sees_entity(id) {
+        return;
+    }
+    let name = world.entities[id].name.clone();
+    let key = match phase {
+        Phase::Melee => return,
+        Phase::Summon => "log.boss-calls-for-help",
+        Phase::Enraged => "log.boss-enraged",
+    };
+    world.log(locale::message(key, &[("name", &name)]));
+}
+
+/// A boss in its `Summon` phase calls its `summons` in next to itself
+/// every `SUMMON_EVERY` turns. Return `true` if it spent its turn on
+/// that.
+fn summon(world: &mut World, id: usize) -> bool {
+    let summons = match &mut world.entities[id].ai {
+        Some(ai) if ai.phase == Phase::Summon => {
+            if ai.summon_cooldown > 0 {
+                ai.summon_cooldown -= 1;
+                return false;
+            }
+            ai.summons.clone()
+        }
+        _ => return false,
+    };
+    let (name, template) =
+        match summons.and_then(|name| world.templates.monsters.get_key_value(&name)) {
+            Some(found) => found,
+            None => return false,
+        };
+    let from = world.entities[id].pos;
+    let pos = match DIRECTIONS
+        .iter()
+        .map(|&(x, y)| from + Vector::new(x, y))
+        .find(|&to| is_free(world, id, to))
+    {
+        Some(pos) => pos,
+        None => return false,
+    };
+    let mut monster = spawn::monster(name, template, pos);
+    if let Some(ai) = &mut monster.ai {
+        ai.alerted = true;
+    }
+    let name = monster.name.clone();
+    world.entities.push(monster);
+    world.rebuild_spatial();
+    if let Some(ai) = &mut world.entities[id].ai {
+        ai.summon_cooldown = SUMMON_EVERY;
+    }
+    if world.is_visible(pos) {
+        world.log(locale::message(
+            "log.summoned",
+            &[("name", &capitalize(&with_article(&name)))],
+        ));
+    }
+    true
+}
+
+/// Who the monster `id` is after. Alone it's the closest living entity
+/// it can see and wants to attack. In a pack it's the target of the
+/// first member that has one, so the pack closes in together.
+pub fn target(world: &World, id: usize) -> Option<usize> {
+    let pack = world.entities[id].ai.as_ref().and_then(|ai| ai.pack);
+    if pack.is_none() {
+        return own_target(world, id);
+    }
+    (0..world.entities.len())
+        .filter(|&member| world.entities[member].is_alive())
+        .filter(|&member| {
+            world.entities[member]
+                .ai
+                .as_ref()
+                .is_some_and(|ai| ai.pack == pack)
+        })
+        .find_map(|member| own_target(world, member))
+}
+
+/// The closest living entity the monster `id` can see and wants to
+/// attack. Sleeping monsters don't see anything.
+fn own_target(world: &World, id: usize) -> Option<usize> {
+    if noise::is_asleep(world, id) {
+        return None;
+    }
+    let pos = world.entities[id].pos;
+    (0..world.entities.len())
+        .filter(|&other| other != id && world.entities[other].is_alive())
+        .filter(|&other| is_hostile(world, id, other))
+        .filter(|&other| world.can_see(pos, world.entities[other].pos))
+        .min_by_key(|&other| distance(pos, world.entities[other].pos))
+}
+
+/// Decide what the monster `id` wants to do, updating its state (being
+/// alerted, confusion wearing off) along the way.
+pub fn mode(world: &mut World, id: usize) -> Mode {
+    let target_pos = target(world, id).map(|target| world.entities[target].pos);
+    let sees_target = target_pos.is_some();
+    let entity = &mut world.entities[id];
+    let ai = match &mut entity.ai {
+        Some(ai) => ai,
+        None => return Mode::Idle,
+    };
+    if ai.asleep {
+        return Mode::Idle;
+    }
+    if ai.confused > 0 {
+        ai.confused -= 1;
+        return Mode::Wander;
+    }
+    if sees_target {
+        ai.alerted = true;
+        ai.last_seen = target_pos;
+        ai.search_turns = SEARCH_TURNS;
+    }
+    let badly_hurt = (entity.hp as f32) < entity.max_hp as f32 * FLEE_HEALTH;
+    if ai.flees && badly_hurt {
+        Mode::Flee
+    } else if matches!(ai.behavior, Behavior::Guard | Behavior::Boss) && !ai.alerted {
+        Mode::Idle
+    } else if !sees_target && ai.last_seen.is_some() {
+        Mode::Search
+    } else if !sees_target {
+        Mode::Wander
+    } else {
+        Mode::Chase
+    }
+}
+
+fn take_turn(world: &mut World, id: usize) {
+    let pos = world.entities[id].pos;
+    let target_pos = target(world, id).map(|target| world.entities[target].pos);
+    let mode = mode(world, id);
+    if mode == Mode::Chase && target_pos.is_some() && summon(world, id) {
+        return;
+    }
+    match (mode, target_pos) {
+        (Mode::Idle, _) => {}
+        (Mode::Chase, Some(target_pos)) if distance(pos, target_pos) == 1 => {
+            move_or_attack(world, id, target_pos - pos);
+        }
+        (Mode::Chase, Some(target_pos)) => {
+            let step = pathfinding::next_step(world, id, target_pos);
+            if let Some(step) = step.filter(|&step| is_free(world, id, step)) {
+                world.move_entity(id, step);
+            }
+        }
+        (Mode::Flee, Some(target_pos)) => {
+            if let Some(step) = best_step(world, id, |to| distance(to, target_pos)) {
+                world.move_entity(id, step);
+            }
+        }
+        (Mode::Chase, None) | (Mode::Flee, None) => {}
+        (Mode::Search, _) => search(world, id),
+        (Mode::Wander, _) => {
+            // Confused monsters hit whoever they stumble into, friend or
+            // foe
+            let (x, y) = DIRECTIONS[world.rng.gen_range(0, DIRECTIONS.len())];
+            move_or_attack(world, id, Vector::new(x, y));
+        }
+    }
+}
+
+/// Walk to where the target was last seen, then look around there for
+/// `SEARCH_TURNS` before forgetting about them. Looking around means
+/// milling about on and right next to that tile.
+fn search(world: &mut World, id: usize) {
+    let pos = world.entities[id].pos;
+    let last_seen = match world.entities[id].ai.as_ref().and_then(|ai| ai.last_seen) {
+        Some(last_seen) => last_seen,
+        None => return,
+    };
+    if distance(pos, last_seen) > 1 {
+        let step = pathfinding::next_step(world, id, last_seen);
+        if let Some(step) = step.filter(|&step| is_free(world, id, step)) {
+            return world.move_entity(id, step);
+        }
+    }
+    // There, or as close as it can get
+    if let Some(ai) = &mut world.entities[id].ai {
+        ai.search_turns = ai.search_turns.saturating_sub(1);
+        if ai.search_turns == 0 {
+            ai.last_seen = None;
+        }
+    }
+    let (x, y) = DIRECTIONS[world.rng.gen_range(0, DIRECTIONS.len())];
+    let to = pos + Vector::new(x, y);
+    if distance(to, last_seen) <= 1 && is_free(world, id, to) {
+        world.move_entity(id, to);
+    }
+}
+
+/// The free neighbour of the entity's tile that scores higher than
+/// staying put, if there is one.
+fn best_step(world: &World, id: usize, score: impl Fn(Vector) -> i32) -> Option<Vector> {
+    let from = world.entities[id].pos;
+    let mut best = None;
+    let mut best_score = score(from);
+    for &(x, y) in &DIRECTIONS {
+        let to = from + Vector::new(x, y);
+        if is_free(world, id, to) && score(to) > best_score {
+            best = Some(to);
+            best_score = score(to);
+        }
+    }
+    best
+}
+
+fn is_free(world: &World, id: usize, pos: Vector) -> bool {
+    world.is_passable(id, pos) && world.blocker_at(pos).is_none()
+}
+
+/// The number of orthogonal steps between two tiles.
+fn distance(from: Vector, to: Vector) -> i32 {
+    let offset = to - from;
+    (offset.x.abs() + offset.y.abs()) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{generate_map, WALL},
+        templates::Faction,
+        world::{Action, Ai, Entity},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn monster(x: i32, y: i32, behavior: Behavior) -> Entity {
+        Entity {
+            pos: Vector::new(x, y),
+            name: "orc".to_string(),
+            hp: 4,
+            max_hp: 4,
+            blocks_tile: true,
+            ai: Some(Ai {
+                behavior,
+                flees: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// The player at (5, 5) with the given monster in an empty room.
+    fn world_with(monster: Entity) -> World {
+        let player = Entity {
+            pos: Vector::new(5, 5),
+            name: "player".to_string(),
+            hp: 5,
+            max_hp: 5,
+            blocks_tile: true,
+            faction: Faction::Player,
+            ..Default::default()
+        };
+        World::from_parts(
+            0,
+            StdRng::seed_from_u64(0),
+            generate_map(Vector::new(20, 15)),
+            vec![monster],
+            player,
+        )
+    }
+
+    #[test]
+    fn chasers_close_in_and_attack() {
+        let mut world = world_with(monster(9, 5, Behavior::Chase));
+        for _ in 0..3 {
+            world.player_turn(Action::Wait);
+        }
+        assert_eq!(world.entities[0].pos, Vector::new(6, 5));
+        assert_eq!(world.player().hp, 5);
+
+        world.player_turn(Action::Wait);
+        assert_eq!(world.player().hp, 4);
+        assert_eq!(world.log.last().unwrap(), "The orc hits you.");
+    }
+
+    #[test]
+    fn guards_wait_until_they_see_the_player() {
+        let mut world = world_with(monster(14, 5, Behavior::Guard));
+        // Out of sight
+        world.player_turn(Action::Wait);
+        assert_eq!(world.entities[0].pos, Vector::new(14, 5));
+
+        // Coming into view is enough to get noticed
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert_eq!(world.entities[0].pos, Vector::new(13, 5));
+        assert!(world.entities[0].ai.as_ref().unwrap().alerted);
+    }
+
+    #[test]
+    fn monsters_search_where_they_last_saw_the_player() {
+        let mut world = world_with(monster(9, 5, Behavior::Chase));
+        world.entities[0].hp = 3;
+        world.player_turn(Action::Wait);
+        assert_eq!(world.entities[0].pos, Vector::new(8, 5));
+
+        // The player hides in a closet in the corner
+        for y in 10..15 {
+            world.map.set_glyph(Vector::new(16, y), WALL);
+        }
+        for x in 16..19 {
+            world.map.set_glyph(Vector::new(x, 10), WALL);
+        }
+        let player_id = world.player_id;
+        world.entities[player_id].pos = Vector::new(17, 12);
+        for _ in 0..2 {
+            world.player_turn(Action::Wait);
+        }
+        assert_eq!(world.entities[0].pos, Vector::new(6, 5));
+        let ai = world.entities[0].ai.as_ref().unwrap();
+        assert_eq!(ai.last_seen, Some(Vector::new(5, 5)));
+        assert_eq!(ai.search_turns, SEARCH_TURNS);
+
+        for _ in 0..SEARCH_TURNS {
+            world.player_turn(Action::Wait);
+        }
+        assert_eq!(world.entities[0].ai.as_ref().unwrap().last_seen, None);
+        assert_eq!(mode(&mut world, 0), Mode::Wander);
+    }
+
+    #[test]
+    fn badly_hurt_monsters_flee() {
+        let mut world = world_with(monster(7, 5, Behavior::Chase));
+        world.entities[0].max_hp = 8;
+        world.entities[0].hp = 1;
+        assert_eq!(mode(&mut world, 0), Mode::Flee);
+        world.player_turn(Action::Wait);
+        let pos = world.entities[0].pos;
+        assert_eq!(distance(pos, world.player().pos), 3);
+    }
+
+    #[test]
+    fn confused_monsters_wander() {
+        let mut world = world_with(monster(7, 5, Behavior::Chase));
+        world.entities[0].ai.as_mut().unwrap().confused = 2;
+        assert_eq!(mode(&mut world, 0), Mode::Wander);
+        assert_eq!(mode(&mut world, 0), Mode::Wander);
+        assert_eq!(mode(&mut world, 0), Mode::Chase);
+    }
+
+    #[test]
+    fn vermin_only_fight_back_when_attacked() {
+        // Guards don't wander off so it stays right next to the player
+        let mut rat = monster(6, 5, Behavior::Guard);
+        rat.faction = Faction::Vermin;
+        let mut world = world_with(rat);
+        world.player_turn(Action::Wait);
+        assert_eq!(world.player().hp, 5);
+        assert_eq!(target(&world, 0), None);
+
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert!(world.entities[0].ai.as_ref().unwrap().provoked);
+        assert_eq!(target(&world, 0), Some(world.player_id));
+        assert_eq!(world.player().hp, 4);
+    }
+
+    #[test]
+    fn confused_monsters_hit_each_other() {
+        let mut world = world_with(monster(7, 5, Behavior::Chase));
+        world.entities.insert(1, monster(7, 6, Behavior::Chase));
+        world.player_id += 1;
+        world.rebuild_spatial();
+        assert!(!is_hostile(&world, 0, 1));
+        assert_eq!(target(&world, 0), Some(world.player_id));
+
+        world.entities[0].ai.as_mut().unwrap().confused = 100;
+        for _ in 0..100 {
+            take_turn(&mut world, 0);
+            events::process(&mut world);
+            world.move_entity(0, Vector::new(7, 5));
+        }
+        assert!(world
+            .log
+            .iter()
+            .any(|message| message.text() == "The orc hits the orc."));
+    }
+
+    #[test]
+    fn bosses_get_worse_as_they_get_hurt() {
+        assert_eq!(Phase::of(10, 10), Phase::Melee);
+        assert_eq!(Phase::of(5, 10), Phase::Summon);
+        assert_eq!(Phase::of(2, 10), Phase::Enraged);
+
+        let mut boss = monster(6, 5, Behavior::Boss);
+        boss.name = "troll king".to_string();
+        boss.max_hp = 10;
+        boss.hp = 5;
+        let ai = boss.ai.as_mut().unwrap();
+        ai.flees = false;
+        ai.summons = Some("goblin".to_string());
+        let mut world = world_with(boss);
+        let player_id = world.player_id;
+        world.entities[player_id].max_hp = 20;
+        world.entities[player_id].hp = 20;
+        world.player_turn(Action::Wait);
+        assert_eq!(world.entities[0].ai.as_ref().unwrap().phase, Phase::Summon);
+        assert!(world.entities.iter().any(|entity| entity.name == "goblin"));
+        assert!(world
+            .log
+            .iter()
+            .any(|message| message == "The troll king bellows for help!"));
+
+        world.entities[0].hp = 2;
+        world.player_turn(Action::Wait);
+        assert_eq!(world.entities[0].ai.as_ref().unwrap().phase, Phase::Enraged);
+        // Healing doesn't calm it down
+        world.entities[0].hp = 10;
+        world.player_turn(Action::Wait);
+        assert_eq!(world.entities[0].ai.as_ref().unwrap().phase, Phase::Enraged);
+        let boss_hits: u32 = world
+            .log
+            .iter()
+            .filter(|message| message.text() == "The troll king hits you.")
+            .map(|message| message.count)
+            .sum();
+        // Calling for help takes a turn, then it hits twice a turn
+        assert_eq!(boss_hits, 4);
+    }
+
+    #[test]
+    fn packs_hunt_together() {
+        let mut near = monster(6, 5, Behavior::Guard);
+        near.faction = Faction::Vermin;
+        near.ai.as_mut().unwrap().pack = Some(0);
+        let mut world = world_with(near.clone());
+        // Too far away to see the player
+        let mut far = near;
+        far.pos = Vector::new(15, 5);
+        world.entities.insert(1, far);
+        world.player_id += 1;
+        world.rebuild_spatial();
+        assert_eq!(target(&world, 1), None);
+
+        // Attacking one of them brings the other over
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert!(world.entities[1].ai.as_ref().unwrap().provoked);
+        assert_eq!(target(&world, 1), Some(world.player_id));
+        assert_eq!(world.entities[1].pos, Vector::new(14, 5));
+    }
+}
@@ -0,0 +1,153 @@
+//! Ranged weapons and what they fire.
+//!
+//! A weapon with `ammo` (the bow, with its arrows) fires one of those
+//! items from the player's inventory at a time, with the weapon's
+//! `fire_damage` and further than anything can be thrown. The shot flies
+//! like something thrown (see `throwing`) and then breaks or, with
+//! `RECOVERY_CHANCE`, lands on the floor to be picked up again.
+
+use quicksilver::geom::Vector;
+use rand::Rng;
+
+use crate::{
+    locale,
+    templates::Slot,
+    throwing::{dodges, flight_path, hit_with},
+    world::World,
+};
+
+/// How far (in tiles) a ranged weapon shoots.
+pub const FIRE_RANGE: usize = 12;
+
+/// The chance of something fired landing whole once it stops.
+pub const RECOVERY_CHANCE: f64 = 0.5;
+
+/// What the player's weapon fires, if it's a ranged one.
+pub fn ammo(world: &World) -> Option<String> {
+    let weapon = world.equipped(world.player_id, Slot::Weapon)?;
+    let template = &weapon.item.as_ref()?.template;
+    world.templates.items.get(template)?.ammo.clone()
+}
+
+/// The damage the player's ranged weapon does with what it fires.
+fn damage(world: &World) -> i32 {
+    let weapon = world.equipped(world.player_id, Slot::Weapon);
+    let template = weapon.and_then(|weapon| weapon.item.as_ref());
+    template
+        .and_then(|item| world.templates.items.get(&item.template))
+        .map_or(0, |template| template.fire_damage)
+}
+
+/// How many of the `ammo` items the player carries.
+pub fn carried(world: &World, ammo: &str) -> u32 {
+    let inventory = world.player().inventory.iter();
+    let items = inventory.filter_map(|entity| entity.item.as_ref());
+    items
+        .filter(|item| item.template == ammo)
+        .map(|item| item.count)
+        .sum()
+}
+
+/// The tiles a shot at `target` passes through, like
+/// `throwing::trajectory`.
+pub fn trajectory(world: &World, target: Vector) -> Vec<Vector> {
+    flight_path(world, world.player().pos, target, FIRE_RANGE)
+}
+
+/// The player fires their ranged weapon at `target`.
+pub fn fire(world: &mut World, target: Vector) {
+    let player_id = world.player_id;
+    let ammo = match ammo(world) {
+        Some(ammo) => ammo,
+        None => return world.log(locale::text("log.no-ranged-weapon")),
+    };
+    let index = world.player().inventory.iter().position(|entity| {
+        let item = entity.item.as_ref();
+        item.is_some_and(|item| item.template == ammo)
+    });
+    let index = match index {
+        Some(index) => index,
+        None => {
+            let name = world.identification.name(&world.templates, &ammo);
+            return world.log(locale::message("log.out-of-ammo", &[("item", &name)]));
+        }
+    };
+    let start = world.player().pos;
+    let path = trajectory(world, target);
+    let landing = match path.last() {
+        Some(&landing) => landing,
+        None => return world.log(locale::text("log.no-room-to-fire")),
+    };
+
+    let mut shot = world.take_one(player_id, index);
+    let name = world.item_name(&shot);
+    world.log(locale::message("log.fire", &[("item", &name)]));
+    let damage = damage(world).max(1);
+    let hit = world
+        .blocker_at(landing)
+        .filter(|&id| world.entities[id].is_alive())
+        .filter(|&id| !dodges(world, start, id));
+    if let Some(id) = hit {
+        hit_with(world, &name, id, damage);
+    }
+    if world.rng.gen_bool(RECOVERY_CHANCE) {
+        shot.pos = landing;
+        world.entities.push(shot);
+        world.rebuild_spatial();
+    } else {
+        world.log(locale::message("log.ammo-breaks", &[("item", &name)]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        spawn,
+        templates::Templates,
+        world::{
+            tests::{goblin, player_with},
+            Action, Entity,
+        },
+    };
+
+    #[test]
+    fn shots_use_up_the_ammo() {
+        let templates = Templates::builtin();
+        let mut bow = spawn::item("bow", &templates.items["bow"], Vector::new(0, 0));
+        bow.item.as_mut().unwrap().equipped = true;
+        let mut arrows = spawn::item("arrow", &templates.items["arrow"], Vector::new(0, 0));
+        arrows.item.as_mut().unwrap().count = 2;
+        // Standing still to be shot at
+        let goblin = Entity {
+            ai: None,
+            ..goblin(16, 9)
+        };
+        let mut world = player_with(vec![goblin], 5);
+        let player_id = world.player_id;
+        world.entities[player_id].inventory = vec![bow, arrows];
+        assert_eq!(ammo(&world), Some("arrow".to_string()));
+        assert_eq!(
+            world.inventory_line(&world.player().inventory[0]),
+            "bow (equipped, 2 left)"
+        );
+
+        // Further than anything can be thrown
+        world.player_turn(Action::Fire(Vector::new(16, 5)));
+        assert_eq!(world.entities[0].hp, 6);
+        assert_eq!(carried(&world, "arrow"), 1);
+        assert_eq!(world.inventory_line(&world.player().inventory[1]), "arrow");
+        world.player_turn(Action::Fire(Vector::new(16, 5)));
+        assert_eq!(world.entities[0].hp, 3);
+        assert_eq!(world.player().inventory.len(), 1);
+        let landed = world
+            .entities
+            .iter()
+            .filter(|entity| entity.name == "arrow");
+        assert!(landed.count() <= 2);
+
+        world.player_turn(Action::Fire(Vector::new(16, 5)));
+        assert_eq!(world.entities[0].hp, 3);
+        assert_eq!(world.log.last().unwrap(), "You have no arrow left to fire.");
+    }
+}
@@ -0,0 +1,72 @@
+//! Things flying across the map.
+//!
+//! Thrown items (and anything else that travels in a line later) are
+//! animated tile by tile before the action they belong to is resolved.
+//! The UI doesn't take any more input until the animation is over, so
+//! from the player's point of view the turn waits for it.
+
+use quicksilver::{geom::Vector, graphics::Color};
+
+use crate::timing;
+
+/// How long a projectile takes to cross one tile.
+pub const SECONDS_PER_TILE: f64 = 0.03;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Projectile {
+    /// The tiles to fly through, in order.
+    pub path: Vec<Vector>,
+    pub glyph: char,
+    pub color: Color,
+    /// In updates.
+    age: u32,
+}
+
+impl Projectile {
+    pub fn new(path: Vec<Vector>, glyph: char, color: Color) -> Self {
+        Projectile {
+            path,
+            glyph,
+            color,
+            age: 0,
+        }
+    }
+
+    /// Count one update.
+    pub fn tick(&mut self) {
+        self.age += 1;
+    }
+
+    /// The tile the projectile is over right now, `None` once it's
+    /// landed.
+    pub fn pos(&self) -> Option<Vector> {
+        let step = self.age / timing::updates(SECONDS_PER_TILE);
+        self.path.get(step as usize).copied()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pos().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn projectiles_fly_along_their_path() {
+        let path = vec![Vector::new(1, 0), Vector::new(2, 0)];
+        let mut projectile = Projectile::new(path, ')', Color::BLACK);
+        let per_tile = timing::updates(SECONDS_PER_TILE);
+        assert_eq!(projectile.pos(), Some(Vector::new(1, 0)));
+        for _ in 0..per_tile {
+            projectile.tick();
+        }
+        assert_eq!(projectile.pos(), Some(Vector::new(2, 0)));
+        for _ in 0..per_tile {
+            projectile.tick();
+        }
+        assert!(projectile.is_done());
+        assert!(Projectile::new(vec![], ')', Color::BLACK).is_done());
+    }
+}
@@ -0,0 +1,60 @@
+//! Where the files the game loads while it runs come from.
+//!
+//! Normally they're read from the `static` folder next to the game (or
+//! fetched from the server on the web). Built with the `embedded-assets`
+//! feature they're part of the binary instead, so the game is a single
+//! file to ship. Either way they're loaded through `load`, by the same
+//! paths. The templates, translations and prefabs are always built in.
+
+use quicksilver::{graphics::Font, Error, Future};
+
+/// The files built into the binary with `embedded-assets`, by their path
+/// in `static`.
+#[cfg(feature = "embedded-assets")]
+const EMBEDDED: &[(&str, &[u8])] = &[
+    (
+        "mononoki-Regular.ttf",
+        include_bytes!("../static/mononoki-Regular.ttf"),
+    ),
+    ("square.ttf", include_bytes!("../static/square.ttf")),
+];
+
+/// Load the file at `path` in `static`.
+#[cfg(not(feature = "embedded-assets"))]
+pub fn load(path: &'static str) -> impl Future<Item = Vec<u8>, Error = Error> {
+    quicksilver::load_file(path)
+}
+
+/// Load the file at `path` in `static` from the ones built in.
+#[cfg(feature = "embedded-assets")]
+pub fn load(path: &'static str) -> impl Future<Item = Vec<u8>, Error = Error> {
+    let file = EMBEDDED
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, bytes)| bytes.to_vec())
+        .ok_or_else(|| {
+            let message = format!("{} isn't one of the embedded assets", path);
+            std::io::Error::new(std::io::ErrorKind::NotFound, message).into()
+        });
+    quicksilver::combinators::result(file)
+}
+
+/// Load the font at `path` in `static`.
+pub fn font(path: &'static str) -> impl Future<Item = Font, Error = Error> {
+    load(path).and_then(Font::from_bytes)
+}
+
+#[cfg(all(test, feature = "embedded-assets"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_assets_are_the_static_files() {
+        for (path, _) in EMBEDDED {
+            let embedded = load(path).wait().unwrap();
+            let file = std::fs::read(format!("static/{}", path)).unwrap();
+            assert!(embedded == file, "{}", path);
+        }
+        assert!(load("missing.png").wait().is_err());
+    }
+}
@@ -0,0 +1,54 @@
+//! Play lots of games with the bot and report how they went. Meant for
+//! checking that balance changes do what they should:
+//!
+//!     cargo run --release --bin simulate -- 200
+//!
+//! Nothing here opens a window, it's only the game logic.
+
+use quicksilver_roguelike::{
+    bot,
+    world::{Outcome, World},
+};
+
+/// Games still going after this many turns count as stuck.
+const MAX_TURNS: u32 = 5000;
+
+const DEFAULT_GAMES: u64 = 100;
+
+fn main() {
+    let games = match std::env::args().nth(1).map(|arg| arg.parse()) {
+        None => DEFAULT_GAMES,
+        Some(Ok(games)) if games > 0 => games,
+        Some(_) => {
+            eprintln!("Usage: simulate [<number of games>]");
+            std::process::exit(1);
+        }
+    };
+
+    let (mut won, mut died, mut stuck) = (0, 0, 0);
+    let mut total_depth = 0;
+    let mut total_turns = 0;
+    for seed in 0..games {
+        let mut world = World::new(seed);
+        while world.outcome.is_none() && world.stats.turns < MAX_TURNS {
+            let action = bot::next_action(&world);
+            world.player_turn(action);
+        }
+        match world.outcome {
+            Some(Outcome::Won) => won += 1,
+            Some(Outcome::Died) => died += 1,
+            None => stuck += 1,
+        }
+        total_depth += world.stats.deepest_level as u64;
+        total_turns += world.stats.turns as u64;
+    }
+
+    let percent = |count: u64| count as f64 * 100.0 / games as f64;
+    println!("Games: {}", games);
+    println!("Won: {} ({:.1}%)", won, percent(won));
+    println!("Died: {} ({:.1}%)", died, percent(died));
+    println!("Stuck: {} ({:.1}%)", stuck, percent(stuck));
+    println!("Survived: {:.1}%", percent(won + stuck));
+    println!("Average depth: {:.2}", total_depth as f64 / games as f64);
+    println!("Average turns: {:.0}", total_turns as f64 / games as f64);
+}
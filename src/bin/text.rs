@@ -0,0 +1,80 @@
+//! Play the game in a terminal, one line of commands at a time:
+//!
+//!     cargo run --bin text
+//!
+//! Every character of a line is a key from the game's keymap (`g` picks
+//! up, `>` goes down the stairs, ...) with `h`, `j`, `k` and `l` moving
+//! west, south, north and east. `w` and `q` take the letter of an
+//! inventory item after them, e.g. `qA` drinks or reads the first item.
+//! After each line the game prints what's changed, see `text_mode`. An
+//! empty line or the end of the input quits.
+
+use quicksilver::geom::Vector;
+use quicksilver_roguelike::{
+    input::{Command, Trigger, KEYMAP},
+    profile::PlayerProfile,
+    text_mode::TextMode,
+    world::{Action, World},
+};
+
+use std::io::BufRead;
+
+/// The keys that move the player, as there are no arrow keys here.
+const MOVE_KEYS: [(char, (i32, i32)); 4] =
+    [('h', (-1, 0)), ('j', (0, 1)), ('k', (0, -1)), ('l', (1, 0))];
+
+fn main() {
+    let mut world = World::with_profile(rand::random(), PlayerProfile::default());
+    let mut text_mode = TextMode::default();
+    if let Some(frame) = text_mode.update(&world) {
+        println!("{}\n", frame);
+    }
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) if !line.trim().is_empty() => line,
+            _ => break,
+        };
+        for action in actions(&line) {
+            if world.outcome.is_some() {
+                break;
+            }
+            world.player_turn(action);
+        }
+        if let Some(frame) = text_mode.update(&world) {
+            println!("{}\n", frame);
+        }
+        if world.outcome.is_some() {
+            break;
+        }
+    }
+}
+
+/// The actions typed on one line. Characters that don't stand for
+/// anything are skipped.
+fn actions(line: &str) -> Vec<Action> {
+    let mut actions = vec![];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if let Some(&(_, (x, y))) = MOVE_KEYS.iter().find(|(key, _)| *key == c) {
+            actions.push(Action::Move(Vector::new(x, y)));
+            continue;
+        }
+        let command = KEYMAP
+            .iter()
+            .find(|(trigger, _)| *trigger == Trigger::Char(c))
+            .map(|(_, command)| command);
+        let item = |letter: Option<char>| {
+            letter
+                .filter(char::is_ascii_uppercase)
+                .map(|letter| (letter as u8 - b'A') as usize)
+        };
+        match command {
+            Some(Command::Equip) => actions.extend(item(chars.next()).map(Action::Equip)),
+            Some(Command::Use) => actions.extend(item(chars.next()).map(Action::Use)),
+            Some(command) => actions.extend(command.action()),
+            None => {}
+        }
+    }
+    actions
+}
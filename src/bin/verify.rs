@@ -0,0 +1,83 @@
+//! Play replays (or saves) back and check they still go the way they
+//! did when their hashes were recorded, see the `checksum` module:
+//!
+//!     cargo run --bin verify -- --record replay.txt
+//!     cargo run --bin verify -- replay.txt other-replay.txt
+//!
+//! The game saves the hashes as it's played, so its saves and replays
+//! can be checked right away. `--record` writes the hashes of the game
+//! as it plays now into the files instead, e.g. for replays recorded
+//! before hashes were. Without it, every file is checked and the first
+//! action each one goes out of sync at is reported.
+
+use quicksilver_roguelike::{checksum, packs, replay::Replay, saves::Save};
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let recording = args.first().is_some_and(|arg| arg == "--record");
+    if recording {
+        args.remove(0);
+    }
+    if args.is_empty() {
+        eprintln!("Usage: verify [--record] <replay>...");
+        std::process::exit(1);
+    }
+
+    let mut failed = 0;
+    for path in &args {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                failed += 1;
+                continue;
+            }
+        };
+        // Plain replays are written back without a save's header
+        let plain = Replay::parse(&text).is_ok();
+        let mut save = match Save::parse(&text) {
+            Ok(save) => save,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                failed += 1;
+                continue;
+            }
+        };
+        // The hashes would be of another game
+        if !packs::matches(&save.replay.packs) {
+            eprintln!(
+                "{}: played with the packs {}, verify only has the built-in content",
+                path,
+                packs::describe(&save.replay.packs)
+            );
+            failed += 1;
+            continue;
+        }
+        if recording {
+            checksum::record(&mut save.replay);
+            let text = if plain {
+                save.replay.to_text()
+            } else {
+                save.to_text()
+            };
+            match std::fs::write(path, text) {
+                Ok(()) => println!("{}: recorded {} hashes", path, save.replay.hashes.len()),
+                Err(error) => {
+                    eprintln!("{}: {}", path, error);
+                    failed += 1;
+                }
+            }
+            continue;
+        }
+        match checksum::verify(&save.replay) {
+            Ok(()) => println!("{}: {} actions in sync", path, save.replay.actions.len()),
+            Err(desync) => {
+                println!("{}: {}", path, desync);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
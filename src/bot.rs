@@ -0,0 +1,152 @@
+//! A player that plays itself, for testing the game's balance without
+//! anybody at the keyboard.
+//!
+//! The bot isn't clever: it fights whatever it sees, rests when it's
+//! hurt and otherwise heads down the main dungeon's stairs (or for the
+//! amulet). It knows the whole level which the player doesn't, but it's
+//! about how hard the fights are, not the exploration. When a locked
+//! door is in the way it fetches a key and opens it.
+
+use quicksilver::geom::Vector;
+
+use crate::{
+    geometry::tile_key,
+    map::{LOCKED_DOOR, STAIRS_DOWN, STAIRS_UP},
+    pathfinding,
+    templates::{AMULET, KEY, MAIN_DUNGEON},
+    world::{is_hostile, Action, World},
+};
+
+/// Below this much health (as a fraction of the maximum) the bot rests
+/// whenever there's nothing around.
+const REST_HEALTH: f32 = 0.6;
+
+/// The bot's action for this turn.
+pub fn next_action(world: &World) -> Action {
+    let player = world.player();
+    let pos = player.pos;
+
+    let monsters = world.visible_monsters();
+    let nearest = monsters
+        .iter()
+        .copied()
+        .filter(|&id| is_hostile(world, world.player_id, id))
+        .min_by_key(|&id| distance(pos, world.entities[id].pos));
+    if let Some(monster) = nearest {
+        return step_towards(world, world.entities[monster].pos);
+    }
+
+    if world.item_at(pos).is_some() {
+        return Action::PickUp;
+    }
+    if (player.hp as f32) < player.max_hp as f32 * REST_HEALTH {
+        return Action::Wait;
+    }
+
+    let glyph = world.map.tile(pos).map(|tile| tile.glyph);
+    if world.has_amulet() {
+        if glyph == Some(STAIRS_UP) {
+            return Action::Ascend;
+        }
+        return go_to_glyph(world, STAIRS_UP);
+    }
+    if world.depth == 0 {
+        return match world.entrances.get(MAIN_DUNGEON) {
+            Some(&entrance) if entrance == pos => Action::Descend,
+            Some(&entrance) => step_towards(world, entrance),
+            None => Action::Wait,
+        };
+    }
+    if world.depth < world.templates.max_depth(world.dungeon) {
+        if glyph == Some(STAIRS_DOWN) {
+            return Action::Descend;
+        }
+        return go_to_glyph(world, STAIRS_DOWN);
+    }
+    let amulet = world
+        .entities
+        .iter()
+        .find(|entity| entity.item.as_ref().map(|item| item.template.as_str()) == Some(AMULET));
+    match amulet {
+        Some(amulet) => step_towards(world, amulet.pos),
+        None => Action::Wait,
+    }
+}
+
+fn go_to_glyph(world: &World, glyph: char) -> Action {
+    let target = world.map.tiles.iter().find(|tile| tile.glyph == glyph);
+    match target {
+        Some(tile) => step_towards(world, tile.pos),
+        None => Action::Wait,
+    }
+}
+
+/// Move (or attack) one step along the cheapest path to `goal`.
+fn step_towards(world: &World, goal: Vector) -> Action {
+    let pos = world.player().pos;
+    match pathfinding::next_step(world, world.player_id, goal) {
+        Some(step) => Action::Move(step - pos),
+        None => unlock_the_way(world),
+    }
+}
+
+/// There's no way to the goal: pick up a key if there's one lying
+/// around, otherwise open the nearest locked door.
+fn unlock_the_way(world: &World) -> Action {
+    let pos = world.player().pos;
+    let key = world
+        .entities
+        .iter()
+        .filter(|entity| {
+            entity
+                .item
+                .as_ref()
+                .is_some_and(|item| item.template == KEY)
+        })
+        .map(|entity| entity.pos)
+        .find(|&key| pathfinding::next_step(world, world.player_id, key).is_some());
+    if let Some(key) = key {
+        return step_towards(world, key);
+    }
+    let doors = world
+        .map
+        .tiles
+        .iter()
+        .filter(|tile| tile.glyph == LOCKED_DOOR)
+        .map(|tile| tile.pos);
+    for door in doors {
+        if distance(pos, door) == 1 {
+            return Action::Move(door - pos);
+        }
+        for &(x, y) in &[(0, -1), (1, 0), (0, 1), (-1, 0)] {
+            let next = door + Vector::new(x, y);
+            if let Some(step) = pathfinding::next_step(world, world.player_id, next) {
+                return Action::Move(step - pos);
+            }
+        }
+    }
+    Action::Wait
+}
+
+fn distance(from: Vector, to: Vector) -> i32 {
+    let (from, to) = (tile_key(from), tile_key(to));
+    (from.0 - to.0).abs() + (from.1 - to.1).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_bot_gets_somewhere() {
+        let mut world = World::new(11);
+        for _ in 0..500 {
+            if world.outcome.is_some() {
+                break;
+            }
+            let action = next_action(&world);
+            world.player_turn(action);
+        }
+        assert!(world.outcome.is_some() || world.stats.deepest_level > 1);
+    }
+}
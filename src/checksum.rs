@@ -0,0 +1,251 @@
+//! Fingerprints of the game state, to catch replays going out of sync.
+//!
+//! `state_hash` boils what matters about the world (the map, where
+//! everybody is and how they're doing, what they carry, the RNG) down
+//! to a number. The game records the hash after every action the player
+//! takes (`Replay::hashes`), so saves and replays can be played back
+//! with `verify`, which stops at the first action
+//! after which the hashes differ. That's where something has made the
+//! game nondeterministic, or changed how it plays. The `verify` binary
+//! does this for replay files.
+
+use std::fmt;
+
+use quicksilver::geom::Vector;
+use rand::Rng;
+
+use crate::{
+    replay::Replay,
+    world::{Entity, World},
+};
+
+/// The 64 bit FNV-1a hash. Unlike `DefaultHasher` it's the same in
+/// every build, so recorded hashes stay valid.
+struct Fnv(u64);
+
+impl Fnv {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn number(&mut self, number: i64) {
+        self.bytes(&number.to_le_bytes());
+    }
+
+    fn text(&mut self, text: &str) {
+        self.number(text.len() as i64);
+        self.bytes(text.as_bytes());
+    }
+
+    fn pos(&mut self, pos: Vector) {
+        self.number(pos.x as i64);
+        self.number(pos.y as i64);
+    }
+}
+
+/// The fingerprint of the world's state. Equal worlds always hash the
+/// same, and a world with anybody somewhere else or hurt differently
+/// (or with the RNG further along) almost certainly doesn't.
+pub fn state_hash(world: &World) -> u64 {
+    let mut hash = Fnv(Fnv::OFFSET);
+    hash.text(&format!("{:?}", world.stats));
+    hash.number(world.dungeon as i64);
+    hash.number(world.depth as i64);
+    // Drawing from a copy tells where the generator is without moving it
+    hash.number(world.rng.clone().gen::<i64>());
+    for tile in &world.map.tiles {
+        hash.number(tile.glyph as i64);
+    }
+    hash.number(world.player_id as i64);
+    for entity in &world.entities {
+        hash.pos(entity.pos);
+        hash.number(entity.glyph as i64);
+        hash.text(&entity.name);
+        hash.number(entity.hp as i64);
+        hash.number(entity.max_hp as i64);
+        hash.number(entity.gold as i64);
+        hash.number(entity.wait as i64);
+        hash.text(&format!("{:?}", entity.ai));
+        hash.text(&format!("{:?}", entity.cooldowns));
+        hash.number(entity.speed.0 as i64);
+        hash.text(&format!("{:?}", entity.chest));
+        hash.text(&format!("{:?}", entity.last_hurt));
+        item(&mut hash, entity);
+        // What a chest holds or a shopkeeper sells too
+        hash.number(entity.inventory.len() as i64);
+        for carried in &entity.inventory {
+            item(&mut hash, carried);
+        }
+    }
+    hash.text(&format!("{:?}", world.identification));
+    hash.text(&format!("{:?} {:?}", world.shop, world.chest));
+    for hazard in &world.hazards {
+        hash.text(&format!("{:?}", hazard.kind));
+        hash.pos(hazard.pos);
+        hash.number(hazard.turns as i64);
+    }
+    hash.number(world.confused as i64);
+    hash.number(world.sneaking as i64);
+    // Not the log: the UI writes its prompts there too, and a replay
+    // doesn't repeat them
+    hash.text(&format!("{:?}", world.outcome));
+    hash.0
+}
+
+fn item(hash: &mut Fnv, entity: &Entity) {
+    if let Some(item) = &entity.item {
+        hash.text(&item.template);
+        hash.number(item.count as i64);
+        hash.number(item.equipped as i64);
+        hash.number(item.cursed as i64);
+        hash.number(item.curse_known as i64);
+    }
+}
+
+/// The state hash after each of the replay's actions.
+pub fn hashes(replay: &Replay) -> Vec<u64> {
+    let mut world = replay.world();
+    replay
+        .actions
+        .iter()
+        .map(|action| {
+            world.player_turn(action.clone());
+            state_hash(&world)
+        })
+        .collect()
+}
+
+/// Hash the game as it plays now into the replay, replacing any hashes
+/// it had.
+pub fn record(replay: &mut Replay) {
+    replay.hashes = hashes(replay);
+}
+
+/// A replay didn't play out the way it was recorded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Desync {
+    /// There's nothing to check against.
+    NoHashes,
+    /// The state differs after the action with this (1-based) number.
+    Action {
+        number: usize,
+        recorded: u64,
+        actual: u64,
+    },
+}
+
+impl fmt::Display for Desync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Desync::NoHashes => write!(f, "The replay has no hashes to check."),
+            Desync::Action {
+                number,
+                recorded,
+                actual,
+            } => write!(
+                f,
+                "Out of sync after action {}: recorded {:016x}, got {:016x}.",
+                number, recorded, actual
+            ),
+        }
+    }
+}
+
+/// Play the replay back, checking the state after every action against
+/// the recorded hashes.
+pub fn verify(replay: &Replay) -> Result<(), Desync> {
+    if replay.hashes.is_empty() {
+        return Err(Desync::NoHashes);
+    }
+    let mut world = replay.world();
+    for (index, (action, &recorded)) in replay.actions.iter().zip(&replay.hashes).enumerate() {
+        world.player_turn(action.clone());
+        let actual = state_hash(&world);
+        if actual != recorded {
+            return Err(Desync::Action {
+                number: index + 1,
+                recorded,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bot, schedule::Speed, world::Action};
+
+    /// The first `turns` actions of the bot playing `seed`.
+    fn bot_replay(seed: u64, turns: usize) -> Replay {
+        let mut replay = Replay::new(seed);
+        let mut world = replay.world();
+        for _ in 0..turns {
+            let action = bot::next_action(&world);
+            world.player_turn(action.clone());
+            replay.record(action);
+        }
+        replay
+    }
+
+    #[test]
+    fn the_same_game_hashes_the_same() {
+        let mut world = World::new(3);
+        let hash = state_hash(&world);
+        assert_eq!(state_hash(&World::new(3)), hash);
+        assert_ne!(state_hash(&World::new(4)), hash);
+
+        world.rng.gen::<u32>();
+        assert_ne!(state_hash(&world), hash);
+
+        // So does what hasn't shown up anywhere else yet
+        let mut slow = World::new(3);
+        let player_id = slow.player_id;
+        slow.entities[player_id].speed = Speed(50);
+        assert_ne!(state_hash(&slow), hash);
+        let mut cursed = World::new(3);
+        if let Some(item) = &mut cursed.entities[player_id].inventory[0].item {
+            item.cursed = !item.cursed;
+        }
+        assert_ne!(state_hash(&cursed), hash);
+    }
+
+    #[test]
+    fn replays_are_checked_action_by_action() {
+        let mut replay = bot_replay(7, 150);
+        assert_eq!(verify(&replay), Err(Desync::NoHashes));
+        record(&mut replay);
+        assert_eq!(replay.hashes.len(), 150);
+        assert_eq!(verify(&replay), Ok(()));
+        // The hashes survive being saved
+        let parsed = Replay::parse(&replay.to_text()).unwrap();
+        assert_eq!(verify(&parsed), Ok(()));
+
+        // Playing something else is caught right away. The bot never
+        // sneaks.
+        let mut changed = replay.clone();
+        changed.actions[40] = Action::Sneak;
+        match verify(&changed) {
+            Err(Desync::Action { number, .. }) => assert_eq!(number, 41),
+            result => panic!("Expected a desync, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn prompts_between_actions_dont_desync() {
+        let mut replay = Replay::new(7);
+        let mut world = replay.world();
+        for _ in 0..3 {
+            world.log("Equip what?");
+            world.player_turn(Action::Wait);
+            replay.record_hashed(Action::Wait, state_hash(&world));
+        }
+        assert_eq!(verify(&replay), Ok(()));
+    }
+}
@@ -0,0 +1,159 @@
+//! Chests full of loot.
+//!
+//! Bumping into a chest opens it (`World::chest`) and the player can
+//! take what's inside: the chest's inventory. Locked chests need a key,
+//! which is used up on the lock. Like trading, taking things out
+//! doesn't take any time.
+
+use crate::{
+    events::{self, Event},
+    locale, locks,
+    world::World,
+};
+
+/// What makes an entity a chest. The items are its inventory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chest {
+    /// It won't open without a key.
+    pub locked: bool,
+}
+
+/// The player bumped into the chest `id`: open it, unlocking it first
+/// if they have a key.
+pub fn open(world: &mut World, id: usize) {
+    let locked = world.entities[id]
+        .chest
+        .as_ref()
+        .is_some_and(|chest| chest.locked);
+    if locked {
+        if !locks::use_key(world) {
+            return world.log(locale::text("log.chest-locked"));
+        }
+        if let Some(chest) = &mut world.entities[id].chest {
+            chest.locked = false;
+        }
+        world.entities[id].name = locale::content("chest");
+        world.log(locale::text("log.unlock-chest"));
+    }
+    world.chest = Some(id);
+    if world.entities[id].inventory.is_empty() {
+        world.log(locale::text("log.chest-empty"));
+    } else {
+        world.log(locale::text("log.open-chest"));
+    }
+}
+
+/// Take the item at `index` out of the open chest.
+pub fn take(world: &mut World, index: usize) {
+    let chest = match world.chest {
+        Some(chest) => chest,
+        None => return,
+    };
+    if index >= world.entities[chest].inventory.len() {
+        return world.log(locale::text("log.no-such-item"));
+    }
+    let player_id = world.player_id;
+    if !world.has_room(player_id, &world.entities[chest].inventory[index]) {
+        return world.log(locale::text("log.inventory-full"));
+    }
+    let item = world.entities[chest].inventory.remove(index);
+    let index = world.give(player_id, item);
+    world.events.push(Event::PickedUp {
+        id: player_id,
+        index,
+        chest: Some(chest),
+    });
+    // Taking things isn't a turn, so nothing else handles the event
+    events::process(world);
+}
+
+/// Take everything out of the open chest, or as much as fits.
+pub fn take_all(world: &mut World) {
+    let chest = match world.chest {
+        Some(chest) => chest,
+        None => return,
+    };
+    while let Some(item) = world.entities[chest].inventory.first() {
+        if !world.has_room(world.player_id, item) {
+            return world.log(locale::text("log.inventory-full"));
+        }
+        take(world, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        templates::{AMULET, KEY},
+        world::{tests::player_with, Action, Entity, Item},
+    };
+    use quicksilver::geom::Vector;
+
+    fn item(template: &str) -> Entity {
+        Entity {
+            name: template.to_string(),
+            item: Some(Item {
+                template: template.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// The player at (5, 5) next to a chest with a dart and a sword.
+    fn world_with_chest(locked: bool) -> World {
+        let chest = Entity {
+            pos: Vector::new(6, 5),
+            name: "chest".to_string(),
+            blocks_tile: true,
+            chest: Some(Chest { locked }),
+            inventory: vec![item("dart"), item("sword")],
+            ..Default::default()
+        };
+        player_with(vec![chest], 5)
+    }
+
+    #[test]
+    fn taking_things_out_of_a_chest() {
+        let mut world = world_with_chest(false);
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert_eq!(world.chest, Some(0));
+
+        world.player_turn(Action::Take(1));
+        assert_eq!(world.log.last().unwrap(), "You take a sword.");
+        world.player_turn(Action::TakeAll);
+        assert_eq!(world.player().inventory.len(), 2);
+        assert!(world.entities[0].inventory.is_empty());
+        // Taking things doesn't take any time
+        assert_eq!(world.stats.turns, 1);
+
+        world.player_turn(Action::Move(Vector::new(0, 1)));
+        assert_eq!(world.chest, None);
+    }
+
+    #[test]
+    fn locked_chests_use_up_a_key() {
+        let mut world = world_with_chest(true);
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert_eq!(world.chest, None);
+        assert_eq!(world.log.last().unwrap(), "The chest is locked.");
+
+        let player_id = world.player_id;
+        world.entities[player_id].inventory.push(item(KEY));
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert_eq!(world.chest, Some(0));
+        assert!(world.player().inventory.is_empty());
+        assert!(!world.entities[0].chest.as_ref().unwrap().locked);
+    }
+
+    #[test]
+    fn items_from_chests_tell_their_story() {
+        let mut world = world_with_chest(false);
+        world.entities[0].inventory.push(item(AMULET));
+        let told = world.stories.len();
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        world.player_turn(Action::Take(2));
+        assert_eq!(world.stories.len(), told + 1);
+    }
+}
@@ -0,0 +1,175 @@
+//! The settings a game is started with: how big the levels are and how
+//! hard the game is.
+//!
+//! They come from the command line (`--map-size 80x50 --difficulty hard`)
+//! or the page's query string on the web (`?size=80x50&difficulty=hard`)
+//! and get recorded in the replay, since the same seed makes a different
+//! game with different settings.
+
+use quicksilver::geom::Vector;
+
+use crate::world::{MAP_HEIGHT, MAP_WIDTH};
+
+/// The smallest levels the town and the dungeon generators have room
+/// for.
+pub const MIN_MAP_SIZE: (i32, i32) = (MAP_WIDTH, MAP_HEIGHT);
+
+/// Levels any bigger take too long to generate and light.
+pub const MAX_MAP_SIZE: (i32, i32) = (120, 80);
+
+/// How many monsters and items there are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    /// Half the monsters and half again as many items.
+    Easy,
+    #[default]
+    Normal,
+    /// Half again as many monsters and three quarters of the items.
+    Hard,
+}
+
+pub const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+impl Difficulty {
+    /// The name used on the command line and in replays.
+    pub fn id(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        DIFFICULTIES
+            .iter()
+            .copied()
+            .find(|difficulty| difficulty.id() == id)
+    }
+
+    /// The monsters there are compared to normal, in percent.
+    fn monster_percent(self) -> u32 {
+        match self {
+            Difficulty::Easy => 50,
+            Difficulty::Normal => 100,
+            Difficulty::Hard => 150,
+        }
+    }
+
+    /// The items there are compared to normal, in percent.
+    fn item_percent(self) -> u32 {
+        match self {
+            Difficulty::Easy => 150,
+            Difficulty::Normal => 100,
+            Difficulty::Hard => 75,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    /// The size of every level in tiles, the surface included.
+    pub map_size: Vector,
+    pub difficulty: Difficulty,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            map_size: Vector::new(MAP_WIDTH, MAP_HEIGHT),
+            difficulty: Difficulty::Normal,
+        }
+    }
+}
+
+impl Config {
+    /// How many monsters go on a level where the spawn table asks for
+    /// `count`. The tables are written for the default size so bigger
+    /// levels get proportionally more.
+    pub fn monster_count(&self, count: u32) -> u32 {
+        self.scale(count, self.difficulty.monster_percent())
+    }
+
+    /// How many items go on a level where the spawn table asks for
+    /// `count`.
+    pub fn item_count(&self, count: u32) -> u32 {
+        self.scale(count, self.difficulty.item_percent())
+    }
+
+    fn scale(&self, count: u32, percent: u32) -> u32 {
+        let area = (self.map_size.x * self.map_size.y) as u32;
+        let default_area = (MAP_WIDTH * MAP_HEIGHT) as u32;
+        // Rounded to the nearest
+        let scaled = count * area * percent;
+        let whole = default_area * 100;
+        (scaled + whole / 2) / whole
+    }
+}
+
+/// Read a map size like `80x50`. It has to be between `MIN_MAP_SIZE` and
+/// `MAX_MAP_SIZE`.
+pub fn parse_map_size(text: &str) -> Result<Vector, String> {
+    let invalid = || format!("Invalid map size `{}`, expected e.g. `80x50`", text);
+    let (width, height) = text.split_once('x').ok_or_else(invalid)?;
+    let width: i32 = width.trim().parse().map_err(|_| invalid())?;
+    let height: i32 = height.trim().parse().map_err(|_| invalid())?;
+    let (min, max) = (MIN_MAP_SIZE, MAX_MAP_SIZE);
+    if width < min.0 || height < min.1 || width > max.0 || height > max.1 {
+        return Err(format!(
+            "The map size must be between {}x{} and {}x{}",
+            min.0, min.1, max.0, max.1
+        ));
+    }
+    Ok(Vector::new(width, height))
+}
+
+pub fn parse_difficulty(text: &str) -> Result<Difficulty, String> {
+    Difficulty::from_id(text).ok_or_else(|| {
+        let ids: Vec<&str> = DIFFICULTIES
+            .iter()
+            .map(|difficulty| difficulty.id())
+            .collect();
+        format!(
+            "Unknown difficulty `{}`, expected one of: {}",
+            text,
+            ids.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_scale_with_the_area_and_difficulty() {
+        let config = Config::default();
+        assert_eq!((config.monster_count(4), config.item_count(3)), (4, 3));
+        let big = Config {
+            map_size: Vector::new(40, 30),
+            difficulty: Difficulty::Normal,
+        };
+        assert_eq!((big.monster_count(4), big.item_count(3)), (16, 12));
+        let hard = Config {
+            difficulty: Difficulty::Hard,
+            ..Config::default()
+        };
+        assert_eq!((hard.monster_count(4), hard.item_count(3)), (6, 2));
+        let easy = Config {
+            difficulty: Difficulty::Easy,
+            ..Config::default()
+        };
+        assert_eq!((easy.monster_count(3), easy.item_count(0)), (2, 0));
+    }
+
+    #[test]
+    fn parse_sizes_and_difficulties() {
+        assert_eq!(parse_map_size("80x50"), Ok(Vector::new(80, 50)));
+        assert!(parse_map_size("80").is_err());
+        assert!(parse_map_size("axb").is_err());
+        assert!(parse_map_size("10x10").is_err());
+        assert!(parse_map_size("500x50").is_err());
+        assert_eq!(parse_difficulty("hard"), Ok(Difficulty::Hard));
+        assert!(parse_difficulty("nightmare").is_err());
+    }
+}
@@ -0,0 +1,60 @@
+//! The daily challenge: everybody who plays it on the same (UTC) day gets
+//! the same dungeon.
+//!
+//! The seed is the number of days since the Unix epoch, so the morgue file
+//! can tell which day a run was from its seed alone. Daily games are
+//! always `GameMode::Daily`, `--wizard` or not, and have the default
+//! `Config` whatever the options say.
+
+use crate::{profile::PlayerProfile, wizard::GameMode, world::World};
+
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The seed of the challenge for the day `seconds` after the Unix epoch
+/// falls on.
+pub fn seed(seconds: u64) -> u64 {
+    seconds / SECONDS_PER_DAY
+}
+
+/// The challenge with the `seed` of the day.
+pub fn world(seed: u64, profile: PlayerProfile) -> World {
+    let mut world = World::with_profile(seed, profile);
+    world.mode = GameMode::Daily;
+    world
+}
+
+/// The day of the challenge with `seed` as `YYYY-MM-DD`.
+pub fn date(seed: u64) -> String {
+    // Howard Hinnant's `civil_from_days`, with the era starting on 0000-03-01
+    let days = seed as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_are_days_since_the_epoch() {
+        assert_eq!(seed(0), 0);
+        assert_eq!(seed(SECONDS_PER_DAY - 1), 0);
+        assert_eq!(seed(1_791_936_000), 20_740);
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(59), "1970-03-01");
+        assert_eq!(date(11_016), "2000-02-29");
+        assert_eq!(date(20_740), "2026-10-14");
+    }
+}
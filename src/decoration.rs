@@ -0,0 +1,176 @@
+//! Making the levels look a little less alike.
+//!
+//! Every level gets a tint of its own that all its tiles lean towards,
+//! each tile a shade lighter or darker than the next, and some rubble,
+//! grass and moss scattered over the floor. None of it changes what the
+//! tiles are: the decorations are only drawn in place of the floor's
+//! glyph, see `Tile::decoration`.
+
+use quicksilver::graphics::Color;
+use rand::{rngs::StdRng, Rng};
+
+use crate::map::{Map, FLOOR};
+
+pub const RUBBLE: char = ',';
+pub const GRASS: char = '\'';
+pub const MOSS: char = ';';
+
+/// The chance of a floor tile being decorated.
+pub const DECORATION_CHANCE: f64 = 0.08;
+
+/// How far the tiles lean towards the level's tint, from 0.0 (not at
+/// all) to 1.0.
+pub const TINT_STRENGTH: f32 = 0.3;
+
+/// How much lighter or darker than the rest a tile can be.
+pub const JITTER: f32 = 0.08;
+
+/// The decorations with their weights and colours.
+const DECORATIONS: [(char, u32, Color); 3] = [
+    (
+        RUBBLE,
+        3,
+        Color {
+            r: 0.45,
+            g: 0.4,
+            b: 0.35,
+            a: 1.0,
+        },
+    ),
+    (
+        GRASS,
+        2,
+        Color {
+            r: 0.2,
+            g: 0.55,
+            b: 0.15,
+            a: 1.0,
+        },
+    ),
+    (
+        MOSS,
+        1,
+        Color {
+            r: 0.3,
+            g: 0.45,
+            b: 0.3,
+            a: 1.0,
+        },
+    ),
+];
+
+/// A random colour for a level to be tinted with: a bright one so it
+/// shows on the dark tiles.
+pub fn random_tint(rng: &mut StdRng) -> Color {
+    let hue = rng.gen_range(0.0, 6.0_f32);
+    // Up one side of the hue wheel, then down the other
+    let rising = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, rising, 0.0),
+        1 => (rising, 1.0, 0.0),
+        2 => (0.0, 1.0, rising),
+        3 => (0.0, rising, 1.0),
+        4 => (rising, 0.0, 1.0),
+        _ => (1.0, 0.0, rising),
+    };
+    Color { r, g, b, a: 1.0 }
+}
+
+/// `color` leaning `amount` of the way towards `tint`.
+pub fn tinted(color: Color, tint: Color, amount: f32) -> Color {
+    let mix = |from: f32, to: f32| from + (to - from) * amount;
+    Color {
+        r: mix(color.r, tint.r),
+        g: mix(color.g, tint.g),
+        b: mix(color.b, tint.b),
+        a: color.a,
+    }
+}
+
+fn jittered(color: Color, rng: &mut StdRng) -> Color {
+    let shade = rng.gen_range(-JITTER, JITTER);
+    let channel = |value: f32| (value + shade).clamp(0.0, 1.0);
+    Color {
+        r: channel(color.r),
+        g: channel(color.g),
+        b: channel(color.b),
+        a: color.a,
+    }
+}
+
+/// Scatter the decorations over the map's floor and tint all of it.
+/// `rng` should be the level's own, so the same seed always makes the
+/// same level look the same.
+pub fn decorate(map: &mut Map, rng: &mut StdRng) {
+    let tint = random_tint(rng);
+    let total: u32 = DECORATIONS.iter().map(|&(_, weight, _)| weight).sum();
+    for tile in &mut map.tiles {
+        if tile.glyph == FLOOR && rng.gen_bool(DECORATION_CHANCE) {
+            let mut roll = rng.gen_range(0, total);
+            for &(glyph, weight, color) in DECORATIONS.iter() {
+                if roll < weight {
+                    tile.decoration = Some(glyph);
+                    tile.color = color;
+                    break;
+                }
+                roll -= weight;
+            }
+        }
+        tile.color = jittered(tinted(tile.color, tint, TINT_STRENGTH), rng);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{generate_map, WALL};
+    use quicksilver::geom::Vector;
+    use rand::SeedableRng;
+
+    #[test]
+    fn decorations_only_change_the_looks() {
+        let plain = generate_map(Vector::new(30, 20));
+        let mut map = plain.clone();
+        decorate(&mut map, &mut StdRng::seed_from_u64(4));
+        let decorated = map.tiles.iter().find(|tile| tile.decoration.is_some());
+        let pos = decorated.expect("Nothing got decorated.").pos;
+        for (tile, before) in map.tiles.iter().zip(plain.tiles.iter()) {
+            assert_eq!(tile.glyph, before.glyph);
+            if tile.decoration.is_some() {
+                assert_eq!(tile.glyph, FLOOR);
+            }
+        }
+
+        // The same seed looks the same, another one doesn't
+        let mut again = plain.clone();
+        decorate(&mut again, &mut StdRng::seed_from_u64(4));
+        assert_eq!(again, map);
+        let mut other = plain.clone();
+        decorate(&mut other, &mut StdRng::seed_from_u64(5));
+        assert_ne!(other, map);
+
+        // Building over a decoration clears it
+        map.set_glyph(pos, WALL);
+        assert_eq!(map.tile(pos).unwrap().decoration, None);
+    }
+
+    #[test]
+    fn tints_are_bright_colours() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let tint = random_tint(&mut rng);
+            let channels = [tint.r, tint.g, tint.b];
+            assert!(channels.contains(&1.0));
+            assert!(channels
+                .iter()
+                .all(|&channel| (0.0..=1.0).contains(&channel)));
+        }
+        let red = Color {
+            r: 1.0,
+            g: 0.0,
+            b: 0.0,
+            a: 1.0,
+        };
+        assert_eq!(tinted(Color::BLACK, red, 0.5).r, 0.5);
+    }
+}
@@ -0,0 +1,168 @@
+//! Things that happen during a turn, for whoever needs to react to them.
+//!
+//! Instead of the combat code also keeping the stats, dropping loot and
+//! provoking monsters, it says what happened by pushing an `Event` onto
+//! `World::events`. `process` hands each one to the code that cares
+//! about it. Handling an event can push more of them: an attack leads
+//! to damage, which can lead to a death.
+
+use std::collections::VecDeque;
+
+use crate::{
+    locale, spawn, story,
+    world::{attack, provoke, with_article, World},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// `attacker` goes for `target`, e.g. by bumping into it.
+    WantsToAttack { attacker: usize, target: usize },
+    /// `target` lost `amount` health, because of `attacker` if it was
+    /// anybody.
+    Damaged {
+        attacker: Option<usize>,
+        target: usize,
+        amount: i32,
+    },
+    /// `id` has just died and left a corpse.
+    Died { attacker: Option<usize>, id: usize },
+    /// `id` put the item at `index` of its inventory there, off the floor
+    /// or out of the chest `chest`.
+    PickedUp {
+        id: usize,
+        index: usize,
+        chest: Option<usize>,
+    },
+}
+
+/// The events that haven't been handled yet, oldest first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventQueue {
+    events: VecDeque<Event>,
+}
+
+impl EventQueue {
+    pub fn push(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    pub fn pop(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Handle every event in the queue, including the ones pushed while
+/// doing so.
+pub fn process(world: &mut World) {
+    while let Some(event) = world.events.pop() {
+        handle(world, event);
+    }
+}
+
+fn handle(world: &mut World, event: Event) {
+    let player_id = world.player_id;
+    match event {
+        Event::WantsToAttack { attacker, target } => {
+            if world.entities[attacker].is_alive() && world.entities[target].is_alive() {
+                attack(world, attacker, target);
+            }
+        }
+        Event::Damaged {
+            attacker,
+            target,
+            amount,
+        } => {
+            if target == player_id {
+                world.stats.damage_taken += amount;
+            }
+            if attacker == Some(player_id) {
+                world.stats.damage_dealt += amount;
+                provoke(world, target);
+            }
+        }
+        Event::Died { attacker, id } => {
+            let entity = &mut world.entities[id];
+            let drops = std::mem::take(&mut entity.drops);
+            let loot = spawn::loot(&drops, &world.templates, entity.pos, &mut world.rng);
+            if !loot.is_empty() {
+                world.entities.extend(loot);
+                world.rebuild_spatial();
+            }
+            if attacker == Some(player_id) {
+                let name = world.entities[id].name.clone();
+                world.stats.record_kill(&name);
+            }
+        }
+        Event::PickedUp { id, index, chest } => {
+            if id == player_id {
+                let item = &world.entities[id].inventory[index];
+                let name = world.item_name(item);
+                let template = item.item.as_ref().map(|item| item.template.clone());
+                let key = if chest.is_some() {
+                    "log.take"
+                } else {
+                    "log.pick-up"
+                };
+                world.log(locale::message(key, &[("item", &with_article(&name))]));
+                if let Some(template) = template {
+                    story::picked_up(world, &template);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        tests::{goblin, player_with},
+        Entity,
+    };
+    use quicksilver::geom::Vector;
+
+    /// The player at (5, 5) with a goblin that always drops a dart right
+    /// next to them.
+    fn world() -> World {
+        let goblin = Entity {
+            drops: vec![("dart".to_string(), 1.0)],
+            ..goblin(6, 1)
+        };
+        player_with(vec![goblin], 5)
+    }
+
+    #[test]
+    fn attacks_lead_to_damage_and_death() {
+        let mut world = world();
+        let player_id = world.player_id;
+        world.events.push(Event::WantsToAttack {
+            attacker: player_id,
+            target: 0,
+        });
+        assert_eq!(world.stats.kills, 0);
+        process(&mut world);
+        assert!(world.events.is_empty());
+        assert!(!world.entities[0].is_alive());
+        assert_eq!(world.stats.damage_dealt, 1);
+        assert_eq!(world.stats.kills_by_monster["goblin"], 1);
+        assert!(world.item_at(Vector::new(6, 5)).is_some());
+    }
+
+    #[test]
+    fn the_dead_dont_attack() {
+        let mut world = world();
+        let player_id = world.player_id;
+        world.entities[0].die();
+        world.events.push(Event::WantsToAttack {
+            attacker: 0,
+            target: player_id,
+        });
+        process(&mut world);
+        assert_eq!(world.player().hp, 5);
+        assert_eq!(world.stats.damage_taken, 0);
+    }
+}
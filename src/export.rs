@@ -0,0 +1,64 @@
+//! The current level as plain text, for bug reports and for checking
+//! maps in tests without a window.
+//!
+//! Every tile is its glyph with the entities drawn over it, the whole
+//! level whether the player has seen it or not. Whoever blocks the tile
+//! covers the items and corpses under them and the player covers
+//! everybody.
+
+use crate::world::World;
+
+/// One line of glyphs per row of the map.
+pub fn ascii(world: &World) -> String {
+    let width = world.map.size.x as usize;
+    let height = world.map.size.y as usize;
+    let mut rows = vec![vec![' '; width]; height];
+    for tile in &world.map.tiles {
+        rows[tile.pos.y as usize][tile.pos.x as usize] = tile.glyph;
+    }
+    let mut entities: Vec<usize> = (0..world.entities.len()).collect();
+    entities.sort_by_key(|&id| (id == world.player_id, world.entities[id].blocks_tile));
+    for id in entities {
+        let entity = &world.entities[id];
+        if world.map.contains(entity.pos) {
+            rows[entity.pos.y as usize][entity.pos.x as usize] = entity.glyph;
+        }
+    }
+    let mut text = String::with_capacity((width + 1) * height);
+    for row in rows {
+        text.extend(row);
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::STAIRS_DOWN,
+        world::{
+            tests::{goblin, player_with},
+            Entity,
+        },
+    };
+    use quicksilver::geom::Vector;
+
+    #[test]
+    fn entities_are_drawn_over_the_tiles() {
+        let item = |x: i32, glyph: char| Entity {
+            pos: Vector::new(x, 5),
+            glyph,
+            ..Default::default()
+        };
+        // The goblin stands on the dart and the player on the gold
+        let monsters = vec![item(5, '$'), goblin(6, 1), item(6, ')')];
+        let mut world = player_with(monsters, 5);
+        world.entities[world.player_id].glyph = '@';
+        world.map.set_glyph(Vector::new(7, 5), STAIRS_DOWN);
+        let text = ascii(&world);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 15);
+        assert_eq!(lines[5], "#....@g>...........#");
+    }
+}
@@ -0,0 +1,112 @@
+//! Making hits feel like hits.
+//!
+//! Purely visual: the screen shakes for a moment when the player gets
+//! hurt and whoever got hit flashes white. The UI feeds every `Hit` in
+//! here, ticks it once per update and asks it how to draw things. None
+//! of this touches the game state.
+
+use quicksilver::geom::Vector;
+
+use crate::{timing, world::Hit};
+
+/// How long the screen shakes after the player gets hurt.
+pub const SHAKE_SECONDS: f64 = 0.25;
+
+/// How far (in pixels) the map moves at the start of a shake.
+pub const SHAKE_PX: f32 = 4.0;
+
+/// How long a hurt entity stays white.
+pub const FLASH_SECONDS: f64 = 0.15;
+
+/// The directions the map jumps in, one after another. A fixed pattern
+/// rather than random numbers so the UI doesn't need its own generator.
+const JITTER: [(f32, f32); 4] = [(1.0, 0.0), (-1.0, 1.0), (0.0, -1.0), (-1.0, -1.0)];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Feedback {
+    /// The updates left until the shake stops.
+    shake: u32,
+    /// The entities flashing with the updates left for each.
+    flashes: Vec<(usize, u32)>,
+}
+
+impl Feedback {
+    /// React to somebody getting hurt.
+    pub fn hit(&mut self, hit: &Hit, player_id: usize) {
+        if hit.target == player_id {
+            self.shake = timing::updates(SHAKE_SECONDS);
+        }
+        self.flashes.retain(|&(id, _)| id != hit.target);
+        self.flashes
+            .push((hit.target, timing::updates(FLASH_SECONDS)));
+    }
+
+    /// Count one update.
+    pub fn tick(&mut self) {
+        self.shake = self.shake.saturating_sub(1);
+        for (_, left) in self.flashes.iter_mut() {
+            *left -= 1;
+        }
+        self.flashes.retain(|&(_, left)| left > 0);
+    }
+
+    /// Stop everything, e.g. when a new game starts.
+    pub fn clear(&mut self) {
+        *self = Feedback::default();
+    }
+
+    /// How far to move the map this frame. It calms down as the shake
+    /// wears off.
+    pub fn shake_offset(&self) -> Vector {
+        if self.shake == 0 {
+            return Vector::ZERO;
+        }
+        let strength = SHAKE_PX * self.shake as f32 / timing::updates(SHAKE_SECONDS) as f32;
+        let (x, y) = JITTER[self.shake as usize % JITTER.len()];
+        Vector::new(x * strength, y * strength)
+    }
+
+    pub fn is_flashing(&self, id: usize) -> bool {
+        self.flashes.iter().any(|&(flashing, _)| flashing == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(target: usize) -> Hit {
+        Hit {
+            target,
+            pos: Vector::ZERO,
+            damage: 1,
+        }
+    }
+
+    #[test]
+    fn only_hurting_the_player_shakes_the_screen() {
+        let mut feedback = Feedback::default();
+        feedback.hit(&hit(3), 0);
+        assert_eq!(feedback.shake_offset(), Vector::ZERO);
+        assert!(feedback.is_flashing(3));
+
+        feedback.hit(&hit(0), 0);
+        assert_ne!(feedback.shake_offset(), Vector::ZERO);
+        for _ in 0..timing::updates(SHAKE_SECONDS) {
+            feedback.tick();
+        }
+        assert_eq!(feedback, Feedback::default());
+    }
+
+    #[test]
+    fn flashes_wear_off() {
+        let mut feedback = Feedback::default();
+        feedback.hit(&hit(1), 0);
+        for _ in 1..timing::updates(FLASH_SECONDS) {
+            feedback.tick();
+        }
+        assert!(feedback.is_flashing(1));
+        feedback.tick();
+        assert!(!feedback.is_flashing(1));
+    }
+}
@@ -0,0 +1,118 @@
+//! What the player can see right now and what they remember.
+
+use quicksilver::{geom::Vector, graphics::Color};
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    geometry::tile_key,
+    world::{is_hostile, World},
+};
+
+/// The player's view of the current level.
+///
+/// The map only shows the tiles the player has seen. Items they saw
+/// stay where they were last seen until the player looks again, even
+/// if a monster has picked them up since.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fov {
+    pub visible: HashSet<(i32, i32)>,
+    pub explored: HashSet<(i32, i32)>,
+    /// The glyph and colour of the items last seen on each tile.
+    pub remembered_items: HashMap<(i32, i32), (char, Color)>,
+    /// The hostile entities in view, nearest first.
+    pub targets: Vec<usize>,
+}
+
+impl Fov {
+    pub fn is_visible(&self, pos: Vector) -> bool {
+        self.visible.contains(&tile_key(pos))
+    }
+
+    pub fn is_explored(&self, pos: Vector) -> bool {
+        self.explored.contains(&tile_key(pos))
+    }
+
+    /// Look around from where the player stands now.
+    pub fn update(world: &World) -> Fov {
+        let mut fov = world.fov.clone();
+        fov.visible = world
+            .map
+            .tiles
+            .iter()
+            .map(|tile| tile.pos)
+            .filter(|&pos| world.is_visible(pos))
+            .map(tile_key)
+            .collect();
+        for &key in &fov.visible {
+            fov.explored.insert(key);
+            fov.remembered_items.remove(&key);
+        }
+        for entity in &world.entities {
+            if entity.item.is_some() && fov.is_visible(entity.pos) {
+                let memory = (entity.glyph, entity.color);
+                fov.remembered_items.insert(tile_key(entity.pos), memory);
+            }
+        }
+        let player_pos = world.player().pos;
+        fov.targets = (0..world.entities.len())
+            .filter(|&id| world.entities[id].is_alive() && world.sees_entity(id))
+            .filter(|&id| is_hostile(world, world.player_id, id))
+            .collect();
+        fov.targets.sort_by_key(|&id| {
+            let offset = world.entities[id].pos - player_pos;
+            ((offset.x * offset.x + offset.y * offset.y) as i32, id)
+        });
+        fov
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{generate_map, WALL},
+        world::{Action, Entity, Item},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn seen_tiles_and_items_are_remembered() {
+        let mut map = generate_map(Vector::new(20, 5));
+        // A wall splitting the room in two, with a gap at the top
+        for y in 2..4 {
+            map.set_glyph(Vector::new(10, y), WALL);
+        }
+        let dart = Entity {
+            pos: Vector::new(8, 3),
+            glyph: ')',
+            item: Some(Item {
+                template: "dart".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let player = Entity {
+            pos: Vector::new(6, 3),
+            hp: 5,
+            max_hp: 5,
+            blocks_tile: true,
+            ..Default::default()
+        };
+        let mut world = World::from_parts(0, StdRng::seed_from_u64(0), map, vec![dart], player);
+        assert!(world.fov.is_visible(Vector::new(8, 3)));
+        assert!(!world.fov.is_explored(Vector::new(12, 3)));
+        assert!(world.fov.remembered_items.contains_key(&(8, 3)));
+
+        // Around the wall
+        for &(x, y, steps) in &[(1, 0, 3), (0, -1, 2), (1, 0, 2), (0, 1, 2)] {
+            for _ in 0..steps {
+                world.player_turn(Action::Move(Vector::new(x, y)));
+            }
+        }
+        assert_eq!(world.player().pos, Vector::new(11, 3));
+        assert!(!world.fov.is_visible(Vector::new(8, 3)));
+        assert!(world.fov.is_explored(Vector::new(8, 3)));
+        assert!(world.fov.remembered_items.contains_key(&(8, 3)));
+    }
+}
@@ -0,0 +1,146 @@
+//! Geometry helpers operating on the tile grid.
+//!
+//! All the positions here are in tile coordinates (not pixels). They're
+//! stored in quicksilver's `Vector` like everywhere else in the game,
+//! but only the whole-number part is ever considered.
+
+use quicksilver::geom::Vector;
+
+/// The whole-number coordinates of the tile at `pos`. Unlike `Vector`
+/// these can be used as a `HashMap` key.
+pub fn tile_key(pos: Vector) -> (i32, i32) {
+    (pos.x as i32, pos.y as i32)
+}
+
+/// Return all the tiles on the line between `from` and `to` (both
+/// included), using Bresenham's line algorithm.
+///
+/// The line is symmetric: `line(a, b)` visits the same tiles as
+/// `line(b, a)`, just in reverse order. Without that, a monster could
+/// see the player while the player couldn't see the monster.
+pub fn line(from: Vector, to: Vector) -> Vec<Vector> {
+    let (x0, y0) = (from.x as i32, from.y as i32);
+    let (x1, y1) = (to.x as i32, to.y as i32);
+
+    // Always walk the line in the same direction so that the rounding
+    // decisions are identical no matter which endpoint we start at.
+    if (x1, y1) < (x0, y0) {
+        let mut points = bresenham((x1, y1), (x0, y0));
+        points.reverse();
+        return points;
+    }
+    bresenham((x0, y0), (x1, y1))
+}
+
+fn bresenham(from: (i32, i32), to: (i32, i32)) -> Vec<Vector> {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - from.0).abs();
+    let dy = -(to.1 - from.1).abs();
+    let step_x = if from.0 < to.0 { 1 } else { -1 };
+    let step_y = if from.1 < to.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    let mut points = Vec::with_capacity((dx - dy) as usize + 1);
+    loop {
+        points.push(Vector::new(x, y));
+        if (x, y) == to {
+            break;
+        }
+        let double_error = 2 * error;
+        if double_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if double_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+    points
+}
+
+/// Return `true` if nothing blocks the view between `from` and `to`.
+///
+/// `is_blocked` tells whether a given tile stops the line of sight
+/// (e.g. a wall). The endpoints themselves are never checked: you can
+/// always see the wall you're looking at and the tile you're standing
+/// on.
+pub fn los(from: Vector, to: Vector, is_blocked: impl Fn(Vector) -> bool) -> bool {
+    let points = line(from, to);
+    let inner = points.len().saturating_sub(1);
+    points
+        .iter()
+        .take(inner)
+        .skip(1)
+        .all(|&pos| !is_blocked(pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(coords: &[(i32, i32)]) -> Vec<Vector> {
+        coords.iter().map(|&pos| Vector::from(pos)).collect()
+    }
+
+    #[test]
+    fn line_to_itself_is_a_single_point() {
+        assert_eq!(
+            line(Vector::new(3, 4), Vector::new(3, 4)),
+            points(&[(3, 4)])
+        );
+    }
+
+    #[test]
+    fn straight_and_diagonal_lines() {
+        assert_eq!(
+            line(Vector::new(0, 0), Vector::new(3, 0)),
+            points(&[(0, 0), (1, 0), (2, 0), (3, 0)])
+        );
+        assert_eq!(
+            line(Vector::new(2, 5), Vector::new(2, 3)),
+            points(&[(2, 5), (2, 4), (2, 3)])
+        );
+        assert_eq!(
+            line(Vector::new(0, 0), Vector::new(2, 2)),
+            points(&[(0, 0), (1, 1), (2, 2)])
+        );
+    }
+
+    #[test]
+    fn lines_are_contiguous() {
+        let path = line(Vector::new(1, 2), Vector::new(9, 5));
+        assert_eq!(path.first(), Some(&Vector::new(1, 2)));
+        assert_eq!(path.last(), Some(&Vector::new(9, 5)));
+        for pair in path.windows(2) {
+            let step = pair[1] - pair[0];
+            assert!(step.x.abs() <= 1.0 && step.y.abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn lines_are_symmetric() {
+        for &(from, to) in &[((0, 0), (5, 2)), ((7, 1), (2, 6)), ((3, 9), (4, 0))] {
+            let (from, to) = (Vector::from(from), Vector::from(to));
+            let mut backwards = line(to, from);
+            backwards.reverse();
+            assert_eq!(line(from, to), backwards);
+        }
+    }
+
+    #[test]
+    fn walls_block_line_of_sight() {
+        let wall = Vector::new(2, 0);
+        let is_blocked = |pos: Vector| pos == wall;
+        assert!(!los(Vector::new(0, 0), Vector::new(4, 0), is_blocked));
+        assert!(!los(Vector::new(4, 0), Vector::new(0, 0), is_blocked));
+        assert!(los(Vector::new(0, 1), Vector::new(4, 1), is_blocked));
+    }
+
+    #[test]
+    fn endpoints_do_not_block_line_of_sight() {
+        let is_blocked = |_pos: Vector| true;
+        assert!(los(Vector::new(0, 0), Vector::new(1, 1), is_blocked));
+        assert!(!los(Vector::new(0, 0), Vector::new(2, 2), is_blocked));
+    }
+}
@@ -0,0 +1,262 @@
+//! Clouds of gas and patches of fire that linger for a few turns.
+//!
+//! Every hazard covers one tile, a cloud is all the gas around where a
+//! confusion potion shattered. Hazards burn down a turn at a time and
+//! gas drifts onto the open tiles next to it, thinning out as it goes.
+//! Whoever is standing in one at the end of a turn gets its effect: gas
+//! confuses, fire burns.
+
+use quicksilver::geom::Vector;
+use quicksilver::graphics::Color;
+use rand::Rng;
+
+use crate::{
+    locale,
+    world::{take_damage, World},
+};
+
+/// How many tiles around the spot a shattered potion fills with gas.
+pub const GAS_RADIUS: i32 = 1;
+
+/// How long standing in gas keeps anybody confused for.
+pub const GAS_CONFUSION_TURNS: u32 = 3;
+
+/// How much damage standing in fire does every turn.
+pub const FIRE_DAMAGE: i32 = 1;
+
+/// The chance of a confused player stepping somewhere else than they
+/// meant to.
+pub const STUMBLE_CHANCE: f64 = 0.5;
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HazardKind {
+    Gas,
+    Fire,
+}
+
+impl HazardKind {
+    /// How many turns a fresh hazard of this kind lasts.
+    pub fn turns(self) -> u32 {
+        match self {
+            HazardKind::Gas => 6,
+            HazardKind::Fire => 4,
+        }
+    }
+
+    /// The chance of the hazard spreading to a tile next to it every
+    /// turn.
+    fn spread_chance(self) -> f64 {
+        match self {
+            HazardKind::Gas => 0.5,
+            HazardKind::Fire => 0.1,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            HazardKind::Gas => Color {
+                r: 0.6,
+                g: 0.3,
+                b: 0.9,
+                a: 1.0,
+            },
+            HazardKind::Fire => Color {
+                r: 1.0,
+                g: 0.45,
+                b: 0.0,
+                a: 1.0,
+            },
+        }
+    }
+
+    /// The look panel's line about the tile.
+    pub fn description(self) -> String {
+        locale::text(match self {
+            HazardKind::Gas => "look.gas",
+            HazardKind::Fire => "look.fire",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hazard {
+    pub kind: HazardKind,
+    pub pos: Vector,
+    /// Turns until it's gone.
+    pub turns: u32,
+}
+
+impl Hazard {
+    /// The colour drawn over the tile, fading as the hazard runs out.
+    pub fn overlay(&self) -> Color {
+        let strength = (self.turns as f32 / self.kind.turns() as f32).min(1.0);
+        self.kind.color().with_alpha(0.15 + 0.35 * strength)
+    }
+}
+
+/// The hazard on the tile at `pos`, if there's one.
+pub fn at(world: &World, pos: Vector) -> Option<&Hazard> {
+    world.hazards.iter().find(|hazard| hazard.pos == pos)
+}
+
+/// Fill the open tiles up to `radius` tiles from `center` with fresh
+/// hazards of `kind`.
+pub fn spawn(world: &mut World, kind: HazardKind, center: Vector, radius: i32) {
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            let pos = center + Vector::new(x, y);
+            if !world.map.is_blocked(pos) {
+                add(world, kind, pos, kind.turns());
+            }
+        }
+    }
+}
+
+/// Put a hazard on `pos`, replacing whatever other kind was there. Fire
+/// burns the gas away and the gas puts the fire out.
+fn add(world: &mut World, kind: HazardKind, pos: Vector, turns: u32) {
+    match world.hazards.iter_mut().find(|hazard| hazard.pos == pos) {
+        Some(hazard) if hazard.kind == kind => hazard.turns = hazard.turns.max(turns),
+        Some(hazard) => *hazard = Hazard { kind, pos, turns },
+        None => world.hazards.push(Hazard { kind, pos, turns }),
+    }
+}
+
+/// Everything that happens to and because of the hazards at the end of
+/// a turn.
+pub fn update(world: &mut World) {
+    world.confused = world.confused.saturating_sub(1);
+    for id in 0..world.entities.len() {
+        let kind = at(world, world.entities[id].pos).map(|hazard| hazard.kind);
+        if let (Some(kind), true) = (kind, world.entities[id].is_alive()) {
+            affect(world, id, kind);
+        }
+    }
+
+    for hazard in &mut world.hazards {
+        hazard.turns -= 1;
+    }
+    world.hazards.retain(|hazard| hazard.turns > 0);
+    // What's spread out lasts a turn less than where it came from
+    let mut spread = vec![];
+    for hazard in &world.hazards {
+        if hazard.turns > 1 && world.rng.gen_bool(hazard.kind.spread_chance()) {
+            let (x, y) = DIRECTIONS[world.rng.gen_range(0, DIRECTIONS.len())];
+            spread.push((
+                hazard.kind,
+                hazard.pos + Vector::new(x, y),
+                hazard.turns - 1,
+            ));
+        }
+    }
+    for (kind, pos, turns) in spread {
+        if !world.map.is_blocked(pos) && at(world, pos).is_none() {
+            add(world, kind, pos, turns);
+        }
+    }
+}
+
+fn affect(world: &mut World, id: usize, kind: HazardKind) {
+    let name = world.entities[id].name.clone();
+    let seen = world.is_visible(world.entities[id].pos);
+    match kind {
+        HazardKind::Gas if id == world.player_id => {
+            if world.confused == 0 {
+                world.log(locale::text("log.gas-confuses-you"));
+            }
+            world.confused = GAS_CONFUSION_TURNS;
+        }
+        HazardKind::Gas => {
+            let ai = match &mut world.entities[id].ai {
+                Some(ai) => ai,
+                None => return,
+            };
+            let fresh = ai.confused == 0;
+            ai.confused = ai.confused.max(GAS_CONFUSION_TURNS);
+            if fresh && seen {
+                world.log(locale::message("log.looks-confused", &[("name", &name)]));
+            }
+        }
+        HazardKind::Fire => {
+            let killed = take_damage(world, None, id, FIRE_DAMAGE);
+            if id == world.player_id {
+                world.log(locale::text("log.fire-burns-you"));
+            } else if seen && killed {
+                world.log(locale::message("log.burns-to-death", &[("name", &name)]));
+            } else if seen {
+                world.log(locale::message("log.burned-by-fire", &[("name", &name)]));
+            }
+        }
+    }
+}
+
+/// Where the player actually steps when they try to move by `offset`.
+/// Confused, they may well go another way.
+pub fn stumble(world: &mut World, offset: Vector) -> Vector {
+    if world.confused == 0 || !world.rng.gen_bool(STUMBLE_CHANCE) {
+        return offset;
+    }
+    let (x, y) = DIRECTIONS[world.rng.gen_range(0, DIRECTIONS.len())];
+    world.log(locale::text("log.stumble"));
+    Vector::new(x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        tests::{goblin, player_with},
+        Action,
+    };
+
+    /// The player at (5, 5) and a goblin at (10, 5).
+    fn world() -> World {
+        player_with(vec![goblin(10, 4)], 10)
+    }
+
+    #[test]
+    fn gas_confuses_and_clears_up() {
+        let mut world = world();
+        spawn(&mut world, HazardKind::Gas, Vector::new(5, 5), GAS_RADIUS);
+        spawn(&mut world, HazardKind::Gas, Vector::new(10, 5), 0);
+        assert_eq!(world.hazards.len(), 10);
+        update(&mut world);
+        assert_eq!(world.confused, GAS_CONFUSION_TURNS);
+        assert!(world.entities[0].ai.as_ref().unwrap().confused > 0);
+
+        // It spreads but never lasts longer than it started with
+        for _ in 1..HazardKind::Gas.turns() {
+            world.player_turn(Action::Wait);
+        }
+        assert!(world.hazards.is_empty());
+        for _ in 0..GAS_CONFUSION_TURNS {
+            world.player_turn(Action::Wait);
+        }
+        assert_eq!(world.confused, 0);
+    }
+
+    #[test]
+    fn fire_burns_and_puts_out_gas() {
+        let mut world = world();
+        spawn(&mut world, HazardKind::Gas, Vector::new(5, 5), 0);
+        spawn(&mut world, HazardKind::Fire, Vector::new(5, 5), 0);
+        assert_eq!(world.hazards.len(), 1);
+        assert_eq!(
+            at(&world, Vector::new(5, 5)).unwrap().kind,
+            HazardKind::Fire
+        );
+
+        world.player_turn(Action::Wait);
+        assert_eq!(world.player().hp, 10 - FIRE_DAMAGE);
+        assert_eq!(world.confused, 0);
+        assert!(world
+            .log
+            .iter()
+            .any(|message| message == "The fire burns you!"));
+        // Walls don't burn
+        spawn(&mut world, HazardKind::Fire, Vector::new(0, 0), 0);
+        assert!(at(&world, Vector::new(0, 0)).is_none());
+    }
+}
@@ -0,0 +1,85 @@
+//! The screen with every message of the run, not just the last few
+//! under the map.
+
+use crate::message::Message;
+
+/// Where the message history is scrolled to and what it's filtered by.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct History {
+    /// How many lines above the newest one the view ends.
+    pub scroll: usize,
+    /// Only the messages containing this are shown. Case doesn't
+    /// matter.
+    pub search: String,
+    /// Whether typed keys go into `search`.
+    pub typing: bool,
+}
+
+impl History {
+    /// The messages matching the search, oldest first.
+    pub fn matches<'a>(&self, log: &'a [Message]) -> Vec<&'a Message> {
+        let search = self.search.to_lowercase();
+        log.iter()
+            .filter(|message| message.to_string().to_lowercase().contains(&search))
+            .collect()
+    }
+
+    /// The part of `lines` in view when `per_page` of them fit.
+    pub fn visible<'a, T>(&self, lines: &'a [T], per_page: usize) -> &'a [T] {
+        let end = lines.len().saturating_sub(self.scroll);
+        &lines[end.saturating_sub(per_page)..end]
+    }
+
+    /// Go a page back towards the oldest of `total` lines.
+    pub fn page_up(&mut self, per_page: usize, total: usize) {
+        self.scroll = (self.scroll + per_page).min(total.saturating_sub(per_page));
+    }
+
+    /// Go a page forward towards the newest line.
+    pub fn page_down(&mut self, per_page: usize) {
+        self.scroll = self.scroll.saturating_sub(per_page);
+    }
+
+    /// Type into the search. The view jumps back to the newest match.
+    pub fn edit_search(&mut self, edit: impl FnOnce(&mut String)) {
+        edit(&mut self.search);
+        self.scroll = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_search_ignores_case() {
+        let log: Vec<Message> = [
+            "You hit the goblin.",
+            "The orc hits you.",
+            "The goblin dies.",
+        ]
+        .iter()
+        .map(|&text| Message::from(text))
+        .collect();
+        let mut history = History::default();
+        assert_eq!(history.matches(&log).len(), 3);
+        history.edit_search(|search| search.push_str("GOBLIN"));
+        assert_eq!(history.matches(&log), vec![&log[0], &log[2]]);
+    }
+
+    #[test]
+    fn scrolling_stops_at_both_ends() {
+        let lines: Vec<usize> = (0..10).collect();
+        let mut history = History::default();
+        assert_eq!(history.visible(&lines, 4), &[6, 7, 8, 9]);
+        history.page_up(4, lines.len());
+        assert_eq!(history.visible(&lines, 4), &[2, 3, 4, 5]);
+        history.page_up(4, lines.len());
+        assert_eq!(history.visible(&lines, 4), &[0, 1, 2, 3]);
+        history.page_down(4);
+        history.page_down(4);
+        history.page_down(4);
+        assert_eq!(history.visible(&lines, 4), &[6, 7, 8, 9]);
+        assert!(history.visible(&lines[..0], 4).is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+//! Reloading the templates while the game runs, so tweaking a monster's
+//! stats doesn't take starting a new run.
+//!
+//! Debug builds on the desktop keep a `Watcher` on `TEMPLATES_PATH` and
+//! once it changes, `reload` swaps the new templates in and respawns
+//! every monster whose template is different now. Templates the world
+//! can't do without (one of its dungeons, or the template of a monster
+//! or item that's in it) have to stay, or the reload is refused. The key
+//! bindings are `input::KEYMAP`, which is compiled in, so they still need
+//! a rebuild.
+//! Reloads aren't recorded, so a replay of the game won't play back the
+//! same: the game stops saving once it's had one.
+
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    packs, spawn,
+    templates::Templates,
+    world::{Ai, Entity, World},
+};
+
+/// The templates in the source tree, which the built-in ones come from.
+pub const TEMPLATES_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/static/templates.ron");
+
+/// How often the file is checked for changes.
+pub const POLL_SECONDS: f64 = 1.0;
+
+/// Notices when a file is modified.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Watcher {
+    path: PathBuf,
+    /// As of the last check, `None` if the file couldn't be read.
+    modified: Option<SystemTime>,
+}
+
+impl Watcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = modified(&path);
+        Watcher { path, modified }
+    }
+
+    /// Whether the file has been modified since the last check. A file
+    /// that's gone missing doesn't count until it's back.
+    pub fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        let changed = modified.is_some() && modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+
+    /// Read the templates from the file, with the content packs' changes
+    /// on top.
+    pub fn templates(&self) -> Result<Templates, String> {
+        let text = std::fs::read_to_string(&self.path).map_err(|error| error.to_string())?;
+        let templates = Templates::parse_with(&text, &packs::current().overlays)?;
+        fit_prefabs(templates)
+    }
+}
+
+/// Check the prefabs are still all right with `templates`, the way they
+/// are when the content loads (see `packs::Content::new`).
+fn fit_prefabs(templates: Templates) -> Result<Templates, String> {
+    for prefab in &packs::current().prefabs {
+        prefab.validate(&templates)?;
+    }
+    Ok(templates)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Use `templates` from now on and respawn the monsters, on every level,
+/// whose template has changed. Returns how many were respawned, or why
+/// the templates don't fit the world (which keeps the old ones).
+pub fn reload(world: &mut World, templates: Templates) -> Result<usize, String> {
+    check(world, &templates)?;
+    let old = std::mem::replace(&mut world.templates, templates);
+    let mut respawned = 0;
+    for entity in &mut world.entities {
+        respawned += respawn(entity, &old, &world.templates) as usize;
+    }
+    for level in world.levels.iter_mut() {
+        for entity in &mut level.entities {
+            respawned += respawn(entity, &old, &world.templates) as usize;
+        }
+    }
+    world.rebuild_spatial();
+    Ok(respawned)
+}
+
+/// Make sure `templates` still have everything `world` refers to.
+fn check(world: &World, templates: &Templates) -> Result<(), String> {
+    let dungeons = world.entrances.len().max(world.dungeon + 1);
+    if templates.dungeons.len() < dungeons {
+        return Err(format!(
+            "The world has {} dungeons but the templates only {}.",
+            dungeons,
+            templates.dungeons.len()
+        ));
+    }
+    let levels = world.levels.iter().map(|level| &level.entities);
+    for entity in std::iter::once(&world.entities).chain(levels).flatten() {
+        check_entity(entity, templates)?;
+    }
+    Ok(())
+}
+
+fn check_entity(entity: &Entity, templates: &Templates) -> Result<(), String> {
+    let monsters = entity
+        .template
+        .iter()
+        .chain(entity.ai.iter().flat_map(|ai| &ai.summons));
+    for monster in monsters {
+        if !templates.monsters.contains_key(monster) {
+            return Err(format!("The monster `{}` is still in the world.", monster));
+        }
+    }
+    let items = entity.item.iter().map(|item| &item.template);
+    for item in items.chain(entity.drops.iter().map(|(item, _)| item)) {
+        if !templates.items.contains_key(item) {
+            return Err(format!("The item `{}` is still in the world.", item));
+        }
+    }
+    entity
+        .inventory
+        .iter()
+        .try_for_each(|entity| check_entity(entity, templates))
+}
+
+/// Replace a living monster with a fresh one from its new template. It
+/// stays where it is with what it carries, keeps the damage it's taken
+/// and carries on with whatever it was doing.
+fn respawn(entity: &mut Entity, old: &Templates, new: &Templates) -> bool {
+    let id = match &entity.template {
+        Some(id) if entity.is_alive() => id.clone(),
+        _ => return false,
+    };
+    let template = match new.monsters.get(&id) {
+        Some(template) if old.monsters.get(&id) != Some(template) => template,
+        _ => return false,
+    };
+    let mut fresh = spawn::monster(&id, template, entity.pos);
+    let damage = entity.max_hp - entity.hp;
+    fresh.hp = (fresh.max_hp - damage).max(1);
+    fresh.inventory = std::mem::take(&mut entity.inventory);
+    fresh.gold = entity.gold;
+    fresh.last_hurt = entity.last_hurt;
+    fresh.cooldowns = entity.cooldowns.clone();
+    fresh.wait = entity.wait;
+    if let (Some(ai), Some(old_ai)) = (&mut fresh.ai, &entity.ai) {
+        *ai = Ai {
+            behavior: ai.behavior,
+            flees: ai.flees,
+            summons: ai.summons.take(),
+            ..old_ai.clone()
+        };
+    }
+    *entity = fresh;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::tests::player_with;
+    use quicksilver::geom::Vector;
+
+    #[test]
+    fn changed_monsters_are_respawned() {
+        let templates = Templates::builtin();
+        let goblin = spawn::monster("goblin", &templates.monsters["goblin"], Vector::new(8, 5));
+        let rat = spawn::monster("rat", &templates.monsters["rat"], Vector::new(10, 5));
+        let mut world = player_with(vec![goblin, rat], 5);
+        // Goblins only have the one hit point
+        world.entities[0].max_hp += 1;
+        world.entities[0].ai.as_mut().unwrap().alerted = true;
+        let rat_before = world.entities[1].clone();
+
+        let mut changed = templates.clone();
+        let goblin = changed.monsters.get_mut("goblin").unwrap();
+        goblin.hp += 10;
+        goblin.glyph = 'G';
+        let max_hp = goblin.hp;
+        assert_eq!(reload(&mut world, changed), Ok(1));
+        let goblin = &world.entities[0];
+        assert_eq!(
+            (goblin.glyph, goblin.hp, goblin.max_hp),
+            ('G', max_hp - 1, max_hp)
+        );
+        assert_eq!(goblin.pos, Vector::new(8, 5));
+        assert!(goblin.ai.as_ref().unwrap().alerted);
+        assert_eq!(world.entities[1], rat_before);
+    }
+
+    #[test]
+    fn prefabs_keep_their_monsters() {
+        let mut templates = Templates::builtin();
+        assert!(fit_prefabs(templates.clone()).is_ok());
+        templates.monsters.remove("troll");
+        assert_eq!(
+            fit_prefabs(templates),
+            Err("Unknown monster `troll` in `vault`.".to_string())
+        );
+    }
+
+    #[test]
+    fn templates_the_world_uses_have_to_stay() {
+        let mut world = player_with(vec![], 5);
+        let dart = spawn::item("dart", &world.templates.items["dart"], Vector::new(0, 0));
+        world.entities[0].inventory.push(dart);
+        let before = world.templates.clone();
+
+        let mut changed = before.clone();
+        changed.items.remove("dart");
+        assert_eq!(
+            reload(&mut world, changed),
+            Err("The item `dart` is still in the world.".to_string())
+        );
+        let mut changed = before.clone();
+        changed.dungeons.clear();
+        assert!(reload(&mut world, changed).is_err());
+        assert_eq!(world.templates, before);
+    }
+}
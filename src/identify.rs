@@ -0,0 +1,308 @@
+//! Potions and scrolls the player hasn't figured out yet.
+//!
+//! Items whose template has an `appearance` (e.g. "potion") don't show
+//! their real name until they're identified. Each run hands out the
+//! appearance names listed in the templates in a different order, so a
+//! bubbly potion heals in one game and confuses in the next. Using an
+//! item, seeing it shatter on somebody or reading a scroll of identify
+//! tells the player what it is for the rest of the run.
+//!
+//! Equipment is known by name but any piece of it could be cursed. That
+//! stays hidden until the player puts it on or identifies it.
+
+use rand::{rngs::StdRng, seq::SliceRandom};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    locale,
+    templates::{Effect, Templates},
+    world::{with_article, Entity, World},
+};
+
+/// What this run's unidentified items look like and which ones the
+/// player knows.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Identification {
+    /// The name each unidentified item template shows up under.
+    appearances: BTreeMap<String, String>,
+    known: BTreeSet<String>,
+}
+
+impl Identification {
+    /// Shuffle the appearance names for a new run.
+    pub fn new(templates: &Templates, rng: &mut StdRng) -> Self {
+        let mut appearances = BTreeMap::new();
+        for (class, names) in &templates.appearances {
+            let mut names = names.clone();
+            names.shuffle(rng);
+            let items = templates
+                .items
+                .iter()
+                .filter(|(_, item)| item.appearance.as_ref() == Some(class));
+            for ((template, _), name) in items.zip(names) {
+                appearances.insert(template.clone(), name);
+            }
+        }
+        Identification {
+            appearances,
+            known: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_known(&self, template: &str) -> bool {
+        self.known.contains(template) || !self.appearances.contains_key(template)
+    }
+
+    /// Return `true` if the item wasn't known before.
+    pub fn identify(&mut self, template: &str) -> bool {
+        !self.is_known(template) && self.known.insert(template.to_string())
+    }
+
+    /// The name the player knows the item template by.
+    pub fn name(&self, templates: &Templates, template: &str) -> String {
+        match self.appearances.get(template) {
+            Some(appearance) if !self.known.contains(template) => locale::content(appearance),
+            _ => templates
+                .items
+                .get(template)
+                .map_or_else(|| template.to_string(), |item| locale::content(&item.name)),
+        }
+    }
+}
+
+/// Tell the player what `template` is if they didn't know yet.
+pub fn identify(world: &mut World, template: &str) {
+    let before = world.identification.name(&world.templates, template);
+    if world.identification.identify(template) {
+        let after = world.identification.name(&world.templates, template);
+        world.log(locale::message(
+            "log.identified",
+            &[("before", &before), ("after", &with_article(&after))],
+        ));
+    }
+}
+
+/// Return `true` if the player knows everything about the item: what
+/// it is and, for equipment, whether it's cursed.
+pub fn is_identified(world: &World, item: &Entity) -> bool {
+    match &item.item {
+        Some(details) => {
+            world.identification.is_known(&details.template)
+                && (details.curse_known || world.slot(item).is_none())
+        }
+        None => true,
+    }
+}
+
+/// Tell the player all about the inventory item at `index`.
+fn identify_item(world: &mut World, index: usize) {
+    let player_id = world.player_id;
+    let entity = &world.entities[player_id].inventory[index];
+    let template = match &entity.item {
+        Some(item) => item.template.clone(),
+        None => return,
+    };
+    identify(world, &template);
+    // Named before the curse is known so it doesn't say "cursed" twice
+    let name = world.item_name(&world.entities[player_id].inventory[index]);
+    let entity = &mut world.entities[player_id].inventory[index];
+    let cursed = match &mut entity.item {
+        Some(item) if !item.curse_known => {
+            item.curse_known = true;
+            item.cursed
+        }
+        _ => return,
+    };
+    if cursed {
+        world.log(locale::message("log.curse-revealed", &[("item", &name)]));
+    } else {
+        world.log(locale::message("log.not-cursed", &[("item", &name)]));
+    }
+}
+
+/// The player drinks or reads the inventory item at `index`.
+pub fn use_item(world: &mut World, index: usize) {
+    let player_id = world.player_id;
+    let template = match world
+        .player()
+        .inventory
+        .get(index)
+        .and_then(|item| item.item.as_ref())
+    {
+        Some(item) => item.template.clone(),
+        None => return world.log(locale::text("log.no-such-item")),
+    };
+    let name = world.item_name(&world.player().inventory[index]);
+    let effect = match world
+        .templates
+        .items
+        .get(&template)
+        .and_then(|item| item.effect)
+    {
+        Some(effect) => effect,
+        None => {
+            return world.log(locale::message(
+                "log.cannot-use",
+                &[("item", &with_article(&name))],
+            ))
+        }
+    };
+
+    world.entities[player_id].inventory.remove(index);
+    world.stats.items_used += 1;
+    match effect {
+        Effect::Heal(amount) => {
+            world.log(locale::message("log.drink", &[("item", &name)]));
+            let player = &mut world.entities[player_id];
+            player.hp = (player.hp + amount).min(player.max_hp);
+            world.log(locale::text("log.feel-better"));
+        }
+        Effect::Confuse(_) => {
+            world.log(locale::message("log.drink", &[("item", &name)]));
+            world.log(locale::text("log.feel-dizzy"));
+        }
+        Effect::Identify => {
+            world.log(locale::message("log.read", &[("item", &name)]));
+            identify(world, &template);
+            let unknown = world
+                .player()
+                .inventory
+                .iter()
+                .position(|item| !is_identified(world, item));
+            match unknown {
+                Some(other) => identify_item(world, other),
+                None => world.log(locale::text("log.nothing-to-identify")),
+            }
+            return;
+        }
+        Effect::RemoveCurse => {
+            world.log(locale::message("log.read", &[("item", &name)]));
+            let mut lifted = false;
+            for item in &mut world.entities[player_id].inventory {
+                if let Some(item) = &mut item.item {
+                    lifted |= item.cursed;
+                    item.cursed = false;
+                }
+            }
+            if lifted {
+                world.log(locale::text("log.curses-lifted"));
+            } else {
+                world.log(locale::text("log.nothing-happens"));
+            }
+        }
+    }
+    identify(world, &template);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{tests::player_with, Action, Item};
+    use rand::SeedableRng;
+
+    fn carried(template: &str) -> Entity {
+        Entity {
+            name: template.to_string(),
+            item: Some(Item {
+                template: template.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn world_with(inventory: Vec<Entity>) -> World {
+        let mut world = player_with(vec![], 5);
+        let player = &mut world.entities[world.player_id];
+        player.hp = 1;
+        player.inventory = inventory;
+        world.identification = Identification::new(&world.templates, &mut world.rng);
+        world
+    }
+
+    #[test]
+    fn appearances_change_between_runs() {
+        let templates = Templates::builtin();
+        let names: BTreeSet<String> = (0..10)
+            .map(|seed| {
+                let mut rng = StdRng::seed_from_u64(seed);
+                Identification::new(&templates, &mut rng).name(&templates, "healing potion")
+            })
+            .collect();
+        assert!(names.len() > 1);
+        assert!(names.iter().all(|name| name.ends_with("potion")));
+        assert!(!names.contains("healing potion"));
+        assert_eq!(
+            Identification::default().name(&templates, "healing potion"),
+            "healing potion"
+        );
+    }
+
+    #[test]
+    fn using_an_item_identifies_it() {
+        let mut world = world_with(vec![carried("healing potion"), carried("healing potion")]);
+        let appearance = world.item_name(&world.player().inventory[0]);
+        assert_ne!(appearance, "healing potion");
+
+        world.player_turn(Action::Use(0));
+        assert_eq!(world.player().hp, 4);
+        assert_eq!(world.player().inventory.len(), 1);
+        assert_eq!(
+            world.item_name(&world.player().inventory[0]),
+            "healing potion"
+        );
+        let identified = format!("The {} was a healing potion.", appearance);
+        assert!(world.log.iter().any(|message| *message == identified));
+    }
+
+    #[test]
+    fn scrolls_of_identify_identify_something_else() {
+        let mut world = world_with(vec![
+            carried("scroll of identify"),
+            carried("confusion potion"),
+        ]);
+        assert!(!world.identification.is_known("confusion potion"));
+        world.player_turn(Action::Use(0));
+        assert!(world.identification.is_known("scroll of identify"));
+        assert!(world.identification.is_known("confusion potion"));
+        assert!(!world.identification.is_known("healing potion"));
+    }
+
+    #[test]
+    fn cursed_equipment_sticks_until_the_curse_is_lifted() {
+        let mut sword = carried("sword");
+        sword.item.as_mut().unwrap().cursed = true;
+        let mut world = world_with(vec![sword, carried("scroll of remove curse")]);
+        assert_eq!(world.item_name(&world.player().inventory[0]), "sword");
+
+        world.player_turn(Action::Equip(0));
+        assert_eq!(
+            world.item_name(&world.player().inventory[0]),
+            "cursed sword"
+        );
+        world.player_turn(Action::Equip(0));
+        assert!(world.player().inventory[0].item.as_ref().unwrap().equipped);
+        assert_eq!(
+            world.log.last().unwrap(),
+            "You can't take off the cursed sword."
+        );
+
+        world.player_turn(Action::Use(1));
+        world.player_turn(Action::Equip(0));
+        assert!(!world.player().inventory[0].item.as_ref().unwrap().equipped);
+    }
+
+    #[test]
+    fn identifying_equipment_reveals_curses() {
+        let mut shield = carried("shield");
+        shield.item.as_mut().unwrap().cursed = true;
+        let mut world = world_with(vec![carried("scroll of identify"), shield]);
+        world.player_turn(Action::Use(0));
+        assert_eq!(
+            world.item_name(&world.player().inventory[0]),
+            "cursed shield"
+        );
+        assert_eq!(world.log.last().unwrap(), "The shield is cursed!");
+    }
+}
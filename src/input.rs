@@ -0,0 +1,326 @@
+//! Turning raw key presses into game actions.
+//!
+//! Every binding lives in `KEYMAP`. Both the input handling and the help
+//! screen read it so they can't get out of sync.
+
+use quicksilver::{geom::Vector, input::Key};
+
+use crate::{locale, skills::HOTBAR, wizard::Cheat, world::Action};
+
+/// Something that can be bound to a command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trigger {
+    /// A key pressed without Shift.
+    Key(Key),
+    /// A key pressed while holding Shift.
+    Shift(Key),
+    /// A typed character. Unlike keys these respect the keyboard layout.
+    Char(char),
+}
+
+impl Trigger {
+    /// The name shown on the help screen.
+    pub fn name(&self) -> String {
+        match self {
+            Trigger::Key(key) => format!("{:?}", key),
+            Trigger::Shift(key) => format!("Shift+{:?}", key),
+            Trigger::Char(c) => c.to_string(),
+        }
+    }
+}
+
+/// Everything the player can ask for from the keyboard.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Step (or attack) in the direction.
+    Move(i32, i32),
+    /// Keep going in the direction until something interesting happens.
+    Run(i32, i32),
+    Wait,
+    Rest,
+    Descend,
+    Ascend,
+    PickUp,
+    /// Choose an inventory item to equip or take off.
+    Equip,
+    /// Move a cursor around the map to see what's there.
+    Look,
+    /// Choose an inventory item and where to throw it.
+    Throw,
+    /// Choose where to shoot the equipped ranged weapon, see `ammo`.
+    Fire,
+    /// Choose an inventory item to drink or read.
+    Use,
+    /// Start or stop sneaking.
+    Sneak,
+    Help,
+    /// List the monsters killed so far.
+    Bestiary,
+    /// Every message so far, with scrolling and search.
+    History,
+    /// Show or hide the frame rate.
+    ToggleFps,
+    /// Save what's on the screen as a PNG.
+    Screenshot,
+    /// Open the pause menu, or close whatever screen is open.
+    Menu,
+    /// Open or close the debug console. Only in wizard mode.
+    Console,
+    /// Show or hide the entity inspector. Only in wizard mode.
+    Inspect,
+    /// Use the skill in the hotbar slot, see `skills::HOTBAR`.
+    Skill(usize),
+    /// Only in wizard mode.
+    Cheat(Cheat),
+}
+
+impl Command {
+    pub fn description(&self) -> String {
+        let key = match self {
+            Command::Move(x, y) => {
+                let direction = locale::text(direction_key(*x, *y));
+                return locale::format("command.move", &[("direction", &direction)]);
+            }
+            Command::Run(x, y) => {
+                let direction = locale::text(direction_key(*x, *y));
+                return locale::format("command.run", &[("direction", &direction)]);
+            }
+            Command::Skill(slot) => match HOTBAR.get(*slot).copied().flatten() {
+                Some(skill) => {
+                    return locale::format("command.skill", &[("skill", &skill.name())]);
+                }
+                None => "command.empty-slot",
+            },
+            Command::Cheat(Cheat::Heal) => "command.cheat-heal",
+            Command::Cheat(Cheat::Reveal) => "command.cheat-reveal",
+            Command::Cheat(Cheat::Descend) => "command.cheat-descend",
+            Command::Cheat(cheat) => {
+                return locale::format("command.cheat", &[("cheat", &cheat.to_text())]);
+            }
+            Command::Wait => "command.wait",
+            Command::Rest => "command.rest",
+            Command::Descend => "command.descend",
+            Command::Ascend => "command.ascend",
+            Command::PickUp => "command.pick-up",
+            Command::Equip => "command.equip",
+            Command::Look => "command.look",
+            Command::Throw => "command.throw",
+            Command::Fire => "command.fire",
+            Command::Use => "command.use",
+            Command::Sneak => "command.sneak",
+            Command::Help => "command.help",
+            Command::Bestiary => "command.bestiary",
+            Command::History => "command.history",
+            Command::ToggleFps => "command.toggle-fps",
+            Command::Screenshot => "command.screenshot",
+            Command::Menu => "command.menu",
+            Command::Console => "command.console",
+            Command::Inspect => "command.inspect",
+        };
+        locale::text(key)
+    }
+
+    /// Cheats are left out of the help outside of wizard mode.
+    pub fn is_cheat(&self) -> bool {
+        matches!(
+            self,
+            Command::Cheat(_) | Command::Console | Command::Inspect
+        )
+    }
+
+    /// The action this command takes in the world, if it's that simple.
+    pub fn action(&self) -> Option<Action> {
+        match self {
+            Command::Move(x, y) => Some(Action::Move(Vector::new(*x, *y))),
+            Command::Wait => Some(Action::Wait),
+            Command::Descend => Some(Action::Descend),
+            Command::Ascend => Some(Action::Ascend),
+            Command::PickUp => Some(Action::PickUp),
+            Command::Sneak => Some(Action::Sneak),
+            Command::Cheat(cheat) => Some(Action::Cheat(cheat.clone())),
+            Command::Run(..)
+            | Command::Rest
+            | Command::Equip
+            | Command::Look
+            | Command::Throw
+            | Command::Fire
+            | Command::Use
+            | Command::Skill(_)
+            | Command::Help
+            | Command::Bestiary
+            | Command::History
+            | Command::ToggleFps
+            | Command::Screenshot
+            | Command::Menu
+            | Command::Console
+            | Command::Inspect => None,
+        }
+    }
+}
+
+fn direction_key(x: i32, y: i32) -> &'static str {
+    match (x, y) {
+        (0, -1) => "direction.north",
+        (1, 0) => "direction.east",
+        (0, 1) => "direction.south",
+        (-1, 0) => "direction.west",
+        _ => "direction.somewhere",
+    }
+}
+
+/// All the key bindings. When several are triggered at once the one
+/// listed first wins.
+pub const KEYMAP: &[(Trigger, Command)] = &[
+    (Trigger::Shift(Key::Up), Command::Run(0, -1)),
+    (Trigger::Shift(Key::Right), Command::Run(1, 0)),
+    (Trigger::Shift(Key::Down), Command::Run(0, 1)),
+    (Trigger::Shift(Key::Left), Command::Run(-1, 0)),
+    (Trigger::Key(Key::Up), Command::Move(0, -1)),
+    (Trigger::Key(Key::Right), Command::Move(1, 0)),
+    (Trigger::Key(Key::Down), Command::Move(0, 1)),
+    (Trigger::Key(Key::Left), Command::Move(-1, 0)),
+    (Trigger::Char('.'), Command::Wait),
+    (Trigger::Key(Key::Numpad5), Command::Wait),
+    (Trigger::Char('R'), Command::Rest),
+    (Trigger::Char('>'), Command::Descend),
+    (Trigger::Char('<'), Command::Ascend),
+    (Trigger::Char('g'), Command::PickUp),
+    (Trigger::Char(','), Command::PickUp),
+    (Trigger::Char('w'), Command::Equip),
+    (Trigger::Char('x'), Command::Look),
+    (Trigger::Char('t'), Command::Throw),
+    (Trigger::Char('f'), Command::Fire),
+    (Trigger::Char('q'), Command::Use),
+    (Trigger::Char('r'), Command::Use),
+    (Trigger::Char('s'), Command::Sneak),
+    (Trigger::Char('1'), Command::Skill(0)),
+    (Trigger::Char('2'), Command::Skill(1)),
+    (Trigger::Char('3'), Command::Skill(2)),
+    (Trigger::Char('4'), Command::Skill(3)),
+    (Trigger::Char('5'), Command::Skill(4)),
+    (Trigger::Char('?'), Command::Help),
+    (Trigger::Char('B'), Command::Bestiary),
+    (Trigger::Char('V'), Command::History),
+    (Trigger::Key(Key::F3), Command::ToggleFps),
+    (Trigger::Key(Key::F12), Command::Screenshot),
+    (Trigger::Key(Key::Escape), Command::Menu),
+    (Trigger::Key(Key::F5), Command::Cheat(Cheat::Heal)),
+    (Trigger::Key(Key::F6), Command::Cheat(Cheat::Reveal)),
+    (Trigger::Key(Key::F7), Command::Cheat(Cheat::Descend)),
+    (Trigger::Char('`'), Command::Console),
+    (Trigger::Key(Key::F2), Command::Inspect),
+];
+
+/// The keymap as text: each command with all the keys bound to it. The
+/// cheats are only listed in wizard mode.
+pub fn help_lines(wizard: bool) -> Vec<String> {
+    let mut commands: Vec<(Command, Vec<String>)> = vec![];
+    for (trigger, command) in KEYMAP {
+        if command.is_cheat() && !wizard {
+            continue;
+        }
+        match commands.iter_mut().find(|(known, _)| known == command) {
+            Some((_, triggers)) => triggers.push(trigger.name()),
+            None => commands.push((command.clone(), vec![trigger.name()])),
+        }
+    }
+    commands
+        .iter()
+        .map(|(command, triggers)| format!("{:<16}{}", triggers.join(" "), command.description()))
+        .collect()
+}
+
+/// The keys pressed since the last update.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Input {
+    pub pressed: Vec<Key>,
+    /// The characters typed. Unlike `pressed` these respect the keyboard
+    /// layout and Shift, so e.g. `>` is easy to tell apart from `.`.
+    pub typed: Vec<char>,
+    /// Whether either Shift key was held down.
+    pub shift: bool,
+    /// How far the mouse wheel turned: below zero when rolled forward,
+    /// above when rolled back.
+    pub wheel: f32,
+}
+
+impl Input {
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    pub fn is_triggered(&self, trigger: Trigger) -> bool {
+        match trigger {
+            Trigger::Key(key) => !self.shift && self.is_pressed(key),
+            Trigger::Shift(key) => self.shift && self.is_pressed(key),
+            Trigger::Char(c) => self.typed.contains(&c),
+        }
+    }
+
+    /// The command the player asked for, if any. Only one counts: one
+    /// press is one turn.
+    pub fn command(&self) -> Option<Command> {
+        KEYMAP
+            .iter()
+            .find(|(trigger, _)| self.is_triggered(*trigger))
+            .map(|(_, command)| command.clone())
+    }
+
+    /// Buying and selling in a shop: a letter buys the item listed under
+    /// it, a capital letter sells the inventory item.
+    pub fn trade_action(&self) -> Option<Action> {
+        self.typed.iter().find_map(|&c| match c {
+            'a'..='z' => Some(Action::Buy(c as usize - 'a' as usize)),
+            'A'..='Z' => Some(Action::Sell(c as usize - 'A' as usize)),
+            _ => None,
+        })
+    }
+
+    /// Taking things out of a chest: a letter takes the item listed
+    /// under it, Enter takes everything.
+    pub fn chest_action(&self) -> Option<Action> {
+        if self.is_pressed(Key::Return) {
+            return Some(Action::TakeAll);
+        }
+        self.typed.iter().find_map(|&c| match c {
+            'a'..='z' => Some(Action::Take(c as usize - 'a' as usize)),
+            _ => None,
+        })
+    }
+
+    /// Picking an inventory item by the letter listed next to it. Case
+    /// doesn't matter here.
+    pub fn inventory_choice(&self) -> Option<usize> {
+        self.typed
+            .iter()
+            .find_map(|&c| match c.to_ascii_uppercase() {
+                c @ 'A'..='Z' => Some(c as usize - 'A' as usize),
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_turns_moving_into_running() {
+        let mut input = Input {
+            pressed: vec![Key::Left],
+            ..Default::default()
+        };
+        assert_eq!(input.command(), Some(Command::Move(-1, 0)));
+        input.shift = true;
+        assert_eq!(input.command(), Some(Command::Run(-1, 0)));
+    }
+
+    #[test]
+    fn the_help_lists_every_command_once() {
+        let lines = help_lines(false);
+        assert_eq!(lines.len(), 30);
+        assert_eq!(help_lines(true).len(), 35);
+        assert!(lines.contains(&format!("{:<16}Wait a turn", ". Numpad5")));
+        assert!(lines.contains(&format!("{:<16}Pick up an item", "g ,")));
+    }
+}
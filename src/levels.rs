@@ -0,0 +1,94 @@
+//! The levels the player has left, kept for when they come back.
+//!
+//! A level is only generated the first time the player gets there.
+//! Leaving it stores its map, everybody on it and what the player has
+//! explored in `World::levels`, and taking the stairs back restores all
+//! of it instead of generating a fresh one. Nothing happens on a level
+//! while the player is away.
+
+use quicksilver::graphics::Color;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{hazards::Hazard, map::Map, world::Entity};
+
+/// Which level: the surface or a level of one of the dungeons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LevelId {
+    pub dungeon: usize,
+    /// 0 is the surface, the dungeon levels start at 1.
+    pub depth: u32,
+}
+
+impl LevelId {
+    /// There's only one surface whichever dungeon the player came out
+    /// of.
+    pub const SURFACE: LevelId = LevelId {
+        dungeon: 0,
+        depth: 0,
+    };
+
+    pub fn new(dungeon: usize, depth: u32) -> Self {
+        if depth == 0 {
+            Self::SURFACE
+        } else {
+            LevelId { dungeon, depth }
+        }
+    }
+}
+
+/// Everything about a level that has to survive the player leaving it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedLevel {
+    pub map: Map,
+    /// Everybody but the player.
+    pub entities: Vec<Entity>,
+    pub explored: HashSet<(i32, i32)>,
+    pub remembered_items: HashMap<(i32, i32), (char, Color)>,
+    /// The gas and fire still hanging around. They wait for the player
+    /// to come back.
+    pub hazards: Vec<Hazard>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelStore {
+    levels: BTreeMap<LevelId, SavedLevel>,
+}
+
+impl LevelStore {
+    pub fn store(&mut self, id: LevelId, level: SavedLevel) {
+        self.levels.insert(id, level);
+    }
+
+    /// Take the level out to play it. It has to be stored again when the
+    /// player leaves.
+    pub fn take(&mut self, id: LevelId) -> Option<SavedLevel> {
+        self.levels.remove(&id)
+    }
+
+    pub fn get(&self, id: LevelId) -> Option<&SavedLevel> {
+        self.levels.get(&id)
+    }
+
+    /// Every stored level, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &SavedLevel> {
+        self.levels.values()
+    }
+
+    /// Every stored level, in no particular order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut SavedLevel> {
+        self.levels.values_mut()
+    }
+
+    pub fn contains(&self, id: LevelId) -> bool {
+        self.levels.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+}
@@ -0,0 +1,57 @@
+pub mod ai;
+pub mod ammo;
+pub mod animation;
+pub mod assets;
+pub mod bot;
+pub mod checksum;
+pub mod chest;
+pub mod config;
+pub mod daily;
+pub mod decoration;
+pub mod events;
+pub mod export;
+pub mod feedback;
+pub mod fov;
+pub mod geometry;
+pub mod hazards;
+pub mod history;
+pub mod hot_reload;
+pub mod identify;
+pub mod input;
+pub mod levels;
+pub mod lighting;
+pub mod locale;
+pub mod locks;
+pub mod map;
+pub mod mapgen;
+pub mod message;
+pub mod morgue;
+pub mod noise;
+#[cfg(feature = "observer")]
+pub mod observer;
+pub mod options;
+pub mod overworld;
+pub mod packs;
+pub mod pathfinding;
+pub mod prefabs;
+pub mod profile;
+pub mod regeneration;
+pub mod replay;
+pub mod saves;
+pub mod schedule;
+pub mod shop;
+pub mod skills;
+pub mod spatial;
+pub mod spawn;
+pub mod stats;
+pub mod story;
+pub mod targeting;
+pub mod templates;
+pub mod text_mode;
+pub mod throwing;
+pub mod tileset;
+pub mod timing;
+pub mod travel;
+pub mod ui;
+pub mod wizard;
+pub mod world;
@@ -0,0 +1,132 @@
+//! How brightly each tile is lit.
+//!
+//! The dungeon is dark unless its branch says otherwise. Light comes
+//! from entities with a `light` radius (the player's torch and the
+//! braziers scattered around) and from lava. The player only sees lit
+//! tiles, see `World::is_visible`.
+
+use quicksilver::geom::Vector;
+
+use std::collections::HashMap;
+
+use crate::{
+    geometry::{los, tile_key},
+    map::{BLOCKS_SIGHT, LAVA},
+    world::World,
+};
+
+/// How far the player's torch reaches.
+pub const TORCH_RADIUS: f32 = 4.5;
+
+pub const BRAZIER_RADIUS: f32 = 3.5;
+
+pub const LAVA_RADIUS: f32 = 2.5;
+
+/// Tiles lit less than this are too dark to see.
+pub const MIN_VISIBLE_LIGHT: f32 = 0.1;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LightMap {
+    /// The light everywhere on the level, from 0.0 (pitch black) to 1.0.
+    pub ambient: f32,
+    levels: HashMap<(i32, i32), f32>,
+}
+
+impl LightMap {
+    /// The same light everywhere, nothing from light sources.
+    pub fn uniform(ambient: f32) -> Self {
+        LightMap {
+            ambient,
+            levels: HashMap::new(),
+        }
+    }
+
+    /// Light the level up with every light source in the world.
+    pub fn new(world: &World, ambient: f32) -> Self {
+        let mut light = LightMap::uniform(ambient);
+        let entities = world
+            .entities
+            .iter()
+            .filter_map(|entity| entity.light.map(|radius| (entity.pos, radius)));
+        let lava = world
+            .map
+            .tiles
+            .iter()
+            .filter(|tile| tile.glyph == LAVA)
+            .map(|tile| (tile.pos, LAVA_RADIUS));
+        let sources: Vec<(Vector, f32)> = entities.chain(lava).collect();
+        for (source, radius) in sources {
+            for tile in &world.map.tiles {
+                let distance = (tile.pos - source).len();
+                if distance > radius
+                    || !los(source, tile.pos, |pos| world.map.blocks(pos, BLOCKS_SIGHT))
+                {
+                    continue;
+                }
+                // Full brightness at the source fading out towards the
+                // edge of the radius
+                let brightness = 1.0 - distance / (radius + 1.0);
+                *light.levels.entry(tile_key(tile.pos)).or_insert(0.0) += brightness;
+            }
+        }
+        light
+    }
+
+    /// The brightness of the tile, from 0.0 to 1.0.
+    pub fn level(&self, pos: Vector) -> f32 {
+        let level = self.levels.get(&tile_key(pos)).copied().unwrap_or(0.0);
+        (self.ambient + level).min(1.0)
+    }
+
+    pub fn is_lit(&self, pos: Vector) -> bool {
+        self.level(pos) >= MIN_VISIBLE_LIGHT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map::generate_map, world::Entity};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn dark_world(light: Option<f32>) -> World {
+        let player = Entity {
+            pos: Vector::new(5, 5),
+            hp: 5,
+            max_hp: 5,
+            blocks_tile: true,
+            light,
+            ..Default::default()
+        };
+        let mut world = World::from_parts(
+            0,
+            StdRng::seed_from_u64(0),
+            generate_map(Vector::new(20, 15)),
+            vec![],
+            player,
+        );
+        world.light = LightMap::new(&world, 0.0);
+        world
+    }
+
+    #[test]
+    fn light_fades_with_distance() {
+        let world = dark_world(Some(TORCH_RADIUS));
+        let near = world.light.level(Vector::new(6, 5));
+        let far = world.light.level(Vector::new(9, 5));
+        assert_eq!(world.light.level(Vector::new(5, 5)), 1.0);
+        assert!(near > far && far > 0.0);
+        assert_eq!(world.light.level(Vector::new(12, 5)), 0.0);
+    }
+
+    #[test]
+    fn darkness_limits_sight() {
+        let world = dark_world(None);
+        assert!(!world.is_visible(Vector::new(6, 5)));
+
+        let world = dark_world(Some(TORCH_RADIUS));
+        assert!(world.is_visible(Vector::new(8, 5)));
+        // In the open but past the torch's reach
+        assert!(!world.is_visible(Vector::new(11, 5)));
+    }
+}
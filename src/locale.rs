@@ -0,0 +1,244 @@
+//! The text the player reads, in their language.
+//!
+//! Every message, menu entry and label is looked up by a key, e.g.
+//! `locale::text("menu.new-game")`. The names and descriptions from the
+//! templates are looked up by their English text instead, see `content`.
+//! The translations live in `static/locale/<language>.ron` and are built
+//! into the executable like the templates.
+//!
+//! Anything a translation leaves out is shown in English, so a language
+//! can be translated a bit at a time.
+
+use serde::Deserialize;
+
+use std::{collections::BTreeMap, fmt::Display, sync::OnceLock};
+
+use crate::message::Message;
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Every language the game ships with and its translation.
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("en", include_str!("../static/locale/en.ron")),
+    ("cs", include_str!("../static/locale/cs.ron")),
+];
+
+static CURRENT: OnceLock<Locale> = OnceLock::new();
+
+/// What a locale file contains.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+struct Strings {
+    /// The game's own text by key. `{name}` is a placeholder `format`
+    /// fills in.
+    #[serde(default)]
+    messages: BTreeMap<String, String>,
+    /// The templates' text by its English original.
+    #[serde(default)]
+    content: BTreeMap<String, String>,
+}
+
+impl Strings {
+    fn parse(text: &str) -> Result<Self, String> {
+        ron::de::from_str(text).map_err(|error| error.to_string())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Locale {
+    pub language: String,
+    strings: Strings,
+}
+
+impl Locale {
+    /// One of the `LANGUAGES`, with English filling in the gaps.
+    pub fn new(language: &str) -> Result<Self, String> {
+        let find = |language: &str| {
+            LANGUAGES
+                .iter()
+                .find(|(name, _)| *name == language)
+                .map(|(_, text)| Strings::parse(text))
+        };
+        let translation = match find(language) {
+            Some(strings) => strings?,
+            None => {
+                let names: Vec<&str> = LANGUAGES.iter().map(|(name, _)| *name).collect();
+                return Err(format!(
+                    "Unknown language `{}`, pick one of: {}.",
+                    language,
+                    names.join(", ")
+                ));
+            }
+        };
+        let mut strings = match find(DEFAULT_LANGUAGE) {
+            Some(strings) => strings?,
+            None => Strings::default(),
+        };
+        strings.messages.extend(translation.messages);
+        strings.content.extend(translation.content);
+        Ok(Locale {
+            language: language.to_string(),
+            strings,
+        })
+    }
+
+    /// The message under `key`. Shows the key itself when nobody wrote
+    /// the message yet, so it's easy to spot.
+    pub fn text(&self, key: &str) -> String {
+        self.strings
+            .messages
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    /// The message under `key` with each `{name}` replaced by the value
+    /// given for it. A value that looks like a placeholder itself is
+    /// left as it is.
+    pub fn format(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        substitute(&self.text(key), args)
+    }
+
+    /// `format` for the message log. Only the message's own asterisks
+    /// highlight, the ones in the values are just asterisks.
+    pub fn message(&self, key: &str, args: &[(&str, &dyn Display)]) -> Message {
+        let mut message = Message::parse(&self.text(key));
+        for segment in &mut message.segments {
+            segment.text = substitute(&segment.text, args);
+        }
+        message.segments.retain(|segment| !segment.text.is_empty());
+        message
+    }
+
+    /// The translation of a name or description from the templates.
+    pub fn content(&self, original: &str) -> String {
+        self.strings
+            .content
+            .get(original)
+            .cloned()
+            .unwrap_or_else(|| original.to_string())
+    }
+}
+
+/// Pick the language for the rest of the run. Only the first call
+/// counts.
+pub fn set(locale: Locale) {
+    let _ = CURRENT.set(locale);
+}
+
+/// The language picked with `set`, English if there wasn't one.
+pub fn current() -> &'static Locale {
+    CURRENT.get_or_init(|| {
+        Locale::new(DEFAULT_LANGUAGE).expect("The built-in English locale must be valid.")
+    })
+}
+
+/// `Locale::text` in the current language.
+pub fn text(key: &str) -> String {
+    current().text(key)
+}
+
+/// `Locale::format` in the current language.
+pub fn format(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    current().format(key, args)
+}
+
+/// `Locale::message` in the current language.
+pub fn message(key: &str, args: &[(&str, &dyn Display)]) -> Message {
+    current().message(key, args)
+}
+
+/// `Locale::content` in the current language.
+pub fn content(original: &str) -> String {
+    current().content(original)
+}
+
+/// Replace each `{name}` in `text` with its value from `args`, going
+/// through it once.
+fn substitute(text: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let arg = after.find('}').and_then(|end| {
+            let (_, value) = args.iter().find(|(name, _)| *name == &after[..end])?;
+            Some((end, value))
+        });
+        match arg {
+            Some((end, value)) => {
+                result.push_str(&value.to_string());
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::HIGHLIGHT;
+
+    #[test]
+    fn translations_only_use_known_keys() {
+        let english = Strings::parse(LANGUAGES[0].1).unwrap();
+        for (language, text) in LANGUAGES {
+            let strings = Strings::parse(text).unwrap();
+            for key in strings.messages.keys() {
+                assert!(
+                    english.messages.contains_key(key),
+                    "`{}` in {} isn't an English key",
+                    key,
+                    language
+                );
+            }
+            assert!(Locale::new(language).is_ok());
+        }
+    }
+
+    #[test]
+    fn only_the_messages_own_asterisks_highlight() {
+        let english = Locale::new("en").unwrap();
+        let message = english.message("log.pick-up", &[("item", &"a *shiny* {item}")]);
+        assert_eq!(message.text(), "You pick up a *shiny* {item}.");
+        let highlighted: Vec<&str> = message
+            .segments
+            .iter()
+            .filter(|segment| segment.color == Some(HIGHLIGHT))
+            .map(|segment| segment.text.as_str())
+            .collect();
+        assert_eq!(highlighted, ["a *shiny* {item}"]);
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let english = Locale::new("en").unwrap();
+        assert_eq!(
+            english.format("log.pick-up-gold", &[("amount", &12)]),
+            "You pick up *12* gold."
+        );
+        assert_eq!(english.text("no.such.key"), "no.such.key");
+        // Values are put in as they are
+        let args: &[(&str, &dyn Display)] = &[("branch", &"{depth}"), ("depth", &1)];
+        assert_eq!(english.format("hud.depth", args), "Depth 1: {depth}");
+        assert_eq!(english.content("goblin"), "goblin");
+        assert!(Locale::new("xx").is_err());
+    }
+
+    #[test]
+    fn missing_translations_fall_back_to_english() {
+        let czech = Locale::new("cs").unwrap();
+        assert_eq!(czech.text("menu.new-game"), "Nová hra");
+        assert_eq!(czech.text("log.cheat-teleport"), "You teleport.");
+        assert_eq!(czech.content("goblin"), "skřet");
+        assert_eq!(
+            czech.content("a name nobody translated"),
+            "a name nobody translated"
+        );
+    }
+}
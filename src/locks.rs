@@ -0,0 +1,235 @@
+//! Locked doors and the keys that open them.
+//!
+//! Any key opens any lock (a locked chest's too) and is used up doing
+//! so, so all that matters is having enough of them. After a level is
+//! generated `lock_doors` locks some of its doors and drops a key for
+//! each one where the player can get to it before they get to the door.
+//! `solvable` checks that this really holds, even for a player who
+//! spends their keys on the chests first.
+
+use quicksilver::geom::Vector;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+
+use crate::{
+    locale,
+    map::{Map, DOOR, FLOOR, LOCKED_DOOR},
+    spatial::SpatialMap,
+    spawn,
+    templates::{Templates, KEY},
+    world::{Entity, World},
+};
+
+/// The chance of each door on a level getting locked.
+const LOCK_CHANCE: f64 = 0.3;
+
+/// No level has more locked doors than this.
+const MAX_LOCKED_DOORS: usize = 2;
+
+/// Use up one of the player's keys. Return `false` if they don't have
+/// any.
+pub fn use_key(world: &mut World) -> bool {
+    let player_id = world.player_id;
+    let inventory = &mut world.entities[player_id].inventory;
+    let key = inventory
+        .iter()
+        .position(|item| item.item.as_ref().is_some_and(|item| item.template == KEY));
+    match key {
+        Some(key) => {
+            inventory.remove(key);
+            world.stats.items_used += 1;
+            true
+        }
+        None => false,
+    }
+}
+
+/// The player bumped into the locked door at `pos`.
+pub fn open_door(world: &mut World, pos: Vector) {
+    if use_key(world) {
+        world.map.set_glyph(pos, DOOR);
+        world.log(locale::text("log.unlock-door"));
+    } else {
+        world.log(locale::text("log.door-locked"));
+    }
+}
+
+/// Lock some of the doors on `map` and put a key for each one in
+/// `entities`, somewhere the player arriving at `start` can get to
+/// first.
+pub fn lock_doors(
+    map: &mut Map,
+    entities: &mut Vec<Entity>,
+    templates: &Templates,
+    start: Vector,
+    rng: &mut StdRng,
+) {
+    let mut doors: Vec<Vector> = map
+        .tiles
+        .iter()
+        .filter(|tile| tile.glyph == DOOR)
+        .map(|tile| tile.pos)
+        .collect();
+    doors.shuffle(rng);
+    let mut locked = 0;
+    for door in doors {
+        if locked >= MAX_LOCKED_DOORS || !rng.gen_bool(LOCK_CHANCE) {
+            continue;
+        }
+        map.set_glyph(door, LOCKED_DOOR);
+        // The key goes wherever the player can still get to
+        let spatial = SpatialMap::new(entities);
+        let reachable: Vec<Vector> = map
+            .flood_fill(start)
+            .into_iter()
+            .filter(|&pos| pos != start && spatial.is_empty_at(pos))
+            .filter(|&pos| map.tile(pos).is_some_and(|tile| tile.glyph == FLOOR))
+            .collect();
+        let key_pos = match reachable.choose(rng) {
+            Some(&pos) => pos,
+            None => {
+                map.set_glyph(door, DOOR);
+                break;
+            }
+        };
+        let keys = key_positions(entities)
+            .chain(Some(key_pos))
+            .collect::<Vec<_>>();
+        if solvable(map, start, &keys, &locked_chests(entities)) {
+            entities.push(spawn::item(KEY, &templates.items[KEY], key_pos));
+            locked += 1;
+        } else {
+            map.set_glyph(door, DOOR);
+        }
+    }
+}
+
+fn key_positions(entities: &[Entity]) -> impl Iterator<Item = Vector> + '_ {
+    entities
+        .iter()
+        .filter(|entity| {
+            entity
+                .item
+                .as_ref()
+                .is_some_and(|item| item.template == KEY)
+        })
+        .map(|entity| entity.pos)
+}
+
+fn locked_chests(entities: &[Entity]) -> Vec<Vector> {
+    entities
+        .iter()
+        .filter(|entity| entity.chest.as_ref().is_some_and(|chest| chest.locked))
+        .map(|entity| entity.pos)
+        .collect()
+}
+
+/// Whether a player starting at `start` can open every locked door on
+/// `map` with the keys lying at `keys`, picking up every key they can
+/// get to and opening doors as they come. They might open every locked
+/// chest at `chests` they get to first, so those keys don't count.
+pub fn solvable(map: &Map, start: Vector, keys: &[Vector], chests: &[Vector]) -> bool {
+    let mut map = map.clone();
+    let mut opened = 0;
+    loop {
+        let reachable = map.flood_fill(start);
+        let collected = keys.iter().filter(|key| reachable.contains(key)).count();
+        let spent = chests
+            .iter()
+            .filter(|chest| reachable.contains(chest))
+            .count();
+        let collected = collected.saturating_sub(spent);
+        let next_door = map
+            .tiles
+            .iter()
+            .filter(|tile| tile.glyph == LOCKED_DOOR)
+            .map(|tile| tile.pos)
+            .find(|&door| reachable.iter().any(|&pos| (pos - door).len2() == 1.0));
+        match next_door {
+            Some(door) if collected > opened => {
+                map.set_glyph(door, DOOR);
+                opened += 1;
+            }
+            Some(_) => return false,
+            None => return !map.tiles.iter().any(|tile| tile.glyph == LOCKED_DOOR),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{generate_map, WALL},
+        mapgen::{Bsp, MapBuilder},
+    };
+    use rand::SeedableRng;
+
+    /// A room split in two by a wall at x = 5 with a door at (5, 3).
+    fn split_room() -> Map {
+        let mut map = generate_map(Vector::new(10, 7));
+        for y in 0..7 {
+            map.set_glyph(Vector::new(5, y), WALL);
+        }
+        map.set_glyph(Vector::new(5, 3), LOCKED_DOOR);
+        map
+    }
+
+    #[test]
+    fn keys_behind_their_door_dont_count() {
+        let map = split_room();
+        let start = Vector::new(2, 3);
+        assert!(solvable(&map, start, &[Vector::new(1, 1)], &[]));
+        assert!(!solvable(&map, start, &[Vector::new(8, 1)], &[]));
+        assert!(!solvable(&map, start, &[], &[]));
+    }
+
+    #[test]
+    fn chests_cant_take_the_key_a_door_needs() {
+        let map = split_room();
+        let start = Vector::new(2, 3);
+        let key = Vector::new(1, 1);
+        let chest = Vector::new(1, 5);
+        // With one key it may well go into the chest
+        assert!(!solvable(&map, start, &[key], &[chest]));
+        assert!(solvable(&map, start, &[key, Vector::new(3, 1)], &[chest]));
+        // A chest behind the door can only be opened after it
+        assert!(solvable(&map, start, &[key], &[Vector::new(8, 5)]));
+    }
+
+    #[test]
+    fn locked_doors_get_reachable_keys() {
+        let templates = Templates::builtin();
+        let mut locked_levels = 0;
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut map = Bsp::default().build(Vector::new(20, 15), &mut rng);
+            let start = map
+                .tiles
+                .iter()
+                .find(|tile| tile.glyph == FLOOR)
+                .unwrap()
+                .pos;
+            // A locked chest next to the start, with its key at the start
+            let chest_pos = start + Vector::new(1, 0);
+            let mut entities = vec![];
+            if map.tile(chest_pos).is_some_and(|tile| tile.glyph == FLOOR) {
+                entities.push(spawn::chest(vec![], true, chest_pos));
+                entities.push(spawn::item(KEY, &templates.items[KEY], start));
+            }
+            lock_doors(&mut map, &mut entities, &templates, start, &mut rng);
+            let locks = map
+                .tiles
+                .iter()
+                .filter(|tile| tile.glyph == LOCKED_DOOR)
+                .count();
+            let keys: Vec<Vector> = key_positions(&entities).collect();
+            let chests = locked_chests(&entities);
+            assert_eq!(keys.len(), locks + chests.len());
+            assert!(solvable(&map, start, &keys, &chests), "seed {}", seed);
+            if locks > 0 {
+                locked_levels += 1;
+            }
+        }
+        assert!(locked_levels > 0);
+    }
+}
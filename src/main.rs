@@ -1,88 +1,338 @@
 use quicksilver::prelude::*;
 
-use std::collections::HashMap;
+use quicksilver_roguelike::{
+    ammo,
+    animation::Projectile,
+    assets, checksum, daily, export,
+    feedback::Feedback,
+    history::History,
+    hot_reload::{self, Watcher},
+    input::{self, Command, Input},
+    locale::{self, Locale},
+    map::WALL,
+    message::{Message, Segment},
+    morgue,
+    options::{Options, USAGE},
+    packs::{self, Content, Pack},
+    profile::{Class, PlayerProfile, CLASSES, MAX_NAME_LENGTH},
+    replay::Replay,
+    saves::{self, Save, SLOT_COUNT},
+    shop,
+    skills::{Aim, Skill, FIREBOLT_RANGE, HOTBAR},
+    targeting::{self, Targeting},
+    text_mode::TextMode,
+    throwing,
+    tileset::{self, FontGlyphs},
+    timing::{self, Timer},
+    travel::{Activity, Rest, Run},
+    ui::{self, MapView, Menu, Panel, TextMetrics},
+    wizard::{self, Cheat, GameMode},
+    world::{capitalize, Action, Entity, Outcome, World},
+};
 
-#[derive(Clone, Debug, PartialEq)]
-struct Tile {
+#[cfg(feature = "observer")]
+use quicksilver_roguelike::observer::Observer;
+
+use std::{collections::HashMap, sync::OnceLock};
+
+/// Where the account of a finished game goes.
+const MORGUE_FILE: &str = "morgue.txt";
+
+/// How long to wait between actions when watching a replay.
+const REPLAY_SECONDS_PER_ACTION: f64 = 0.17;
+
+/// How long each step of running or resting takes.
+const ACTIVITY_SECONDS_PER_STEP: f64 = 0.033;
+
+/// How long a damage number stays on the screen.
+const FLOATING_TEXT_SECONDS: f64 = 0.67;
+
+/// The size of the text in the user interface.
+const TEXT_SIZE: f32 = 20.0;
+
+/// The size of the map's tiles in pixels when it's not zoomed.
+const TILE_SIZE_PX: f32 = 24.0;
+
+/// The room left under the messages.
+const BOTTOM_MARGIN_PX: f32 = 42.0;
+
+/// Where the top-left corner of the map is on the screen.
+const MAP_OFFSET_PX: (f32, f32) = (50.0, 120.0);
+
+/// How many of the latest messages are shown under the map.
+const MESSAGES_SHOWN: usize = 3;
+
+/// The height of the skill hotbar under the map.
+const HOTBAR_HEIGHT_PX: f32 = 24.0;
+
+/// The width of the panels (e.g. the inspector) drawn over the
+/// inventory to the right of the map.
+const SIDE_PANEL_WIDTH_PX: f32 = 270.0;
+
+/// The messages shown above the debug console's prompt.
+const CONSOLE_MESSAGES: usize = 8;
+
+/// How many rendered texts `cached_text` keeps. Past that the cache
+/// starts over, so texts that change every frame (the FPS counter, what's
+/// being typed) don't pile up for the whole session.
+const TEXT_CACHE_SIZE: usize = 512;
+
+/// How wide the paragraphs of a story screen are.
+const STORY_WIDTH_PX: f32 = 600.0;
+
+/// `State::new` doesn't take any arguments so `main` stores the parsed
+/// command line here.
+static OPTIONS: OnceLock<Options> = OnceLock::new();
+
+/// A short text (e.g. damage dealt) rising above a tile and fading out.
+struct FloatingText {
+    text: String,
     pos: Vector,
-    glyph: char,
-    color: Color,
+    /// In updates.
+    age: u32,
 }
 
-fn generate_map(size: Vector) -> Vec<Tile> {
-    let width = size.x as usize;
-    let height = size.y as usize;
-    let mut map = Vec::with_capacity(width * height);
-    for x in 0..width {
-        for y in 0..height {
-            let mut tile = Tile {
-                pos: Vector::new(x as f32, y as f32),
-                glyph: '.',
-                color: Color::BLACK,
-            };
+/// The map glyphs rendered from a font.
+struct Tileset {
+    font: Font,
+    glyphs: FontGlyphs,
+    tiles: HashMap<char, Image>,
+    tile_size_px: Vector,
+}
 
-            if x == 0 || x == width - 1 || y == 0 || y == height - 1 {
-                tile.glyph = '#';
-            };
-            map.push(tile);
+impl Tileset {
+    /// `font` is the contents of a TrueType file.
+    fn new(font: Vec<u8>, tile_size_px: Vector) -> Result<Self> {
+        Ok(Tileset {
+            glyphs: FontGlyphs::new(font.clone()).map_err(Error::ContextError)?,
+            font: Font::from_bytes(font)?,
+            tiles: HashMap::new(),
+            tile_size_px,
+        })
+    }
+
+    /// Render the glyphs at a new size from now on.
+    fn resize(&mut self, tile_size_px: Vector) {
+        if tile_size_px != self.tile_size_px {
+            self.tile_size_px = tile_size_px;
+            self.tiles.clear();
         }
     }
-    map
+
+    /// The tile for `glyph`, rendering it the first time it's asked
+    /// for. Glyphs missing from the font get `tileset::FALLBACK`.
+    fn get(&mut self, glyph: char) -> Result<&Image> {
+        if !self.tiles.contains_key(&glyph) {
+            let drawn = self.glyphs.resolve(glyph);
+            if drawn != glyph {
+                eprintln!("The tileset has no `{}`, using `{}` instead.", glyph, drawn);
+            }
+            let style = FontStyle::new(self.tile_size_px.y, Color::WHITE);
+            let tile = self.font.render(&drawn.to_string(), &style)?;
+            self.tiles.insert(glyph, tile);
+        }
+        Ok(&self.tiles[&glyph])
+    }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-struct Entity {
-    pos: Vector,
-    glyph: char,
-    color: Color,
-    hp: i32,
-    max_hp: i32,
+/// The choices in the pause menu.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PauseEntry {
+    Resume,
+    Save,
+    HealthBars,
+    Quit,
+}
+
+/// What the player is picking an inventory item for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ItemChoice {
+    Equip,
+    Throw,
+    Use,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MainEntry {
+    Continue,
+    Load,
+    NewGame,
+    Daily,
+    Quit,
+}
+
+/// The menu shown at the start. `Continue` (the game saved last) and
+/// `Load` are only there when there's a saved game to go back to.
+fn main_menu(has_saves: bool) -> Menu<MainEntry> {
+    let mut entries = vec![];
+    if has_saves {
+        entries.push((MainEntry::Continue, locale::text("menu.continue")));
+        entries.push((MainEntry::Load, locale::text("menu.load")));
+    }
+    entries.push((MainEntry::NewGame, locale::text("menu.new-game")));
+    entries.push((MainEntry::Daily, locale::text("menu.daily")));
+    entries.push((MainEntry::Quit, locale::text("menu.quit")));
+    Menu::new(entries)
+}
+
+/// The classes to pick from before a new game starts.
+fn class_menu() -> Menu<Class> {
+    Menu::new(CLASSES.iter().map(|&class| (class, class.name())).collect())
+}
+
+/// The save slots, to load a game from or save the current one into.
+struct SlotMenu {
+    menu: Menu<usize>,
+    saving: bool,
+    /// Set while the player decides whether to save over another game.
+    confirming: bool,
 }
 
-fn generate_entities() -> Vec<Entity> {
-    vec![
-        Entity {
-            pos: Vector::new(9, 6),
-            glyph: 'g',
-            color: Color::RED,
-            hp: 1,
-            max_hp: 1,
-        },
-        Entity {
-            pos: Vector::new(2, 4),
-            glyph: 'g',
-            color: Color::RED,
-            hp: 1,
-            max_hp: 1,
-        },
-        Entity {
-            pos: Vector::new(7, 5),
-            glyph: '%',
-            color: Color::PURPLE,
-            hp: 0,
-            max_hp: 0,
-        },
-        Entity {
-            pos: Vector::new(4, 8),
-            glyph: '%',
-            color: Color::PURPLE,
-            hp: 0,
-            max_hp: 0,
-        },
-    ]
+fn slot_menu(saves: &[Option<Save>], saving: bool) -> SlotMenu {
+    let entries = saves
+        .iter()
+        .enumerate()
+        .map(|(slot, save)| {
+            let summary = match save {
+                // They can't be loaded, see `Game::load_slot`
+                Some(save) if !packs::matches(&save.replay.packs) => locale::format(
+                    "save.other-packs",
+                    &[
+                        ("summary", &save.summary()),
+                        ("packs", &packs::describe(&save.replay.packs)),
+                    ],
+                ),
+                Some(save) => save.summary(),
+                None => locale::text("save.empty"),
+            };
+            let number = slot + 1;
+            let line = locale::format("save.slot", &[("number", &number), ("summary", &summary)]);
+            (slot, line)
+        })
+        .collect();
+    SlotMenu {
+        menu: Menu::new(entries),
+        saving,
+        confirming: false,
+    }
+}
+
+/// The pause menu, with the monsters' health bars shown as on or off.
+fn pause_menu(health_bars: bool) -> Menu<PauseEntry> {
+    let state = locale::text(if health_bars { "menu.on" } else { "menu.off" });
+    Menu::new(vec![
+        (PauseEntry::Resume, locale::text("menu.resume")),
+        (PauseEntry::Save, locale::text("menu.save")),
+        (
+            PauseEntry::HealthBars,
+            locale::format("menu.health-bars", &[("state", &state)]),
+        ),
+        (PauseEntry::Quit, locale::text("menu.quit")),
+    ])
 }
 
 struct Game {
     title: Asset<Image>,
     mononoki_font_info: Asset<Image>,
     square_font_info: Asset<Image>,
-    inventory: Asset<Image>,
-    map_size: Vector,
-    map: Vec<Tile>,
-    entities: Vec<Entity>,
-    player_id: usize,
-    tileset: Asset<HashMap<char, Image>>,
+    world: World,
+    input: Input,
+    replay: Replay,
+    playback: Option<std::vec::IntoIter<Action>>,
+    playback_timer: Timer,
+    /// Set while the player keeps doing something on their own (running,
+    /// resting).
+    activity: Option<Activity>,
+    activity_timer: Timer,
+    tileset: Asset<Tileset>,
+    /// The size of the map's tiles when it's not zoomed, which decides
+    /// how big the map's area on the screen is.
     tile_size_px: Vector,
+    /// The size the map's tiles are drawn at, changed with the mouse
+    /// wheel.
+    zoomed_tile_px: f32,
+    /// How many tiles across and down the map's area is. The window is
+    /// made to fit it at the start, see `window_size`.
+    view_tiles: Vector,
+    font: Asset<Font>,
+    text_metrics: Asset<TextMetrics>,
+    text_cache: HashMap<String, Image>,
+    floating_texts: Vec<FloatingText>,
+    feedback: Feedback,
+    /// Something in flight, with the action to take once it lands.
+    projectile: Option<(Projectile, Action)>,
+    /// Flips every `ui::BLINK_SECONDS` for entities that blink.
+    blink: bool,
+    blink_timer: Timer,
+    /// Keeps an eye on the templates in debug builds on the desktop, see
+    /// `hot_reload`.
+    templates_watcher: Option<Watcher>,
+    templates_timer: Timer,
+    /// Set once the templates have been reloaded into the current game.
+    /// Its replay won't play back the same from then on, so it isn't
+    /// saved any more.
+    reloaded: bool,
+    /// The page of the help screen, when it's open.
+    help_page: Option<usize>,
+    /// Set while the bestiary is open.
+    show_bestiary: bool,
+    /// Set while the message history is open.
+    history: Option<History>,
+    /// Shown before the game starts.
+    main_menu: Option<Menu<MainEntry>>,
+    /// Shown after picking a new game, before it starts.
+    class_menu: Option<Menu<Class>>,
+    /// The seed of today's challenge while the class menu is open for it.
+    daily: Option<u64>,
+    /// Shown after picking the class: the profile being made and the
+    /// name typed so far.
+    naming: Option<(PlayerProfile, String)>,
+    /// What's in every save slot.
+    saves: Vec<Option<Save>>,
+    /// The slot the current game is saved in, `None` until the player
+    /// picks one if they were all taken when it started.
+    slot: Option<usize>,
+    /// Set while a slot's being picked to load or save a game.
+    slot_menu: Option<SlotMenu>,
+    /// Set while the game is paused.
+    pause_menu: Option<Menu<PauseEntry>>,
+    /// The command being typed into the debug console, while it's open.
+    console: Option<String>,
+    /// How many of `World::stories` the player has read. The next one
+    /// is in the way until they press a key.
+    stories_shown: usize,
+    /// Set while the player moves the cursor around the map.
+    targeting: Option<Targeting>,
+    /// The inventory item to throw once the target has been picked.
+    throwing: Option<usize>,
+    /// Set when the ranged weapon gets fired once the target has been
+    /// picked.
+    firing: bool,
+    /// The skill to use once the target has been picked.
+    casting: Option<Skill>,
+    /// Waiting for the direction to use the skill in.
+    aiming: Option<Skill>,
+    /// Waiting for the player to pick an inventory item.
+    choosing_item: Option<ItemChoice>,
+    /// Toggled with F3.
+    show_fps: bool,
+    /// Whether wounded monsters have health bars, toggled in the pause
+    /// menu.
+    health_bars: bool,
+    /// Set by F12: save a screenshot once the frame is drawn.
+    take_screenshot: bool,
+    /// Set when every turn gets shown as text too.
+    text_mode: Option<TextMode>,
+    /// Set when spectators can watch the game, see `observer`.
+    #[cfg(feature = "observer")]
+    observer: Option<Observer>,
+    /// Toggled with F2 in wizard mode.
+    show_inspector: bool,
+    /// Set until every asset has finished loading.
+    loading: bool,
+    /// Something went wrong and the game can't go on. Shown instead of
+    /// the game.
+    error: Option<String>,
 }
 
 impl State for Game {
@@ -92,57 +342,82 @@ impl State for Game {
         // License: SIL Open Font License 1.1
         let font_mononoki = "mononoki-Regular.ttf";
 
-        let title = Asset::new(Font::load(font_mononoki).and_then(|font| {
+        let title = Asset::new(assets::font(font_mononoki).and_then(|font| {
             font.render("Quicksilver Roguelike", &FontStyle::new(72.0, Color::BLACK))
         }));
 
-        let mononoki_font_info = Asset::new(Font::load(font_mononoki).and_then(|font| {
+        let mononoki_font_info = Asset::new(assets::font(font_mononoki).and_then(|font| {
             font.render(
-                "Mononoki font by Matthias Tellen, terms: SIL Open Font License 1.1",
+                &locale::text("title.mononoki"),
                 &FontStyle::new(20.0, Color::BLACK),
             )
         }));
 
-        let square_font_info = Asset::new(Font::load(font_mononoki).and_then(move |font| {
+        let square_font_info = Asset::new(assets::font(font_mononoki).and_then(move |font| {
             font.render(
-                "Square font by Wouter Van Oortmerssen, terms: CC BY 3.0",
+                &locale::text("title.square"),
                 &FontStyle::new(20.0, Color::BLACK),
             )
         }));
 
-        let inventory = Asset::new(Font::load(font_mononoki).and_then(move |font| {
-            font.render(
-                "Inventory:\n[A] Sword\n[B] Shield\n[C] Darts",
-                &FontStyle::new(20.0, Color::BLACK),
-            )
-        }));
+        let font = Asset::new(assets::font(font_mononoki));
+        let text_metrics = Asset::new(
+            assets::load(font_mononoki)
+                .and_then(|bytes| TextMetrics::new(bytes, TEXT_SIZE).map_err(Error::ContextError)),
+        );
 
-        let map_size = Vector::new(20, 15);
-        let map = generate_map(map_size);
-        let mut entities = generate_entities();
-        let player_id = entities.len();
-        entities.push(Entity {
-            pos: Vector::new(5, 3),
-            glyph: '@',
-            color: Color::BLUE,
-            hp: 3,
-            max_hp: 5,
-        });
+        let options = OPTIONS.get().cloned().unwrap_or_default();
+        let mut error = None;
+        let (world, playback) = match options.replay.as_deref().map(load_replay) {
+            Some(Ok(replay)) => (replay.world(), Some(replay.actions.into_iter())),
+            Some(Err(replay_error)) => {
+                error = Some(replay_error.to_string());
+                (new_world(PlayerProfile::default()), None)
+            }
+            None => (new_world(PlayerProfile::default()), None),
+        };
+        #[cfg(feature = "observer")]
+        let observer = match options.observe.as_deref().map(Observer::bind) {
+            Some(Ok(observer)) => Some(observer),
+            Some(Err(bind_error)) => {
+                error = Some(format!("Could not listen for spectators: {}", bind_error));
+                None
+            }
+            None => None,
+        };
+        let view_tiles = ui::view_tiles(world.map.size);
+        let mut replay = Replay::new(world.seed);
+        replay.mode = world.mode;
+        replay.profile = world.profile.clone();
+        replay.config = world.config;
+        replay.packs = packs::current().names.clone();
+        let saves = if playback.is_none() {
+            load_saves()
+        } else {
+            vec![None; SLOT_COUNT]
+        };
+        let main_menu = if playback.is_none() {
+            Some(main_menu(saves.iter().any(Option::is_some)))
+        } else {
+            None
+        };
 
         // The Square font: http://strlen.com/square/?s[]=font
         // License: CC BY 3.0 https://creativecommons.org/licenses/by/3.0/deed.en_US
         let font_square = "square.ttf";
-        let game_glyphs = "#@g.%";
-        let tile_size_px = Vector::new(24, 24);
-        let tileset = Asset::new(Font::load(font_square).and_then(move |text| {
-            let tiles = text
-                .render(game_glyphs, &FontStyle::new(tile_size_px.y, Color::WHITE))
-                .expect("Could not render the font tileset.");
-            let mut tileset = HashMap::new();
-            for (index, glyph) in game_glyphs.chars().enumerate() {
-                let pos = (index as i32 * tile_size_px.x as i32, 0);
-                let tile = tiles.subimage(Rectangle::new(pos, tile_size_px));
-                tileset.insert(glyph, tile);
+        let game_glyphs = tileset::glyphs(&world.templates);
+        let tile_size_px = Vector::new(TILE_SIZE_PX, TILE_SIZE_PX);
+        // A content pack can bring its own
+        let tileset_font: Box<dyn Future<Item = Vec<u8>, Error = Error>> =
+            match &packs::current().tileset {
+                Some(font) => Box::new(quicksilver::combinators::ok(font.clone())),
+                None => Box::new(assets::load(font_square)),
+            };
+        let tileset = Asset::new(tileset_font.and_then(move |bytes| {
+            let mut tileset = Tileset::new(bytes, tile_size_px)?;
+            // Everything else gets rendered when it first shows up
+            for glyph in game_glyphs.chars() {
+                tileset.get(glyph)?;
             }
             Ok(tileset)
         }));
@@ -151,144 +426,1869 @@ impl State for Game {
             title,
             mononoki_font_info,
             square_font_info,
-            inventory,
-            map_size,
-            map,
-            entities,
-            player_id,
+            world,
+            input: Input::default(),
+            replay,
+            playback,
+            playback_timer: Timer::from_seconds(REPLAY_SECONDS_PER_ACTION),
+            activity: None,
+            activity_timer: Timer::from_seconds(ACTIVITY_SECONDS_PER_STEP),
             tileset,
             tile_size_px,
+            zoomed_tile_px: tile_size_px.y,
+            view_tiles,
+            font,
+            text_metrics,
+            text_cache: HashMap::new(),
+            floating_texts: vec![],
+            feedback: Feedback::default(),
+            projectile: None,
+            blink: false,
+            blink_timer: Timer::from_seconds(ui::BLINK_SECONDS),
+            templates_watcher: if cfg!(debug_assertions) && !cfg!(target_arch = "wasm32") {
+                Some(Watcher::new(hot_reload::TEMPLATES_PATH))
+            } else {
+                None
+            },
+            templates_timer: Timer::from_seconds(hot_reload::POLL_SECONDS),
+            reloaded: false,
+            help_page: None,
+            show_bestiary: false,
+            history: None,
+            main_menu,
+            class_menu: None,
+            daily: None,
+            naming: None,
+            saves,
+            slot: None,
+            slot_menu: None,
+            pause_menu: None,
+            console: None,
+            stories_shown: 0,
+            targeting: None,
+            throwing: None,
+            firing: false,
+            casting: None,
+            aiming: None,
+            choosing_item: None,
+            show_fps: false,
+            health_bars: options.health_bars,
+            take_screenshot: false,
+            // The web page decides by having a place to put the text
+            text_mode: if options.text || cfg!(target_arch = "wasm32") {
+                Some(TextMode::default())
+            } else {
+                None
+            },
+            #[cfg(feature = "observer")]
+            observer,
+            show_inspector: false,
+            loading: true,
+            error,
         })
     }
 
-    /// Process keyboard and mouse, update the game state
+    /// Remember the keys pressed since the last update
+    fn event(&mut self, event: &Event, _window: &mut Window) -> Result<()> {
+        match event {
+            Event::Key(key, ButtonState::Pressed) => self.input.pressed.push(*key),
+            Event::Typed(c) => self.input.typed.push(*c),
+            Event::MouseWheel(delta) => self.input.wheel += delta.y,
+            Event::Closed => {
+                self.save_game();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     fn update(&mut self, window: &mut Window) -> Result<()> {
-        use ButtonState::*;
+        if self.error.is_some() {
+            if window.keyboard()[Key::Escape].is_down() {
+                window.close();
+            }
+        } else if let Err(error) = self.update_game(window) {
+            self.fail(error.to_string());
+        }
+        let (text_mode, world) = (&mut self.text_mode, &self.world);
+        let frame = text_mode.as_mut().and_then(|text| text.update(world));
+        if let Some(frame) = frame {
+            show_text(&frame);
+        }
+        #[cfg(feature = "observer")]
+        if let Some(observer) = &mut self.observer {
+            observer.update(&self.world);
+        }
+        Ok(())
+    }
 
-        let player = &mut self.entities[self.player_id];
-        if window.keyboard()[Key::Left] == Pressed {
-            player.pos.x -= 1.0;
+    fn draw(&mut self, window: &mut Window) -> Result<()> {
+        if let Some(message) = self.error.clone() {
+            return self.draw_error_screen(window, &message);
+        }
+        if let Err(error) = self.draw_game(window) {
+            self.fail(error.to_string());
         }
-        if window.keyboard()[Key::Right] == Pressed {
-            player.pos.x += 1.0;
+        if self.take_screenshot {
+            self.take_screenshot = false;
+            self.save_screenshot(window);
         }
-        if window.keyboard()[Key::Up] == Pressed {
-            player.pos.y -= 1.0;
+        Ok(())
+    }
+}
+
+impl Game {
+    /// Process keyboard and mouse, update the game state
+    fn update_game(&mut self, window: &mut Window) -> Result<()> {
+        if self.loading {
+            let (loaded, total) = self.loaded_assets()?;
+            self.loading = loaded < total;
+            // Don't let key presses pile up
+            self.input = Input::default();
+            return Ok(());
         }
-        if window.keyboard()[Key::Down] == Pressed {
-            player.pos.y += 1.0;
+
+        let mut input = std::mem::take(&mut self.input);
+        input.shift =
+            window.keyboard()[Key::LShift].is_down() || window.keyboard()[Key::RShift].is_down();
+        // Screenshots work everywhere, menus included
+        if input.command() == Some(Command::Screenshot) {
+            self.take_screenshot = true;
+        }
+        let escape = input.command() == Some(Command::Menu);
+        let mut quit = false;
+        if self.blink_timer.tick() {
+            self.blink = !self.blink;
+        }
+        if self.templates_timer.tick() {
+            self.reload_templates();
+        }
+        if let Some((projectile, _)) = &mut self.projectile {
+            // Nothing else happens until it lands
+            projectile.tick();
+            if projectile.is_done() {
+                if let Some((_, action)) = self.projectile.take() {
+                    if self.playback.is_some() {
+                        self.world.player_turn(action);
+                    } else {
+                        self.take_turn(action);
+                    }
+                }
+            }
+            self.input = Input::default();
+            return Ok(());
         }
-        if window.keyboard()[Key::Escape].is_down() {
+        if let Some(menu) = &mut self.main_menu {
+            if escape {
+                window.close();
+            } else if input.is_pressed(Key::Down) {
+                menu.select_next();
+            } else if input.is_pressed(Key::Up) {
+                menu.select_previous();
+            } else if input.is_pressed(Key::Return) {
+                match menu.selected() {
+                    MainEntry::Continue => match saves::latest(&self.saves) {
+                        // Show why it won't load
+                        Some(slot) if !self.loads(slot) => {
+                            self.slot_menu = Some(slot_menu(&self.saves, false))
+                        }
+                        Some(slot) => self.load_slot(slot),
+                        None => {}
+                    },
+                    MainEntry::Load => self.slot_menu = Some(slot_menu(&self.saves, false)),
+                    MainEntry::NewGame => self.class_menu = Some(class_menu()),
+                    MainEntry::Daily => {
+                        self.daily = Some(daily::seed(now_seconds()));
+                        self.class_menu = Some(class_menu());
+                    }
+                    MainEntry::Quit => window.close(),
+                }
+                self.main_menu = None;
+            }
+            return Ok(());
+        }
+        if let Some(menu) = &mut self.class_menu {
+            if escape {
+                self.class_menu = None;
+                self.daily = None;
+                self.main_menu = Some(main_menu(self.has_saves()));
+            } else if input.is_pressed(Key::Down) {
+                menu.select_next();
+            } else if input.is_pressed(Key::Up) {
+                menu.select_previous();
+            } else if input.is_pressed(Key::Return) {
+                self.naming = Some((PlayerProfile::new(menu.selected()), String::new()));
+                self.class_menu = None;
+            }
+            return Ok(());
+        }
+        if let Some((profile, name)) = &mut self.naming {
+            if escape {
+                self.naming = None;
+                self.class_menu = Some(class_menu());
+            } else if input.is_pressed(Key::Back) {
+                name.pop();
+            } else if input.is_pressed(Key::Return) {
+                let profile = PlayerProfile {
+                    name: name.trim().to_string(),
+                    ..profile.clone()
+                };
+                self.naming = None;
+                let world = match self.daily.take() {
+                    Some(seed) => daily::world(seed, profile),
+                    None => new_world(profile),
+                };
+                self.new_game(world);
+                // With every slot taken, the player picks one to save over
+                self.slot = self.saves.iter().position(Option::is_none);
+            } else {
+                let room = MAX_NAME_LENGTH.saturating_sub(name.chars().count());
+                name.extend(input.typed.iter().filter(|c| !c.is_control()).take(room));
+            }
+            return Ok(());
+        }
+        if let Some(slots) = &mut self.slot_menu {
+            let slot = slots.menu.selected();
+            if slots.confirming {
+                if escape {
+                    slots.confirming = false;
+                } else if input.is_pressed(Key::Return) {
+                    self.slot_menu = None;
+                    self.save_into(slot);
+                }
+            } else if escape {
+                self.slot_menu = None;
+                if self.playback.is_none() && !self.in_game() {
+                    self.main_menu = Some(main_menu(self.has_saves()));
+                }
+            } else if input.is_pressed(Key::Down) {
+                slots.menu.select_next();
+            } else if input.is_pressed(Key::Up) {
+                slots.menu.select_previous();
+            } else if input.is_pressed(Key::Return) {
+                let taken = self.saves[slot].is_some();
+                if !slots.saving {
+                    if taken && self.loads(slot) {
+                        self.slot_menu = None;
+                        self.load_slot(slot);
+                    }
+                } else if taken && self.slot != Some(slot) {
+                    slots.confirming = true;
+                } else {
+                    self.slot_menu = None;
+                    self.save_into(slot);
+                }
+            }
+            return Ok(());
+        }
+        if self.playback.is_some() {
+            // Replays are for watching, there's no stopping to read
+            self.stories_shown = self.world.stories.len();
+        } else if self.stories_shown < self.world.stories.len() {
+            if !input.pressed.is_empty() || !input.typed.is_empty() {
+                self.stories_shown += 1;
+            }
+            return Ok(());
+        }
+        if let Some(line) = &mut self.console {
+            if escape || input.command() == Some(Command::Console) {
+                self.console = None;
+            } else if input.is_pressed(Key::Back) {
+                line.pop();
+            } else if input.is_pressed(Key::Return) {
+                let text = std::mem::take(line);
+                // Dumping the map doesn't change anything so it isn't a
+                // cheat and doesn't get recorded
+                match text.split_whitespace().collect::<Vec<_>>().as_slice() {
+                    ["dump"] => self.dump_map(None),
+                    ["dump", path] => self.dump_map(Some(path.to_string())),
+                    _ => match Cheat::parse(&text) {
+                        Ok(cheat) => self.take_turn(Action::Cheat(cheat)),
+                        Err(message) => self.world.log(message),
+                    },
+                }
+            } else {
+                line.extend(input.typed.iter().filter(|c| !c.is_control()));
+            }
+            return Ok(());
+        }
+        if let Some(menu) = &mut self.pause_menu {
+            if escape {
+                self.pause_menu = None;
+            } else if input.is_pressed(Key::Down) {
+                menu.select_next();
+            } else if input.is_pressed(Key::Up) {
+                menu.select_previous();
+            } else if input.is_pressed(Key::Return) {
+                let (entry, selected) = (menu.selected(), menu.selected);
+                match entry {
+                    PauseEntry::Resume => {}
+                    PauseEntry::Save => {
+                        let mut slots = slot_menu(&self.saves, true);
+                        slots.menu.selected = self.slot.unwrap_or(0);
+                        self.slot_menu = Some(slots);
+                    }
+                    PauseEntry::HealthBars => self.health_bars = !self.health_bars,
+                    PauseEntry::Quit => quit = true,
+                }
+                self.pause_menu = None;
+                if entry == PauseEntry::HealthBars {
+                    // Stay in the menu to see it change
+                    let mut menu = pause_menu(self.health_bars);
+                    menu.selected = selected;
+                    self.pause_menu = Some(menu);
+                }
+            }
+        } else if self.show_bestiary {
+            self.show_bestiary = !escape;
+        } else if self.history.is_some() {
+            let (lines, per_page) = self.history_lines()?;
+            if let Some(history) = &mut self.history {
+                let typed: String = input.typed.iter().filter(|c| !c.is_control()).collect();
+                if history.typing {
+                    if escape {
+                        history.typing = false;
+                        history.edit_search(String::clear);
+                    } else if input.is_pressed(Key::Return) {
+                        history.typing = false;
+                    } else if input.is_pressed(Key::Back) {
+                        history.edit_search(|search| {
+                            search.pop();
+                        });
+                    } else if !typed.is_empty() {
+                        history.edit_search(|search| search.push_str(&typed));
+                    }
+                } else if escape {
+                    self.history = None;
+                } else if typed == "/" {
+                    history.typing = true;
+                } else if input.is_pressed(Key::PageUp) {
+                    history.page_up(per_page, lines.len());
+                } else if input.is_pressed(Key::PageDown) {
+                    history.page_down(per_page);
+                }
+            }
+        } else if let Some(page) = self.help_page {
+            let page_count = self.help_pages()?.len();
+            if escape {
+                self.help_page = None;
+            } else if input.is_pressed(Key::Right) || input.is_pressed(Key::PageDown) {
+                self.help_page = Some((page + 1).min(page_count - 1));
+            } else if input.is_pressed(Key::Left) || input.is_pressed(Key::PageUp) {
+                self.help_page = Some(page.saturating_sub(1));
+            }
+        } else if self.world.outcome.is_some() {
+            // The game's over, there's nothing to lose by quitting
+            quit = escape;
+            if input.is_pressed(Key::Return) {
+                self.class_menu = Some(class_menu());
+            }
+        } else if escape && self.world.shop.is_some() {
+            // Leave the shop without pausing
+            self.world.shop = None;
+        } else if escape && self.world.chest.is_some() {
+            self.world.chest = None;
+        } else if escape && self.choosing_item.is_some() {
+            self.choosing_item = None;
+        } else if escape && self.targeting.is_some() {
+            self.targeting = None;
+            self.throwing = None;
+            self.firing = false;
+            self.casting = None;
+        } else if escape && self.aiming.is_some() {
+            self.aiming = None;
+        } else if escape {
+            self.activity = None;
+            self.pause_menu = Some(pause_menu(self.health_bars));
+        } else if let Some(playback) = &mut self.playback {
+            // Slow the replay down so it can actually be watched
+            if self.playback_timer.tick() {
+                match playback.next() {
+                    Some(Action::Throw(index, target)) => self.throw(index, target),
+                    Some(Action::Fire(target)) => self.fire(target),
+                    Some(Action::Skill(skill, target)) if skill.aim() == Aim::Target => {
+                        self.cast(skill, target)
+                    }
+                    Some(action) => self.world.player_turn(action),
+                    None => {}
+                }
+            }
+        } else if self.world.shop.is_some() {
+            if let Some(action) = input.trade_action() {
+                self.take_turn(action);
+            }
+        } else if self.world.chest.is_some() {
+            if let Some(action) = input.chest_action() {
+                self.take_turn(action);
+            }
+        } else if let Some(targeting) = &mut self.targeting {
+            if input.is_pressed(Key::Tab) {
+                targeting.next_target(&self.world);
+            } else if input.is_pressed(Key::Return) {
+                let target = targeting.cursor;
+                self.targeting = None;
+                if let Some(index) = self.throwing.take() {
+                    self.throw(index, target);
+                } else if std::mem::take(&mut self.firing) {
+                    self.fire(target);
+                } else if let Some(skill) = self.casting.take() {
+                    self.cast(skill, target);
+                }
+            } else {
+                let offsets = [
+                    (Key::Up, (0, -1)),
+                    (Key::Right, (1, 0)),
+                    (Key::Down, (0, 1)),
+                    (Key::Left, (-1, 0)),
+                ];
+                for &(key, offset) in &offsets {
+                    if input.is_pressed(key) {
+                        targeting.move_cursor(&self.world, Vector::from(offset));
+                    }
+                }
+            }
+        } else if let Some(skill) = self.aiming {
+            if let Some(Command::Move(x, y)) = input.command() {
+                self.aiming = None;
+                self.take_turn(Action::Skill(skill, Vector::new(x, y)));
+            }
+        } else if let Some(choice) = self.choosing_item {
+            if let Some(index) = input.inventory_choice() {
+                self.choosing_item = None;
+                match choice {
+                    ItemChoice::Equip => self.take_turn(Action::Equip(index)),
+                    ItemChoice::Throw if index < self.world.player().inventory.len() => {
+                        self.throwing = Some(index);
+                        self.targeting = Some(Targeting::new(&self.world));
+                    }
+                    ItemChoice::Throw => self.world.log(locale::text("log.no-such-item")),
+                    ItemChoice::Use => self.take_turn(Action::Use(index)),
+                }
+            }
+        } else if let Some(command) = input.command() {
+            self.activity = None;
+            match command {
+                Command::Run(x, y) => {
+                    let dir = Vector::new(x, y);
+                    self.activity = Some(Activity::Run(Run::new(&self.world, dir)));
+                }
+                Command::Rest => self.activity = Some(Activity::Rest(Rest::new(&self.world))),
+                Command::Help => self.help_page = Some(0),
+                Command::Bestiary => self.show_bestiary = true,
+                Command::History => self.history = Some(History::default()),
+                Command::Look => self.targeting = Some(Targeting::new(&self.world)),
+                Command::Console if self.world.mode == GameMode::Wizard => {
+                    self.console = Some(String::new());
+                }
+                Command::Console => self.world.log(locale::text("log.console-wizard-only")),
+                Command::Equip => {
+                    self.choosing_item = Some(ItemChoice::Equip);
+                    self.world.log(locale::text("prompt.equip"));
+                }
+                Command::Throw => {
+                    self.choosing_item = Some(ItemChoice::Throw);
+                    self.world.log(locale::text("prompt.throw"));
+                }
+                Command::Fire => self.start_firing(),
+                Command::Use => {
+                    self.choosing_item = Some(ItemChoice::Use);
+                    self.world.log(locale::text("prompt.use"));
+                }
+                Command::Skill(slot) => self.start_skill(slot),
+                _ => {
+                    if let Some(action) = command.action() {
+                        self.take_turn(action);
+                    }
+                }
+            }
+        } else if !input.pressed.is_empty() {
+            // Any other key stops whatever the player was doing
+            self.activity = None;
+        }
+
+        if input.command() == Some(Command::ToggleFps) {
+            self.show_fps = !self.show_fps;
+        }
+        if input.wheel != 0.0 {
+            self.zoomed_tile_px = ui::zoom(self.zoomed_tile_px, input.wheel);
+        }
+        if input.command() == Some(Command::Inspect) {
+            if self.world.mode == GameMode::Wizard {
+                self.show_inspector = !self.show_inspector;
+            } else {
+                self.world.log(locale::text("log.inspector-wizard-only"));
+            }
+        }
+
+        let activity_step = self.activity_timer.tick();
+        if let (Some(activity), true) = (&mut self.activity, activity_step) {
+            match activity.next_action(&self.world) {
+                Some(action) => self.take_turn(action),
+                None => self.activity = None,
+            }
+            // Running into a shopkeeper or a chest
+            if self.world.shop.is_some() || self.world.chest.is_some() {
+                self.activity = None;
+            }
+        }
+
+        for floating in self.floating_texts.iter_mut() {
+            floating.age += 1;
+        }
+        self.floating_texts
+            .retain(|floating| floating.age < timing::updates(FLOATING_TEXT_SECONDS));
+        self.feedback.tick();
+        for hit in self.world.hits.drain(..) {
+            self.feedback.hit(&hit, self.world.player_id);
+            self.floating_texts.push(FloatingText {
+                text: format!("-{}", hit.damage),
+                pos: hit.pos,
+                age: 0,
+            });
+        }
+
+        if quit {
+            self.save_game();
             window.close();
         }
         Ok(())
     }
 
     /// Draw stuff on the screen
-    fn draw(&mut self, window: &mut Window) -> Result<()> {
+    fn draw_game(&mut self, window: &mut Window) -> Result<()> {
         window.clear(Color::WHITE)?;
 
+        if self.loading {
+            return self.draw_loading_screen(window);
+        }
+
         // Draw the game title
         self.title.execute(|image| {
             window.draw(
                 &image
                     .area()
                     .with_center((window.screen_size().x as i32 / 2, 40)),
-                Img(&image),
+                Img(image),
             );
             Ok(())
         })?;
 
-        // Draw the mononoki font credits
-        self.mononoki_font_info.execute(|image| {
-            window.draw(
-                &image
-                    .area()
-                    .translate((2, window.screen_size().y as i32 - 60)),
-                Img(&image),
-            );
-            Ok(())
-        })?;
+        // The font credits only fit on the menus, the game needs the
+        // room under the map for the hotbar and the messages
+        if !self.in_game() {
+            // Draw the mononoki font credits
+            self.mononoki_font_info.execute(|image| {
+                window.draw(
+                    &image
+                        .area()
+                        .translate((2, window.screen_size().y as i32 - 60)),
+                    Img(image),
+                );
+                Ok(())
+            })?;
 
-        // Draw the Square font credits
-        self.square_font_info.execute(|image| {
-            window.draw(
-                &image
-                    .area()
-                    .translate((2, window.screen_size().y as i32 - 30)),
-                Img(&image),
-            );
-            Ok(())
-        })?;
+            // Draw the Square font credits
+            self.square_font_info.execute(|image| {
+                window.draw(
+                    &image
+                        .area()
+                        .translate((2, window.screen_size().y as i32 - 30)),
+                    Img(image),
+                );
+                Ok(())
+            })?;
+        }
+
+        if let Some(menu) = &self.main_menu {
+            let mut lines = menu.lines();
+            lines.push(String::new());
+            lines.push(locale::text("menu.hint"));
+            return self.draw_centered_lines(window, &lines);
+        }
+
+        if let Some(menu) = &self.class_menu {
+            let mut lines = vec![locale::text("title.class"), String::new()];
+            if let Some(seed) = self.daily {
+                let date = daily::date(seed);
+                lines.insert(0, locale::format("title.daily", &[("date", &date)]));
+            }
+            lines.extend(menu.lines());
+            lines.push(String::new());
+            lines.push(menu.selected().description());
+            lines.push(String::new());
+            lines.push(locale::text("menu.hint"));
+            return self.draw_centered_lines(window, &lines);
+        }
+
+        if let Some((profile, name)) = &self.naming {
+            let class = profile.class.name();
+            let lines = vec![
+                locale::text("title.name"),
+                String::new(),
+                format!("> {}_", name),
+                String::new(),
+                locale::format("menu.name-hint", &[("class", &class)]),
+            ];
+            return self.draw_centered_lines(window, &lines);
+        }
+
+        if let Some(slots) = self.slot_menu.as_ref().filter(|slots| !slots.saving) {
+            let mut lines = vec![locale::text("title.load"), String::new()];
+            lines.extend(slots.menu.lines());
+            lines.push(String::new());
+            lines.push(locale::text("menu.hint"));
+            return self.draw_centered_lines(window, &lines);
+        }
+
+        if let Some(story) = self.world.stories.get(self.stories_shown).cloned() {
+            return self.draw_story(window, &story);
+        }
+
+        if let Some(outcome) = self.world.outcome {
+            return self.draw_end_screen(window, outcome);
+        }
 
         let tile_size_px = self.tile_size_px;
-        let offset_px = Vector::new(50, 120);
+        let offset_px = Vector::from(MAP_OFFSET_PX);
+        let view = self.map_view();
+        // Only the map shakes, the panels around it stay put
+        let shaken = MapView {
+            offset_px: view.offset_px + self.feedback.shake_offset(),
+            ..view
+        };
 
-        // Draw the map
-        let (tileset, map) = (&mut self.tileset, &self.map);
+        // Draw the map. Only the tiles the player has seen, greyed out
+        // unless they're in view right now.
+        let (tileset, map, fov) = (&mut self.tileset, &self.world.map, &self.world.fov);
+        let (light, world_hazards) = (&self.world.light, &self.world.hazards);
         tileset.execute(|tileset| {
-            for tile in map.iter() {
-                if let Some(image) = tileset.get(&tile.glyph) {
-                    let pos_px = tile.pos.times(tile_size_px);
+            tileset.resize(shaken.tile_size_px);
+            for tile in map.tiles.iter() {
+                let pos_px = match shaken.tile_px(tile.pos) {
+                    Some(pos_px) => pos_px,
+                    None => continue,
+                };
+                let color = if fov.is_visible(tile.pos) {
+                    ui::lit_color(tile.color, light.level(tile.pos))
+                } else if fov.is_explored(tile.pos) {
+                    ui::remembered_color(tile.color)
+                } else {
+                    continue;
+                };
+                if tile.glyph == WALL {
+                    let mask = map.wall_mask(tile.pos);
+                    for part in ui::wall_sprite(mask, shaken.tile_size_px) {
+                        window.draw(&part.translate(pos_px), Col(color));
+                    }
+                } else {
+                    let image = tileset.get(tile.shown_glyph())?;
+                    window.draw(
+                        &Rectangle::new(pos_px, image.area().size()),
+                        Blended(image, color),
+                    );
+                }
+            }
+
+            // The gas and fire in view, see-through over the floor
+            for hazard in &world_hazards[..] {
+                if let (true, Some(pos_px)) =
+                    (fov.is_visible(hazard.pos), shaken.tile_px(hazard.pos))
+                {
+                    window.draw(
+                        &Rectangle::new(pos_px, shaken.tile_size_px),
+                        Col(hazard.overlay()),
+                    );
+                }
+            }
+
+            // The items where the player last saw them
+            for (&(x, y), &(glyph, color)) in fov.remembered_items.iter() {
+                let pos = Vector::new(x, y);
+                if let (false, Some(pos_px)) = (fov.is_visible(pos), shaken.tile_px(pos)) {
+                    let image = tileset.get(glyph)?;
                     window.draw(
-                        &Rectangle::new(offset_px + pos_px, image.area().size()),
-                        Blended(&image, tile.color),
+                        &Rectangle::new(pos_px, image.area().size()),
+                        Blended(image, ui::remembered_color(color)),
                     );
                 }
             }
             Ok(())
         })?;
 
-        // Draw entities
-        let (tileset, entities) = (&mut self.tileset, &self.entities);
+        // Draw the entities in view
+        let (tileset, world, feedback) = (&mut self.tileset, &self.world, &self.feedback);
+        let (projectile, blink) = (&self.projectile, self.blink);
         tileset.execute(|tileset| {
-            for entity in entities.iter() {
-                if let Some(image) = tileset.get(&entity.glyph) {
-                    let pos_px = offset_px + entity.pos.times(tile_size_px);
+            for (id, entity) in world.entities.iter().enumerate() {
+                if !world.fov.is_visible(entity.pos) {
+                    continue;
+                }
+                let color = match ui::entity_color(world, id, blink) {
+                    Some(color) => color,
+                    None => continue,
+                };
+                let pos_px = match shaken.tile_px(entity.pos) {
+                    Some(pos_px) => pos_px,
+                    None => continue,
+                };
+                let color = if feedback.is_flashing(id) {
+                    // The background is white too so flash the tile
+                    // inverted
+                    window.draw(
+                        &Rectangle::new(pos_px, shaken.tile_size_px),
+                        Col(Color::BLACK),
+                    );
+                    Color::WHITE
+                } else {
+                    color
+                };
+                let image = tileset.get(entity.glyph)?;
+                window.draw(
+                    &Rectangle::new(pos_px, image.area().size()),
+                    Blended(image, color),
+                );
+            }
+
+            // Whatever is flying through the air, over everything else
+            if let Some((projectile, _)) = projectile {
+                if let Some(pos_px) = projectile.pos().and_then(|pos| shaken.tile_px(pos)) {
+                    let image = tileset.get(projectile.glyph)?;
                     window.draw(
                         &Rectangle::new(pos_px, image.area().size()),
-                        Blended(&image, entity.color),
+                        Blended(image, projectile.color),
                     );
                 }
             }
             Ok(())
         })?;
 
-        let player = &self.entities[self.player_id];
-        let full_health_width_px = 100.0;
-        let current_health_width_px =
-            (player.hp as f32 / player.max_hp as f32) * full_health_width_px;
+        // Draw the health bars of the wounded monsters, like the
+        // player's
+        if self.health_bars {
+            for id in 0..self.world.entities.len() {
+                let pos_px = match shaken.tile_px(self.world.entities[id].pos) {
+                    Some(pos_px) if ui::shows_health_bar(&self.world, id) => pos_px,
+                    _ => continue,
+                };
+                let entity = &self.world.entities[id];
+                let ratio = entity.hp as f32 / entity.max_hp as f32;
+                let color = ui::health_color(ratio);
+                let bar = ui::monster_bar(Rectangle::new(pos_px, shaken.tile_size_px));
+                window.draw(&bar, Col(color.with_alpha(0.5)));
+                window.draw(&ui::bar_fill(bar, ratio), Col(color));
+            }
+        }
+
+        // What stepping onto the tile under the mouse would cost
+        let hovered = view.tile_at(window.mouse().pos()) - self.world.player().pos;
+        let idle = self.targeting.is_none() && !self.show_inspector && self.projectile.is_none();
+        if idle && hovered.x.abs() + hovered.y.abs() == 1.0 {
+            let label = ui::cost_label(&self.world, &Action::Move(hovered));
+            let area = Rectangle::new(window.mouse().pos() + Vector::new(16, 16), (160, 40));
+            self.draw_panel(window, &Panel::new(area), &[label])?;
+        }
+
+        // Draw the damage numbers rising above whoever got hit
+        let (font, text_cache) = (&mut self.font, &mut self.text_cache);
+        let floating_texts = &self.floating_texts;
+        font.execute(|font| {
+            for floating in floating_texts.iter() {
+                let tile_px = match shaken.tile_px(floating.pos) {
+                    Some(tile_px) => tile_px,
+                    None => continue,
+                };
+                let image = cached_text(font, text_cache, &floating.text)?;
+                let lifetime = timing::updates(FLOATING_TEXT_SECONDS);
+                let progress = floating.age as f32 / lifetime as f32;
+                let tile_center_px = tile_px + shaken.tile_size_px / 2.0;
+                let rise_px = Vector::new(0.0, shaken.tile_size_px.y * (0.5 + progress));
+                window.draw(
+                    &image.area().with_center(tile_center_px - rise_px),
+                    Blended(image, Color::RED.with_alpha(1.0 - progress)),
+                );
+            }
+            Ok(())
+        })?;
 
-        let map_size_px = self.map_size.times(tile_size_px);
+        // The screens covering the map
+        let map_area = self.map_area();
+        let map_size_px = map_area.size;
         let health_bar_pos_px = offset_px + Vector::new(map_size_px.x, 0.0);
 
-        // Full health
-        window.draw(
-            &Rectangle::new(health_bar_pos_px, (full_health_width_px, tile_size_px.y)),
-            Col(Color::RED.with_alpha(0.5)),
+        // Draw the latest messages under the map and the current level
+        // above the health bar
+        let messages: Vec<Message> = self
+            .world
+            .log
+            .iter()
+            .rev()
+            .take(MESSAGES_SHOWN)
+            .rev()
+            .cloned()
+            .collect();
+        let log_area = Rectangle::new(
+            offset_px + Vector::new(0.0, map_size_px.y + HOTBAR_HEIGHT_PX),
+            (map_size_px.x, MESSAGES_SHOWN as f32 * 18.0),
+        );
+        let log = Panel::plain(log_area).with_line_height(18.0);
+        let mut lines = self.wrap_messages(&messages, log.text_width())?;
+        // Only the end of the latest message if it's too long
+        lines.drain(..lines.len().saturating_sub(MESSAGES_SHOWN));
+        self.draw_colored_panel(window, &log, &lines)?;
+
+        // The hotbar between the map and the messages
+        let hotbar_area = Rectangle::new(
+            offset_px + Vector::new(0.0, map_size_px.y),
+            (map_size_px.x, HOTBAR_HEIGHT_PX),
+        );
+        let hotbar = ui::hotbar(&self.world.player().cooldowns);
+        self.draw_colored_panel(window, &Panel::plain(hotbar_area), &[hotbar])?;
+
+        let level = if self.world.depth == 0 {
+            locale::text("hud.surface")
+        } else {
+            let branch = &self
+                .world
+                .templates
+                .branch(self.world.dungeon, self.world.depth)
+                .name;
+            locale::format(
+                "hud.depth",
+                &[
+                    ("depth", &self.world.depth),
+                    ("branch", &locale::content(branch)),
+                ],
+            )
+        };
+        let level_area = Rectangle::new(health_bar_pos_px - Vector::new(0, 30), (0, 30));
+        self.draw_panel(window, &Panel::plain(level_area), &[level])?;
+
+        let player = self.world.player();
+        let health_ratio = player.hp as f32 / player.max_hp as f32;
+        let health_color = ui::health_color(health_ratio);
+        let health_bar = Rectangle::new(health_bar_pos_px, (100.0, tile_size_px.y));
+        window.draw(&health_bar, Col(health_color.with_alpha(0.5)));
+        window.draw(&ui::bar_fill(health_bar, health_ratio), Col(health_color));
+
+        // The boss gets a long health bar of its own above the map
+        if let Some(boss) = self.world.visible_boss() {
+            let boss = &self.world.entities[boss];
+            let ratio = boss.hp as f32 / boss.max_hp as f32;
+            let name = capitalize(&boss.name);
+            let name_area = Rectangle::new(offset_px - Vector::new(0, 44), (map_size_px.x, 24));
+            self.draw_panel(window, &Panel::plain(name_area), &[name])?;
+            let bar = Rectangle::new(offset_px - Vector::new(0, 18), (map_size_px.x, 12));
+            window.draw(&bar, Col(Color::RED.with_alpha(0.3)));
+            window.draw(&ui::bar_fill(bar, ratio), Col(Color::RED));
+        }
+
+        // Draw the inventory under the health bar
+        let world = &self.world;
+        let mut lines = vec![
+            locale::format("hud.gold", &[("amount", &world.player().gold)]),
+            locale::text("hud.inventory"),
+        ];
+        if world.confused > 0 {
+            lines.insert(0, locale::text("hud.confused"));
+        }
+        if world.sneaking {
+            lines.insert(0, locale::text("hud.sneaking"));
+        }
+        for (letter, item) in (b'A'..=b'Z').zip(world.player().inventory.iter()) {
+            lines.push(format!(
+                "[{}] {}",
+                letter as char,
+                world.inventory_line(item)
+            ));
+        }
+        let inventory_area = Rectangle::new(
+            health_bar_pos_px + Vector::new(0.0, 24.0),
+            (SIDE_PANEL_WIDTH_PX, map_size_px.y),
         );
+        let inventory = Panel::plain(inventory_area).with_line_height(24.0);
+        self.draw_panel(window, &inventory, &lines)?;
+
+        if let Some(shopkeeper) = self.world.shop {
+            self.draw_shop(window, map_area, shopkeeper)?;
+        }
+        if let Some(chest) = self.world.chest {
+            self.draw_chest(window, map_area, chest)?;
+        }
+        if let Some(targeting) = &self.targeting {
+            if let Some(cursor_px) = view.tile_px(targeting.cursor) {
+                window.draw(
+                    &Rectangle::new(cursor_px, view.tile_size_px),
+                    Col(Color::ORANGE.with_alpha(0.5)),
+                );
+            }
+            let mut lines = targeting::describe(&self.world, targeting.cursor);
+            let cursor = targeting.cursor;
+            let action = match (self.throwing, self.casting) {
+                (Some(index), _) => Some(Action::Throw(index, cursor)),
+                (None, Some(skill)) => Some(Action::Skill(skill, cursor)),
+                _ if self.firing => Some(Action::Fire(cursor)),
+                _ => None,
+            };
+            if let Some(action) = action {
+                lines.push(ui::cost_label(&self.world, &action));
+            }
+            lines.push(String::new());
+            lines.push(locale::text("look.hint-next"));
+            if self.throwing.is_some() {
+                lines.push(locale::text("look.hint-throw"));
+                lines.push(locale::text("look.hint-cancel"));
+            } else if self.firing {
+                lines.push(locale::text("look.hint-fire"));
+                lines.push(locale::text("look.hint-cancel"));
+            } else if self.casting.is_some() {
+                lines.push(locale::text("look.hint-cast"));
+                lines.push(locale::text("look.hint-cancel"));
+            } else {
+                lines.push(locale::text("look.hint-done"));
+            }
+            let area = Rectangle::new(health_bar_pos_px, (SIDE_PANEL_WIDTH_PX, map_size_px.y));
+            self.draw_panel(
+                window,
+                &Panel::new(area).with_title(locale::text("title.look")),
+                &lines,
+            )?;
+        }
+        if self.show_inspector {
+            // Whatever is on the tile under the mouse, in the panel next
+            // to the map
+            let pos = view.tile_at(window.mouse().pos());
+            if let Some(pos_px) = view.tile_px(pos) {
+                window.draw(
+                    &Rectangle::new(pos_px, view.tile_size_px),
+                    Col(Color::ORANGE.with_alpha(0.5)),
+                );
+            }
+            let lines = wizard::inspect(&self.world, pos);
+            let area = Rectangle::new(health_bar_pos_px, (SIDE_PANEL_WIDTH_PX, map_size_px.y));
+            self.draw_panel(
+                window,
+                &Panel::new(area).with_title(locale::text("title.inspector")),
+                &lines,
+            )?;
+        }
+        if self.show_bestiary {
+            let mut lines = vec![];
+            let bestiary = self.world.stats.bestiary(&self.world.templates);
+            if bestiary.is_empty() {
+                lines.push(locale::text("bestiary.empty"));
+            }
+            lines.extend(bestiary);
+            lines.push(String::new());
+            lines.push(locale::text("hint.close"));
+            let panel = Panel::new(map_area).with_title(locale::text("title.bestiary"));
+            self.draw_panel(window, &panel, &lines)?;
+        }
+        if let Some(page) = self.help_page {
+            self.draw_help(window, map_area, page)?;
+        }
+        if self.history.is_some() {
+            self.draw_history(window, map_area)?;
+        }
+        if let Some(line) = &self.console {
+            let messages = self.world.log.iter().rev().take(CONSOLE_MESSAGES).rev();
+            let mut lines: Vec<String> = messages.map(|message| message.to_string()).collect();
+            lines.push(String::new());
+            lines.push(format!("> {}_", line));
+            let panel = Panel::new(map_area).with_title(locale::text("title.console"));
+            self.draw_panel(window, &panel, &lines)?;
+        }
+        if let Some(menu) = &self.pause_menu {
+            let panel = Panel::new(map_area).with_title(locale::text("title.paused"));
+            self.draw_panel(window, &panel, &menu.lines())?;
+        }
+        if let Some(slots) = &self.slot_menu {
+            let mut lines = slots.menu.lines();
+            if slots.confirming {
+                lines.push(String::new());
+                lines.push(locale::text("save.overwrite"));
+            }
+            let panel = Panel::new(map_area).with_title(locale::text("title.save"));
+            self.draw_panel(window, &panel, &lines)?;
+        }
+
+        if self.show_fps {
+            let fps = format!("FPS: {:.0}", window.average_fps());
+            let (font, text_cache) = (&mut self.font, &mut self.text_cache);
+            font.execute(|font| {
+                let image = cached_text(font, text_cache, &fps)?;
+                window.draw(
+                    &image.area().translate((2, 2)),
+                    Blended(image, Color::BLACK),
+                );
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
 
-        // Current health
+    /// How many of the assets (the fonts and everything rendered with
+    /// them) are ready, out of how many.
+    fn loaded_assets(&mut self) -> Result<(usize, usize)> {
+        let mut loaded = 0;
+        for image in [
+            &mut self.title,
+            &mut self.mononoki_font_info,
+            &mut self.square_font_info,
+        ] {
+            image.execute(|_| {
+                loaded += 1;
+                Ok(())
+            })?;
+        }
+        self.tileset.execute(|_| {
+            loaded += 1;
+            Ok(())
+        })?;
+        self.font.execute(|_| {
+            loaded += 1;
+            Ok(())
+        })?;
+        self.text_metrics.execute(|_| {
+            loaded += 1;
+            Ok(())
+        })?;
+        Ok((loaded, 6))
+    }
+
+    /// A progress bar. It can't show any text: the fonts are what's being
+    /// loaded.
+    fn draw_loading_screen(&mut self, window: &mut Window) -> Result<()> {
+        let (loaded, total) = self.loaded_assets()?;
+        let size_px = Vector::new(300, 24);
+        let pos_px = (window.screen_size() - size_px) / 2.0;
+        let progress = loaded as f32 / total as f32;
+        window.draw(
+            &Rectangle::new(pos_px, size_px),
+            Col(Color::BLACK.with_alpha(0.2)),
+        );
         window.draw(
-            &Rectangle::new(health_bar_pos_px, (current_health_width_px, tile_size_px.y)),
-            Col(Color::RED),
+            &Rectangle::new(pos_px, (size_px.x * progress, size_px.y)),
+            Col(Color::BLACK),
         );
+        Ok(())
+    }
 
-        self.inventory.execute(|image| {
+    /// Stop the game and show `message` instead.
+    fn fail(&mut self, message: String) {
+        eprintln!("Error: {}", message);
+        self.error = Some(message);
+    }
+
+    /// Tell the player what went wrong. The fonts may be what failed to
+    /// load, so if there's no way to write the message, show a big
+    /// exclamation mark at least.
+    fn draw_error_screen(&mut self, window: &mut Window, message: &str) -> Result<()> {
+        window.clear(Color::from_rgba(96, 0, 0, 1.0))?;
+        let mut lines = vec![locale::text("error.heading"), String::new()];
+        lines.extend(message.lines().map(String::from));
+        lines.push(String::new());
+        lines.push(locale::text("error.hint"));
+
+        let (font, text_cache) = (&mut self.font, &mut self.text_cache);
+        let written = font.execute(|font| {
+            for (index, line) in lines.iter().enumerate() {
+                if line.is_empty() {
+                    continue;
+                }
+                let image = cached_text(font, text_cache, line)?;
+                window.draw(
+                    &image.area().translate((40.0, 60.0 + index as f32 * 24.0)),
+                    Blended(image, Color::WHITE),
+                );
+            }
+            Ok(())
+        });
+        if written.is_err() {
+            let center_px = window.screen_size() / 2.0;
             window.draw(
-                &image
-                    .area()
-                    .translate(health_bar_pos_px + Vector::new(0, tile_size_px.y)),
-                Img(&image),
+                &Rectangle::new(center_px - Vector::new(12, 100), (24, 140)),
+                Col(Color::WHITE),
+            );
+            window.draw(
+                &Rectangle::new(center_px + Vector::new(-12, 64), (24, 24)),
+                Col(Color::WHITE),
             );
+        }
+        Ok(())
+    }
+
+    fn take_turn(&mut self, action: Action) {
+        let was_over = self.world.outcome.is_some();
+        let depth = self.world.depth;
+        self.world.player_turn(action.clone());
+        // Kept as the game is played, so replays show where they go
+        // out of sync, see `checksum`
+        let hash = checksum::state_hash(&self.world);
+        self.replay.record_hashed(action, hash);
+        if !was_over && self.world.outcome.is_some() {
+            self.save_morgue();
+            // Permadeath, and there's nothing left to do in a won game
+            if wizard::is_permadeath(&self.world) || self.world.outcome == Some(Outcome::Won) {
+                self.delete_slot();
+            }
+        }
+        // Autosave on every level change so a crash doesn't cost much
+        if self.world.depth != depth {
+            self.save_game();
+        }
+    }
+
+    /// Throw the inventory item, resolving the throw once the item has
+    /// flown to where it lands.
+    fn throw(&mut self, index: usize, target: Vector) {
+        let action = Action::Throw(index, target);
+        let path = throwing::trajectory(&self.world, self.world.player().pos, target);
+        match self.world.player().inventory.get(index) {
+            Some(item) if !path.is_empty() => {
+                let projectile = Projectile::new(path, item.glyph, item.color);
+                self.projectile = Some((projectile, action));
+            }
+            // Let the world explain what's wrong
+            _ if self.playback.is_some() => self.world.player_turn(action),
+            _ => self.take_turn(action),
+        }
+    }
+
+    /// Pick where to shoot the ranged weapon, if there's anything to
+    /// shoot with.
+    fn start_firing(&mut self) {
+        match ammo::ammo(&self.world) {
+            None => self.world.log(locale::text("log.no-ranged-weapon")),
+            Some(ammo) if ammo::carried(&self.world, &ammo) == 0 => {
+                let name = self.world.identification.name(&self.world.templates, &ammo);
+                self.world
+                    .log(locale::message("log.out-of-ammo", &[("item", &name)]));
+            }
+            Some(_) => {
+                self.firing = true;
+                self.targeting = Some(Targeting::new(&self.world));
+            }
+        }
+    }
+
+    /// Like `throw`, for the ranged weapon's ammo.
+    fn fire(&mut self, target: Vector) {
+        let action = Action::Fire(target);
+        let path = ammo::trajectory(&self.world, target);
+        let shot = ammo::ammo(&self.world).and_then(|ammo| {
+            let inventory = self.world.player().inventory.iter();
+            inventory
+                .filter(|entity| {
+                    entity
+                        .item
+                        .as_ref()
+                        .is_some_and(|item| item.template == ammo)
+                })
+                .map(|entity| (entity.glyph, entity.color))
+                .next()
+        });
+        match shot {
+            Some((glyph, color)) if !path.is_empty() => {
+                self.projectile = Some((Projectile::new(path, glyph, color), action));
+            }
+            _ if self.playback.is_some() => self.world.player_turn(action),
+            _ => self.take_turn(action),
+        }
+    }
+
+    /// Get a skill from the hotbar ready: ask which way to use it or
+    /// where to aim it.
+    fn start_skill(&mut self, slot: usize) {
+        let skill = match HOTBAR.get(slot).copied().flatten() {
+            Some(skill) => skill,
+            None => return self.world.log(locale::text("log.empty-slot")),
+        };
+        let remaining = self.world.player().cooldowns.remaining(skill);
+        if remaining > 0 {
+            return self.world.log(locale::message(
+                "log.skill-not-ready",
+                &[("skill", &skill.name()), ("turns", &remaining)],
+            ));
+        }
+        match skill.aim() {
+            Aim::Direction => {
+                self.aiming = Some(skill);
+                self.world.log(locale::text("prompt.direction"));
+            }
+            Aim::Target => {
+                self.casting = Some(skill);
+                self.targeting = Some(Targeting::new(&self.world));
+            }
+        }
+    }
+
+    /// Like `throw`, for skills that fly at their target.
+    fn cast(&mut self, skill: Skill, target: Vector) {
+        let action = Action::Skill(skill, target);
+        let path =
+            throwing::flight_path(&self.world, self.world.player().pos, target, FIREBOLT_RANGE);
+        let ready = self.world.player().cooldowns.is_ready(skill);
+        if !path.is_empty() && ready {
+            let projectile = Projectile::new(path, skill.glyph(), Color::ORANGE);
+            self.projectile = Some((projectile, action));
+        } else if self.playback.is_some() {
+            self.world.player_turn(action);
+        } else {
+            self.take_turn(action);
+        }
+    }
+
+    /// Break the messages up to fit the width, keeping their colours.
+    fn wrap_messages(&mut self, messages: &[Message], width_px: f32) -> Result<Vec<Vec<Segment>>> {
+        let mut lines = vec![];
+        for message in messages {
+            let wrapped = self.wrap_lines(&[message.to_string()], width_px)?;
+            lines.extend(ui::split_segments(&message.display_segments(), &wrapped));
+        }
+        Ok(lines)
+    }
+
+    /// Throw the current world away and play `world` instead.
+    fn new_game(&mut self, world: World) {
+        self.world = world;
+        self.replay = Replay::new(self.world.seed);
+        self.replay.mode = self.world.mode;
+        self.replay.profile = self.world.profile.clone();
+        self.replay.config = self.world.config;
+        self.replay.packs = packs::current().names.clone();
+        self.playback = None;
+        self.activity = None;
+        self.choosing_item = None;
+        self.throwing = None;
+        self.firing = false;
+        self.casting = None;
+        self.aiming = None;
+        self.show_bestiary = false;
+        self.history = None;
+        self.console = None;
+        self.reloaded = false;
+        self.stories_shown = 0;
+        self.targeting = None;
+        self.projectile = None;
+        self.floating_texts.clear();
+        self.feedback.clear();
+    }
+
+    /// The help text for the current game mode.
+    fn help_lines(&self) -> Vec<String> {
+        input::help_lines(self.world.mode == GameMode::Wizard)
+    }
+
+    /// The buy/sell screen, drawn over the map.
+    fn draw_shop(&mut self, window: &mut Window, area: Rectangle, shopkeeper: usize) -> Result<()> {
+        let world = &self.world;
+        let title = locale::format("shop.title", &[("amount", &world.player().gold)]);
+        let mut lines = vec![locale::text("shop.for-sale")];
+        let stock = &world.entities[shopkeeper].inventory;
+        for (letter, item) in (b'a'..=b'z').zip(stock.iter()) {
+            let price = shop::price(world, item);
+            lines.push(shop_line(world, letter, item, price));
+        }
+        lines.push(String::new());
+        lines.push(locale::text("shop.sell"));
+        for (letter, item) in (b'A'..=b'Z').zip(world.player().inventory.iter()) {
+            let price = shop::sell_price(world, item);
+            lines.push(shop_line(world, letter, item, price));
+        }
+        lines.push(String::new());
+        lines.push(locale::text("shop.hint"));
+        self.draw_panel(window, &Panel::new(area).with_title(title), &lines)
+    }
+
+    /// What's in the open chest, drawn over the map.
+    fn draw_chest(&mut self, window: &mut Window, area: Rectangle, chest: usize) -> Result<()> {
+        let world = &self.world;
+        let title = capitalize(&world.entities[chest].name);
+        let contents = &world.entities[chest].inventory;
+        let mut lines: Vec<String> = (b'a'..=b'z')
+            .zip(contents.iter())
+            .map(|(letter, item)| format!("[{}] {}", letter as char, world.stack_name(item)))
+            .collect();
+        if lines.is_empty() {
+            lines.push(locale::text("chest.empty"));
+        }
+        lines.push(String::new());
+        lines.push(locale::text("chest.hint"));
+        self.draw_panel(window, &Panel::new(area).with_title(title), &lines)
+    }
+
+    /// The key bindings, drawn over the map.
+    fn draw_help(&mut self, window: &mut Window, area: Rectangle, page: usize) -> Result<()> {
+        let pages = self.help_pages()?;
+        let page = page.min(pages.len() - 1);
+        let mut lines = pages[page].clone();
+        lines.push(String::new());
+        if pages.len() > 1 {
+            lines.push(locale::text("help.hint-pages"));
+        } else {
+            lines.push(locale::text("hint.close"));
+        }
+        let title = locale::text("title.keys");
+        self.draw_panel(window, &Panel::new(area).with_title(title), &lines)
+    }
+
+    /// Every message matching the search of the history screen, wrapped
+    /// to fit, and how many lines of them fit on a page.
+    fn history_lines(&mut self) -> Result<(Vec<Vec<Segment>>, usize)> {
+        let panel = Panel::new(self.map_area()).with_title(locale::text("title.history"));
+        let history = self.history.clone().unwrap_or_default();
+        let messages: Vec<Message> = history
+            .matches(&self.world.log)
+            .into_iter()
+            .cloned()
+            .collect();
+        let lines = self.wrap_messages(&messages, panel.text_width())?;
+        // Leave room for the blank line, the search and the instructions
+        Ok((lines, panel.capacity().saturating_sub(3)))
+    }
+
+    /// The message history, scrolled to where the player left it.
+    fn draw_history(&mut self, window: &mut Window, area: Rectangle) -> Result<()> {
+        let (lines, per_page) = self.history_lines()?;
+        let history = self.history.clone().unwrap_or_default();
+        let mut shown = history.visible(&lines, per_page).to_vec();
+        if lines.is_empty() {
+            shown.push(vec![Segment::plain(locale::text("history.no-matches"))]);
+        }
+        shown.push(vec![]);
+        if history.typing || !history.search.is_empty() {
+            let cursor = if history.typing { "_" } else { "" };
+            let search = format!("{}{}", history.search, cursor);
+            let search = locale::format("history.search", &[("search", &search)]);
+            shown.push(vec![Segment::plain(search)]);
+        }
+        shown.push(vec![Segment::plain(locale::text("history.hint"))]);
+        let panel = Panel::new(area).with_title(locale::text("title.history"));
+        self.draw_colored_panel(window, &panel, &shown)
+    }
+
+    /// The help wrapped to fit its panel and split into pages.
+    fn help_pages(&mut self) -> Result<Vec<Vec<String>>> {
+        let panel = Panel::new(self.map_area()).with_title(locale::text("title.keys"));
+        let lines = self.wrap_lines(&self.help_lines(), panel.text_width())?;
+        // Leave room for the blank line and the instructions under it
+        Ok(ui::more_pages(&lines, panel.capacity().saturating_sub(2)))
+    }
+
+    /// Where the map is drawn on the screen.
+    fn map_area(&self) -> Rectangle {
+        let size_px = self.view_tiles.times(self.tile_size_px);
+        Rectangle::new(Vector::from(MAP_OFFSET_PX), size_px)
+    }
+
+    /// The part of the map that fits in its area at the current zoom,
+    /// around the player.
+    fn map_view(&self) -> MapView {
+        MapView::new(
+            self.map_area(),
+            self.zoomed_tile_px,
+            self.world.map.size,
+            self.world.player().pos,
+        )
+    }
+
+    /// Break every one of `lines` up to fit the width. Until the font is
+    /// loaded they're left as they are.
+    fn wrap_lines(&mut self, lines: &[String], width_px: f32) -> Result<Vec<String>> {
+        let mut wrapped = lines.to_vec();
+        self.text_metrics.execute(|metrics| {
+            wrapped = lines
+                .iter()
+                .flat_map(|line| metrics.wrap(line, width_px))
+                .collect();
             Ok(())
         })?;
+        Ok(wrapped)
+    }
 
-        Ok(())
+    /// A panel with its title and `lines` of text, see `ui::Panel`.
+    /// Lines too long for it are wrapped.
+    fn draw_panel(&mut self, window: &mut Window, panel: &Panel, lines: &[String]) -> Result<()> {
+        let lines: Vec<Vec<Segment>> = self
+            .wrap_lines(lines, panel.text_width())?
+            .into_iter()
+            .map(|line| vec![Segment::plain(line)])
+            .collect();
+        self.draw_colored_panel(window, panel, &lines)
+    }
+
+    /// `draw_panel` with every line made of segments in their own
+    /// colours. The lines must fit the panel already.
+    fn draw_colored_panel(
+        &mut self,
+        window: &mut Window,
+        panel: &Panel,
+        lines: &[Vec<Segment>],
+    ) -> Result<()> {
+        if let Some(background) = panel.background {
+            window.draw(&panel.area, Col(background));
+        }
+        if let Some(border) = panel.border {
+            for side in panel.borders() {
+                window.draw(&side, Col(border));
+            }
+        }
+        let title = panel
+            .title
+            .iter()
+            .map(|title| (vec![Segment::plain(title.as_str())], panel.title_pos()));
+        let text = lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| (line.clone(), panel.line_pos(index)));
+        let lines: Vec<(Vec<Segment>, Vector)> = title.chain(text).collect();
+        let (font, text_cache) = (&mut self.font, &mut self.text_cache);
+        let text_metrics = &mut self.text_metrics;
+        text_metrics.execute(|metrics| {
+            font.execute(|font| {
+                for (line, pos_px) in &lines {
+                    // Each segment starts where the text before it ends
+                    let mut before = String::new();
+                    for segment in line {
+                        if !segment.text.trim().is_empty() {
+                            let image = cached_text(font, text_cache, &segment.text)?;
+                            let offset_px = Vector::new(metrics.width(&before), 0.0);
+                            window.draw(
+                                &image.area().translate(*pos_px + offset_px),
+                                Blended(image, segment.color.unwrap_or(Color::BLACK)),
+                            );
+                        }
+                        before.push_str(&segment.text);
+                    }
+                }
+                Ok(())
+            })
+        })
+    }
+
+    /// The game over and victory screens: the run statistics and a way
+    /// to start again.
+    fn draw_end_screen(&mut self, window: &mut Window, outcome: Outcome) -> Result<()> {
+        let heading = match outcome {
+            Outcome::Died => locale::text("end.died"),
+            Outcome::Won => locale::text("end.won"),
+        };
+        let mut lines = vec![heading, String::new()];
+        lines.extend(self.world.stats.summary());
+        lines.push(String::new());
+        lines.push(locale::text("end.hint"));
+        self.draw_centered_lines(window, &lines)
+    }
+
+    /// A story screen: its paragraphs one after another and how to get
+    /// back to the game.
+    fn draw_story(&mut self, window: &mut Window, paragraphs: &[String]) -> Result<()> {
+        let mut lines = vec![];
+        for paragraph in paragraphs {
+            lines.extend(self.wrap_lines(std::slice::from_ref(paragraph), STORY_WIDTH_PX)?);
+            lines.push(String::new());
+        }
+        lines.push(locale::text("story.hint"));
+        self.draw_centered_lines(window, &lines)
+    }
+
+    /// Lines of text in the middle of the screen, under the title.
+    fn draw_centered_lines(&mut self, window: &mut Window, lines: &[String]) -> Result<()> {
+        let (font, text_cache) = (&mut self.font, &mut self.text_cache);
+        font.execute(|font| {
+            for (index, line) in lines.iter().enumerate() {
+                if line.is_empty() {
+                    continue;
+                }
+                let image = cached_text(font, text_cache, line)?;
+                let pos_px = Vector::new(window.screen_size().x / 2.0, 120.0 + index as f32 * 24.0);
+                window.draw(
+                    &image.area().with_center(pos_px),
+                    Blended(image, Color::BLACK),
+                );
+            }
+            Ok(())
+        })
+    }
+
+    /// Save what's just been drawn as `screenshot-<seconds>.png`, or
+    /// offer it as a download on the web.
+    fn save_screenshot(&mut self, window: &mut Window) {
+        let saved = window
+            .flush()
+            .map_err(|error| error.to_string())
+            .and_then(|()| {
+                let image = window.screenshot(quicksilver::graphics::PixelFormat::RGB);
+                save_png(image)
+            });
+        match saved {
+            Ok(path) => self
+                .world
+                .log(locale::message("log.screenshot", &[("path", &path)])),
+            Err(error) => {
+                eprintln!("Could not save the screenshot: {}", error);
+                self.world.log(locale::text("log.screenshot-failed"));
+            }
+        }
+    }
+
+    /// Switch to the templates on disk if they've changed since the last
+    /// check.
+    fn reload_templates(&mut self) {
+        let watcher = match &mut self.templates_watcher {
+            Some(watcher) => watcher,
+            None => return,
+        };
+        if !watcher.changed() {
+            return;
+        }
+        let templates = watcher.templates();
+        let world = &mut self.world;
+        match templates.and_then(|templates| hot_reload::reload(world, templates)) {
+            Ok(count) => {
+                self.world.log(locale::message(
+                    "log.templates-reloaded",
+                    &[("count", &count)],
+                ));
+                if !self.reloaded && self.playback.is_none() {
+                    self.world.log(locale::text("log.saving-off"));
+                }
+                self.reloaded = true;
+            }
+            Err(error) => self.world.log(locale::message(
+                "log.templates-invalid",
+                &[("error", &error)],
+            )),
+        }
+    }
+
+    /// Print the current level as text (see `export::ascii`), or write
+    /// it to `path`.
+    fn dump_map(&mut self, path: Option<String>) {
+        let text = export::ascii(&self.world);
+        match path {
+            None => {
+                print!("{}", text);
+                self.world.log(locale::text("log.map-printed"));
+            }
+            Some(path) => match std::fs::write(&path, text) {
+                Ok(()) => self
+                    .world
+                    .log(locale::message("log.map-saved", &[("path", &path)])),
+                Err(error) => self.world.log(error.to_string()),
+            },
+        }
+    }
+
+    /// Write the morgue file for the game that's just ended.
+    fn save_morgue(&self) {
+        if cfg!(target_arch = "wasm32") {
+            return;
+        }
+        match std::fs::write(MORGUE_FILE, morgue::morgue(&self.world)) {
+            Ok(()) => println!("Morgue file saved to {}", MORGUE_FILE),
+            Err(error) => eprintln!("Could not save the morgue file: {}", error),
+        }
+    }
+
+    /// Whether a game's being played, rather than picked on one of the
+    /// menus before it starts.
+    fn in_game(&self) -> bool {
+        let loading = self.slot_menu.as_ref().is_some_and(|slots| !slots.saving);
+        self.main_menu.is_none() && self.class_menu.is_none() && self.naming.is_none() && !loading
+    }
+
+    fn has_saves(&self) -> bool {
+        self.saves.iter().any(Option::is_some)
+    }
+
+    /// Whether the game saved in `slot` plays back the same with the
+    /// packs of this run. Saves from other packs aren't loaded.
+    fn loads(&self, slot: usize) -> bool {
+        self.saves[slot]
+            .as_ref()
+            .is_some_and(|save| packs::matches(&save.replay.packs))
+    }
+
+    /// Continue the game saved in `slot`.
+    fn load_slot(&mut self, slot: usize) {
+        if let Some(save) = self.saves[slot].clone() {
+            self.new_game(save.replay.play());
+            // They've been read before the game was saved
+            self.stories_shown = self.world.stories.len();
+            self.replay = save.replay;
+            self.slot = Some(slot);
+        }
+    }
+
+    /// Save the current game in `slot` and keep saving it there.
+    fn save_into(&mut self, slot: usize) {
+        self.slot = Some(slot);
+        if self.save_game() {
+            self.world.log(locale::text("log.saved"));
+        } else {
+            self.world.log(locale::text("log.not-saved"));
+        }
+    }
+
+    /// Write the current game into its slot. Nothing is saved while
+    /// watching a replay, before a game has started (that would
+    /// overwrite the saved game), after it's over or once the templates
+    /// have been reloaded into it. Return whether it was saved.
+    fn save_game(&mut self) -> bool {
+        let slot = match self.slot {
+            Some(slot) if self.playback.is_none() && self.in_game() && !self.reloaded => slot,
+            _ => return false,
+        };
+        if self.world.outcome.is_some() || cfg!(target_arch = "wasm32") {
+            return false;
+        }
+        let save = Save::new(&self.world, self.replay.clone(), now_seconds());
+        match saves::write(slot, &save) {
+            Ok(path) => {
+                println!("Game saved to {}", path.display());
+                self.saves[slot] = Some(save);
+                true
+            }
+            Err(error) => {
+                eprintln!("Could not save the game: {}", error);
+                false
+            }
+        }
+    }
+
+    /// Empty the current game's slot: once it's over, there's no going
+    /// back to it.
+    fn delete_slot(&mut self) {
+        let slot = match self.slot.take() {
+            Some(slot) => slot,
+            None => return,
+        };
+        self.saves[slot] = None;
+        if cfg!(target_arch = "wasm32") {
+            return;
+        }
+        let path = saves::path(slot);
+        match saves::delete(slot) {
+            Ok(()) => println!("Removed {} after the game ended.", path.display()),
+            Err(error) => eprintln!("Could not remove the save {}: {}", path.display(), error),
+        }
     }
 }
 
+/// An item listed in the shop under `letter`.
+fn shop_line(world: &World, letter: u8, item: &Entity, price: u32) -> String {
+    locale::format(
+        "shop.item",
+        &[
+            ("letter", &(letter as char)),
+            ("item", &world.stack_name(item)),
+            ("price", &price),
+        ],
+    )
+}
+
+/// Render `text` in white (tint it with `Blended` when drawing) or reuse
+/// the image rendered in an earlier frame.
+fn cached_text<'a>(
+    font: &Font,
+    cache: &'a mut HashMap<String, Image>,
+    text: &str,
+) -> Result<&'a Image> {
+    if !cache.contains_key(text) {
+        if cache.len() >= TEXT_CACHE_SIZE {
+            cache.clear();
+        }
+        let image = font.render(text, &FontStyle::new(TEXT_SIZE, Color::WHITE))?;
+        cache.insert(text.to_string(), image);
+    }
+    Ok(&cache[text])
+}
+
+/// A new world with a random seed and the map size and difficulty from
+/// the options, in wizard mode if the player asked for it.
+fn new_world(profile: PlayerProfile) -> World {
+    let options = OPTIONS.get().cloned().unwrap_or_default();
+    let mut world = World::with_config(rand::random(), profile, options.config);
+    if options.wizard {
+        world.mode = GameMode::Wizard;
+    }
+    world
+}
+
+/// The window has room for the map's area at the normal tile size with
+/// the messages under it and the inventory to its right.
+fn window_size(view_tiles: Vector) -> Vector {
+    let map_size_px = view_tiles * TILE_SIZE_PX;
+    let below_px = HOTBAR_HEIGHT_PX + MESSAGES_SHOWN as f32 * 18.0 + BOTTOM_MARGIN_PX;
+    Vector::from(MAP_OFFSET_PX) + map_size_px + Vector::new(SIDE_PANEL_WIDTH_PX, below_px)
+}
+
+/// The seconds since the Unix epoch, by the system's clock.
+#[cfg(not(target_arch = "wasm32"))]
+fn now_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// The seconds since the Unix epoch, by the browser's clock.
+#[cfg(target_arch = "wasm32")]
+fn now_seconds() -> u64 {
+    use std::convert::TryInto;
+
+    let seconds: f64 = stdweb::js! { return Math.floor(Date.now() / 1000); }
+        .try_into()
+        .unwrap_or(0.0);
+    seconds as u64
+}
+
+/// Print a text mode frame, with a blank line after it.
+#[cfg(not(target_arch = "wasm32"))]
+fn show_text(frame: &str) {
+    println!("{}\n", frame);
+}
+
+/// Put a text mode frame in the page's `text-mode` element, if it has
+/// one. It should be marked `aria-live` for screen readers to read it
+/// out.
+#[cfg(target_arch = "wasm32")]
+fn show_text(frame: &str) {
+    stdweb::js! {
+        var element = document.getElementById("text-mode");
+        if (element) {
+            element.textContent = @{frame};
+        }
+    }
+}
+
+/// Write `image` to a new file named after the current time and return
+/// its name.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_png(image: image::DynamicImage) -> std::result::Result<String, String> {
+    let path = format!("screenshot-{}.png", now_seconds());
+    image.save(&path).map_err(|error| error.to_string())?;
+    Ok(path)
+}
+
+/// Have the browser download `image`.
+#[cfg(target_arch = "wasm32")]
+fn save_png(image: image::DynamicImage) -> std::result::Result<String, String> {
+    use std::convert::TryInto;
+
+    let mut png = vec![];
+    image
+        .write_to(&mut png, image::ImageOutputFormat::PNG)
+        .map_err(|error| error.to_string())?;
+    let png = stdweb::web::TypedArray::<u8>::from(png.as_slice());
+    let path: String = stdweb::js! {
+        var name = "screenshot-" + Math.floor(Date.now() / 1000) + ".png";
+        var link = document.createElement("a");
+        link.href = URL.createObjectURL(new Blob([@{png}], { type: "image/png" }));
+        link.download = name;
+        link.click();
+        URL.revokeObjectURL(link.href);
+        return name;
+    }
+    .try_into()
+    .map_err(|_| "the download failed".to_string())?;
+    Ok(path)
+}
+
+/// What's in the save slots. There aren't any on the web.
+fn load_saves() -> Vec<Option<Save>> {
+    if cfg!(target_arch = "wasm32") {
+        return vec![None; SLOT_COUNT];
+    }
+    saves::load_all()
+}
+
+/// The replay in the file at `path`, which can be a save too.
+fn load_replay(path: &str) -> Result<Replay> {
+    let text = std::fs::read_to_string(path)?;
+    Save::parse(&text)
+        .and_then(|save| {
+            if packs::matches(&save.replay.packs) {
+                Ok(save.replay)
+            } else {
+                Err(locale::format(
+                    "error.other-packs",
+                    &[
+                        ("recorded", &packs::describe(&save.replay.packs)),
+                        ("current", &packs::describe(&packs::current().names)),
+                    ],
+                ))
+            }
+        })
+        .map_err(|message| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: {}", path, message),
+            )
+            .into()
+        })
+}
+
+/// The options from the command line.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_options() -> std::result::Result<Options, String> {
+    Options::from_args(std::env::args().skip(1))
+}
+
+/// The options from the page's query string, e.g. `?size=80x50`.
+#[cfg(target_arch = "wasm32")]
+fn read_options() -> std::result::Result<Options, String> {
+    use std::convert::TryInto;
+
+    let query: String = stdweb::js! { return window.location.search; }
+        .try_into()
+        .unwrap_or_default();
+    Options::from_query(&query)
+}
+
 fn main() {
+    let options = match read_options() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            std::process::exit(1);
+        }
+    };
+    if options.observe.is_some() && !cfg!(feature = "observer") {
+        eprintln!("--observe needs a build with the `observer` feature.");
+        std::process::exit(1);
+    }
+    match Locale::new(&options.language) {
+        Ok(strings) => locale::set(strings),
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    }
+    let content = options
+        .packs
+        .iter()
+        .map(Pack::load)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .and_then(|packs| Content::new(&packs));
+    match content {
+        Ok(content) => packs::set(content),
+        Err(message) => {
+            eprintln!("Could not load the content packs: {}", message);
+            std::process::exit(1);
+        }
+    }
+
+    // A replay is played on levels of the size it was recorded with
+    let map_size = match options.replay.as_deref().map(load_replay) {
+        Some(Ok(replay)) => replay.config.map_size,
+        _ => options.config.map_size,
+    };
+    OPTIONS
+        .set(options)
+        .expect("The options are only set once.");
+
     // NOTE: Set HIDPI to 1.0 to get pixel-perfect rendering.
     // Otherwise the window resizes to whatever value the OS sets and
     // scales the contents.
@@ -299,7 +2299,12 @@ fn main() {
         // If the graphics do need to be scaled (e.g. using
         // `with_center`), blur them. This looks better with fonts.
         scale: quicksilver::graphics::ImageScaleStrategy::Blur,
+        // Spell these out: all the game's timing depends on them, see the
+        // `timing` module
+        update_rate: timing::UPDATE_RATE_MS,
+        max_updates: timing::MAX_UPDATES,
         ..Default::default()
     };
-    run::<Game>("Quicksilver Roguelike", Vector::new(800, 600), settings);
+    let size = window_size(ui::view_tiles(map_size));
+    run::<Game>("Quicksilver Roguelike", size, settings);
 }
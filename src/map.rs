@@ -0,0 +1,310 @@
+//! The dungeon map: a rectangular grid of tiles.
+
+use quicksilver::{geom::Vector, graphics::Color};
+
+pub const WALL: char = '#';
+pub const FLOOR: char = '.';
+pub const DOOR: char = '+';
+/// A door that needs a key, see `locks`.
+pub const LOCKED_DOOR: char = '|';
+pub const STAIRS_DOWN: char = '>';
+pub const STAIRS_UP: char = '<';
+/// Slows everybody down.
+pub const SHALLOW_WATER: char = '~';
+/// Only swimmers can get through.
+pub const DEEP_WATER: char = '=';
+/// Burns whoever stands in it.
+pub const LAVA: char = '^';
+/// Waist high: it can be seen over but not walked or thrown through.
+pub const LOW_WALL: char = '_';
+
+/// Bits of `tile_flags` telling what a tile gets in the way of.
+pub const BLOCKS_MOVEMENT: u8 = 1;
+pub const BLOCKS_SIGHT: u8 = 2;
+pub const BLOCKS_PROJECTILES: u8 = 4;
+/// Whoever stands next to it is harder to hit from afar, see
+/// `throwing::dodges`.
+pub const COVER: u8 = 8;
+
+/// What tiles with `glyph` get in the way of (see the flag constants).
+pub fn tile_flags(glyph: char) -> u8 {
+    match glyph {
+        WALL | LOCKED_DOOR => BLOCKS_MOVEMENT | BLOCKS_SIGHT | BLOCKS_PROJECTILES,
+        LOW_WALL => BLOCKS_MOVEMENT | BLOCKS_PROJECTILES | COVER,
+        _ => 0,
+    }
+}
+
+/// How many turns it takes to step onto a tile with `glyph`. `None` if
+/// it's impossible.
+pub fn movement_cost(glyph: char, swims: bool) -> Option<u32> {
+    match glyph {
+        _ if tile_flags(glyph) & BLOCKS_MOVEMENT != 0 => None,
+        DEEP_WATER if !swims => None,
+        SHALLOW_WATER | DEEP_WATER => Some(2),
+        _ => Some(1),
+    }
+}
+
+/// The colour tiles with `glyph` are drawn in.
+pub fn glyph_color(glyph: char) -> Color {
+    match glyph {
+        SHALLOW_WATER => Color::from_rgba(64, 128, 224, 1.0),
+        DEEP_WATER => Color::from_rgba(16, 48, 160, 1.0),
+        LAVA => Color::from_rgba(224, 64, 0, 1.0),
+        LOCKED_DOOR => Color::from_rgba(160, 96, 32, 1.0),
+        LOW_WALL => Color::from_rgba(112, 112, 112, 1.0),
+        _ => Color::BLACK,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub pos: Vector,
+    pub glyph: char,
+    pub color: Color,
+    /// Drawn instead of the glyph, for the looks only. See `decoration`.
+    pub decoration: Option<char>,
+}
+
+impl Tile {
+    /// The glyph the tile is drawn with.
+    pub fn shown_glyph(&self) -> char {
+        self.decoration.unwrap_or(self.glyph)
+    }
+
+    /// Whether the tile stops movement and line of sight. Locked doors
+    /// do too.
+    pub fn is_wall(&self) -> bool {
+        self.glyph == WALL || self.glyph == LOCKED_DOOR
+    }
+
+    /// Whether the tile has all of `flags`, see `tile_flags`.
+    pub fn has(&self, flags: u8) -> bool {
+        tile_flags(self.glyph) & flags == flags
+    }
+}
+
+/// Bits of `Map::wall_mask` telling which neighbours are walls.
+pub const WALL_NORTH: u8 = 1;
+pub const WALL_EAST: u8 = 2;
+pub const WALL_SOUTH: u8 = 4;
+pub const WALL_WEST: u8 = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Map {
+    pub size: Vector,
+    /// The tiles stored column by column (i.e. `x * height + y`).
+    pub tiles: Vec<Tile>,
+}
+
+impl Map {
+    /// Create a map of the given size with every tile set to `glyph`.
+    pub fn filled(size: Vector, glyph: char) -> Self {
+        let width = size.x as usize;
+        let height = size.y as usize;
+        let mut tiles = Vec::with_capacity(width * height);
+        for x in 0..width {
+            for y in 0..height {
+                tiles.push(Tile {
+                    pos: Vector::new(x as f32, y as f32),
+                    glyph,
+                    color: Color::BLACK,
+                    decoration: None,
+                });
+            }
+        }
+        Map { size, tiles }
+    }
+
+    /// Return `true` if `pos` lies within the map.
+    pub fn contains(&self, pos: Vector) -> bool {
+        pos.x >= 0.0 && pos.y >= 0.0 && pos.x < self.size.x && pos.y < self.size.y
+    }
+
+    fn index(&self, pos: Vector) -> Option<usize> {
+        if self.contains(pos) {
+            Some(pos.x as usize * self.size.y as usize + pos.y as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn tile(&self, pos: Vector) -> Option<&Tile> {
+        self.index(pos).map(|index| &self.tiles[index])
+    }
+
+    pub fn tile_mut(&mut self, pos: Vector) -> Option<&mut Tile> {
+        self.index(pos).map(move |index| &mut self.tiles[index])
+    }
+
+    /// Change the glyph (and with it the colour) of the tile at `pos`,
+    /// clearing any decoration. Positions outside of the map are ignored.
+    pub fn set_glyph(&mut self, pos: Vector, glyph: char) {
+        if let Some(tile) = self.tile_mut(pos) {
+            tile.glyph = glyph;
+            tile.color = glyph_color(glyph);
+            tile.decoration = None;
+        }
+    }
+
+    /// See the `movement_cost` function. Everything outside of the map
+    /// is impossible to get to.
+    pub fn movement_cost(&self, pos: Vector, swims: bool) -> Option<u32> {
+        self.tile(pos)
+            .and_then(|tile| movement_cost(tile.glyph, swims))
+    }
+
+    /// Return all the tiles that can be walked to from `start` (moving
+    /// horizontally and vertically), including `start` itself.
+    pub fn flood_fill(&self, start: Vector) -> Vec<Vector> {
+        let mut visited = vec![false; self.tiles.len()];
+        let mut reachable = vec![];
+        let mut stack = vec![start];
+        while let Some(pos) = stack.pop() {
+            let index = match self.index(pos) {
+                Some(index) if !visited[index] && !self.tiles[index].has(BLOCKS_MOVEMENT) => index,
+                _ => continue,
+            };
+            visited[index] = true;
+            reachable.push(pos);
+            for &(dx, dy) in &[(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                stack.push(pos + Vector::new(dx, dy));
+            }
+        }
+        reachable
+    }
+
+    /// Return `true` if nothing can move to `pos`. Everything outside of
+    /// the map is blocked.
+    pub fn is_blocked(&self, pos: Vector) -> bool {
+        self.blocks(pos, BLOCKS_MOVEMENT)
+    }
+
+    /// Return `true` if the tile at `pos` has `flags`. Everything
+    /// outside of the map blocks everything.
+    pub fn blocks(&self, pos: Vector, flags: u8) -> bool {
+        self.tile(pos).is_none_or(|tile| tile.has(flags))
+    }
+
+    /// Return `true` if there's cover right next to `pos`, diagonals
+    /// included.
+    pub fn in_cover(&self, pos: Vector) -> bool {
+        (-1..=1).any(|x| {
+            (-1..=1).any(|y| {
+                self.tile(pos + Vector::new(x, y))
+                    .is_some_and(|tile| tile.has(COVER))
+            })
+        })
+    }
+
+    /// Return a bitmask of the walls directly next to `pos` (see the
+    /// `WALL_*` constants). This lets the walls be drawn connected to
+    /// each other rather than as a bunch of separate blocks.
+    pub fn wall_mask(&self, pos: Vector) -> u8 {
+        let neighbours = [
+            (Vector::new(0, -1), WALL_NORTH),
+            (Vector::new(1, 0), WALL_EAST),
+            (Vector::new(0, 1), WALL_SOUTH),
+            (Vector::new(-1, 0), WALL_WEST),
+        ];
+        neighbours
+            .iter()
+            .filter(|(offset, _)| self.tile(pos + *offset).is_some_and(Tile::is_wall))
+            .fold(0, |mask, (_, bit)| mask | bit)
+    }
+}
+
+/// Generate an empty room surrounded by walls.
+pub fn generate_map(size: Vector) -> Map {
+    let mut map = Map::filled(size, FLOOR);
+    let (width, height) = (size.x as i32, size.y as i32);
+    for x in 0..width {
+        map.set_glyph(Vector::new(x, 0), WALL);
+        map.set_glyph(Vector::new(x, height - 1), WALL);
+    }
+    for y in 0..height {
+        map.set_glyph(Vector::new(0, y), WALL);
+        map.set_glyph(Vector::new(width - 1, y), WALL);
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_map_is_surrounded_by_walls() {
+        let map = generate_map(Vector::new(5, 4));
+        assert_eq!(map.tiles.len(), 20);
+        assert!(map.is_blocked(Vector::new(0, 0)));
+        assert!(map.is_blocked(Vector::new(4, 2)));
+        assert!(map.is_blocked(Vector::new(2, 3)));
+        assert!(!map.is_blocked(Vector::new(1, 1)));
+        assert!(!map.is_blocked(Vector::new(3, 2)));
+    }
+
+    #[test]
+    fn tiles_are_looked_up_by_position() {
+        let map = generate_map(Vector::new(5, 4));
+        for tile in &map.tiles {
+            assert_eq!(map.tile(tile.pos).map(|t| t.pos), Some(tile.pos));
+        }
+        assert_eq!(map.tile(Vector::new(5, 0)), None);
+        assert_eq!(map.tile(Vector::new(-1, 2)), None);
+        assert!(map.is_blocked(Vector::new(-1, 2)));
+    }
+
+    #[test]
+    fn walls_know_their_neighbours() {
+        let map = generate_map(Vector::new(5, 4));
+        assert_eq!(map.wall_mask(Vector::new(0, 0)), WALL_EAST | WALL_SOUTH);
+        assert_eq!(map.wall_mask(Vector::new(2, 0)), WALL_EAST | WALL_WEST);
+        assert_eq!(map.wall_mask(Vector::new(4, 3)), WALL_NORTH | WALL_WEST);
+        assert_eq!(map.wall_mask(Vector::new(4, 1)), WALL_NORTH | WALL_SOUTH);
+        assert_eq!(map.wall_mask(Vector::new(2, 2)), WALL_SOUTH);
+    }
+
+    #[test]
+    fn flood_fill_stops_at_walls() {
+        let mut map = generate_map(Vector::new(7, 4));
+        assert_eq!(map.flood_fill(Vector::new(1, 1)).len(), 10);
+        assert_eq!(map.flood_fill(Vector::new(0, 0)), vec![]);
+
+        map.set_glyph(Vector::new(3, 1), WALL);
+        map.set_glyph(Vector::new(3, 2), WALL);
+        assert_eq!(map.flood_fill(Vector::new(1, 1)).len(), 4);
+    }
+
+    #[test]
+    fn low_walls_block_everything_but_sight() {
+        let mut map = generate_map(Vector::new(7, 4));
+        let low_wall = Vector::new(3, 1);
+        map.set_glyph(low_wall, LOW_WALL);
+        assert!(map.is_blocked(low_wall));
+        assert!(map.blocks(low_wall, BLOCKS_PROJECTILES));
+        assert!(!map.blocks(low_wall, BLOCKS_SIGHT));
+        assert!(map.blocks(Vector::new(0, 0), BLOCKS_SIGHT));
+        assert!(map.in_cover(Vector::new(4, 2)));
+        assert!(!map.in_cover(Vector::new(5, 2)));
+    }
+
+    #[test]
+    fn water_slows_and_deep_water_needs_swimming() {
+        let mut map = generate_map(Vector::new(5, 4));
+        map.set_glyph(Vector::new(1, 1), SHALLOW_WATER);
+        map.set_glyph(Vector::new(2, 1), DEEP_WATER);
+        map.set_glyph(Vector::new(3, 1), LAVA);
+        assert_eq!(map.movement_cost(Vector::new(1, 2), false), Some(1));
+        assert_eq!(map.movement_cost(Vector::new(1, 1), false), Some(2));
+        assert_eq!(map.movement_cost(Vector::new(2, 1), false), None);
+        assert_eq!(map.movement_cost(Vector::new(2, 1), true), Some(2));
+        assert_eq!(map.movement_cost(Vector::new(3, 1), false), Some(1));
+        assert_eq!(map.movement_cost(Vector::new(0, 0), true), None);
+        assert_eq!(
+            map.tile(Vector::new(3, 1)).unwrap().color,
+            glyph_color(LAVA)
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Random map generators.
+//!
+//! Every algorithm implements `MapBuilder` so a new level can just pick
+//! one (see `MapType`) without caring how it works. To add a new
+//! generator, implement the trait and add a `MapType` variant for it.
+
+mod bsp;
+mod caves;
+mod rooms;
+
+pub use self::{bsp::Bsp, caves::Caves, rooms::RoomsAndCorridors};
+
+use quicksilver::geom::Vector;
+use rand::{rngs::StdRng, Rng};
+
+use crate::map::{Map, BLOCKS_MOVEMENT, DEEP_WATER, FLOOR, LAVA, LOW_WALL, SHALLOW_WATER};
+
+/// The chance of a level having a pond.
+const POND_CHANCE: f64 = 0.5;
+
+/// The chance of a level having a pool of lava.
+const LAVA_CHANCE: f64 = 0.25;
+
+/// The chance of a level having low walls to take cover behind.
+const LOW_WALL_CHANCE: f64 = 0.5;
+
+/// The most low walls a level can have. Each is three tiles long.
+const MAX_LOW_WALLS: usize = 3;
+
+pub trait MapBuilder {
+    /// Generate a new map of the given size. It must be surrounded by
+    /// walls and all its floor tiles must be reachable from each other.
+    fn build(&mut self, size: Vector, rng: &mut StdRng) -> Map;
+}
+
+/// The kinds of levels the game can generate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MapType {
+    RoomsAndCorridors,
+    Caves,
+    Bsp,
+}
+
+impl MapType {
+    pub const ALL: [MapType; 3] = [MapType::RoomsAndCorridors, MapType::Caves, MapType::Bsp];
+
+    pub fn random(rng: &mut StdRng) -> Self {
+        Self::ALL[rng.gen_range(0, Self::ALL.len())]
+    }
+
+    pub fn builder(self) -> Box<dyn MapBuilder> {
+        match self {
+            MapType::RoomsAndCorridors => Box::new(RoomsAndCorridors::default()),
+            MapType::Caves => Box::new(Caves::default()),
+            MapType::Bsp => Box::new(Bsp::default()),
+        }
+    }
+}
+
+/// Generate a map for a new level using a randomly picked generator.
+pub fn generate_level(size: Vector, rng: &mut StdRng) -> Map {
+    let mut map = MapType::random(rng).builder().build(size, rng);
+    if rng.gen_bool(POND_CHANCE) {
+        add_pool(&mut map, SHALLOW_WATER, DEEP_WATER, rng);
+    }
+    if rng.gen_bool(LAVA_CHANCE) {
+        add_pool(&mut map, LAVA, LAVA, rng);
+    }
+    if rng.gen_bool(LOW_WALL_CHANCE) {
+        add_low_walls(&mut map, rng);
+    }
+    map
+}
+
+/// The tiles with nothing but floor within two tiles of them.
+fn open_spots(map: &Map) -> Vec<Vector> {
+    let is_open = |pos: Vector| {
+        (-2..=2).all(|x| {
+            (-2..=2).all(|y| {
+                let tile = map.tile(pos + Vector::new(x, y));
+                tile.is_some_and(|tile| tile.glyph == FLOOR)
+            })
+        })
+    };
+    map.tiles
+        .iter()
+        .map(|tile| tile.pos)
+        .filter(|&pos| is_open(pos))
+        .collect()
+}
+
+/// Put a 3x3 pool of `glyph` (with `center` in the middle) somewhere in
+/// the open. There's always a ring of floor around it so even an
+/// impassable pool doesn't cut any part of the map off.
+pub fn add_pool(map: &mut Map, glyph: char, center: char, rng: &mut StdRng) {
+    let spots = open_spots(map);
+    if spots.is_empty() {
+        return;
+    }
+    let spot = spots[rng.gen_range(0, spots.len())];
+    for x in -1..=1 {
+        for y in -1..=1 {
+            map.set_glyph(spot + Vector::new(x, y), glyph);
+        }
+    }
+    map.set_glyph(spot, center);
+}
+
+/// Put up to `MAX_LOW_WALLS` short stretches of low wall out in the
+/// open. A wall that would cut off any of the floor is taken down again.
+pub fn add_low_walls(map: &mut Map, rng: &mut StdRng) {
+    for _ in 0..rng.gen_range(1, MAX_LOW_WALLS + 1) {
+        let spots = open_spots(map);
+        if spots.is_empty() {
+            return;
+        }
+        let spot = spots[rng.gen_range(0, spots.len())];
+        let along = if rng.gen() {
+            Vector::new(1, 0)
+        } else {
+            Vector::new(0, 1)
+        };
+        let wall = [spot - along, spot, spot + along];
+        for &pos in &wall {
+            map.set_glyph(pos, LOW_WALL);
+        }
+        let open = map.tiles.iter().filter(|tile| !tile.has(BLOCKS_MOVEMENT));
+        let next_to = spot + Vector::new(along.y, along.x) * 2;
+        if map.flood_fill(next_to).len() != open.count() {
+            for &pos in &wall {
+                map.set_glyph(pos, FLOOR);
+            }
+        }
+    }
+}
+
+/// A rectangular room. The coordinates are its floor, not the walls
+/// around it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Room {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Room {
+    pub fn center(&self) -> Vector {
+        Vector::new(self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    /// Return `true` if the rooms overlap or touch.
+    pub fn intersects(&self, other: &Room) -> bool {
+        self.x <= other.x + other.width
+            && other.x <= self.x + self.width
+            && self.y <= other.y + other.height
+            && other.y <= self.y + self.height
+    }
+
+    pub fn carve(&self, map: &mut Map) {
+        for x in self.x..self.x + self.width {
+            for y in self.y..self.y + self.height {
+                map.set_glyph(Vector::new(x, y), FLOOR);
+            }
+        }
+    }
+}
+
+/// Dig an L-shaped corridor between `from` and `to`.
+pub fn carve_corridor(map: &mut Map, from: Vector, to: Vector, horizontal_first: bool) {
+    let corner = if horizontal_first {
+        Vector::new(to.x, from.y)
+    } else {
+        Vector::new(from.x, to.y)
+    };
+    carve_straight(map, from, corner);
+    carve_straight(map, corner, to);
+}
+
+fn carve_straight(map: &mut Map, from: Vector, to: Vector) {
+    let (x0, x1) = (from.x.min(to.x) as i32, from.x.max(to.x) as i32);
+    let (y0, y1) = (from.y.min(to.y) as i32, from.y.max(to.y) as i32);
+    for x in x0..=x1 {
+        for y in y0..=y1 {
+            map.set_glyph(Vector::new(x, y), FLOOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::DOOR;
+    use rand::SeedableRng;
+
+    #[test]
+    fn generated_maps_are_enclosed_and_connected() {
+        let size = Vector::new(20, 15);
+        for &map_type in &MapType::ALL {
+            for seed in 0..20 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let map = map_type.builder().build(size, &mut rng);
+                assert_eq!(map.size, size);
+
+                for tile in &map.tiles {
+                    let (x, y) = (tile.pos.x, tile.pos.y);
+                    if x == 0.0 || y == 0.0 || x == size.x - 1.0 || y == size.y - 1.0 {
+                        assert!(tile.is_wall(), "{:?} {} has a hole", map_type, seed);
+                    }
+                }
+
+                let floor: Vec<_> = map.tiles.iter().filter(|t| !t.is_wall()).collect();
+                assert!(!floor.is_empty(), "{:?} {} has no floor", map_type, seed);
+                let reachable = map.flood_fill(floor[0].pos);
+                assert_eq!(reachable.len(), floor.len(), "{:?} {}", map_type, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn bsp_doors_sit_in_walls() {
+        let mut doors = 0;
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let map = Bsp::default().build(Vector::new(20, 15), &mut rng);
+            for tile in map.tiles.iter().filter(|tile| tile.glyph == DOOR) {
+                doors += 1;
+                let pos = tile.pos;
+                let horizontal = map.is_blocked(pos + Vector::new(1, 0))
+                    && map.is_blocked(pos - Vector::new(1, 0));
+                let vertical = map.is_blocked(pos + Vector::new(0, 1))
+                    && map.is_blocked(pos - Vector::new(0, 1));
+                assert!(horizontal || vertical, "seed {}: door at {}", seed, pos);
+            }
+        }
+        assert!(doors > 0);
+    }
+
+    #[test]
+    fn low_walls_cut_nothing_off() {
+        let mut low_walls = 0;
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut map = Caves::default().build(Vector::new(20, 15), &mut rng);
+            add_low_walls(&mut map, &mut rng);
+            low_walls += map.tiles.iter().filter(|t| t.glyph == LOW_WALL).count();
+            let open = map.tiles.iter().filter(|t| !t.has(BLOCKS_MOVEMENT));
+            let floor: Vec<_> = open.collect();
+            let reachable = map.flood_fill(floor[0].pos);
+            assert_eq!(reachable.len(), floor.len(), "seed {}", seed);
+        }
+        assert!(low_walls > 0);
+    }
+
+    #[test]
+    fn pools_sit_in_the_open() {
+        let mut pools = 0;
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut map = Caves::default().build(Vector::new(20, 15), &mut rng);
+            add_pool(&mut map, SHALLOW_WATER, DEEP_WATER, &mut rng);
+            for tile in map.tiles.iter().filter(|tile| tile.glyph == DEEP_WATER) {
+                pools += 1;
+                for x in -2..=2 {
+                    for y in -2..=2 {
+                        let neighbour = map.tile(tile.pos + Vector::new(x, y)).unwrap();
+                        assert!(!neighbour.is_wall(), "seed {}: {}", seed, tile.pos);
+                    }
+                }
+            }
+        }
+        assert!(pools > 0);
+    }
+}
@@ -0,0 +1,136 @@
+use quicksilver::geom::Vector;
+use rand::{rngs::StdRng, Rng};
+
+use super::{carve_corridor, MapBuilder, Room};
+use crate::map::{Map, DOOR, WALL};
+
+/// Binary space partitioning: keep splitting the map in two until the
+/// pieces are small enough, put a room in each one and connect the
+/// halves back together. The result is more regular and looks more like
+/// a building than `RoomsAndCorridors`.
+pub struct Bsp {
+    /// Don't split areas that would end up smaller than this.
+    pub min_area_size: i32,
+    pub max_depth: u32,
+}
+
+impl Default for Bsp {
+    fn default() -> Self {
+        Bsp {
+            min_area_size: 5,
+            max_depth: 4,
+        }
+    }
+}
+
+impl MapBuilder for Bsp {
+    fn build(&mut self, size: Vector, rng: &mut StdRng) -> Map {
+        let mut map = Map::filled(size, WALL);
+        let mut rooms = vec![];
+        let everything = Room {
+            x: 1,
+            y: 1,
+            width: size.x as i32 - 2,
+            height: size.y as i32 - 2,
+        };
+        self.build_area(everything, self.max_depth, &mut map, rng, &mut rooms);
+        for room in &rooms {
+            place_doors(&mut map, room);
+        }
+        map
+    }
+}
+
+impl Bsp {
+    /// Fill `area` with rooms and return one of them so the caller can
+    /// connect it to the rest of the map.
+    fn build_area(
+        &self,
+        area: Room,
+        depth: u32,
+        map: &mut Map,
+        rng: &mut StdRng,
+        rooms: &mut Vec<Room>,
+    ) -> Room {
+        let min = self.min_area_size;
+        let can_split_vertically = area.width >= 2 * min;
+        let can_split_horizontally = area.height >= 2 * min;
+
+        if depth == 0 || !(can_split_vertically || can_split_horizontally) {
+            // Leave at least one tile of wall towards the neighbouring areas
+            let width = rng.gen_range(area.width.min(3), area.width);
+            let height = rng.gen_range(area.height.min(3), area.height);
+            let room = Room {
+                x: area.x + rng.gen_range(0, area.width - width),
+                y: area.y + rng.gen_range(0, area.height - height),
+                width,
+                height,
+            };
+            room.carve(map);
+            rooms.push(room);
+            return room;
+        }
+
+        let split_vertically = if can_split_vertically && can_split_horizontally {
+            area.width > area.height || (area.width == area.height && rng.gen())
+        } else {
+            can_split_vertically
+        };
+        let (first, second) = if split_vertically {
+            let cut = rng.gen_range(min, area.width - min + 1);
+            (
+                Room { width: cut, ..area },
+                Room {
+                    x: area.x + cut,
+                    width: area.width - cut,
+                    ..area
+                },
+            )
+        } else {
+            let cut = rng.gen_range(min, area.height - min + 1);
+            (
+                Room {
+                    height: cut,
+                    ..area
+                },
+                Room {
+                    y: area.y + cut,
+                    height: area.height - cut,
+                    ..area
+                },
+            )
+        };
+
+        let first = self.build_area(first, depth - 1, map, rng, rooms);
+        let second = self.build_area(second, depth - 1, map, rng, rooms);
+        carve_corridor(map, first.center(), second.center(), split_vertically);
+        if rng.gen() {
+            first
+        } else {
+            second
+        }
+    }
+}
+
+/// Put a door wherever a corridor goes through the wall around `room`.
+fn place_doors(map: &mut Map, room: &Room) {
+    let mut doorways = vec![];
+    for x in room.x..room.x + room.width {
+        doorways.push((Vector::new(x, room.y - 1), Vector::new(1, 0)));
+        doorways.push((Vector::new(x, room.y + room.height), Vector::new(1, 0)));
+    }
+    for y in room.y..room.y + room.height {
+        doorways.push((Vector::new(room.x - 1, y), Vector::new(0, 1)));
+        doorways.push((Vector::new(room.x + room.width, y), Vector::new(0, 1)));
+    }
+
+    for (pos, along_wall) in doorways {
+        let is_floor = map.tile(pos).is_some_and(|tile| !tile.is_wall());
+        // Only a gap with wall on both sides is a doorway. Otherwise the
+        // corridor runs along the room and there's nothing to close off.
+        let in_wall = map.is_blocked(pos + along_wall) && map.is_blocked(pos - along_wall);
+        if is_floor && in_wall {
+            map.set_glyph(pos, DOOR);
+        }
+    }
+}
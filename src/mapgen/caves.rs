@@ -0,0 +1,96 @@
+use quicksilver::geom::Vector;
+use rand::{rngs::StdRng, Rng};
+
+use super::MapBuilder;
+use crate::map::{Map, FLOOR, WALL};
+
+/// Natural-looking caves made by a cellular automaton.
+///
+/// The map starts as random noise and then every tile becomes a wall if
+/// most of its neighbours are walls. After a few rounds the noise
+/// smooths out into caverns.
+pub struct Caves {
+    /// The chance of a tile starting out as a wall.
+    pub wall_chance: f64,
+    pub iterations: usize,
+}
+
+impl Default for Caves {
+    fn default() -> Self {
+        Caves {
+            wall_chance: 0.45,
+            iterations: 4,
+        }
+    }
+}
+
+impl MapBuilder for Caves {
+    fn build(&mut self, size: Vector, rng: &mut StdRng) -> Map {
+        let (width, height) = (size.x as i32, size.y as i32);
+        let is_border = |x: i32, y: i32| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+
+        loop {
+            let mut walls = vec![vec![false; height as usize]; width as usize];
+            for x in 0..width {
+                for y in 0..height {
+                    walls[x as usize][y as usize] =
+                        is_border(x, y) || rng.gen_bool(self.wall_chance);
+                }
+            }
+
+            for _ in 0..self.iterations {
+                let mut next = walls.clone();
+                for x in 1..width - 1 {
+                    for y in 1..height - 1 {
+                        let mut neighbours = 0;
+                        for dx in -1..=1 {
+                            for dy in -1..=1 {
+                                if (dx, dy) != (0, 0) && walls[(x + dx) as usize][(y + dy) as usize]
+                                {
+                                    neighbours += 1;
+                                }
+                            }
+                        }
+                        next[x as usize][y as usize] =
+                            neighbours >= 5 || (walls[x as usize][y as usize] && neighbours >= 4);
+                    }
+                }
+                walls = next;
+            }
+
+            let mut map = Map::filled(size, FLOOR);
+            for tile in map.tiles.iter_mut() {
+                if walls[tile.pos.x as usize][tile.pos.y as usize] {
+                    tile.glyph = WALL;
+                }
+            }
+
+            // The automaton can leave isolated pockets behind. Keep only
+            // the biggest cave so every floor tile is reachable.
+            let mut largest: Vec<Vector> = vec![];
+            let mut seen = vec![false; map.tiles.len()];
+            for (index, tile) in map.tiles.iter().enumerate() {
+                if tile.is_wall() || seen[index] {
+                    continue;
+                }
+                let region = map.flood_fill(tile.pos);
+                for pos in &region {
+                    seen[pos.x as usize * height as usize + pos.y as usize] = true;
+                }
+                if region.len() > largest.len() {
+                    largest = region;
+                }
+            }
+
+            // Too small a cave isn't worth playing in, try again.
+            if largest.len() < map.tiles.len() / 4 {
+                continue;
+            }
+            let mut cave = Map::filled(size, WALL);
+            for pos in largest {
+                cave.set_glyph(pos, FLOOR);
+            }
+            return cave;
+        }
+    }
+}
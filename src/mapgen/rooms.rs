@@ -0,0 +1,59 @@
+use quicksilver::geom::Vector;
+use rand::{rngs::StdRng, Rng};
+
+use super::{carve_corridor, MapBuilder, Room};
+use crate::map::{Map, WALL};
+
+/// Rectangular rooms connected by L-shaped corridors.
+pub struct RoomsAndCorridors {
+    pub max_rooms: usize,
+    pub min_room_size: i32,
+    pub max_room_size: i32,
+}
+
+impl Default for RoomsAndCorridors {
+    fn default() -> Self {
+        RoomsAndCorridors {
+            max_rooms: 6,
+            min_room_size: 3,
+            max_room_size: 6,
+        }
+    }
+}
+
+impl MapBuilder for RoomsAndCorridors {
+    fn build(&mut self, size: Vector, rng: &mut StdRng) -> Map {
+        let mut map = Map::filled(size, WALL);
+        let mut rooms: Vec<Room> = vec![];
+
+        // Try to place rooms at random, skipping the ones that would
+        // overlap an existing room.
+        for _ in 0..self.max_rooms * 5 {
+            if rooms.len() >= self.max_rooms {
+                break;
+            }
+            let width = rng.gen_range(self.min_room_size, self.max_room_size + 1);
+            let height = rng.gen_range(self.min_room_size, self.max_room_size + 1);
+            if width + 2 > size.x as i32 || height + 2 > size.y as i32 {
+                continue;
+            }
+            let room = Room {
+                x: rng.gen_range(1, size.x as i32 - width),
+                y: rng.gen_range(1, size.y as i32 - height),
+                width,
+                height,
+            };
+            if rooms.iter().any(|other| room.intersects(other)) {
+                continue;
+            }
+
+            room.carve(&mut map);
+            if let Some(previous) = rooms.last() {
+                carve_corridor(&mut map, previous.center(), room.center(), rng.gen());
+            }
+            rooms.push(room);
+        }
+
+        map
+    }
+}
@@ -0,0 +1,174 @@
+//! The entries of the message log.
+//!
+//! A message is a run of text segments, each with its own colour. The
+//! locale strings mark the parts worth highlighting with asterisks:
+//! "You hit the *goblin*." turns the name `HIGHLIGHT`. Asterisks in the
+//! names and numbers `locale::message` fills in don't count. The same
+//! message logged several times in a row is kept once with a count, see
+//! `World::log`.
+
+use quicksilver::graphics::Color;
+
+use std::fmt;
+
+/// The colour of the parts of a message between asterisks.
+pub const HIGHLIGHT: Color = Color {
+    r: 0.75,
+    g: 0.25,
+    b: 0.0,
+    a: 1.0,
+};
+
+/// The colour of the "x3" after a repeated message.
+pub const COUNT_COLOR: Color = Color {
+    r: 0.5,
+    g: 0.5,
+    b: 0.5,
+    a: 1.0,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    /// `None` is the usual text colour.
+    pub color: Option<Color>,
+}
+
+impl Segment {
+    pub fn plain(text: impl Into<String>) -> Self {
+        Segment {
+            text: text.into(),
+            color: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub segments: Vec<Segment>,
+    /// How many times in a row the message came up.
+    pub count: u32,
+}
+
+impl Message {
+    /// Split `text` into segments, the ones between asterisks
+    /// highlighted.
+    pub fn parse(text: &str) -> Self {
+        let segments = text
+            .split('*')
+            .enumerate()
+            .filter(|(_, part)| !part.is_empty())
+            .map(|(index, part)| Segment {
+                text: part.to_string(),
+                color: if index % 2 == 1 {
+                    Some(HIGHLIGHT)
+                } else {
+                    None
+                },
+            })
+            .collect();
+        Message { segments, count: 1 }
+    }
+
+    /// The text without the colours or the count.
+    pub fn text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect()
+    }
+
+    /// Whether `other` says the same thing, in the same colours.
+    pub fn is_repeat_of(&self, other: &Message) -> bool {
+        self.segments == other.segments
+    }
+
+    /// The segments to draw: the message and, if it came up more than
+    /// once, the count.
+    pub fn display_segments(&self) -> Vec<Segment> {
+        let mut segments = self.segments.clone();
+        if self.count > 1 {
+            segments.push(Segment {
+                text: format!(" x{}", self.count),
+                color: Some(COUNT_COLOR),
+            });
+        }
+        segments
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text())?;
+        if self.count > 1 {
+            write!(f, " x{}", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Message::parse(text)
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Message::parse(&text)
+    }
+}
+
+/// Messages compare equal to their text as displayed, count included.
+impl PartialEq<str> for Message {
+    fn eq(&self, other: &str) -> bool {
+        let shown = self.to_string();
+        shown == other
+    }
+}
+
+impl PartialEq<&str> for Message {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<String> for Message {
+    fn eq(&self, other: &String) -> bool {
+        self == other.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asterisks_highlight() {
+        let message = Message::parse("You hit the *goblin*.");
+        assert_eq!(
+            message.segments,
+            vec![
+                Segment::plain("You hit the "),
+                Segment {
+                    text: "goblin".to_string(),
+                    color: Some(HIGHLIGHT),
+                },
+                Segment::plain("."),
+            ]
+        );
+        assert_eq!(message, "You hit the goblin.");
+        assert_eq!(
+            Message::parse("*3* gold").segments[0].color,
+            Some(HIGHLIGHT)
+        );
+    }
+
+    #[test]
+    fn repeats_show_their_count() {
+        let mut message = Message::parse("You wait.");
+        message.count = 3;
+        assert_eq!(message.to_string(), "You wait. x3");
+        assert_eq!(message.display_segments().len(), 2);
+    }
+}
@@ -0,0 +1,81 @@
+//! The morgue file: a plain text account of a finished run to keep
+//! around or share.
+
+use crate::{
+    daily, locale,
+    wizard::GameMode,
+    world::{Outcome, World},
+};
+
+/// How many of the last messages go into the morgue file.
+pub const MORGUE_MESSAGES: usize = 10;
+
+pub fn morgue(world: &World) -> String {
+    let outcome = match world.outcome {
+        Some(Outcome::Died) if world.depth == 0 => locale::text("morgue.died-on-surface"),
+        Some(Outcome::Died) => locale::format("morgue.died", &[("depth", &world.depth)]),
+        Some(Outcome::Won) => locale::text("morgue.won"),
+        None if world.depth == 0 => locale::text("morgue.alive-on-surface"),
+        None => locale::format("morgue.alive", &[("depth", &world.depth)]),
+    };
+    let seed = locale::format("morgue.seed", &[("seed", &world.seed)]);
+    let class = locale::format("morgue.class", &[("class", &world.profile.class.name())]);
+    let mut lines = vec![seed, class, outcome, String::new()];
+    if !world.profile.name.is_empty() {
+        let name = locale::format("morgue.name", &[("name", &world.profile.name)]);
+        lines.insert(1, name);
+    }
+    if world.mode == GameMode::Daily {
+        let date = daily::date(world.seed);
+        lines.insert(0, locale::format("morgue.daily", &[("date", &date)]));
+    }
+    lines.extend(world.stats.summary());
+
+    lines.push(String::new());
+    lines.push(locale::text("morgue.inventory"));
+    if world.player().inventory.is_empty() {
+        lines.push(format!("  {}", locale::text("morgue.nothing")));
+    }
+    for item in &world.player().inventory {
+        lines.push(format!("  {}", item.name));
+    }
+    let gold = locale::format("morgue.gold", &[("amount", &world.player().gold)]);
+    lines.push(format!("  {}", gold));
+
+    lines.push(String::new());
+    lines.push(locale::text("morgue.messages"));
+    let skip = world.log.len().saturating_sub(MORGUE_MESSAGES);
+    for message in &world.log[skip..] {
+        lines.push(format!("  {}", message));
+    }
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Action;
+
+    #[test]
+    fn the_morgue_has_the_outcome_and_statistics() {
+        let mut world = World::new(7);
+        let player_id = world.player_id;
+        world.entities[player_id].hp = 0;
+        world.player_turn(Action::Wait);
+
+        let text = morgue(&world);
+        assert!(text.starts_with("Seed: 7\nClass: Fighter\nDied on the surface.\n"));
+        assert!(text.contains("Turns taken: 1\n"));
+        assert!(text.ends_with("  You die...\n"));
+    }
+
+    #[test]
+    fn daily_runs_are_tagged_with_the_day() {
+        let world = daily::world(20_740, Default::default());
+        let text = morgue(&world);
+        assert!(text.starts_with("Daily challenge: 2026-10-14\nSeed: 20740\n"));
+    }
+}
@@ -0,0 +1,177 @@
+//! Sleeping monsters and the noise that wakes them up.
+//!
+//! Whatever the player does makes some noise: `World::noises` collects
+//! it during the player's action and the monsters listen to all of it
+//! at the start of their turns. A noise gets a point quieter with every
+//! step it travels, and it goes around walls rather than through them.
+
+use quicksilver::geom::Vector;
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{geometry::tile_key, locale, world::World};
+
+/// How loud a step is. Sneaking halves it.
+pub const STEP_NOISE: u32 = 6;
+
+/// How loud a fight is.
+pub const FIGHT_NOISE: u32 = 10;
+
+/// How loud a potion shattering is.
+pub const SHATTER_NOISE: u32 = 8;
+
+/// A sleeping monster wakes up when it hears something louder than
+/// this.
+pub const WAKE_THRESHOLD: u32 = 2;
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// Something the monsters might hear.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Noise {
+    pub pos: Vector,
+    pub volume: u32,
+}
+
+/// Make a noise at `pos`. The monsters hear it on their next turn.
+pub fn make(world: &mut World, pos: Vector, volume: u32) {
+    world.noises.push(Noise { pos, volume });
+}
+
+/// How loud `noise` is on every tile it reaches, spreading out around
+/// walls.
+pub fn spread(world: &World, noise: Noise) -> HashMap<(i32, i32), u32> {
+    let mut volumes = HashMap::new();
+    let mut queue = VecDeque::new();
+    if !world.map.is_blocked(noise.pos) {
+        volumes.insert(tile_key(noise.pos), noise.volume);
+        queue.push_back(noise.pos);
+    }
+    while let Some(pos) = queue.pop_front() {
+        let volume = volumes[&tile_key(pos)];
+        if volume <= 1 {
+            continue;
+        }
+        for &(x, y) in &DIRECTIONS {
+            let next = pos + Vector::new(x, y);
+            if world.map.is_blocked(next) || volumes.contains_key(&tile_key(next)) {
+                continue;
+            }
+            volumes.insert(tile_key(next), volume - 1);
+            queue.push_back(next);
+        }
+    }
+    volumes
+}
+
+/// Every sleeping monster that hears one of the noises made since the
+/// last time wakes up.
+pub fn listen(world: &mut World) {
+    let noises: Vec<Noise> = world.noises.drain(..).collect();
+    for noise in noises {
+        let volumes = spread(world, noise);
+        for id in 0..world.entities.len() {
+            let heard = volumes
+                .get(&tile_key(world.entities[id].pos))
+                .copied()
+                .unwrap_or(0);
+            if heard > WAKE_THRESHOLD && is_asleep(world, id) {
+                wake_up(world, id);
+            }
+        }
+    }
+}
+
+pub fn is_asleep(world: &World, id: usize) -> bool {
+    let entity = &world.entities[id];
+    entity.is_alive() && entity.ai.as_ref().is_some_and(|ai| ai.asleep)
+}
+
+pub fn wake_up(world: &mut World, id: usize) {
+    if !is_asleep(world, id) {
+        return;
+    }
+    if let Some(ai) = &mut world.entities[id].ai {
+        ai.asleep = false;
+    }
+    if world.sees_entity(id) {
+        let name = world.entities[id].name.clone();
+        world.log(locale::message("log.wakes-up", &[("name", &name)]));
+    }
+}
+
+/// How loud it is when the player takes a step.
+pub fn step_volume(sneaking: bool) -> u32 {
+    if sneaking {
+        STEP_NOISE / 2
+    } else {
+        STEP_NOISE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{FLOOR, WALL},
+        world::{
+            tests::{goblin, player_with},
+            Action,
+        },
+    };
+
+    /// The player at (5, 5) with a sleeping goblin `distance` tiles east.
+    fn world(distance: i32) -> World {
+        let mut goblin = goblin(5 + distance, 3);
+        goblin.ai.as_mut().unwrap().asleep = true;
+        player_with(vec![goblin], 5)
+    }
+
+    #[test]
+    fn noise_fades_and_goes_around_walls() {
+        let mut world = world(5);
+        let noise = Noise {
+            pos: Vector::new(5, 5),
+            volume: 4,
+        };
+        let volumes = spread(&world, noise);
+        assert_eq!(volumes[&(5, 5)], 4);
+        assert_eq!(volumes[&(7, 5)], 2);
+        assert!(!volumes.contains_key(&(9, 5)));
+
+        for y in 0..15 {
+            world.map.set_glyph(Vector::new(6, y), WALL);
+        }
+        world.map.set_glyph(Vector::new(6, 4), FLOOR);
+        let volumes = spread(&world, noise);
+        assert_eq!(volumes.get(&(6, 5)), None);
+        assert_eq!(volumes[&(7, 4)], 1);
+        assert!(!volumes.contains_key(&(7, 5)));
+    }
+
+    #[test]
+    fn sleepers_sleep_through_sneaking() {
+        let mut world = world(4);
+        world.player_turn(Action::Sneak);
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert!(is_asleep(&world, 0));
+        world.player_turn(Action::Sneak);
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert!(!is_asleep(&world, 0));
+        assert!(world
+            .log
+            .iter()
+            .any(|message| message == "The goblin wakes up."));
+    }
+
+    #[test]
+    fn sleepers_ignore_the_player_until_they_wake() {
+        let mut world = world(1);
+        for _ in 0..3 {
+            world.player_turn(Action::Wait);
+        }
+        assert_eq!(world.player().hp, 5);
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert!(!is_asleep(&world, 0));
+    }
+}
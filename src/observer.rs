@@ -0,0 +1,347 @@
+//! Spectating a run live from another page, with the `observer` feature.
+//!
+//! `--observe <address>` (e.g. `--observe 127.0.0.1:9001`) listens for
+//! WebSocket connections there and after every turn sends each spectator
+//! a `snapshot` of the game as JSON: what the player knows of the map,
+//! whoever they can see, their status and the latest messages. The
+//! spectators see only what the player does. `static/observer.html` is a
+//! page that draws it.
+//!
+//! This is only the bare minimum of the protocol: the handshake and
+//! unfragmented text frames from the game to the page. Whatever the page
+//! sends is ignored.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use quicksilver::graphics::Color;
+use serde_json::{json, Value};
+
+use crate::world::World;
+
+/// How many of the latest messages go into a snapshot.
+pub const SNAPSHOT_MESSAGES: usize = 10;
+
+/// Appended to the page's key to prove the server speaks WebSocket, see
+/// RFC 6455.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// A spectator that's slower than this to shake hands (counting from
+/// when it connected) is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The longest upgrade request the page may send.
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// The game state the spectators get after each turn.
+pub fn snapshot(world: &World) -> Value {
+    let width = world.map.size.x as usize;
+    let height = world.map.size.y as usize;
+    let mut rows = vec![vec![' '; width]; height];
+    for tile in &world.map.tiles {
+        if world.fov.is_explored(tile.pos) {
+            rows[tile.pos.y as usize][tile.pos.x as usize] = tile.glyph;
+        }
+    }
+    let map: Vec<String> = rows
+        .into_iter()
+        .map(|row| row.into_iter().collect())
+        .collect();
+
+    // Drawn in this order: items first, then whoever stands on them
+    let mut seen: Vec<usize> = (0..world.entities.len())
+        .filter(|&id| world.sees_entity(id))
+        .collect();
+    seen.sort_by_key(|&id| (id == world.player_id, world.entities[id].blocks_tile));
+    let entities: Vec<Value> = seen
+        .into_iter()
+        .map(|id| {
+            let entity = &world.entities[id];
+            json!({
+                "x": entity.pos.x as i32,
+                "y": entity.pos.y as i32,
+                "glyph": entity.glyph.to_string(),
+                "color": hex(entity.color),
+                "name": world.item_name(entity),
+                "hp": entity.hp,
+                "max_hp": entity.max_hp,
+                "player": id == world.player_id,
+            })
+        })
+        .collect();
+
+    let skip = world.log.len().saturating_sub(SNAPSHOT_MESSAGES);
+    let log: Vec<String> = world.log[skip..]
+        .iter()
+        .map(|message| message.to_string())
+        .collect();
+    let player = world.player();
+    json!({
+        "turn": world.stats.turns,
+        "depth": world.depth,
+        "map": map,
+        "entities": entities,
+        "log": log,
+        "hp": player.hp,
+        "max_hp": player.max_hp,
+        "gold": player.gold,
+        "outcome": world.outcome.map(|outcome| format!("{:?}", outcome)),
+    })
+}
+
+fn hex(color: Color) -> String {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
+
+/// A spectator that's connected but hasn't sent all of its upgrade
+/// request yet.
+struct Handshake {
+    stream: TcpStream,
+    /// What's arrived of the request so far.
+    request: Vec<u8>,
+    connected: Instant,
+}
+
+/// The server the spectators connect to.
+pub struct Observer {
+    listener: TcpListener,
+    /// Read a bit more of every turn, so nobody can hold up the game.
+    handshakes: Vec<Handshake>,
+    spectators: Vec<TcpStream>,
+    /// The turn and the number of messages (and repeats of the last one)
+    /// of the last snapshot sent.
+    sent: Option<(u32, usize, u32)>,
+}
+
+impl Observer {
+    /// Start listening at `address`. Nobody's let in until the next
+    /// `update` or `broadcast`.
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Observer {
+            listener,
+            handshakes: vec![],
+            spectators: vec![],
+            sent: None,
+        })
+    }
+
+    /// Send everyone a `snapshot` if there's been a new turn or message
+    /// since the last one, or someone new to show the game to.
+    pub fn update(&mut self, world: &World) {
+        let last_count = world.log.last().map_or(0, |message| message.count);
+        let state = Some((world.stats.turns, world.log.len(), last_count));
+        if self.accept() || self.sent != state {
+            self.sent = state;
+            self.broadcast(&snapshot(world).to_string());
+        }
+    }
+
+    /// Send `text` to every spectator, letting in the ones who've
+    /// connected since the last time and dropping the ones who've left.
+    pub fn broadcast(&mut self, text: &str) {
+        self.accept();
+        let frame = text_frame(text);
+        self.spectators
+            .retain_mut(|stream| stream.write_all(&frame).is_ok());
+    }
+
+    /// Let in whoever has finished shaking hands since the last time.
+    /// Returns whether anyone has.
+    fn accept(&mut self) -> bool {
+        while let Ok((stream, _)) = self.listener.accept() {
+            // A spectator that stops reading (or writing) mustn't hold
+            // up the game
+            if stream.set_nonblocking(true).is_ok() {
+                self.handshakes.push(Handshake {
+                    stream,
+                    request: vec![],
+                    connected: Instant::now(),
+                });
+            }
+        }
+        let before = self.spectators.len();
+        let mut waiting = vec![];
+        for mut handshake in self.handshakes.drain(..) {
+            match handshake.advance() {
+                Ok(true) => self.spectators.push(handshake.stream),
+                Ok(false) if handshake.connected.elapsed() < HANDSHAKE_TIMEOUT => {
+                    waiting.push(handshake)
+                }
+                _ => {}
+            }
+        }
+        self.handshakes = waiting;
+        self.spectators.len() > before
+    }
+}
+
+impl Handshake {
+    /// Read whatever's arrived of the request without waiting for the
+    /// rest, and answer it once it's all there. Returns whether it is.
+    fn advance(&mut self) -> io::Result<bool> {
+        let mut buffer = [0; 1024];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(read) => self.request.extend(&buffer[..read]),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+            if self.request.len() > MAX_REQUEST_BYTES {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "request too long",
+                ));
+            }
+        }
+        let request = String::from_utf8_lossy(&self.request);
+        let (head, _) = match request.split_once("\r\n\r\n") {
+            Some(parts) => parts,
+            None => return Ok(false),
+        };
+        let key = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-key"))
+            .map(|(_, value)| value.trim().to_string())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a WebSocket"))?;
+        respond(&mut self.stream, &key)?;
+        Ok(true)
+    }
+}
+
+/// Accept the page's upgrade request.
+fn respond(stream: &mut TcpStream, key: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )
+}
+
+/// The `Sec-WebSocket-Accept` answer to the page's key.
+fn accept_key(key: &str) -> String {
+    let digest = sha1::Sha1::from(format!("{}{}", key, HANDSHAKE_GUID)).digest();
+    base64::encode(digest.bytes())
+}
+
+/// `text` as a single, unmasked WebSocket text frame.
+fn text_frame(text: &str) -> Vec<u8> {
+    let payload = text.as_bytes();
+    let mut frame = vec![0x81];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::tests::player_with;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn snapshots_show_what_the_player_sees() {
+        let mut world = player_with(vec![], 5);
+        let player = &mut world.entities[world.player_id];
+        player.glyph = '@';
+        player.color = Color::WHITE;
+        world.log("You hear something.");
+        let snapshot = snapshot(&world);
+        // Too far to have been seen yet is left blank
+        assert_eq!(snapshot["map"][5], "#.............      ");
+        assert_eq!(snapshot["entities"][0]["glyph"], "@");
+        assert_eq!(snapshot["entities"][0]["color"], "#ffffff");
+        assert_eq!(snapshot["log"], json!(["You hear something."]));
+        assert_eq!(snapshot["outcome"], Value::Null);
+    }
+
+    #[test]
+    fn spectators_get_every_turn() {
+        let mut observer = Observer::bind("127.0.0.1:0").unwrap();
+        let address = observer.listener.local_addr().unwrap();
+        let mut page = TcpStream::connect(address).unwrap();
+        write!(
+            page,
+            "GET / HTTP/1.1\r\nUpgrade: websocket\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+        )
+        .unwrap();
+        // The request takes a moment to arrive
+        while observer.spectators.is_empty() {
+            observer.broadcast("turn");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut page = BufReader::new(page);
+        let mut response = String::new();
+        while !response.ends_with("\r\n\r\n") {
+            page.read_line(&mut response).unwrap();
+        }
+        assert!(response.starts_with("HTTP/1.1 101"));
+        assert!(response.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        let mut frame = [0; 6];
+        page.read_exact(&mut frame).unwrap();
+        assert_eq!(&frame, b"\x81\x04turn");
+    }
+
+    #[test]
+    fn slow_handshakes_dont_hold_up_the_game() {
+        let mut observer = Observer::bind("127.0.0.1:0").unwrap();
+        let address = observer.listener.local_addr().unwrap();
+        let mut page = TcpStream::connect(address).unwrap();
+        write!(page, "GET / HTTP/1.1\r\n").unwrap();
+        let start = Instant::now();
+        while observer.handshakes.is_empty() {
+            observer.broadcast("turn");
+        }
+        assert!(start.elapsed() < HANDSHAKE_TIMEOUT);
+
+        // A line now and then doesn't keep it from timing out
+        while start.elapsed() < HANDSHAKE_TIMEOUT * 2 {
+            // Until the game hangs up
+            let _ = write!(page, "X-Slow: yes\r\n");
+            observer.broadcast("turn");
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert!(observer.handshakes.is_empty());
+        assert!(observer.spectators.is_empty());
+    }
+
+    #[test]
+    fn the_protocol_follows_the_rfc() {
+        // The example from RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(text_frame("Hi"), vec![0x81, 2, b'H', b'i']);
+        let long = text_frame(&"x".repeat(300));
+        assert_eq!(&long[..4], &[0x81, 126, 1, 44]);
+        assert_eq!(long.len(), 304);
+    }
+}
@@ -0,0 +1,171 @@
+//! Command line options, and the page's query string on the web.
+
+use crate::{
+    config::{self, Config},
+    locale::DEFAULT_LANGUAGE,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    /// Play back the replay file instead of starting a new game.
+    pub replay: Option<String>,
+    /// Start in wizard mode: no permadeath and cheats are allowed.
+    pub wizard: bool,
+    /// One of `locale::LANGUAGES`.
+    pub language: String,
+    /// Print every turn as text too, see `text_mode`.
+    pub text: bool,
+    /// Draw health bars over wounded monsters. It can be changed in the
+    /// pause menu too.
+    pub health_bars: bool,
+    /// The map size and difficulty of new games.
+    pub config: Config,
+    /// The directories of the content packs to play with, in the order
+    /// they apply. See `packs`.
+    pub packs: Vec<String>,
+    /// Where to listen for spectators, see `observer`. Only builds with
+    /// the `observer` feature can be watched.
+    pub observe: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            replay: None,
+            wizard: false,
+            language: DEFAULT_LANGUAGE.to_string(),
+            text: false,
+            health_bars: true,
+            config: Config::default(),
+            packs: vec![],
+            observe: None,
+        }
+    }
+}
+
+pub const USAGE: &str = "Usage: quicksilver-roguelike [--replay <file>] [--wizard] \
+     [--lang <language>] [--text] [--no-health-bars] [--map-size <width>x<height>] [--difficulty <easy|normal|hard>] \
+     [--pack <directory>]... [--observe <address>]";
+
+impl Options {
+    /// Parse the command line arguments (without the program name).
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--replay" => {
+                    let path = args.next().ok_or("--replay needs a file name")?;
+                    options.replay = Some(path);
+                }
+                "--wizard" => options.wizard = true,
+                "--text" => options.text = true,
+                "--no-health-bars" => options.health_bars = false,
+                "--lang" => {
+                    let language = args.next().ok_or("--lang needs a language")?;
+                    options.language = language;
+                }
+                "--map-size" => {
+                    let size = args.next().ok_or("--map-size needs a size, e.g. 80x50")?;
+                    options.config.map_size = config::parse_map_size(&size)?;
+                }
+                "--difficulty" => {
+                    let difficulty = args.next().ok_or("--difficulty needs a difficulty")?;
+                    options.config.difficulty = config::parse_difficulty(&difficulty)?;
+                }
+                "--pack" => {
+                    let pack = args.next().ok_or("--pack needs a directory")?;
+                    options.packs.push(pack);
+                }
+                "--observe" => {
+                    let address = args.next().ok_or("--observe needs an address")?;
+                    options.observe = Some(address);
+                }
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Parse a page's query string, e.g.
+    /// `?size=80x50&difficulty=hard&health_bars=off`.
+    /// Anything it doesn't know about is left alone as it could be meant
+    /// for the page itself.
+    pub fn from_query(query: &str) -> Result<Self, String> {
+        let mut options = Options::default();
+        let query = query.strip_prefix('?').unwrap_or(query);
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "size" => options.config.map_size = config::parse_map_size(value)?,
+                "difficulty" => options.config.difficulty = config::parse_difficulty(value)?,
+                "lang" => options.language = value.to_string(),
+                "health_bars" => options.health_bars = value != "off",
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Difficulty;
+    use quicksilver::geom::Vector;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_replay() {
+        assert_eq!(parse(&[]), Ok(Options::default()));
+        assert_eq!(
+            parse(&["--replay", "bug.txt"]).map(|options| options.replay),
+            Ok(Some("bug.txt".to_string()))
+        );
+        assert!(parse(&["--replay"]).is_err());
+        assert!(parse(&["--fly"]).is_err());
+        assert_eq!(parse(&["--wizard"]).map(|options| options.wizard), Ok(true));
+        assert_eq!(
+            parse(&["--lang", "cs"]).map(|options| options.language),
+            Ok("cs".to_string())
+        );
+        assert!(parse(&["--lang"]).is_err());
+        assert_eq!(parse(&["--text"]).map(|options| options.text), Ok(true));
+        assert_eq!(
+            parse(&["--no-health-bars"]).map(|options| options.health_bars),
+            Ok(false)
+        );
+        assert_eq!(
+            parse(&["--pack", "a", "--pack", "b"]).map(|options| options.packs),
+            Ok(vec!["a".to_string(), "b".to_string()])
+        );
+        assert!(parse(&["--pack"]).is_err());
+        assert_eq!(
+            parse(&["--observe", "127.0.0.1:9001"]).map(|options| options.observe),
+            Ok(Some("127.0.0.1:9001".to_string()))
+        );
+        assert!(parse(&["--observe"]).is_err());
+    }
+
+    #[test]
+    fn parse_config() {
+        let options = parse(&["--map-size", "80x50", "--difficulty", "hard"]).unwrap();
+        let config = Config {
+            map_size: Vector::new(80, 50),
+            difficulty: Difficulty::Hard,
+        };
+        assert_eq!(options.config, config);
+        assert!(parse(&["--map-size"]).is_err());
+        assert!(parse(&["--map-size", "huge"]).is_err());
+        assert!(parse(&["--difficulty", "nightmare"]).is_err());
+
+        let options = Options::from_query("?size=80x50&difficulty=hard&utm_source=x").unwrap();
+        assert_eq!(options.config, config);
+        assert_eq!(Options::from_query(""), Ok(Options::default()));
+        assert!(!Options::from_query("?health_bars=off").unwrap().health_bars);
+        assert!(Options::from_query("?size=1x1").is_err());
+    }
+}
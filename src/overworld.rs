@@ -0,0 +1,171 @@
+//! The surface: a small town in the wilderness with the entrances to
+//! every dungeon.
+//!
+//! The surface is generated when the game starts and kept in
+//! `World::levels` like any other level while the player is
+//! underground. Each of the `Templates::dungeons` gets its own way down,
+//! and climbing out of its first level leads back up to it.
+
+use quicksilver::geom::Vector;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+
+use crate::{
+    geometry::tile_key,
+    map::{Map, DOOR, FLOOR, STAIRS_DOWN, WALL},
+    mapgen::Room,
+    spawn,
+    templates::{pick_weighted, Templates, MAIN_DUNGEON},
+    world::Entity,
+};
+
+/// How bright it is up here.
+pub const DAYLIGHT: f32 = 1.0;
+
+/// The chance of each tile of the wilderness being a boulder or a tree.
+const ROCK_CHANCE: f64 = 0.12;
+
+/// How many items the town's shopkeeper sells.
+const TOWN_STOCK: usize = 5;
+
+/// The entrances are at least this far (in tiles) from the town and
+/// from each other, if there's room.
+const ENTRANCE_SPACING: i32 = 5;
+
+/// The surface as it's first generated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Surface {
+    pub map: Map,
+    pub entities: Vec<Entity>,
+    /// The way down into each dungeon, in the order of
+    /// `Templates::dungeons`.
+    pub entrances: Vec<Vector>,
+    /// Where the player starts the game: just outside the shop.
+    pub start: Vector,
+}
+
+/// Generate the town and the wilderness around it.
+pub fn generate(size: Vector, templates: &Templates, rng: &mut StdRng) -> Surface {
+    let mut map = Map::filled(size, WALL);
+    let open = Room {
+        x: 1,
+        y: 1,
+        width: size.x as i32 - 2,
+        height: size.y as i32 - 2,
+    };
+    open.carve(&mut map);
+
+    // The shop sits in the middle with its door facing south
+    let shop = Room {
+        x: size.x as i32 / 2 - 2,
+        y: size.y as i32 / 2 - 2,
+        width: 5,
+        height: 3,
+    };
+    let town = Room {
+        x: shop.x - 2,
+        y: shop.y - 2,
+        width: shop.width + 4,
+        height: shop.height + 5,
+    };
+    for x in shop.x - 1..=shop.x + shop.width {
+        for y in shop.y - 1..=shop.y + shop.height {
+            map.set_glyph(Vector::new(x, y), WALL);
+        }
+    }
+    shop.carve(&mut map);
+    let door = Vector::new(shop.center().x, (shop.y + shop.height) as f32);
+    map.set_glyph(door, DOOR);
+    let start = door + Vector::new(0, 1);
+
+    let in_town = |pos: Vector| {
+        let (x, y) = tile_key(pos);
+        x >= town.x && x < town.x + town.width && y >= town.y && y < town.y + town.height
+    };
+    for tile in &mut map.tiles {
+        if tile.glyph == FLOOR && !in_town(tile.pos) && rng.gen_bool(ROCK_CHANCE) {
+            tile.glyph = WALL;
+        }
+    }
+    // Whatever the rocks cut off doesn't exist
+    let reachable = map.flood_fill(start);
+    for tile in &mut map.tiles {
+        if tile.glyph == FLOOR && !reachable.contains(&tile.pos) {
+            tile.glyph = WALL;
+        }
+    }
+
+    let mut entrances = vec![];
+    for _ in &templates.dungeons {
+        let far_enough = |pos: Vector, spacing: i32| {
+            distance(pos, start) >= spacing
+                && entrances
+                    .iter()
+                    .all(|&other| distance(pos, other) >= spacing)
+        };
+        let candidates = |spacing: i32| -> Vec<Vector> {
+            reachable
+                .iter()
+                .copied()
+                .filter(|&pos| !in_town(pos) && pos != start && far_enough(pos, spacing))
+                .filter(|&pos| map.tile(pos).is_some_and(|tile| tile.glyph == FLOOR))
+                .collect()
+        };
+        let mut spots = candidates(ENTRANCE_SPACING);
+        if spots.is_empty() {
+            spots = candidates(1);
+        }
+        if let Some(&pos) = spots.choose(rng) {
+            map.set_glyph(pos, STAIRS_DOWN);
+            entrances.push(pos);
+        }
+    }
+
+    let mut entities = vec![];
+    let items = &templates.branch(MAIN_DUNGEON, 1).items;
+    let mut stock = vec![];
+    for _ in 0..TOWN_STOCK {
+        if let Some(name) = pick_weighted(items, rng) {
+            let item = spawn::random_item(name, &templates.items[name], shop.center(), rng);
+            stock.push(item);
+        }
+    }
+    entities.push(spawn::shopkeeper(stock, shop.center()));
+    entities.push(spawn::brazier(start + Vector::new(-2, 0)));
+    entities.push(spawn::brazier(start + Vector::new(2, 0)));
+
+    Surface {
+        map,
+        entities,
+        entrances,
+        start,
+    }
+}
+
+fn distance(from: Vector, to: Vector) -> i32 {
+    let (from, to) = (tile_key(from), tile_key(to));
+    (from.0 - to.0).abs().max((from.1 - to.1).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn every_dungeon_has_a_reachable_entrance() {
+        let templates = Templates::builtin();
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let surface = generate(Vector::new(20, 15), &templates, &mut rng);
+            assert_eq!(surface.entrances.len(), templates.dungeons.len());
+            let reachable = surface.map.flood_fill(surface.start);
+            for entrance in &surface.entrances {
+                assert!(reachable.contains(entrance), "seed {}", seed);
+                assert_eq!(surface.map.tile(*entrance).unwrap().glyph, STAIRS_DOWN);
+            }
+            let floor = surface.map.tiles.iter().filter(|tile| !tile.is_wall());
+            assert_eq!(floor.count(), reachable.len(), "seed {}", seed);
+            assert!(surface.entities.iter().any(|entity| entity.shopkeeper));
+        }
+    }
+}
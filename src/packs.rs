@@ -0,0 +1,211 @@
+//! Content packs: new monsters, items, dungeons and looks for the game
+//! without touching its code.
+//!
+//! A pack is a directory with any of:
+//!
+//! ```text
+//! templates.ron   changes to the templates and their palette, see
+//!                 `templates::Overlay`
+//! prefabs/*.txt   more prefabs, see `prefabs`. One named like a built-in
+//!                 one (`vault.txt`) replaces it.
+//! tileset.ttf     the font the map is drawn with
+//! ```
+//!
+//! Packs are picked with `--pack <directory>` and apply in the order
+//! they're given, so a later pack changes what the earlier ones left.
+//! `set` makes them the content of the run: every new world starts with
+//! their templates and prefabs. A replay only plays back the same with
+//! the same packs, so replays and saves list the packs they were played
+//! with by name (see `Replay::packs`) and the game won't play one back
+//! with any others.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::{
+    locale,
+    prefabs::{self, Prefab},
+    templates::{Overlay, Templates},
+};
+
+static CURRENT: OnceLock<Content> = OnceLock::new();
+
+/// One pack as it's read from its directory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pack {
+    pub path: PathBuf,
+    pub templates: Option<Overlay>,
+    /// Each prefab's name (the file name without the `.txt`) and text,
+    /// by name.
+    pub prefabs: Vec<(String, String)>,
+    pub tileset: Option<Vec<u8>>,
+}
+
+impl Pack {
+    /// Read everything in the pack's directory, leaving what's not there
+    /// out.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let error =
+            |file: &Path, error: &dyn std::fmt::Display| format!("{}: {}", file.display(), error);
+        if !path.is_dir() {
+            return Err(error(&path, &"not a directory"));
+        }
+        let mut pack = Pack {
+            path: path.clone(),
+            ..Default::default()
+        };
+        let templates = path.join("templates.ron");
+        if templates.exists() {
+            let text = std::fs::read_to_string(&templates).map_err(|e| error(&templates, &e))?;
+            pack.templates = Some(Overlay::parse(&text).map_err(|e| error(&templates, &e))?);
+        }
+        let prefabs = path.join("prefabs");
+        if prefabs.is_dir() {
+            let entries = std::fs::read_dir(&prefabs).map_err(|e| error(&prefabs, &e))?;
+            for entry in entries {
+                let file = entry.map_err(|e| error(&prefabs, &e))?.path();
+                if file.extension().is_some_and(|extension| extension == "txt") {
+                    let name = file.file_stem().unwrap_or_default().to_string_lossy();
+                    let text = std::fs::read_to_string(&file).map_err(|e| error(&file, &e))?;
+                    pack.prefabs.push((name.into_owned(), text));
+                }
+            }
+            // The order files are listed in depends on the system
+            pack.prefabs.sort();
+        }
+        let tileset = path.join("tileset.ttf");
+        if tileset.exists() {
+            pack.tileset = Some(std::fs::read(&tileset).map_err(|e| error(&tileset, &e))?);
+        }
+        Ok(pack)
+    }
+
+    /// What replays call the pack: the name of its directory.
+    pub fn name(&self) -> String {
+        match self.path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => self.path.display().to_string(),
+        }
+    }
+}
+
+/// The game's content with all the packs applied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Content {
+    pub templates: Templates,
+    pub prefabs: Vec<Prefab>,
+    /// The font of the last pack that has one.
+    pub tileset: Option<Vec<u8>>,
+    /// Every pack's changes to the templates, in order, for whoever
+    /// reads the templates again (see `hot_reload`).
+    pub overlays: Vec<Overlay>,
+    /// The names of the packs, in the order they apply.
+    pub names: Vec<String>,
+}
+
+impl Content {
+    /// Apply `packs` in order to the built-in content and check it's all
+    /// still consistent.
+    pub fn new(packs: &[Pack]) -> Result<Self, String> {
+        let overlays: Vec<Overlay> = packs
+            .iter()
+            .filter_map(|pack| pack.templates.clone())
+            .collect();
+        let templates = Templates::with_overlays(&overlays)?;
+        let mut prefabs = prefabs::builtin().to_vec();
+        for pack in packs {
+            for (name, text) in &pack.prefabs {
+                let prefab = Prefab::parse(name, text)?;
+                match prefabs.iter_mut().find(|other| other.name == *name) {
+                    Some(other) => *other = prefab,
+                    None => prefabs.push(prefab),
+                }
+            }
+        }
+        // The built-in prefabs have to work with the packs' templates too
+        for prefab in &prefabs {
+            prefab.validate(&templates)?;
+        }
+        let tileset = packs.iter().rev().find_map(|pack| pack.tileset.clone());
+        Ok(Content {
+            templates,
+            prefabs,
+            tileset,
+            overlays,
+            names: packs.iter().map(Pack::name).collect(),
+        })
+    }
+}
+
+/// Use `content` for the rest of the run. Only the first call counts.
+pub fn set(content: Content) {
+    let _ = CURRENT.set(content);
+}
+
+/// The content picked with `set`, the built-in one if there wasn't any.
+pub fn current() -> &'static Content {
+    CURRENT.get_or_init(|| Content::new(&[]).expect("The built-in content must be valid."))
+}
+
+/// Whether a game played with the packs `names` plays back the same
+/// now.
+pub fn matches(names: &[String]) -> bool {
+    current().names == names
+}
+
+/// The pack names as a list for the player to read.
+pub fn describe(names: &[String]) -> String {
+    if names.is_empty() {
+        locale::text("packs.none")
+    } else {
+        names.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_packs_change_what_earlier_ones_left() {
+        let overlay = |text: &str| Some(Overlay::parse(text).unwrap());
+        let slimes = Pack {
+            path: PathBuf::from("packs/slimes"),
+            templates: overlay(
+                r##"(monsters: {"slime": (name: "slime", glyph: 'j', color: "#40c040", hp: 2)})"##,
+            ),
+            prefabs: vec![(
+                "pit".to_string(),
+                "j = monster slime\n\n#####\n#.j.#\n##+##\n".to_string(),
+            )],
+            ..Default::default()
+        };
+        let big_slimes = Pack {
+            path: PathBuf::from("packs/big-slimes"),
+            templates: overlay(
+                r##"(monsters: {"slime": (name: "slime", glyph: 'J', color: "#40c040", hp: 8)})"##,
+            ),
+            tileset: Some(vec![1, 2, 3]),
+            ..Default::default()
+        };
+        let content = Content::new(&[slimes.clone(), big_slimes]).unwrap();
+        assert_eq!(content.templates.monsters["slime"].hp, 8);
+        assert!(content.prefabs.iter().any(|prefab| prefab.name == "pit"));
+        assert_eq!(content.prefabs.len(), prefabs::builtin().len() + 1);
+        assert_eq!(content.tileset, Some(vec![1, 2, 3]));
+        assert_eq!(content.names, ["slimes", "big-slimes"]);
+        assert!(matches(&[]));
+        assert_eq!(describe(&content.names[..1]), "slimes");
+
+        // The prefab needs the slime from its own pack
+        let pit_only = Pack {
+            templates: None,
+            ..slimes
+        };
+        assert!(Content::new(&[pit_only]).is_err());
+        assert!(Pack::load("no/such/pack").is_err());
+    }
+}
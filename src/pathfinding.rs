@@ -0,0 +1,126 @@
+//! Finding the cheapest way from one tile to another.
+
+use quicksilver::geom::Vector;
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{geometry::tile_key, map::LAVA, world::World};
+
+/// The extra cost of walking through lava. Nobody takes that shortcut
+/// unless there's no other way.
+const LAVA_PENALTY: u32 = 10;
+
+/// Give up on paths longer than this.
+const MAX_COST: u32 = 100;
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// The first step the entity `id` should take to get to `goal` as
+/// cheaply as it can, according to the terrain's movement costs. Tiles
+/// with somebody standing on them are avoided, except for `goal`
+/// itself.
+pub fn next_step(world: &World, id: usize, goal: Vector) -> Option<Vector> {
+    let start = world.entities[id].pos;
+    let swims = world.entities[id].swims;
+    let goal_key = tile_key(goal);
+
+    // Dijkstra's algorithm remembering the first step of every path
+    let mut costs: HashMap<(i32, i32), u32> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    costs.insert(tile_key(start), 0);
+    queue.push(Reverse((0, tile_key(start), None)));
+    while let Some(Reverse((cost, key, first_step))) = queue.pop() {
+        if key == goal_key {
+            return first_step.map(|(x, y)| Vector::new(x, y));
+        }
+        if cost > costs[&key] || cost > MAX_COST {
+            continue;
+        }
+        for &(x, y) in &DIRECTIONS {
+            let next: Vector = Vector::new(key.0 + x, key.1 + y);
+            let next_key = tile_key(next);
+            let occupied = next_key != goal_key && world.blocker_at(next).is_some();
+            let step_cost = match world.map.movement_cost(next, swims) {
+                Some(step_cost) if !occupied => step_cost,
+                _ => continue,
+            };
+            let penalty = match world.map.tile(next) {
+                Some(tile) if tile.glyph == LAVA => LAVA_PENALTY,
+                _ => 0,
+            };
+            let next_cost = cost + step_cost + penalty;
+            if costs.get(&next_key).is_none_or(|&known| next_cost < known) {
+                costs.insert(next_key, next_cost);
+                let first_step = first_step.or(Some(next_key));
+                queue.push(Reverse((next_cost, next_key, first_step)));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{generate_map, DEEP_WATER, SHALLOW_WATER},
+        world::Entity,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// A monster at (1, 2) wanting to get to (5, 2) with a column of
+    /// `glyph` in the way, except at the top.
+    fn world_with_column(glyph: char, swims: bool) -> World {
+        let mut map = generate_map(Vector::new(7, 6));
+        for y in 2..5 {
+            map.set_glyph(Vector::new(3, y), glyph);
+        }
+        let monster = Entity {
+            pos: Vector::new(1, 2),
+            hp: 1,
+            max_hp: 1,
+            blocks_tile: true,
+            swims,
+            ..Default::default()
+        };
+        let player = Entity {
+            pos: Vector::new(5, 2),
+            hp: 1,
+            max_hp: 1,
+            blocks_tile: true,
+            ..Default::default()
+        };
+        World::from_parts(0, StdRng::seed_from_u64(0), map, vec![monster], player)
+    }
+
+    fn path_length(world: &mut World) -> usize {
+        let goal = world.player().pos;
+        let mut steps = 0;
+        while let Some(step) = next_step(world, 0, goal) {
+            if step == goal {
+                return steps + 1;
+            }
+            world.move_entity(0, step);
+            steps += 1;
+            assert!(steps < 20, "The path goes nowhere.");
+        }
+        panic!("No path found.")
+    }
+
+    #[test]
+    fn paths_go_around_deep_water_unless_swimming() {
+        // Up to the gap and back down: 1 + 4 + 1 steps
+        assert_eq!(path_length(&mut world_with_column(DEEP_WATER, false)), 6);
+        assert_eq!(path_length(&mut world_with_column(DEEP_WATER, true)), 4);
+    }
+
+    #[test]
+    fn paths_avoid_slow_and_dangerous_terrain() {
+        // Wading through takes 5 turns, the way around 6
+        assert_eq!(path_length(&mut world_with_column(SHALLOW_WATER, false)), 4);
+        assert_eq!(path_length(&mut world_with_column(LAVA, false)), 6);
+    }
+}
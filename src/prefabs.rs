@@ -0,0 +1,453 @@
+//! Hand-made rooms stamped into the generated levels: shrines, vaults
+//! and the like.
+//!
+//! Each prefab is a text file in `static/prefabs` built into the
+//! executable, or in a content pack (see `packs`). It starts with its
+//! settings and a legend, then a blank line and the layout:
+//!
+//! ```text
+//! ; Lines starting with a semicolon are comments.
+//! depth: 2
+//! ! = item healing potion
+//!
+//! #####
+//! #.!.#
+//! ##+##
+//! ```
+//!
+//! `depth` is the shallowest level the prefab shows up on. The layout
+//! uses the map's own glyphs. The legend tells what stands on the other
+//! characters (which are floor underneath): an `item`, a `monster`, a
+//! `brazier`, a `chest` or a `locked chest`, or `gold` and how much.
+//! The floor and doors on the edge of the layout are its entrances.
+//!
+//! A prefab only goes into solid rock, so it never cuts anything off,
+//! and a corridor is dug from each entrance to the nearest open tile.
+
+use quicksilver::geom::Vector;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::OnceLock,
+};
+
+use crate::{
+    geometry::tile_key,
+    map::{Map, DEEP_WATER, DOOR, FLOOR, LAVA, SHALLOW_WATER, WALL},
+    packs, spawn,
+    templates::{Branch, Templates, KEY},
+    world::Entity,
+};
+
+/// The chance of a level getting a prefab.
+const PREFAB_CHANCE: f64 = 0.3;
+
+/// The prefabs the game ships with.
+pub const BUILTIN_PREFABS: &[(&str, &str)] = &[
+    ("shrine", include_str!("../static/prefabs/shrine.txt")),
+    ("grotto", include_str!("../static/prefabs/grotto.txt")),
+    ("vault", include_str!("../static/prefabs/vault.txt")),
+];
+
+/// The glyphs a layout can use besides the legend.
+const LAYOUT_GLYPHS: [char; 6] = [WALL, FLOOR, DOOR, SHALLOW_WATER, DEEP_WATER, LAVA];
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+static BUILTIN: OnceLock<Vec<Prefab>> = OnceLock::new();
+
+/// What a legend character puts on its tile.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fixture {
+    Item(String),
+    Monster(String),
+    Brazier,
+    Chest { locked: bool },
+    Gold(u32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prefab {
+    pub name: String,
+    /// The shallowest level it can show up on.
+    pub depth: u32,
+    /// The rows of the layout, all of them the same length.
+    pub layout: Vec<Vec<char>>,
+    pub legend: BTreeMap<char, Fixture>,
+}
+
+impl Prefab {
+    /// Read a prefab (see the module documentation for the format) and
+    /// check that its layout makes sense.
+    pub fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let mut prefab = Prefab {
+            name: name.to_string(),
+            depth: 1,
+            layout: vec![],
+            legend: BTreeMap::new(),
+        };
+        let mut lines = text.lines().filter(|line| !line.starts_with(';'));
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(depth) = line.strip_prefix("depth:") {
+                prefab.depth = depth
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("`{}` has an invalid depth: {}", name, line))?;
+            } else if let Some((glyph, fixture)) = line.split_once('=') {
+                let mut chars = glyph.trim().chars();
+                let glyph = match (chars.next(), chars.next()) {
+                    (Some(glyph), None) => glyph,
+                    _ => return Err(format!("`{}` has an invalid legend: {}", name, line)),
+                };
+                if LAYOUT_GLYPHS.contains(&glyph) {
+                    return Err(format!("`{}` redefines the `{}` tile.", name, glyph));
+                }
+                prefab
+                    .legend
+                    .insert(glyph, parse_fixture(name, fixture.trim())?);
+            } else {
+                return Err(format!("`{}` has an invalid line: {}", name, line));
+            }
+        }
+        prefab.layout = lines
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty())
+            .map(|line| line.chars().collect())
+            .collect();
+        prefab.check()?;
+        Ok(prefab)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let (width, height) = self.dimensions();
+        if width < 3 || height < 3 {
+            return Err(format!("`{}` is too small.", self.name));
+        }
+        if self.layout.iter().any(|row| row.len() != width as usize) {
+            return Err(format!("`{}` has rows of different lengths.", self.name));
+        }
+        for (x, y, glyph) in self.glyphs() {
+            if !LAYOUT_GLYPHS.contains(&glyph) && !self.legend.contains_key(&glyph) {
+                return Err(format!(
+                    "`{}` uses `{}` without a legend.",
+                    self.name, glyph
+                ));
+            }
+            let on_edge = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+            let corner = (x == 0 || x == width - 1) && (y == 0 || y == height - 1);
+            if on_edge && glyph != WALL && (corner || !is_entrance(glyph)) {
+                return Err(format!(
+                    "`{}` has a hole in its wall at ({}, {}).",
+                    self.name, x, y
+                ));
+            }
+        }
+        if self.entrances().is_empty() {
+            return Err(format!("`{}` has no way in.", self.name));
+        }
+        Ok(())
+    }
+
+    /// Every template the legend mentions exists.
+    pub fn validate(&self, templates: &Templates) -> Result<(), String> {
+        for fixture in self.legend.values() {
+            match fixture {
+                Fixture::Item(id) if !templates.items.contains_key(id) => {
+                    return Err(format!("Unknown item `{}` in `{}`.", id, self.name));
+                }
+                Fixture::Monster(id) if !templates.monsters.contains_key(id) => {
+                    return Err(format!("Unknown monster `{}` in `{}`.", id, self.name));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The width and height of the layout.
+    pub fn dimensions(&self) -> (i32, i32) {
+        let height = self.layout.len() as i32;
+        let width = self.layout.first().map_or(0, |row| row.len() as i32);
+        (width, height)
+    }
+
+    fn glyphs(&self) -> impl Iterator<Item = (i32, i32, char)> + '_ {
+        self.layout.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, &glyph)| (x as i32, y as i32, glyph))
+        })
+    }
+
+    /// The entrances on the edge of the layout and the direction out of
+    /// each.
+    fn entrances(&self) -> Vec<(Vector, Vector)> {
+        let (width, height) = self.dimensions();
+        self.glyphs()
+            .filter(|&(_, _, glyph)| is_entrance(glyph))
+            .filter_map(|(x, y, _)| {
+                let out = if x == 0 {
+                    (-1, 0)
+                } else if x == width - 1 {
+                    (1, 0)
+                } else if y == 0 {
+                    (0, -1)
+                } else if y == height - 1 {
+                    (0, 1)
+                } else {
+                    return None;
+                };
+                Some((Vector::new(x, y), Vector::new(out.0, out.1)))
+            })
+            .collect()
+    }
+}
+
+fn is_entrance(glyph: char) -> bool {
+    glyph == FLOOR || glyph == DOOR
+}
+
+fn parse_fixture(name: &str, text: &str) -> Result<Fixture, String> {
+    let (kind, rest) = match text.split_once(' ') {
+        Some((kind, rest)) => (kind, rest.trim()),
+        None => (text, ""),
+    };
+    match (kind, rest) {
+        ("item", id) if !id.is_empty() => Ok(Fixture::Item(id.to_string())),
+        ("monster", id) if !id.is_empty() => Ok(Fixture::Monster(id.to_string())),
+        ("brazier", "") => Ok(Fixture::Brazier),
+        ("chest", "") => Ok(Fixture::Chest { locked: false }),
+        ("locked", "chest") => Ok(Fixture::Chest { locked: true }),
+        ("gold", amount) => amount
+            .parse()
+            .map(Fixture::Gold)
+            .map_err(|_| format!("`{}` has an invalid amount of gold: {}", name, text)),
+        _ => Err(format!("`{}` has an unknown fixture: {}", name, text)),
+    }
+}
+
+/// The prefabs the game ships with.
+pub fn builtin() -> &'static [Prefab] {
+    BUILTIN.get_or_init(|| {
+        BUILTIN_PREFABS
+            .iter()
+            .map(|(name, text)| Prefab::parse(name, text).expect("A built-in prefab is invalid."))
+            .collect()
+    })
+}
+
+/// Maybe stamp one of the prefabs that belong on level `depth` of
+/// `dungeon` into `map` and return everything standing in it.
+pub fn add_prefab(
+    map: &mut Map,
+    templates: &Templates,
+    dungeon: usize,
+    depth: u32,
+    rng: &mut StdRng,
+) -> Vec<Entity> {
+    if !rng.gen_bool(PREFAB_CHANCE) {
+        return vec![];
+    }
+    let prefabs: Vec<&Prefab> = packs::current()
+        .prefabs
+        .iter()
+        .filter(|prefab| prefab.depth <= depth)
+        .collect();
+    match prefabs.choose(rng) {
+        Some(prefab) => match stamp(map, prefab, rng) {
+            Some(corner) => {
+                let branch = templates.branch(dungeon, depth);
+                fixtures(map, prefab, corner, templates, branch, rng)
+            }
+            None => vec![],
+        },
+        None => vec![],
+    }
+}
+
+/// Put `prefab` somewhere in the solid rock of `map` and dig a way to
+/// each of its entrances. Return its top left corner, or `None` if it
+/// doesn't fit anywhere.
+pub fn stamp(map: &mut Map, prefab: &Prefab, rng: &mut StdRng) -> Option<Vector> {
+    let (width, height) = prefab.dimensions();
+    let inside =
+        |x: i32, y: i32| x >= 1 && y >= 1 && x < map.size.x as i32 - 1 && y < map.size.y as i32 - 1;
+    let entrances = prefab.entrances();
+    let mut spots = vec![];
+    for x in 1..map.size.x as i32 - width {
+        for y in 1..map.size.y as i32 - height {
+            let solid = (x..x + width).all(|x| {
+                (y..y + height).all(|y| {
+                    let tile = map.tile(Vector::new(x, y));
+                    tile.is_some_and(|tile| tile.glyph == WALL)
+                })
+            });
+            let doors_inside = entrances.iter().all(|&(pos, out)| {
+                let outside = Vector::new(x, y) + pos + out;
+                inside(outside.x as i32, outside.y as i32)
+            });
+            if solid && doors_inside {
+                spots.push(Vector::new(x, y));
+            }
+        }
+    }
+    let corner = *spots.choose(rng)?;
+
+    let original = map.clone();
+    for (x, y, glyph) in prefab.glyphs() {
+        let glyph = if prefab.legend.contains_key(&glyph) {
+            FLOOR
+        } else {
+            glyph
+        };
+        map.set_glyph(corner + Vector::new(x, y), glyph);
+    }
+    let footprint = |pos: Vector| {
+        let (x, y) = (pos.x - corner.x, pos.y - corner.y);
+        x >= 0.0 && y >= 0.0 && x < width as f32 && y < height as f32
+    };
+    for (pos, out) in entrances {
+        if !dig(map, corner + pos + out, &footprint) {
+            *map = original;
+            return None;
+        }
+    }
+    Some(corner)
+}
+
+/// Dig a corridor from `start` to the nearest open tile, going around
+/// the tiles in `footprint`.
+fn dig(map: &mut Map, start: Vector, footprint: &impl Fn(Vector) -> bool) -> bool {
+    let mut came_from: HashMap<(i32, i32), Option<Vector>> = HashMap::new();
+    let mut queue = VecDeque::new();
+    came_from.insert(tile_key(start), None);
+    queue.push_back(start);
+    let (width, height) = (map.size.x as i32, map.size.y as i32);
+    while let Some(pos) = queue.pop_front() {
+        if map.tile(pos).is_some_and(|tile| tile.glyph != WALL) {
+            let mut step = Some(pos);
+            while let Some(pos) = step {
+                if map.tile(pos).is_some_and(|tile| tile.glyph == WALL) {
+                    map.set_glyph(pos, FLOOR);
+                }
+                step = came_from[&tile_key(pos)];
+            }
+            return true;
+        }
+        for &(x, y) in &DIRECTIONS {
+            let next = pos + Vector::new(x, y);
+            let (nx, ny) = tile_key(next);
+            if nx < 1 || ny < 1 || nx >= width - 1 || ny >= height - 1 {
+                continue;
+            }
+            if footprint(next) || came_from.contains_key(&(nx, ny)) {
+                continue;
+            }
+            came_from.insert((nx, ny), Some(pos));
+            queue.push_back(next);
+        }
+    }
+    false
+}
+
+/// Create the fixtures of `prefab` stamped at `corner`. A locked chest
+/// comes with a key lying somewhere on the level.
+fn fixtures(
+    map: &Map,
+    prefab: &Prefab,
+    corner: Vector,
+    templates: &Templates,
+    branch: &Branch,
+    rng: &mut StdRng,
+) -> Vec<Entity> {
+    let mut entities = vec![];
+    for (x, y, glyph) in prefab.glyphs() {
+        let pos = corner + Vector::new(x, y);
+        match prefab.legend.get(&glyph) {
+            Some(Fixture::Item(id)) => {
+                if let Some(template) = templates.items.get(id) {
+                    entities.push(spawn::random_item(id, template, pos, rng));
+                }
+            }
+            Some(Fixture::Monster(id)) => {
+                if let Some(template) = templates.monsters.get(id) {
+                    entities.push(spawn::monster(id, template, pos));
+                }
+            }
+            Some(Fixture::Brazier) => entities.push(spawn::brazier(pos)),
+            Some(Fixture::Gold(amount)) => entities.push(spawn::gold(*amount, pos)),
+            Some(&Fixture::Chest { locked }) => {
+                let contents = spawn::chest_contents(templates, branch, pos, rng);
+                entities.push(spawn::chest(contents, locked, pos));
+                if locked {
+                    if let Some(pos) = spawn::random_free_tile(map, &entities, rng) {
+                        entities.push(spawn::item(KEY, &templates.items[KEY], pos));
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+    entities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapgen::MapType;
+    use rand::SeedableRng;
+
+    #[test]
+    fn builtin_prefabs_are_valid() {
+        let templates = Templates::builtin();
+        assert_eq!(builtin().len(), BUILTIN_PREFABS.len());
+        for prefab in builtin() {
+            prefab.validate(&templates).unwrap();
+        }
+
+        let bad = [
+            "! = item dart\n\n#####\n#.?.#\n##+##\n",
+            "depth: 2\n\n#####\n#...#\n#####\n",
+            "\n#####\n#...#\n.#+##\n",
+            "$ = gold lots\n\n###\n#$.\n###\n",
+        ];
+        for text in &bad {
+            assert!(Prefab::parse("bad", text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn prefabs_go_into_rock_and_stay_connected() {
+        let mut stamped = 0;
+        for &map_type in &MapType::ALL {
+            for seed in 0..10 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                for prefab in builtin() {
+                    let mut map = map_type.builder().build(Vector::new(20, 15), &mut rng);
+                    let corner = match stamp(&mut map, prefab, &mut rng) {
+                        Some(corner) => corner,
+                        None => continue,
+                    };
+                    stamped += 1;
+                    assert_eq!(map.tile(corner).unwrap().glyph, WALL);
+                    let floor = map.tiles.iter().filter(|tile| !tile.is_wall()).count();
+                    let reachable = map.flood_fill(corner + prefab.entrances()[0].0);
+                    assert_eq!(
+                        reachable.len(),
+                        floor,
+                        "{} {:?} {}",
+                        prefab.name,
+                        map_type,
+                        seed
+                    );
+                }
+            }
+        }
+        assert!(stamped > 0);
+    }
+}
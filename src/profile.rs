@@ -0,0 +1,141 @@
+//! Who the player is, picked on the screen before the game starts.
+//!
+//! The class sets how much health the player has, what they start out
+//! carrying and the one thing only they are good at, see `Ability`.
+
+use crate::locale;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Class {
+    #[default]
+    Fighter,
+    Rogue,
+    Mage,
+}
+
+/// Every class, in the order the selection screen lists them.
+pub const CLASSES: [Class; 3] = [Class::Fighter, Class::Rogue, Class::Mage];
+
+/// The longest name a character can be given.
+pub const MAX_NAME_LENGTH: usize = 20;
+
+/// How close (in tiles) an invisible monster has to be for a player with
+/// `KeenEyes` to spot it.
+pub const KEEN_EYES_RANGE: f32 = 3.0;
+
+/// Something one class does better than the others.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ability {
+    /// Every hit with a shield knocks the enemy back, not just some.
+    ShieldMastery,
+    /// Invisible monsters show up within `KEEN_EYES_RANGE`, not only
+    /// right next to the player.
+    KeenEyes,
+    /// Every potion and scroll is known from the start.
+    Lore,
+}
+
+impl Class {
+    /// What the class is called in replays and the locale keys.
+    pub fn id(self) -> &'static str {
+        match self {
+            Class::Fighter => "fighter",
+            Class::Rogue => "rogue",
+            Class::Mage => "mage",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Class> {
+        CLASSES.iter().copied().find(|class| class.id() == id)
+    }
+
+    pub fn name(self) -> String {
+        locale::text(&format!("class.{}", self.id()))
+    }
+
+    /// One line about the class for the selection screen.
+    pub fn description(self) -> String {
+        locale::text(&format!("class.{}-description", self.id()))
+    }
+
+    /// The player starts with full health.
+    pub fn max_hp(self) -> i32 {
+        match self {
+            Class::Fighter => 7,
+            Class::Rogue => 5,
+            Class::Mage => 4,
+        }
+    }
+
+    /// The item templates the player starts out carrying and whether
+    /// each one is equipped.
+    pub fn starting_items(self) -> &'static [(&'static str, bool)] {
+        match self {
+            Class::Fighter => &[("shield", true)],
+            Class::Rogue => &[("dart", false), ("dart", false), ("dart", false)],
+            Class::Mage => &[
+                ("healing potion", false),
+                ("confusion potion", false),
+                ("scroll of identify", false),
+            ],
+        }
+    }
+
+    pub fn ability(self) -> Ability {
+        match self {
+            Class::Fighter => Ability::ShieldMastery,
+            Class::Rogue => Ability::KeenEyes,
+            Class::Mage => Ability::Lore,
+        }
+    }
+}
+
+/// Everything the player picked before the game started. The world
+/// keeps it around for the rules that depend on it and replays record
+/// it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerProfile {
+    pub class: Class,
+    /// What the player named their character, empty if they didn't.
+    pub name: String,
+}
+
+impl PlayerProfile {
+    pub fn new(class: Class) -> Self {
+        PlayerProfile {
+            class,
+            name: String::new(),
+        }
+    }
+
+    /// The character's name, or their class if they weren't given one.
+    pub fn character(&self) -> String {
+        if self.name.is_empty() {
+            self.class.name()
+        } else {
+            self.name.clone()
+        }
+    }
+
+    pub fn has(&self, ability: Ability) -> bool {
+        self.class.ability() == ability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::Templates;
+
+    #[test]
+    fn classes_start_with_known_items() {
+        let templates = Templates::builtin();
+        for class in CLASSES {
+            assert_eq!(Class::from_id(class.id()), Some(class));
+            for (item, _) in class.starting_items() {
+                assert!(templates.items.contains_key(*item), "{}", item);
+            }
+        }
+        assert_eq!(Class::from_id("bard"), None);
+    }
+}
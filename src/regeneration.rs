@@ -0,0 +1,89 @@
+//! Healing slowly over time.
+//!
+//! Every entity with a `regeneration` rate heals a point of damage each
+//! time that many turns have passed, as long as they haven't been hurt
+//! recently. The player heals slowly, trolls a lot faster.
+
+use crate::world::World;
+
+/// How often (in turns) the player heals on their own.
+pub const PLAYER_REGENERATION_TURNS: u32 = 10;
+
+/// Nobody heals for this many turns after being hurt.
+pub const RECENTLY_HURT_TURNS: u32 = 5;
+
+/// Heal everybody whose turn has come.
+pub fn regenerate(world: &mut World) {
+    let turn = world.stats.turns;
+    for entity in &mut world.entities {
+        let rate = match entity.regeneration {
+            Some(rate) if rate > 0 => rate,
+            _ => continue,
+        };
+        let recently_hurt = entity
+            .last_hurt
+            .is_some_and(|hurt| turn < hurt + RECENTLY_HURT_TURNS);
+        if entity.is_alive()
+            && entity.hp < entity.max_hp
+            && !recently_hurt
+            && turn.is_multiple_of(rate)
+        {
+            entity.hp += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{take_damage, tests::player_with, Action, Entity};
+    use quicksilver::geom::Vector;
+
+    fn wounded(pos: Vector, regeneration: u32) -> Entity {
+        Entity {
+            pos,
+            hp: 1,
+            max_hp: 10,
+            blocks_tile: true,
+            regeneration: Some(regeneration),
+            ..Default::default()
+        }
+    }
+
+    /// The player down to 1 of 10 health, healing every `regeneration`
+    /// turns.
+    fn world_with(monsters: Vec<Entity>, regeneration: u32) -> World {
+        let mut world = player_with(monsters, 10);
+        let player = &mut world.entities[world.player_id];
+        player.hp = 1;
+        player.regeneration = Some(regeneration);
+        world
+    }
+
+    fn wait(world: &mut World, turns: u32) {
+        for _ in 0..turns {
+            world.player_turn(Action::Wait);
+        }
+    }
+
+    #[test]
+    fn each_entity_heals_at_its_own_rate() {
+        let troll = wounded(Vector::new(10, 10), 2);
+        let mut world = world_with(vec![troll], PLAYER_REGENERATION_TURNS);
+        wait(&mut world, 20);
+        assert_eq!(world.player().hp, 3);
+        assert_eq!(world.entities[0].hp, 10);
+    }
+
+    #[test]
+    fn getting_hurt_stops_healing_for_a_while() {
+        let mut world = world_with(vec![], 1);
+        let player_id = world.player_id;
+        world.entities[player_id].hp = 5;
+        take_damage(&mut world, None, player_id, 1);
+        wait(&mut world, RECENTLY_HURT_TURNS - 1);
+        assert_eq!(world.player().hp, 4);
+        wait(&mut world, 2);
+        assert_eq!(world.player().hp, 6);
+    }
+}
@@ -0,0 +1,405 @@
+//! Recording and replaying games.
+//!
+//! A replay is just the world seed, the game mode, the player's class
+//! and name, the `Config` if it isn't the default, the content packs
+//! and the list of actions the player took. Since all randomness comes
+//! from the seeded RNG, playing the same actions back recreates the
+//! exact same game.
+//!
+//! The file format is plain text so it can be attached to bug reports
+//! and edited by hand:
+//!
+//! ```text
+//! seed 1234
+//! wizard
+//! class rogue
+//! name Ada
+//! size 80x50
+//! difficulty hard
+//! pack more-monsters
+//! move 1 0
+//! move 0 -1
+//! descend
+//! ```
+//!
+//! Only the `seed` line is required and the others come in this order.
+//! Any action can be followed by a `hash` line with the state hash of
+//! the game after it, see `checksum`.
+
+use quicksilver::geom::Vector;
+
+use crate::{
+    config::{self, Config},
+    profile::{Class, PlayerProfile},
+    skills::Skill,
+    wizard::{Cheat, GameMode},
+    world::{Action, World},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    /// Wizard mode games have a `wizard` line after the seed and daily
+    /// challenges a `daily` one.
+    pub mode: GameMode,
+    /// The `class` line and the `name` one. Replays without a class are
+    /// fighters and the name's left out if the character doesn't have
+    /// one.
+    pub profile: PlayerProfile,
+    /// The `size` and `difficulty` lines, each left out when it's the
+    /// default.
+    pub config: Config,
+    /// A `pack` line for each of the content packs the game was played
+    /// with, in the order they applied. See `packs::matches`.
+    pub packs: Vec<String>,
+    pub actions: Vec<Action>,
+    /// The `checksum::state_hash` after each action, if they were
+    /// recorded. Empty otherwise.
+    pub hashes: Vec<u64>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay {
+            seed,
+            mode: GameMode::Normal,
+            profile: PlayerProfile::default(),
+            config: Config::default(),
+            packs: vec![],
+            actions: vec![],
+            hashes: vec![],
+        }
+    }
+
+    pub fn record(&mut self, action: Action) {
+        self.actions.push(action);
+    }
+
+    /// Record `action` along with the `checksum::state_hash` of the
+    /// world after it. Every action has a hash or none does, so a replay
+    /// recorded without them goes on without.
+    pub fn record_hashed(&mut self, action: Action, hash: u64) {
+        let hashed = self.hashes.len() == self.actions.len();
+        self.record(action);
+        if hashed {
+            self.hashes.push(hash);
+        }
+    }
+
+    /// Create a fresh world in the same state the recording started in.
+    pub fn world(&self) -> World {
+        let mut world = World::with_config(self.seed, self.profile.clone(), self.config);
+        world.mode = self.mode;
+        world
+    }
+
+    /// Play all the recorded actions at once and return the final world.
+    pub fn play(&self) -> World {
+        let mut world = self.world();
+        for action in &self.actions {
+            world.player_turn(action.clone());
+        }
+        world
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("seed {}\n", self.seed);
+        match self.mode {
+            GameMode::Normal => {}
+            GameMode::Wizard => text.push_str("wizard\n"),
+            GameMode::Daily => text.push_str("daily\n"),
+        }
+        text.push_str(&format!("class {}\n", self.profile.class.id()));
+        if !self.profile.name.is_empty() {
+            text.push_str(&format!("name {}\n", self.profile.name));
+        }
+        let default = Config::default();
+        if self.config.map_size != default.map_size {
+            let size = self.config.map_size;
+            text.push_str(&format!("size {}x{}\n", size.x, size.y));
+        }
+        if self.config.difficulty != default.difficulty {
+            text.push_str(&format!("difficulty {}\n", self.config.difficulty.id()));
+        }
+        for pack in &self.packs {
+            text.push_str(&format!("pack {}\n", pack));
+        }
+        for (index, action) in self.actions.iter().enumerate() {
+            match action {
+                Action::Move(offset) => {
+                    text.push_str(&format!("move {} {}\n", offset.x as i32, offset.y as i32))
+                }
+                Action::Descend => text.push_str("descend\n"),
+                Action::Ascend => text.push_str("ascend\n"),
+                Action::PickUp => text.push_str("pick_up\n"),
+                Action::Wait => text.push_str("wait\n"),
+                Action::Buy(index) => text.push_str(&format!("buy {}\n", index)),
+                Action::Sell(index) => text.push_str(&format!("sell {}\n", index)),
+                Action::Equip(index) => text.push_str(&format!("equip {}\n", index)),
+                Action::Throw(index, target) => text.push_str(&format!(
+                    "throw {} {} {}\n",
+                    index, target.x as i32, target.y as i32
+                )),
+                Action::Fire(target) => {
+                    text.push_str(&format!("fire {} {}\n", target.x as i32, target.y as i32))
+                }
+                Action::Use(index) => text.push_str(&format!("use {}\n", index)),
+                Action::Skill(skill, aim) => text.push_str(&format!(
+                    "skill {} {} {}\n",
+                    skill.id(),
+                    aim.x as i32,
+                    aim.y as i32
+                )),
+                Action::Sneak => text.push_str("sneak\n"),
+                Action::Take(index) => text.push_str(&format!("take {}\n", index)),
+                Action::TakeAll => text.push_str("take_all\n"),
+                Action::Cheat(cheat) => text.push_str(&format!("cheat {}\n", cheat.to_text())),
+            }
+            if let Some(hash) = self.hashes.get(index) {
+                text.push_str(&format!("hash {:016x}\n", hash));
+            }
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let seed = match lines.next() {
+            Some((_, line)) if line.starts_with("seed ") => line["seed ".len()..]
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid seed: `{}`", line))?,
+            _ => return Err("The replay must start with the `seed` line.".to_string()),
+        };
+
+        let mut replay = Replay::new(seed);
+        let mut lines = lines.peekable();
+        if lines.next_if(|&(_, line)| line == "wizard").is_some() {
+            replay.mode = GameMode::Wizard;
+        } else if lines.next_if(|&(_, line)| line == "daily").is_some() {
+            replay.mode = GameMode::Daily;
+        }
+        if let Some((number, line)) = lines.next_if(|&(_, line)| line.starts_with("class ")) {
+            let id = line["class ".len()..].trim();
+            let class = Class::from_id(id)
+                .ok_or_else(|| format!("Line {}: unknown class `{}`", number, id))?;
+            replay.profile = PlayerProfile::new(class);
+        }
+        if let Some((_, line)) = lines.next_if(|&(_, line)| line.starts_with("name ")) {
+            replay.profile.name = line["name ".len()..].trim().to_string();
+        }
+        if let Some((number, line)) = lines.next_if(|&(_, line)| line.starts_with("size ")) {
+            replay.config.map_size = config::parse_map_size(line["size ".len()..].trim())
+                .map_err(|error| format!("Line {}: {}", number, error))?;
+        }
+        if let Some((number, line)) = lines.next_if(|&(_, line)| line.starts_with("difficulty ")) {
+            replay.config.difficulty = config::parse_difficulty(line["difficulty ".len()..].trim())
+                .map_err(|error| format!("Line {}: {}", number, error))?;
+        }
+        while let Some((_, line)) = lines.next_if(|&(_, line)| line.starts_with("pack ")) {
+            replay.packs.push(line["pack ".len()..].trim().to_string());
+        }
+        for (number, line) in lines {
+            if let Some(hash) = line.strip_prefix("hash ") {
+                // Each one belongs to the action right before it
+                if replay.hashes.len() + 1 != replay.actions.len() {
+                    return Err(format!("Line {}: a hash without an action", number));
+                }
+                let hash = u64::from_str_radix(hash.trim(), 16)
+                    .map_err(|_| format!("Line {}: invalid hash `{}`", number, hash))?;
+                replay.hashes.push(hash);
+                continue;
+            }
+            let action = parse_action(line)
+                .ok_or_else(|| format!("Line {}: unknown action `{}`", number, line))?;
+            replay.record(action);
+        }
+        Ok(replay)
+    }
+}
+
+fn parse_action(line: &str) -> Option<Action> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["move", x, y] => {
+            let x: i32 = x.parse().ok()?;
+            let y: i32 = y.parse().ok()?;
+            Some(Action::Move(Vector::new(x, y)))
+        }
+        ["descend"] => Some(Action::Descend),
+        ["ascend"] => Some(Action::Ascend),
+        ["pick_up"] => Some(Action::PickUp),
+        ["wait"] => Some(Action::Wait),
+        ["buy", index] => index.parse().ok().map(Action::Buy),
+        ["sell", index] => index.parse().ok().map(Action::Sell),
+        ["equip", index] => index.parse().ok().map(Action::Equip),
+        ["throw", index, x, y] => {
+            let index = index.parse().ok()?;
+            let x: i32 = x.parse().ok()?;
+            let y: i32 = y.parse().ok()?;
+            Some(Action::Throw(index, Vector::new(x, y)))
+        }
+        ["fire", x, y] => {
+            let x: i32 = x.parse().ok()?;
+            let y: i32 = y.parse().ok()?;
+            Some(Action::Fire(Vector::new(x, y)))
+        }
+        ["use", index] => index.parse().ok().map(Action::Use),
+        ["skill", id, x, y] => {
+            let skill = Skill::from_id(id)?;
+            let x: i32 = x.parse().ok()?;
+            let y: i32 = y.parse().ok()?;
+            Some(Action::Skill(skill, Vector::new(x, y)))
+        }
+        ["sneak"] => Some(Action::Sneak),
+        ["take", index] => index.parse().ok().map(Action::Take),
+        ["take_all"] => Some(Action::TakeAll),
+        ["cheat", ..] => Cheat::parse(&line["cheat".len()..]).ok().map(Action::Cheat),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Replay {
+        let mut replay = Replay::new(42);
+        for &(x, y) in &[(0, 1), (-1, 0), (-1, 0), (-1, 0), (0, -1)] {
+            replay.record(Action::Move(Vector::new(x, y)));
+        }
+        replay.record(Action::Descend);
+        replay.record(Action::PickUp);
+        replay.record(Action::Wait);
+        replay.record(Action::Buy(1));
+        replay.record(Action::Sell(0));
+        replay.record(Action::Equip(2));
+        replay.record(Action::Throw(0, Vector::new(7, -3)));
+        replay.record(Action::Fire(Vector::new(-2, 5)));
+        replay.record(Action::Use(1));
+        replay.record(Action::Skill(Skill::Dash, Vector::new(0, -1)));
+        replay.record(Action::Skill(Skill::Firebolt, Vector::new(12, 3)));
+        replay.record(Action::Sneak);
+        replay.record(Action::Take(3));
+        replay.record(Action::TakeAll);
+        replay.record(Action::Ascend);
+        replay
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let replay = sample();
+        assert_eq!(Replay::parse(&replay.to_text()), Ok(replay));
+    }
+
+    #[test]
+    fn wizard_mode_round_trip() {
+        let mut replay = sample();
+        replay.mode = GameMode::Wizard;
+        replay.record(Action::Cheat(Cheat::Reveal));
+        replay.record(Action::Cheat(Cheat::Spawn(
+            "goblin boss".to_string(),
+            Vector::new(3, 4),
+        )));
+        assert_eq!(Replay::parse(&replay.to_text()), Ok(replay));
+    }
+
+    #[test]
+    fn daily_challenges_round_trip() {
+        let mut replay = sample();
+        replay.mode = GameMode::Daily;
+        let text = replay.to_text();
+        assert!(text.starts_with("seed 42\ndaily\n"));
+        assert_eq!(Replay::parse(&text), Ok(replay));
+    }
+
+    #[test]
+    fn the_config_is_recorded() {
+        let mut replay = sample();
+        replay.config = Config {
+            map_size: Vector::new(80, 50),
+            difficulty: config::Difficulty::Hard,
+        };
+        let text = replay.to_text();
+        assert!(text.starts_with("seed 42\nclass fighter\nsize 80x50\ndifficulty hard\n"));
+        assert_eq!(Replay::parse(&text), Ok(replay));
+        assert!(!sample().to_text().contains("size"));
+        assert!(Replay::parse("seed 1\nsize 5x5\n").is_err());
+    }
+
+    #[test]
+    fn the_class_is_recorded() {
+        let mut replay = sample();
+        replay.profile = PlayerProfile::new(Class::Mage);
+        let text = replay.to_text();
+        assert!(text.starts_with("seed 42\nclass mage\n"));
+        assert_eq!(Replay::parse(&text), Ok(replay.clone()));
+        replay.profile.name = "Ada".to_string();
+        let text = replay.to_text();
+        assert!(text.starts_with("seed 42\nclass mage\nname Ada\n"));
+        assert_eq!(Replay::parse(&text), Ok(replay));
+        // Older replays don't have the line
+        let old = Replay::parse("seed 1\nwait\n").unwrap();
+        assert_eq!(old.profile.class, Class::Fighter);
+        assert!(Replay::parse("seed 1\nclass bard\n").is_err());
+    }
+
+    #[test]
+    fn the_packs_are_recorded_in_order() {
+        let mut replay = sample();
+        replay.packs = vec!["slimes".to_string(), "big slimes".to_string()];
+        let text = replay.to_text();
+        assert!(text.contains("\npack slimes\npack big slimes\nmove "));
+        assert_eq!(Replay::parse(&text), Ok(replay));
+        assert!(Replay::parse("seed 1\nwait\n").unwrap().packs.is_empty());
+    }
+
+    #[test]
+    fn hashes_follow_their_actions() {
+        let mut replay = sample();
+        replay.actions.truncate(2);
+        replay.hashes = vec![0xabc, u64::MAX];
+        let text = replay.to_text();
+        assert!(
+            text.ends_with("move 0 1\nhash 0000000000000abc\nmove -1 0\nhash ffffffffffffffff\n")
+        );
+        assert_eq!(Replay::parse(&text), Ok(replay));
+        assert!(Replay::parse("seed 1\nhash 12\n").is_err());
+        assert!(Replay::parse("seed 1\nwait\nhash 1\nhash 2\n").is_err());
+        assert!(Replay::parse("seed 1\nwait\nhash xyz\n").is_err());
+
+        // Going on with a replay that has no hashes doesn't start them
+        let mut old = sample();
+        old.record_hashed(Action::Wait, 1);
+        assert!(old.hashes.is_empty());
+        let mut new = Replay::new(1);
+        new.record_hashed(Action::Wait, 1);
+        assert_eq!(new.hashes, [1]);
+    }
+
+    #[test]
+    fn invalid_replays_are_rejected() {
+        assert!(Replay::parse("").is_err());
+        assert!(Replay::parse("move 1 0\n").is_err());
+        assert!(Replay::parse("seed 1\nmove 1\n").is_err());
+        assert!(Replay::parse("seed 1\njump\n").is_err());
+    }
+
+    #[test]
+    fn replaying_reproduces_the_game() {
+        let replay = sample();
+        let mut world = World::new(replay.seed);
+        for action in &replay.actions {
+            world.player_turn(action.clone());
+        }
+
+        let replayed = replay.play();
+        assert_eq!(replayed.entities, world.entities);
+        assert_eq!(replayed.map, world.map);
+    }
+}
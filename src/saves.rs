@@ -0,0 +1,201 @@
+//! Save slots, so there can be more than one game on the go.
+//!
+//! Every slot is a file in `SAVES_DIR` with a few lines about the game,
+//! for the menus to list it without playing it back, and then its
+//! replay:
+//!
+//! ```text
+//! depth 3
+//! turns 120
+//! saved 1791936000
+//! seed 1234
+//! class rogue
+//! name Ada
+//! move 1 0
+//! ```
+//!
+//! The lines before the seed are all optional, so a plain replay reads
+//! as a save that nothing is known about and a save can be played back
+//! with `--replay`.
+
+use std::path::PathBuf;
+
+use crate::{daily, locale, replay::Replay, world::World};
+
+/// Where the slots' files go.
+pub const SAVES_DIR: &str = "saves";
+
+/// How many games can be saved at once.
+pub const SLOT_COUNT: usize = 5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Save {
+    /// The level the player was on.
+    pub depth: u32,
+    pub turns: u32,
+    /// When the game was saved, in seconds since the Unix epoch.
+    pub saved: u64,
+    /// The game itself. Its profile has the character's name.
+    pub replay: Replay,
+}
+
+impl Save {
+    /// The game in `world`, as recorded in `replay`, saved `saved`
+    /// seconds after the Unix epoch.
+    pub fn new(world: &World, replay: Replay, saved: u64) -> Self {
+        Save {
+            depth: world.depth,
+            turns: world.stats.turns,
+            saved,
+            replay,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "depth {}\nturns {}\nsaved {}\n{}",
+            self.depth,
+            self.turns,
+            self.saved,
+            self.replay.to_text()
+        )
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut save = Save {
+            depth: 0,
+            turns: 0,
+            saved: 0,
+            replay: Replay::new(0),
+        };
+        let mut rest = text;
+        while let Some((line, after)) = rest.split_once('\n') {
+            let line = line.trim();
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let invalid = || format!("Invalid save: `{}`", line);
+            match key {
+                "depth" => save.depth = value.trim().parse().map_err(|_| invalid())?,
+                "turns" => save.turns = value.trim().parse().map_err(|_| invalid())?,
+                "saved" => save.saved = value.trim().parse().map_err(|_| invalid())?,
+                "" => {}
+                _ => break,
+            }
+            rest = after;
+        }
+        save.replay = Replay::parse(rest)?;
+        Ok(save)
+    }
+
+    /// The line about the game in the slot menus.
+    pub fn summary(&self) -> String {
+        locale::format(
+            "save.summary",
+            &[
+                ("character", &self.replay.profile.character()),
+                ("class", &self.replay.profile.class.name()),
+                ("depth", &self.depth),
+                ("turns", &self.turns),
+                ("saved", &timestamp(self.saved)),
+            ],
+        )
+    }
+}
+
+/// The file of the slot with index `slot`.
+pub fn path(slot: usize) -> PathBuf {
+    PathBuf::from(SAVES_DIR).join(format!("slot-{}.txt", slot + 1))
+}
+
+/// What's in every slot, `None` for the empty ones and those that can't
+/// be read.
+pub fn load_all() -> Vec<Option<Save>> {
+    (0..SLOT_COUNT)
+        .map(|slot| {
+            let text = std::fs::read_to_string(path(slot)).ok()?;
+            Save::parse(&text).ok()
+        })
+        .collect()
+}
+
+/// Put `save` in `slot`, replacing what was there.
+pub fn write(slot: usize, save: &Save) -> std::io::Result<PathBuf> {
+    let path = path(slot);
+    std::fs::create_dir_all(SAVES_DIR)?;
+    std::fs::write(&path, save.to_text())?;
+    Ok(path)
+}
+
+/// Empty `slot`.
+pub fn delete(slot: usize) -> std::io::Result<()> {
+    match std::fs::remove_file(path(slot)) {
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// The slot saved in last, if any is.
+pub fn latest(saves: &[Option<Save>]) -> Option<usize> {
+    (0..saves.len())
+        .filter(|&slot| saves[slot].is_some())
+        .max_by_key(|&slot| saves[slot].as_ref().map(|save| save.saved))
+}
+
+/// `seconds` after the Unix epoch as `YYYY-MM-DD HH:MM`, in UTC.
+pub fn timestamp(seconds: u64) -> String {
+    let minutes = seconds % daily::SECONDS_PER_DAY / 60;
+    format!(
+        "{} {:02}:{:02}",
+        daily::date(seconds / daily::SECONDS_PER_DAY),
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        profile::{Class, PlayerProfile},
+        world::Action,
+    };
+
+    #[test]
+    fn saves_are_replays_with_a_summary() {
+        let mut replay = Replay::new(7);
+        replay.profile = PlayerProfile {
+            class: Class::Rogue,
+            name: "Ada".to_string(),
+        };
+        replay.record(Action::Wait);
+        let save = Save {
+            depth: 3,
+            turns: 120,
+            saved: 1_791_985_500,
+            replay,
+        };
+        let text = save.to_text();
+        assert!(text.starts_with("depth 3\nturns 120\nsaved 1791985500\nseed 7\n"));
+        assert_eq!(Save::parse(&text), Ok(save.clone()));
+        assert_eq!(timestamp(save.saved), "2026-10-14 13:45");
+
+        let plain = Save::parse(&save.replay.to_text()).unwrap();
+        assert_eq!((plain.depth, plain.saved), (0, 0));
+        assert_eq!(plain.replay, save.replay);
+        assert!(Save::parse("depth deep\nseed 7\n").is_err());
+        assert!(Save::parse("depth 3\n").is_err());
+    }
+
+    #[test]
+    fn the_latest_save_is_continued() {
+        let save = |saved| {
+            Some(Save {
+                depth: 1,
+                turns: 1,
+                saved,
+                replay: Replay::new(0),
+            })
+        };
+        assert_eq!(latest(&[save(5), None, save(9), save(2)]), Some(2));
+        assert_eq!(latest(&[None, None]), None);
+    }
+}
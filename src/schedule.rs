@@ -0,0 +1,155 @@
+//! Who gets to act when.
+//!
+//! Time passes in ticks. Every action takes `TURN` ticks at `Speed::NORMAL`
+//! and proportionally fewer or more for faster or slower entities, and
+//! the player's action decides how many ticks pass. `run` lets the
+//! monsters act during that time, soonest first, so a monster twice as
+//! fast as the player acts twice per player turn and one half as fast
+//! every other turn. Each entity's `wait` carries over what's left of
+//! its next action to the next player turn. What the player's actions
+//! cost is known before they're taken, see `cost`.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{
+    ai::{self, Phase},
+    noise,
+    world::{Action, World},
+};
+
+/// How many ticks an action takes at normal speed.
+pub const TURN: u32 = 100;
+
+/// How quickly an entity acts, with `Speed::NORMAL` acting once a turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Speed(pub u32);
+
+impl Speed {
+    pub const NORMAL: Speed = Speed(100);
+
+    /// How many ticks an action takes at this speed.
+    pub fn delay(self) -> u32 {
+        TURN * Self::NORMAL.0 / self.0.max(1)
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::NORMAL
+    }
+}
+
+/// How fast `id` is right now. Enraged bosses are twice as fast.
+pub fn speed(world: &World, id: usize) -> Speed {
+    let entity = &world.entities[id];
+    match &entity.ai {
+        Some(ai) if ai.phase == Phase::Enraged => Speed(entity.speed.0 * 2),
+        _ => entity.speed,
+    }
+}
+
+/// How many ticks the player's `action` takes, 0 for the ones that
+/// don't take any time. Wading through water takes longer, giving the
+/// monsters more time.
+pub fn cost(world: &World, action: &Action) -> u32 {
+    let player = world.player();
+    let turns = match action {
+        Action::Buy(_)
+        | Action::Sell(_)
+        | Action::Sneak
+        | Action::Take(_)
+        | Action::TakeAll
+        | Action::Cheat(_) => 0,
+        Action::Move(offset) => {
+            let destination = player.pos + *offset;
+            match world.blocker_at(destination) {
+                Some(_) => 1,
+                None => world
+                    .map
+                    .movement_cost(destination, player.swims)
+                    .unwrap_or(1),
+            }
+        }
+        _ => 1,
+    };
+    turns * player.speed.delay()
+}
+
+fn is_actor(world: &World, id: usize) -> bool {
+    id != world.player_id && world.entities[id].is_alive() && world.entities[id].ai.is_some()
+}
+
+/// Let the monsters act for `duration` ticks, in the order their actions
+/// come up. Ties go to whoever was spawned first. First they hear
+/// everything that happened since their last turn.
+pub fn run(world: &mut World, duration: u32) {
+    noise::listen(world);
+    let mut queue = BinaryHeap::new();
+    for id in 0..world.entities.len() {
+        if is_actor(world, id) {
+            queue.push(Reverse((world.entities[id].wait, id)));
+        }
+    }
+    while let Some(Reverse((time, id))) = queue.pop() {
+        if time >= duration {
+            break;
+        }
+        // Killed by somebody who acted earlier
+        if !is_actor(world, id) {
+            continue;
+        }
+        ai::act(world, id);
+        let next = time + speed(world, id).delay();
+        world.entities[id].wait = next;
+        queue.push(Reverse((next, id)));
+    }
+    for entity in &mut world.entities {
+        entity.wait = entity.wait.saturating_sub(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        tests::{goblin, player_with},
+        Action, Entity,
+    };
+    use quicksilver::geom::Vector;
+
+    /// A world with three confused monsters of different speeds, too far
+    /// from the player and each other to bump into anybody.
+    fn race() -> World {
+        let monster = |y: i32, speed: u32| {
+            let mut monster = Entity {
+                pos: Vector::new(14, y),
+                speed: Speed(speed),
+                ..goblin(0, 1)
+            };
+            monster.ai.as_mut().unwrap().confused = 100;
+            monster
+        };
+        player_with(vec![monster(1, 50), monster(7, 100), monster(13, 200)], 5)
+    }
+
+    #[test]
+    fn faster_monsters_act_more_often() {
+        let mut world = race();
+        for _ in 0..4 {
+            world.player_turn(Action::Wait);
+        }
+        // Every action burns a turn of confusion
+        let actions: Vec<u32> = (0..3)
+            .map(|id| 100 - world.entities[id].ai.as_ref().unwrap().confused)
+            .collect();
+        assert_eq!(actions, vec![2, 4, 8]);
+    }
+
+    #[test]
+    fn delays_scale_with_speed() {
+        assert_eq!(Speed::NORMAL.delay(), TURN);
+        assert_eq!(Speed(200).delay(), TURN / 2);
+        assert_eq!(Speed(50).delay(), TURN * 2);
+        assert_eq!(Speed(0).delay(), TURN * 100);
+    }
+}
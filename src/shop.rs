@@ -0,0 +1,182 @@
+//! Buying and selling items for gold.
+//!
+//! Bumping into a shopkeeper opens their shop (`World::shop`). The
+//! shopkeeper's inventory is the stock for sale. Trading doesn't take
+//! any time.
+
+use crate::{
+    locale,
+    templates::AMULET,
+    world::{is_stuck, with_article, Entity, World},
+};
+
+/// What the item costs in a shop.
+pub fn price(world: &World, item: &Entity) -> u32 {
+    item.item
+        .as_ref()
+        .and_then(|item| world.templates.items.get(&item.template))
+        .map_or(0, |template| template.price)
+}
+
+/// What a shopkeeper pays for the item: half its price.
+pub fn sell_price(world: &World, item: &Entity) -> u32 {
+    price(world, item) / 2
+}
+
+/// Buy the item at `index` of the open shop's stock.
+pub fn buy(world: &mut World, index: usize) {
+    let shopkeeper = match world.shop {
+        Some(shopkeeper) => shopkeeper,
+        None => return,
+    };
+    let item = match world.entities[shopkeeper].inventory.get(index) {
+        Some(item) => item,
+        None => return,
+    };
+    let price = price(world, item);
+    let name = world.item_name(item);
+    if world.player().gold < price {
+        world.log(locale::message(
+            "log.cannot-afford",
+            &[("item", &with_article(&name))],
+        ));
+    } else if !world.has_room(world.player_id, item) {
+        world.log(locale::text("log.inventory-full"));
+    } else {
+        // Stacks are sold one at a time
+        let item = world.take_one(shopkeeper, index);
+        let player_id = world.player_id;
+        world.entities[player_id].gold -= price;
+        world.give(player_id, item);
+        world.log(locale::message(
+            "log.buy",
+            &[("item", &with_article(&name)), ("price", &price)],
+        ));
+    }
+}
+
+/// Sell the item at `index` of the player's inventory to the open shop.
+pub fn sell(world: &mut World, index: usize) {
+    let shopkeeper = match world.shop {
+        Some(shopkeeper) => shopkeeper,
+        None => return,
+    };
+    let item = match world.player().inventory.get(index) {
+        Some(item) => item,
+        None => return,
+    };
+    let is_amulet = item
+        .item
+        .as_ref()
+        .is_some_and(|item| item.template == AMULET);
+    let price = sell_price(world, item);
+    let name = world.item_name(item);
+    if is_stuck(item) {
+        return world.log(locale::message("log.cannot-take-off", &[("item", &name)]));
+    }
+    if is_amulet {
+        world.log(locale::message("log.priceless", &[("item", &name)]));
+        return;
+    }
+    let player_id = world.player_id;
+    let item = world.take_one(player_id, index);
+    world.entities[player_id].gold += price;
+    world.give(shopkeeper, item);
+    world.log(locale::message(
+        "log.sell",
+        &[("item", &with_article(&name)), ("price", &price)],
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::generate_map,
+        world::{Action, Item},
+    };
+    use quicksilver::geom::Vector;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn dart() -> Entity {
+        Entity {
+            name: "dart".to_string(),
+            item: Some(Item {
+                template: "dart".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// The player at (5, 5) next to a shopkeeper selling two darts.
+    fn world_with_shop(gold: u32) -> World {
+        let shopkeeper = Entity {
+            pos: Vector::new(6, 5),
+            name: "shopkeeper".to_string(),
+            hp: 10,
+            max_hp: 10,
+            blocks_tile: true,
+            shopkeeper: true,
+            inventory: vec![dart(), dart()],
+            ..Default::default()
+        };
+        let player = Entity {
+            pos: Vector::new(5, 5),
+            name: "player".to_string(),
+            hp: 5,
+            max_hp: 5,
+            blocks_tile: true,
+            gold,
+            ..Default::default()
+        };
+        World::from_parts(
+            0,
+            StdRng::seed_from_u64(0),
+            generate_map(Vector::new(20, 15)),
+            vec![shopkeeper],
+            player,
+        )
+    }
+
+    #[test]
+    fn bumping_into_the_shopkeeper_opens_the_shop() {
+        let mut world = world_with_shop(0);
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        assert_eq!(world.shop, Some(0));
+        assert_eq!(world.entities[0].hp, 10);
+
+        // Walking away closes it
+        world.player_turn(Action::Move(Vector::new(-1, 0)));
+        assert_eq!(world.shop, None);
+    }
+
+    #[test]
+    fn buying_and_selling() {
+        let mut world = world_with_shop(5);
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        let dart_price = price(&world, &dart());
+        assert!(dart_price > 0 && dart_price <= 5);
+
+        world.player_turn(Action::Buy(0));
+        assert_eq!(world.player().gold, 5 - dart_price);
+        assert_eq!(world.player().inventory.len(), 1);
+        assert_eq!(world.entities[0].inventory.len(), 1);
+
+        world.player_turn(Action::Buy(0));
+        assert_eq!(world.log.last().unwrap(), "You can't afford a dart.");
+        assert_eq!(world.player().inventory.len(), 1);
+
+        world.player_turn(Action::Sell(0));
+        assert_eq!(world.player().gold, 5 - dart_price + dart_price / 2);
+        assert!(world.player().inventory.is_empty());
+        // Back on the stack of darts for sale
+        assert_eq!(world.entities[0].inventory.len(), 1);
+        assert_eq!(
+            world.entities[0].inventory[0].item.as_ref().unwrap().count,
+            2
+        );
+        // Trading doesn't take any time
+        assert_eq!(world.stats.turns, 1);
+    }
+}
@@ -0,0 +1,307 @@
+//! Skills: what the player can do besides walking, hitting and using
+//! items. They sit on the hotbar under the number keys and each one has
+//! to cool down for a few turns after it's been used.
+
+use quicksilver::geom::Vector;
+
+use crate::{
+    hazards::{self, HazardKind},
+    locale,
+    templates::Slot,
+    throwing::{dodges, flight_path, hit_with},
+    world::{knock_back, strike, World},
+};
+
+use std::collections::BTreeMap;
+
+/// How many slots the hotbar has, one for each of the keys 1 to 5.
+pub const HOTBAR_SLOTS: usize = 5;
+
+/// The skill in each hotbar slot. The empty ones are waiting for skills
+/// nobody has come up with yet.
+pub const HOTBAR: [Option<Skill>; HOTBAR_SLOTS] = [
+    Some(Skill::ShieldBash),
+    Some(Skill::Dash),
+    Some(Skill::Firebolt),
+    None,
+    None,
+];
+
+/// How many tiles a dash covers at most.
+pub const DASH_DISTANCE: usize = 3;
+
+/// How far (in tiles) a firebolt flies.
+pub const FIREBOLT_RANGE: usize = 6;
+
+pub const FIREBOLT_DAMAGE: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Skill {
+    /// Hit the monster next to the player and always knock it back.
+    /// Needs a shield.
+    ShieldBash,
+    /// Run up to `DASH_DISTANCE` tiles in one turn.
+    Dash,
+    /// Hurl fire at a monster up to `FIREBOLT_RANGE` tiles away.
+    Firebolt,
+}
+
+/// What the player picks when using a skill.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aim {
+    Direction,
+    /// A tile, with the same cursor as throwing.
+    Target,
+}
+
+impl Skill {
+    /// What the skill is called in replays and the locale keys.
+    pub fn id(self) -> &'static str {
+        match self {
+            Skill::ShieldBash => "shield-bash",
+            Skill::Dash => "dash",
+            Skill::Firebolt => "firebolt",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Skill> {
+        HOTBAR
+            .iter()
+            .flatten()
+            .copied()
+            .find(|skill| skill.id() == id)
+    }
+
+    pub fn name(self) -> String {
+        locale::text(&format!("skill.{}", self.id()))
+    }
+
+    /// The icon on the hotbar.
+    pub fn glyph(self) -> char {
+        match self {
+            Skill::ShieldBash => '[',
+            Skill::Dash => '>',
+            Skill::Firebolt => '*',
+        }
+    }
+
+    /// How many turns it takes before the skill can be used again.
+    pub fn cooldown(self) -> u32 {
+        match self {
+            Skill::ShieldBash => 5,
+            Skill::Dash => 8,
+            Skill::Firebolt => 10,
+        }
+    }
+
+    pub fn aim(self) -> Aim {
+        match self {
+            Skill::ShieldBash | Skill::Dash => Aim::Direction,
+            Skill::Firebolt => Aim::Target,
+        }
+    }
+}
+
+/// How many turns are left until each skill is ready again. Skills that
+/// aren't in here are ready.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cooldowns {
+    turns: BTreeMap<Skill, u32>,
+}
+
+impl Cooldowns {
+    pub fn remaining(&self, skill: Skill) -> u32 {
+        self.turns.get(&skill).copied().unwrap_or(0)
+    }
+
+    pub fn is_ready(&self, skill: Skill) -> bool {
+        self.remaining(skill) == 0
+    }
+
+    /// The skill has just been used.
+    pub fn start(&mut self, skill: Skill) {
+        self.turns.insert(skill, skill.cooldown());
+    }
+
+    /// A turn has passed.
+    pub fn tick(&mut self) {
+        for turns in self.turns.values_mut() {
+            *turns = turns.saturating_sub(1);
+        }
+        self.turns.retain(|_, turns| *turns > 0);
+    }
+}
+
+/// The player uses `skill` towards `aim`: a direction or a tile,
+/// depending on `Skill::aim`. The cooldown only starts if the skill
+/// actually did something.
+pub fn use_skill(world: &mut World, skill: Skill, aim: Vector) {
+    let player_id = world.player_id;
+    let remaining = world.player().cooldowns.remaining(skill);
+    if remaining > 0 {
+        return world.log(locale::message(
+            "log.skill-not-ready",
+            &[("skill", &skill.name()), ("turns", &remaining)],
+        ));
+    }
+    let used = match skill {
+        Skill::ShieldBash => shield_bash(world, aim),
+        Skill::Dash => dash(world, aim),
+        Skill::Firebolt => firebolt(world, aim),
+    };
+    if used {
+        world.entities[player_id].cooldowns.start(skill);
+    }
+}
+
+fn shield_bash(world: &mut World, direction: Vector) -> bool {
+    let player_id = world.player_id;
+    if world.equipped(player_id, Slot::Shield).is_none() {
+        world.log(locale::text("log.bash-no-shield"));
+        return false;
+    }
+    let pos = world.player().pos + direction;
+    let target = world
+        .blocker_at(pos)
+        .filter(|&id| world.entities[id].is_alive() && world.entities[id].ai.is_some());
+    let target = match target {
+        Some(target) => target,
+        None => {
+            world.log(locale::text("log.bash-nothing"));
+            return false;
+        }
+    };
+    let name = world.entities[target].name.clone();
+    world.log(locale::message("log.bash", &[("name", &name)]));
+    if !strike(world, player_id, target) {
+        knock_back(world, player_id, target, direction);
+    }
+    true
+}
+
+fn dash(world: &mut World, direction: Vector) -> bool {
+    let player_id = world.player_id;
+    let mut steps = 0;
+    for _ in 0..DASH_DISTANCE {
+        let next = world.player().pos + direction;
+        if !world.is_passable(player_id, next) || world.blocker_at(next).is_some() {
+            break;
+        }
+        world.move_entity(player_id, next);
+        steps += 1;
+    }
+    if steps == 0 {
+        world.log(locale::text("log.dash-blocked"));
+        return false;
+    }
+    world.log(locale::text("log.dash"));
+    true
+}
+
+fn firebolt(world: &mut World, target: Vector) -> bool {
+    let start = world.player().pos;
+    let path = flight_path(world, start, target, FIREBOLT_RANGE);
+    let landing = match path.last() {
+        Some(&landing) => landing,
+        None => {
+            world.log(locale::text("log.no-room-to-throw"));
+            return false;
+        }
+    };
+    let name = Skill::Firebolt.name();
+    let hit = world
+        .blocker_at(landing)
+        .filter(|&id| world.entities[id].is_alive() && world.entities[id].ai.is_some())
+        .filter(|&id| !dodges(world, start, id));
+    match hit {
+        Some(id) => hit_with(world, &name, id, FIREBOLT_DAMAGE),
+        None => world.log(locale::text("log.firebolt-fizzles")),
+    }
+    // The ground keeps burning for a bit
+    hazards::spawn(world, HazardKind::Fire, landing, 0);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        tests::{goblin, player_with},
+        Action, Entity, Item,
+    };
+
+    /// The player at (5, 5) with a goblin two tiles east.
+    fn world() -> World {
+        player_with(vec![goblin(7, 4)], 10)
+    }
+
+    #[test]
+    fn cooldowns_count_down() {
+        let mut cooldowns = Cooldowns::default();
+        cooldowns.start(Skill::Dash);
+        assert_eq!(cooldowns.remaining(Skill::Dash), 8);
+        assert!(cooldowns.is_ready(Skill::Firebolt));
+        for _ in 0..8 {
+            assert!(!cooldowns.is_ready(Skill::Dash));
+            cooldowns.tick();
+        }
+        assert!(cooldowns.is_ready(Skill::Dash));
+        for skill in HOTBAR.iter().flatten() {
+            assert_eq!(Skill::from_id(skill.id()), Some(*skill));
+        }
+    }
+
+    #[test]
+    fn firebolts_hit_from_afar_then_cool_down() {
+        let mut world = world();
+        world.player_turn(Action::Skill(Skill::Firebolt, Vector::new(9, 5)));
+        assert_eq!(world.entities[0].hp, 2);
+        let remaining = world.player().cooldowns.remaining(Skill::Firebolt);
+        assert_eq!(remaining, Skill::Firebolt.cooldown());
+
+        world.player_turn(Action::Skill(Skill::Firebolt, Vector::new(9, 5)));
+        assert!(world
+            .log
+            .iter()
+            .any(|message| message == "You can't use firebolt for 9 more turns."));
+    }
+
+    #[test]
+    fn dashing_stops_in_front_of_monsters() {
+        let mut world = world();
+        world.player_turn(Action::Skill(Skill::Dash, Vector::new(1, 0)));
+        assert_eq!(world.player().pos, Vector::new(6, 5));
+        // Nowhere to go: no cooldown
+        let mut world = self::world();
+        world.entities[0].pos = Vector::new(6, 5);
+        world.rebuild_spatial();
+        world.player_turn(Action::Skill(Skill::Dash, Vector::new(1, 0)));
+        assert!(world.player().cooldowns.is_ready(Skill::Dash));
+    }
+
+    #[test]
+    fn shield_bashes_need_a_shield_and_knock_back() {
+        let mut world = world();
+        world.entities[0].pos = Vector::new(6, 5);
+        world.rebuild_spatial();
+        world.player_turn(Action::Skill(Skill::ShieldBash, Vector::new(1, 0)));
+        assert_eq!(world.entities[0].hp, 4);
+
+        let player_id = world.player_id;
+        world.entities[player_id].inventory.push(Entity {
+            name: "shield".to_string(),
+            item: Some(Item {
+                template: "shield".to_string(),
+                equipped: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        world.player_turn(Action::Skill(Skill::ShieldBash, Vector::new(1, 0)));
+        assert_eq!(world.entities[0].hp, 3);
+        assert!(world
+            .log
+            .iter()
+            .any(|message| message == "The goblin is knocked back."));
+    }
+}
@@ -0,0 +1,79 @@
+//! Fast lookup of the entities on a given tile.
+
+use quicksilver::geom::Vector;
+
+use std::collections::HashMap;
+
+use crate::{geometry::tile_key, world::Entity};
+
+/// Which entities are standing on which tile.
+///
+/// Going through all the entities to find the ones at a position gets
+/// slow once there's a lot of them and every monster keeps asking. This
+/// answers the same question in constant time. The values are indices
+/// into `World::entities` so the map must be rebuilt whenever entities
+/// are added or removed; movement can be updated in place.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpatialMap {
+    tiles: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialMap {
+    pub fn new(entities: &[Entity]) -> Self {
+        let mut spatial = SpatialMap::default();
+        spatial.rebuild(entities);
+        spatial
+    }
+
+    pub fn rebuild(&mut self, entities: &[Entity]) {
+        self.tiles.clear();
+        for (id, entity) in entities.iter().enumerate() {
+            self.tiles.entry(tile_key(entity.pos)).or_default().push(id);
+        }
+    }
+
+    /// The entities at `pos` in the order they were added.
+    pub fn entities_at(&self, pos: Vector) -> &[usize] {
+        self.tiles.get(&tile_key(pos)).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_empty_at(&self, pos: Vector) -> bool {
+        self.entities_at(pos).is_empty()
+    }
+
+    /// Record that entity `id` moved from `from` to `to`.
+    pub fn move_entity(&mut self, id: usize, from: Vector, to: Vector) {
+        let key = tile_key(from);
+        if let Some(ids) = self.tiles.get_mut(&key) {
+            ids.retain(|&other| other != id);
+            if ids.is_empty() {
+                self.tiles.remove(&key);
+            }
+        }
+        self.tiles.entry(tile_key(to)).or_default().push(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: i32, y: i32) -> Entity {
+        Entity {
+            pos: Vector::new(x, y),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn entities_are_found_by_position() {
+        let mut spatial = SpatialMap::new(&[at(1, 1), at(2, 3), at(1, 1)]);
+        assert_eq!(spatial.entities_at(Vector::new(1, 1)), &[0, 2]);
+        assert_eq!(spatial.entities_at(Vector::new(2, 3)), &[1]);
+        assert!(spatial.is_empty_at(Vector::new(3, 2)));
+
+        spatial.move_entity(0, Vector::new(1, 1), Vector::new(2, 3));
+        assert_eq!(spatial.entities_at(Vector::new(1, 1)), &[2]);
+        assert_eq!(spatial.entities_at(Vector::new(2, 3)), &[1, 0]);
+    }
+}
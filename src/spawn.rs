@@ -0,0 +1,584 @@
+//! Filling the levels with monsters and items.
+
+use quicksilver::{geom::Vector, graphics::Color};
+use rand::{rngs::StdRng, Rng};
+
+use crate::{
+    chest::Chest,
+    config::Config,
+    lighting::{BRAZIER_RADIUS, TORCH_RADIUS},
+    locale,
+    map::{Map, FLOOR},
+    profile::PlayerProfile,
+    regeneration::PLAYER_REGENERATION_TURNS,
+    schedule::Speed,
+    spatial::SpatialMap,
+    templates::{
+        parse_color, pick_weighted, Branch, Faction, ItemTemplate, MonsterTemplate, Templates, KEY,
+        MAIN_DUNGEON,
+    },
+    world::{stack, Ai, Entity, Item},
+};
+
+/// How many corpses of monsters killed before the player got there to
+/// leave around each level.
+const CORPSES_PER_LEVEL: usize = 2;
+
+/// How many braziers (min and max) to place on each level.
+const BRAZIERS: (u32, u32) = (0, 2);
+
+/// How many piles of gold (min and max) to place on each level.
+const GOLD_PILES: (u32, u32) = (1, 3);
+
+/// How far (in tiles) from the first member the rest of a pack spawns.
+const PACK_RADIUS: i32 = 2;
+
+/// The chance of a level having a shop.
+const SHOP_CHANCE: f64 = 0.3;
+
+/// How many items a shopkeeper sells.
+const SHOP_STOCK: usize = 4;
+
+/// How many chests (min and max) to place on each level.
+const CHESTS: (u32, u32) = (0, 2);
+
+/// How many items (min and max) there are in a chest.
+const CHEST_ITEMS: (u32, u32) = (2, 4);
+
+/// The chance of a chest being locked.
+const LOCKED_CHEST_CHANCE: f64 = 0.4;
+
+/// Create the monsters, items and decorations for level `depth` of
+/// `dungeon`, using the spawn tables of its branch scaled to the
+/// `config`.
+pub fn level_entities(
+    map: &Map,
+    templates: &Templates,
+    config: &Config,
+    dungeon: usize,
+    depth: u32,
+    rng: &mut StdRng,
+) -> Vec<Entity> {
+    let branch = templates.branch(dungeon, depth);
+    let mut entities = vec![];
+
+    let monster_count = rng.gen_range(branch.monster_count.0, branch.monster_count.1 + 1);
+    let monster_count = config.monster_count(monster_count);
+    let mut packs = 0;
+    for _ in 0..monster_count {
+        let (name, template) = match pick_weighted(&branch.monsters, rng) {
+            Some(name) => (name, &templates.monsters[name]),
+            None => break,
+        };
+        let pos = match random_free_tile(map, &entities, rng) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        match template.pack {
+            Some((min, max)) => {
+                let size = rng.gen_range(min, max + 1);
+                let leader = monster(name, template, pos);
+                add_pack(leader, packs, size, map, &mut entities, rng);
+                packs += 1;
+            }
+            None => entities.push(monster(name, template, pos)),
+        }
+    }
+
+    if dungeon == MAIN_DUNGEON && depth == templates.max_depth(dungeon) {
+        if let Some((name, template)) = templates
+            .boss
+            .as_ref()
+            .map(|name| (name, &templates.monsters[name]))
+        {
+            if let Some(pos) = random_free_tile(map, &entities, rng) {
+                entities.push(monster(name, template, pos));
+            }
+        }
+    }
+
+    let item_count = rng.gen_range(branch.item_count.0, branch.item_count.1 + 1);
+    let item_count = config.item_count(item_count);
+    for _ in 0..item_count {
+        let name = match pick_weighted(&branch.items, rng) {
+            Some(name) => name,
+            None => break,
+        };
+        if let Some(pos) = random_free_tile(map, &entities, rng) {
+            entities.push(random_item(name, &templates.items[name], pos, rng));
+        }
+    }
+
+    for _ in 0..rng.gen_range(BRAZIERS.0, BRAZIERS.1 + 1) {
+        if let Some(pos) = random_free_tile(map, &entities, rng) {
+            entities.push(brazier(pos));
+        }
+    }
+
+    for _ in 0..rng.gen_range(GOLD_PILES.0, GOLD_PILES.1 + 1) {
+        if let Some(pos) = random_free_tile(map, &entities, rng) {
+            // Deeper levels have more gold lying around
+            let amount = rng.gen_range(1, 5 * depth + 6);
+            entities.push(gold(amount, pos));
+        }
+    }
+
+    if rng.gen_bool(SHOP_CHANCE) {
+        if let Some(pos) = random_free_tile(map, &entities, rng) {
+            let mut stock = vec![];
+            for _ in 0..SHOP_STOCK {
+                if let Some(name) = pick_weighted(&branch.items, rng) {
+                    stock.push(random_item(name, &templates.items[name], pos, rng));
+                }
+            }
+            entities.push(shopkeeper(stock, pos));
+        }
+    }
+
+    for _ in 0..rng.gen_range(CHESTS.0, CHESTS.1 + 1) {
+        let pos = match free_tile(map, &entities, rng, |pos| against_wall(map, pos)) {
+            Some(pos) => pos,
+            None => break,
+        };
+        let contents = chest_contents(templates, branch, pos, rng);
+        let locked = rng.gen_bool(LOCKED_CHEST_CHANCE);
+        entities.push(chest(contents, locked, pos));
+        if locked {
+            if let Some(pos) = random_free_tile(map, &entities, rng) {
+                entities.push(item(KEY, &templates.items[KEY], pos));
+            }
+        }
+    }
+
+    for _ in 0..CORPSES_PER_LEVEL {
+        let (name, template) = match pick_weighted(&branch.monsters, rng) {
+            Some(name) => (name, &templates.monsters[name]),
+            None => break,
+        };
+        if let Some(pos) = random_free_tile(map, &entities, rng) {
+            let mut corpse = monster(name, template, pos);
+            corpse.die();
+            let drops = std::mem::take(&mut corpse.drops);
+            entities.push(corpse);
+            entities.extend(loot(&drops, templates, pos, rng));
+        }
+    }
+
+    entities
+}
+
+/// The player as `profile` describes them, carrying what their class
+/// starts out with. They're put on the map once the level exists.
+pub fn player(profile: &PlayerProfile, templates: &Templates) -> Entity {
+    let class = profile.class;
+    let mut inventory = vec![];
+    for &(id, equipped) in class.starting_items() {
+        if let Some(template) = templates.items.get(id) {
+            let mut entity = item(id, template, Vector::new(0, 0));
+            if let Some(item) = &mut entity.item {
+                item.equipped = equipped;
+            }
+            stack(&mut inventory, entity, templates);
+        }
+    }
+    Entity {
+        glyph: '@',
+        color: Color::BLUE,
+        name: "player".to_string(),
+        hp: class.max_hp(),
+        max_hp: class.max_hp(),
+        blocks_tile: true,
+        faction: Faction::Player,
+        light: Some(TORCH_RADIUS),
+        regeneration: Some(PLAYER_REGENERATION_TURNS),
+        inventory,
+        ..Default::default()
+    }
+}
+
+/// A monster. `id` is the key of its template.
+pub fn monster(id: &str, template: &MonsterTemplate, pos: Vector) -> Entity {
+    Entity {
+        pos,
+        template: Some(id.to_string()),
+        glyph: template.glyph,
+        color: parse_color(&template.color).unwrap_or(Color::BLACK),
+        name: locale::content(&template.name),
+        hp: template.hp,
+        max_hp: template.hp,
+        blocks_tile: true,
+        faction: template.faction,
+        swims: template.swims,
+        invisible: template.invisible,
+        drops: template.drops.clone(),
+        regeneration: template.regeneration,
+        speed: Speed(template.speed),
+        description: Some(locale::content(&template.description)).filter(|text| !text.is_empty()),
+        ai: Some(Ai {
+            behavior: template.behavior,
+            flees: template.flees,
+            asleep: template.sleeps,
+            summons: template.summons.clone(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Add up to `size` copies of `leader` around it, all in pack number
+/// `id`. The pack is smaller when there isn't enough room.
+fn add_pack(
+    leader: Entity,
+    id: usize,
+    size: u32,
+    map: &Map,
+    entities: &mut Vec<Entity>,
+    rng: &mut StdRng,
+) {
+    let pos = leader.pos;
+    let near = |tile: Vector| {
+        let offset = tile - pos;
+        offset.x.abs().max(offset.y.abs()) <= PACK_RADIUS as f32
+    };
+    for index in 0..size {
+        let member_pos = if index == 0 {
+            Some(pos)
+        } else {
+            free_tile(map, entities, rng, near)
+        };
+        if let Some(member_pos) = member_pos {
+            let mut member = Entity {
+                pos: member_pos,
+                ..leader.clone()
+            };
+            if let Some(ai) = &mut member.ai {
+                ai.pack = Some(id);
+            }
+            entities.push(member);
+        }
+    }
+}
+
+/// An item lying on the floor. `id` is the key of its template.
+pub fn item(id: &str, template: &ItemTemplate, pos: Vector) -> Entity {
+    Entity {
+        pos,
+        glyph: template.glyph,
+        color: parse_color(&template.color).unwrap_or(Color::BLACK),
+        name: locale::content(&template.name),
+        description: Some(locale::content(&template.description)).filter(|text| !text.is_empty()),
+        item: Some(Item {
+            template: id.to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Roll on a drop table: each item has its own chance of being left
+/// at `pos`.
+pub fn loot(
+    drops: &[(String, f64)],
+    templates: &Templates,
+    pos: Vector,
+    rng: &mut StdRng,
+) -> Vec<Entity> {
+    let mut items = vec![];
+    for (name, chance) in drops {
+        if let Some(template) = templates.items.get(name) {
+            if rng.gen_bool(chance.clamp(0.0, 1.0)) {
+                items.push(random_item(name, template, pos, rng));
+            }
+        }
+    }
+    items
+}
+
+/// An item that may come out cursed, see `ItemTemplate::curse_chance`.
+pub fn random_item(id: &str, template: &ItemTemplate, pos: Vector, rng: &mut StdRng) -> Entity {
+    let mut entity = item(id, template, pos);
+    if template.curse_chance > 0.0 && rng.gen_bool(template.curse_chance) {
+        if let Some(item) = &mut entity.item {
+            item.cursed = true;
+        }
+    }
+    if let (Some((min, max)), Some(item)) = (template.bundle, &mut entity.item) {
+        item.count = rng.gen_range(min, max + 1);
+    }
+    entity
+}
+
+/// A fire lighting up its surroundings. It's in the way but can't be
+/// attacked.
+pub fn brazier(pos: Vector) -> Entity {
+    Entity {
+        pos,
+        glyph: '&',
+        color: Color::from_rgba(224, 96, 0, 1.0),
+        name: locale::content("brazier"),
+        blocks_tile: true,
+        light: Some(BRAZIER_RADIUS),
+        ..Default::default()
+    }
+}
+
+pub fn gold(amount: u32, pos: Vector) -> Entity {
+    Entity {
+        pos,
+        glyph: '$',
+        color: Color::from_rgba(224, 176, 0, 1.0),
+        name: locale::content("gold"),
+        gold: amount,
+        ..Default::default()
+    }
+}
+
+/// A shopkeeper selling `stock`. They just stand there, waiting for
+/// the player.
+pub fn shopkeeper(stock: Vec<Entity>, pos: Vector) -> Entity {
+    Entity {
+        pos,
+        glyph: '@',
+        color: Color::from_rgba(192, 160, 0, 1.0),
+        name: locale::content("shopkeeper"),
+        hp: 10,
+        max_hp: 10,
+        blocks_tile: true,
+        shopkeeper: true,
+        inventory: stock,
+        ..Default::default()
+    }
+}
+
+/// A few random items from the spawn table of `branch` for a chest at
+/// `pos`.
+pub fn chest_contents(
+    templates: &Templates,
+    branch: &Branch,
+    pos: Vector,
+    rng: &mut StdRng,
+) -> Vec<Entity> {
+    let mut contents = vec![];
+    for _ in 0..rng.gen_range(CHEST_ITEMS.0, CHEST_ITEMS.1 + 1) {
+        if let Some(name) = pick_weighted(&branch.items, rng) {
+            contents.push(random_item(name, &templates.items[name], pos, rng));
+        }
+    }
+    contents
+}
+
+/// A chest holding `contents`.
+pub fn chest(contents: Vec<Entity>, locked: bool, pos: Vector) -> Entity {
+    let name = if locked { "locked chest" } else { "chest" };
+    Entity {
+        pos,
+        glyph: '(',
+        color: Color::from_rgba(160, 96, 32, 1.0),
+        name: locale::content(name),
+        blocks_tile: true,
+        chest: Some(Chest { locked }),
+        inventory: contents,
+        ..Default::default()
+    }
+}
+
+/// Whether `pos` is a good place for something that's in the way: up
+/// against a straight wall with open floor everywhere else around it,
+/// so it can't block a corridor.
+fn against_wall(map: &Map, pos: Vector) -> bool {
+    let open = |x, y| {
+        map.tile(pos + Vector::new(x, y))
+            .is_some_and(|tile| tile.glyph == FLOOR)
+    };
+    [(0, -1), (1, 0), (0, 1), (-1, 0)]
+        .iter()
+        .any(|&(x, y): &(i32, i32)| {
+            // The wall is on the (x, y) side, the rest of the 3x3 is open
+            (-1..=1).all(|side| {
+                let (sx, sy) = (side * y.abs(), side * x.abs());
+                map.is_blocked(pos + Vector::new(x + sx, y + sy))
+                    && open(-x + sx, -y + sy)
+                    && (side == 0 || open(sx, sy))
+            })
+        })
+}
+
+/// Pick a random floor tile that nobody's standing on.
+pub fn random_free_tile(map: &Map, entities: &[Entity], rng: &mut StdRng) -> Option<Vector> {
+    free_tile(map, entities, rng, |_| true)
+}
+
+/// Pick a random free floor tile out of the ones `allowed` lets
+/// through.
+fn free_tile(
+    map: &Map,
+    entities: &[Entity],
+    rng: &mut StdRng,
+    allowed: impl Fn(Vector) -> bool,
+) -> Option<Vector> {
+    let spatial = SpatialMap::new(entities);
+    let free: Vec<Vector> = map
+        .tiles
+        .iter()
+        .filter(|tile| tile.glyph == FLOOR)
+        .map(|tile| tile.pos)
+        .filter(|&pos| spatial.is_empty_at(pos) && allowed(pos))
+        .collect();
+    if free.is_empty() {
+        None
+    } else {
+        Some(free[rng.gen_range(0, free.len())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::generate_map;
+    use rand::SeedableRng;
+
+    #[test]
+    fn spawns_follow_the_branch_tables() {
+        let templates = Templates::builtin();
+        let map = generate_map(Vector::new(20, 15));
+        let mut rng = StdRng::seed_from_u64(3);
+        for &depth in &[1, 5, 8] {
+            let mut allowed: Vec<&str> = templates
+                .branch(MAIN_DUNGEON, depth)
+                .monsters
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect();
+            if depth == templates.max_depth(MAIN_DUNGEON) {
+                allowed.extend(templates.boss.as_deref());
+            }
+            for _ in 0..10 {
+                for entity in level_entities(
+                    &map,
+                    &templates,
+                    &Config::default(),
+                    MAIN_DUNGEON,
+                    depth,
+                    &mut rng,
+                ) {
+                    if entity.is_alive() && !entity.shopkeeper {
+                        assert!(
+                            allowed.contains(&entity.name.as_str()),
+                            "{} on {}",
+                            entity.name,
+                            depth
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_boss_waits_on_the_deepest_level() {
+        let templates = Templates::builtin();
+        let map = generate_map(Vector::new(20, 15));
+        let mut rng = StdRng::seed_from_u64(1);
+        let is_boss = |entity: &Entity| entity.name == "troll king";
+        let deepest = templates.max_depth(MAIN_DUNGEON);
+        let level = level_entities(
+            &map,
+            &templates,
+            &Config::default(),
+            MAIN_DUNGEON,
+            deepest,
+            &mut rng,
+        );
+        assert_eq!(level.iter().filter(|&entity| is_boss(entity)).count(), 1);
+        let above = level_entities(
+            &map,
+            &templates,
+            &Config::default(),
+            MAIN_DUNGEON,
+            deepest - 1,
+            &mut rng,
+        );
+        assert!(!above.iter().any(is_boss));
+        // The other dungeons have no boss
+        let side = templates.max_depth(1);
+        let level = level_entities(&map, &templates, &Config::default(), 1, side, &mut rng);
+        assert!(!level.iter().any(is_boss));
+    }
+
+    #[test]
+    fn packs_spawn_together() {
+        let templates = Templates::builtin();
+        let map = generate_map(Vector::new(20, 15));
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut packs_seen = 0;
+        for _ in 0..10 {
+            let entities = level_entities(
+                &map,
+                &templates,
+                &Config::default(),
+                MAIN_DUNGEON,
+                1,
+                &mut rng,
+            );
+            let mut packs: Vec<Vec<&Entity>> = vec![];
+            for entity in entities.iter().filter(|entity| entity.is_alive()) {
+                if let Some(pack) = entity.ai.as_ref().and_then(|ai| ai.pack) {
+                    if packs.len() <= pack {
+                        packs.resize(pack + 1, vec![]);
+                    }
+                    packs[pack].push(entity);
+                }
+            }
+            for members in &packs {
+                assert!((3..=6).contains(&members.len()));
+                let leader = members[0];
+                for member in members {
+                    assert_eq!(member.name, "rat");
+                    let offset = member.pos - leader.pos;
+                    assert!(offset.x.abs() <= 2.0 && offset.y.abs() <= 2.0);
+                }
+            }
+            packs_seen += packs.len();
+        }
+        assert!(packs_seen > 0);
+    }
+
+    #[test]
+    fn chests_stand_against_walls_and_come_with_keys() {
+        let templates = Templates::builtin();
+        let map = generate_map(Vector::new(20, 15));
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut chests_seen = 0;
+        for _ in 0..20 {
+            let entities = level_entities(
+                &map,
+                &templates,
+                &Config::default(),
+                MAIN_DUNGEON,
+                1,
+                &mut rng,
+            );
+            let keys = entities
+                .iter()
+                .filter(|entity| {
+                    entity
+                        .item
+                        .as_ref()
+                        .is_some_and(|item| item.template == KEY)
+                })
+                .count();
+            let mut locked = 0;
+            for chest in entities.iter().filter(|entity| entity.chest.is_some()) {
+                let pos = chest.pos;
+                assert!(pos.x == 1.0 || pos.y == 1.0 || pos.x == 18.0 || pos.y == 13.0);
+                assert!(!chest.inventory.is_empty());
+                if chest.chest.as_ref().unwrap().locked {
+                    locked += 1;
+                }
+                chests_seen += 1;
+            }
+            assert_eq!(keys, locked);
+        }
+        assert!(chests_seen > 0);
+    }
+}
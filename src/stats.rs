@@ -0,0 +1,97 @@
+//! Statistics about the current run, shown when it ends.
+
+use std::collections::BTreeMap;
+
+use crate::{locale, templates::Templates};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// The number of actions the player took.
+    pub turns: u32,
+    /// The number of those that moved the player to a new tile.
+    pub steps: u32,
+    /// Damage done by the player to others.
+    pub damage_dealt: i32,
+    /// Damage of any kind done to the player.
+    pub damage_taken: i32,
+    /// Items thrown, drunk or otherwise used up.
+    pub items_used: u32,
+    pub kills: u32,
+    /// The number of kills of each kind of monster, by name.
+    pub kills_by_monster: BTreeMap<String, u32>,
+    pub deepest_level: u32,
+}
+
+impl Stats {
+    /// The player killed the monster called `name`.
+    pub fn record_kill(&mut self, name: &str) {
+        self.kills += 1;
+        *self.kills_by_monster.entry(name.to_string()).or_insert(0) += 1;
+    }
+
+    /// The lines shown on the game over and victory screens.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            locale::format("stats.turns", &[("count", &self.turns)]),
+            locale::format("stats.steps", &[("count", &self.steps)]),
+            locale::format("stats.deepest-level", &[("count", &self.deepest_level)]),
+            locale::format("stats.damage-dealt", &[("count", &self.damage_dealt)]),
+            locale::format("stats.damage-taken", &[("count", &self.damage_taken)]),
+            locale::format("stats.items-used", &[("count", &self.items_used)]),
+            locale::format("stats.kills", &[("count", &self.kills)]),
+        ];
+        for (name, count) in &self.kills_by_monster {
+            lines.push(format!("  {}: {}", name, count));
+        }
+        lines
+    }
+
+    /// Every kind of monster the player has killed at least once, with
+    /// its description.
+    pub fn bestiary(&self, templates: &Templates) -> Vec<String> {
+        let mut lines = vec![];
+        for monster in templates.monsters.values() {
+            // The kills are recorded under the names the player saw
+            let name = locale::content(&monster.name);
+            if let Some(count) = self.kills_by_monster.get(&name) {
+                lines.push(locale::format(
+                    "stats.bestiary-entry",
+                    &[("glyph", &monster.glyph), ("name", &name), ("count", count)],
+                ));
+                if !monster.description.is_empty() {
+                    lines.push(format!("  {}", locale::content(&monster.description)));
+                }
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_bestiary_lists_killed_monsters_only() {
+        let mut stats = Stats::default();
+        stats.record_kill("orc");
+        stats.record_kill("goblin");
+        stats.record_kill("goblin");
+        let bestiary = stats.bestiary(&Templates::builtin());
+        assert_eq!(stats.kills, 3);
+        assert_eq!(bestiary.len(), 4);
+        assert_eq!(bestiary[0], "g goblin: 2 killed");
+        assert_eq!(bestiary[2], "o orc: 1 killed");
+    }
+
+    #[test]
+    fn the_summary_breaks_kills_down_by_monster() {
+        let mut stats = Stats::default();
+        stats.record_kill("orc");
+        stats.record_kill("goblin");
+        let summary = stats.summary();
+        let kills = summary.iter().position(|line| line == "Monsters killed: 2");
+        let kills = kills.expect("No kill count.");
+        assert_eq!(summary[kills + 1..], ["  goblin: 1", "  orc: 1"]);
+    }
+}
@@ -0,0 +1,94 @@
+//! The story told between the turns.
+//!
+//! The templates can have an `intro` for the start of a new game,
+//! `stories` for some of the levels and a `story` for the plot items.
+//! Each is told once: the intro when the game starts, a level's story
+//! when the player first gets there and an item's the first time they
+//! pick one up. Telling one only adds it to `World::stories`, it's up to
+//! the UI to show it (a screen of its own until a key is pressed) and to
+//! remember how many it's shown already.
+
+use crate::{locale, world::World};
+
+/// Queue the new game's intro.
+pub fn intro(world: &mut World) {
+    let paragraphs = world.templates.intro.clone();
+    tell(world, &paragraphs);
+}
+
+/// Queue the story of the level the player has just got to for the
+/// first time, if it has one.
+pub fn level(world: &mut World) {
+    let paragraphs = world.templates.dungeons[world.dungeon]
+        .stories
+        .get(&world.depth)
+        .cloned()
+        .unwrap_or_default();
+    tell(world, &paragraphs);
+}
+
+/// Queue the story of the item made from `template` the player has just
+/// picked up, unless it's been told before.
+pub fn picked_up(world: &mut World, template: &str) {
+    let paragraphs = match world.templates.items.get(template) {
+        Some(item) if !item.story.is_empty() => item.story.clone(),
+        _ => return,
+    };
+    if world.told.insert(template.to_string()) {
+        tell(world, &paragraphs);
+    }
+}
+
+fn tell(world: &mut World, paragraphs: &[String]) {
+    if !paragraphs.is_empty() {
+        let story = paragraphs.iter().map(|text| locale::content(text));
+        world.stories.push(story.collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{templates::AMULET, world::Action};
+
+    #[test]
+    fn new_games_start_with_the_intro() {
+        let world = World::new(0);
+        assert_eq!(world.stories.len(), 1);
+        assert_eq!(world.stories[0], world.templates.intro);
+    }
+
+    #[test]
+    fn levels_tell_their_story_on_the_first_visit() {
+        let mut world = World::new(0);
+        world.templates.dungeons[0]
+            .stories
+            .insert(1, vec!["It's damp.".to_string()]);
+        world.descend();
+        assert_eq!(world.stories.last().unwrap(), &["It's damp."]);
+
+        // Coming back doesn't tell it again. Going down leaves the
+        // player on the stairs up.
+        world.descend();
+        let told = world.stories.len();
+        world.player_turn(Action::Ascend);
+        assert_eq!(world.depth, 1);
+        assert_eq!(world.stories.len(), told);
+    }
+
+    #[test]
+    fn items_tell_their_story_once() {
+        let mut world = World::new(0);
+        let told = world.stories.len();
+        picked_up(&mut world, "dart");
+        assert_eq!(world.stories.len(), told);
+
+        picked_up(&mut world, AMULET);
+        picked_up(&mut world, AMULET);
+        assert_eq!(world.stories.len(), told + 1);
+        assert_eq!(
+            world.stories.last().unwrap(),
+            &world.templates.items[AMULET].story
+        );
+    }
+}
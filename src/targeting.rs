@@ -0,0 +1,131 @@
+//! Picking a tile on the map with the keyboard. The cursor starts on the
+//! nearest enemy in view and Tab jumps to the next nearest one, so
+//! there's rarely any need to walk it across the map by hand.
+
+use quicksilver::geom::Vector;
+
+use crate::{
+    hazards, locale, noise,
+    world::{capitalize, with_article, World},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Targeting {
+    pub cursor: Vector,
+    /// The position of the current target in `Fov::targets`, if the
+    /// cursor is on one.
+    target: Option<usize>,
+}
+
+impl Targeting {
+    pub fn new(world: &World) -> Self {
+        let mut targeting = Targeting {
+            cursor: world.player().pos,
+            target: None,
+        };
+        targeting.next_target(world);
+        targeting
+    }
+
+    /// Move the cursor by `offset`, staying on the map.
+    pub fn move_cursor(&mut self, world: &World, offset: Vector) {
+        let cursor = self.cursor + offset;
+        if world.map.tile(cursor).is_some() {
+            self.cursor = cursor;
+            self.target = None;
+        }
+    }
+
+    /// Jump to the next visible enemy, nearest first, wrapping back
+    /// around to the nearest one after the farthest.
+    pub fn next_target(&mut self, world: &World) {
+        let targets = &world.fov.targets;
+        if targets.is_empty() {
+            return;
+        }
+        let next = match self.target {
+            Some(index) => (index + 1) % targets.len(),
+            None => 0,
+        };
+        self.target = Some(next);
+        self.cursor = world.entities[targets[next]].pos;
+    }
+}
+
+/// What the player knows about the tile at `pos`.
+pub fn describe(world: &World, pos: Vector) -> Vec<String> {
+    if !world.fov.is_visible(pos) {
+        if world.fov.is_explored(pos) {
+            return vec![locale::text("look.remembered")];
+        }
+        return vec![locale::text("look.unseen")];
+    }
+    let mut lines = vec![];
+    for &id in world.spatial.entities_at(pos) {
+        let entity = &world.entities[id];
+        if id == world.player_id {
+            lines.push(locale::text("look.you"));
+            continue;
+        }
+        if entity.invisible && !world.sees_entity(id) {
+            continue;
+        }
+        let mut line = capitalize(&with_article(&world.item_name(entity)));
+        if entity.ai.is_some() && entity.is_alive() {
+            line = locale::format(
+                "look.health",
+                &[("name", &line), ("hp", &entity.hp), ("max", &entity.max_hp)],
+            );
+        }
+        if noise::is_asleep(world, id) {
+            line = locale::format("look.asleep", &[("name", &line)]);
+        }
+        lines.push(locale::format("look.entity", &[("name", &line)]));
+        // The description would give unidentified items away
+        let known = entity
+            .item
+            .as_ref()
+            .is_none_or(|item| world.identification.is_known(&item.template));
+        if let (Some(description), true) = (&entity.description, known) {
+            lines.push(description.clone());
+        }
+    }
+    if let Some(hazard) = hazards::at(world, pos) {
+        lines.push(hazard.kind.description());
+    }
+    if lines.is_empty() {
+        lines.push(locale::text("look.nothing"));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        tests::{goblin, player_with},
+        Entity,
+    };
+
+    #[test]
+    fn tab_cycles_through_enemies_nearest_first() {
+        let goblin_at = |x: i32, y: i32| Entity {
+            pos: Vector::new(x, y),
+            ..goblin(x, 1)
+        };
+        let monsters = vec![goblin_at(9, 5), goblin_at(6, 6), goblin_at(5, 8)];
+        let world = player_with(monsters, 5);
+        assert_eq!(world.fov.targets, [1, 2, 0]);
+
+        let mut targeting = Targeting::new(&world);
+        assert_eq!(targeting.cursor, Vector::new(6, 6));
+        let mut visited = vec![];
+        for _ in 0..3 {
+            targeting.next_target(&world);
+            visited.push(targeting.cursor);
+        }
+        let expected = [Vector::new(5, 8), Vector::new(9, 5), Vector::new(6, 6)];
+        assert_eq!(visited, expected);
+        assert_eq!(describe(&world, targeting.cursor), ["A goblin (1/1 hp)."]);
+    }
+}
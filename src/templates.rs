@@ -0,0 +1,619 @@
+//! The data-driven part of the game: what monsters and items exist and
+//! where they show up.
+//!
+//! The templates live in `static/templates.ron` and are built into the
+//! executable so they work the same on the desktop and the web. Content
+//! packs change them with an `Overlay` each, see `packs`.
+
+use quicksilver::graphics::Color;
+use rand::{rngs::StdRng, Rng};
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+
+use crate::schedule::Speed;
+
+const BUILTIN_TEMPLATES: &str = include_str!("../static/templates.ron");
+
+/// The item the player must bring back to the surface to win.
+pub const AMULET: &str = "amulet";
+
+/// The item that opens a locked chest or door.
+pub const KEY: &str = "key";
+
+/// The dungeon with the amulet (and the boss) at the bottom.
+pub const MAIN_DUNGEON: usize = 0;
+
+/// How a monster spends its turns when nothing special is going on.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Behavior {
+    /// Go after the player whenever they're in sight.
+    #[default]
+    Chase,
+    /// Stay put until the player shows up, then chase them.
+    Guard,
+    /// Guard at first, then fight harder and harder as it gets hurt. See
+    /// `ai::Phase`.
+    Boss,
+}
+
+/// Who's on whose side. See `world::is_hostile`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Faction {
+    Player,
+    /// Everything that's out to get the player.
+    #[default]
+    Monsters,
+    /// Critters that leave everybody alone unless the player attacks
+    /// them.
+    Vermin,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: String,
+    pub hp: i32,
+    #[serde(default)]
+    pub behavior: Behavior,
+    /// Run away from the player when badly hurt.
+    #[serde(default)]
+    pub flees: bool,
+    #[serde(default)]
+    pub faction: Faction,
+    /// Can go through deep water.
+    #[serde(default)]
+    pub swims: bool,
+    /// Only visible from right next to it.
+    #[serde(default)]
+    pub invisible: bool,
+    /// Starts out asleep.
+    #[serde(default)]
+    pub sleeps: bool,
+    /// Item templates with the chance (from 0.0 to 1.0) of each being
+    /// dropped when the monster dies.
+    #[serde(default)]
+    pub drops: Vec<(String, f64)>,
+    /// Heals a point of damage every this many turns.
+    #[serde(default)]
+    pub regeneration: Option<u32>,
+    /// Spawns in a group of this many (min and max) instead of alone.
+    #[serde(default)]
+    pub pack: Option<(u32, u32)>,
+    /// How quickly it acts: 100 is as fast as the player, 200 twice as
+    /// fast.
+    #[serde(default = "normal_speed")]
+    pub speed: u32,
+    /// The monster a `Boss` calls for help.
+    #[serde(default)]
+    pub summons: Option<String>,
+    /// Shown in the bestiary.
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: String,
+    /// What it costs in a shop, in gold.
+    #[serde(default)]
+    pub price: u32,
+    /// Where the item goes when it's equipped. Items without a slot
+    /// can't be equipped.
+    #[serde(default)]
+    pub slot: Option<Slot>,
+    /// The damage done when the item is thrown at somebody. Anything
+    /// without an `effect` does at least 1.
+    #[serde(default)]
+    pub throw_damage: i32,
+    /// What happens to whoever the item is used on. Items with an effect
+    /// are used up.
+    #[serde(default)]
+    pub effect: Option<Effect>,
+    /// The chance of the item being cursed when it's generated, from 0.0
+    /// to 1.0. Only equippable items should be cursed.
+    #[serde(default)]
+    pub curse_chance: f64,
+    /// What kind of thing the item looks like (e.g. "potion") until it's
+    /// identified. Items without one are always known. See `identify`.
+    #[serde(default)]
+    pub appearance: Option<String>,
+    /// More of the item take up a single inventory slot, see
+    /// `Item::count`.
+    #[serde(default)]
+    pub stacks: bool,
+    /// How many of an item that stacks are found together, at least and
+    /// at most. One at a time if it's not given.
+    #[serde(default)]
+    pub bundle: Option<(u32, u32)>,
+    /// For ranged weapons: the item they fire, see `ammo`.
+    #[serde(default)]
+    pub ammo: Option<String>,
+    /// The damage a ranged weapon's ammo does when it's fired.
+    #[serde(default)]
+    pub fire_damage: i32,
+    #[serde(default)]
+    pub description: String,
+    /// Paragraphs shown the first time the player picks the item up,
+    /// see `story`.
+    #[serde(default)]
+    pub story: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Effect {
+    /// Restore this much health.
+    Heal(i32),
+    /// Make a monster stumble around for this many turns.
+    Confuse(u32),
+    /// Tell the player what one of their unknown items is.
+    Identify,
+    /// Lift the curses off everything the player carries.
+    RemoveCurse,
+}
+
+/// Only one item can be equipped in each slot.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Slot {
+    Weapon,
+    Shield,
+}
+
+/// A stack of branches with its own entrance on the surface.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Dungeon {
+    pub name: String,
+    /// From the top down.
+    pub branches: Vec<Branch>,
+    /// Paragraphs shown when the player first gets to the level, by
+    /// depth.
+    #[serde(default)]
+    pub stories: BTreeMap<u32, Vec<String>>,
+}
+
+impl Dungeon {
+    /// The branch the given level belongs to. Anything past the last
+    /// level counts as the last branch.
+    pub fn branch(&self, depth: u32) -> &Branch {
+        self.branches
+            .iter()
+            .find(|branch| branch.contains(depth))
+            .unwrap_or_else(|| self.branches.last().expect("There are no branches."))
+    }
+
+    /// The deepest level of the dungeon.
+    pub fn max_depth(&self) -> u32 {
+        self.branches.last().map_or(1, |branch| branch.depths.1)
+    }
+}
+
+/// A part of a dungeon with its own monsters and loot.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Branch {
+    pub name: String,
+    /// The first and the last level (both included) of the branch.
+    pub depths: (u32, u32),
+    /// How many monsters (min and max) to place on each level.
+    pub monster_count: (u32, u32),
+    pub item_count: (u32, u32),
+    /// How bright the levels are without any light sources, from 0.0
+    /// (pitch black) to 1.0.
+    #[serde(default)]
+    pub light: f32,
+    /// Template names with their weights.
+    pub monsters: Vec<(String, u32)>,
+    pub items: Vec<(String, u32)>,
+}
+
+impl Branch {
+    pub fn contains(&self, depth: u32) -> bool {
+        self.depths.0 <= depth && depth <= self.depths.1
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Templates {
+    /// Colour names the templates can use instead of hex codes.
+    #[serde(default)]
+    pub palette: BTreeMap<String, String>,
+    // These are `BTreeMap`s rather than `HashMap`s so the iteration order
+    // is always the same. Otherwise replays wouldn't be deterministic.
+    pub monsters: BTreeMap<String, MonsterTemplate>,
+    pub items: BTreeMap<String, ItemTemplate>,
+    /// The names unidentified items get, for each `appearance`.
+    #[serde(default)]
+    pub appearances: BTreeMap<String, Vec<String>>,
+    /// The monster waiting on the deepest level of the main dungeon.
+    #[serde(default)]
+    pub boss: Option<String>,
+    /// The main dungeon comes first, see `MAIN_DUNGEON`.
+    pub dungeons: Vec<Dungeon>,
+    /// Paragraphs shown when a new game starts.
+    #[serde(default)]
+    pub intro: Vec<String>,
+}
+
+/// What a content pack's `templates.ron` adds to or replaces in the
+/// templates before it. Everything is optional: entries of the maps
+/// replace the ones with the same key and the rest stay, while `boss`,
+/// `dungeons` and `intro` replace the originals when they're there.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Overlay {
+    pub palette: BTreeMap<String, String>,
+    pub monsters: BTreeMap<String, MonsterTemplate>,
+    pub items: BTreeMap<String, ItemTemplate>,
+    pub appearances: BTreeMap<String, Vec<String>>,
+    pub boss: Option<String>,
+    pub dungeons: Option<Vec<Dungeon>>,
+    pub intro: Option<Vec<String>>,
+}
+
+impl Overlay {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        ron::de::from_str(text).map_err(|error| error.to_string())
+    }
+}
+
+impl Templates {
+    /// The templates shipped with the game.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_TEMPLATES).expect("The built-in templates are invalid.")
+    }
+
+    /// The templates shipped with the game changed by `overlays`, in
+    /// order.
+    pub fn with_overlays(overlays: &[Overlay]) -> Result<Self, String> {
+        Self::parse_with(BUILTIN_TEMPLATES, overlays)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::parse_with(text, &[])
+    }
+
+    /// Read the templates, apply the `overlays` and check the result is
+    /// consistent: every colour is valid (palette names are replaced by
+    /// their hex codes), the spawn and drop tables only mention known
+    /// templates, every size, count and chance is in range, there are
+    /// enough appearance names for the unidentified items, every level
+    /// belongs to exactly one branch and the stories are for levels that
+    /// exist.
+    pub fn parse_with(text: &str, overlays: &[Overlay]) -> Result<Self, String> {
+        let mut templates: Templates =
+            ron::de::from_str(text).map_err(|error| error.to_string())?;
+        for overlay in overlays.iter().cloned() {
+            templates.palette.extend(overlay.palette);
+            templates.monsters.extend(overlay.monsters);
+            templates.items.extend(overlay.items);
+            templates.appearances.extend(overlay.appearances);
+            if overlay.boss.is_some() {
+                templates.boss = overlay.boss;
+            }
+            if let Some(dungeons) = overlay.dungeons {
+                templates.dungeons = dungeons;
+            }
+            if let Some(intro) = overlay.intro {
+                templates.intro = intro;
+            }
+        }
+
+        // Look the palette colours up once so everything else can expect
+        // hex codes
+        let palette = templates.palette.clone();
+        let resolve = |color: &mut String| {
+            if let Some(hex) = palette.get(color.as_str()) {
+                *color = hex.clone();
+            }
+        };
+        templates
+            .monsters
+            .values_mut()
+            .for_each(|monster| resolve(&mut monster.color));
+        templates
+            .items
+            .values_mut()
+            .for_each(|item| resolve(&mut item.color));
+
+        for monster in templates.monsters.values() {
+            parse_color(&monster.color)?;
+            if monster.regeneration == Some(0) {
+                return Err(format!(
+                    "`{}` can't regenerate every 0 turns.",
+                    monster.name
+                ));
+            }
+            if monster.speed == 0 {
+                return Err(format!("`{}` can't have a speed of 0.", monster.name));
+            }
+            if let Some((min, max)) = monster.pack {
+                if min == 0 || min > max {
+                    return Err(format!(
+                        "`{}` has an invalid pack size: {} to {}.",
+                        monster.name, min, max
+                    ));
+                }
+            }
+            if let Some(summons) = &monster.summons {
+                if !templates.monsters.contains_key(summons) {
+                    return Err(format!(
+                        "Unknown monster `{}` summoned by `{}`.",
+                        summons, monster.name
+                    ));
+                }
+            }
+            for (item, _) in &monster.drops {
+                if !templates.items.contains_key(item) {
+                    return Err(format!(
+                        "Unknown item `{}` dropped by `{}`.",
+                        item, monster.name
+                    ));
+                }
+            }
+        }
+        for item in templates.items.values() {
+            parse_color(&item.color)?;
+            if !(0.0..=1.0).contains(&item.curse_chance) {
+                return Err(format!(
+                    "`{}` has an invalid curse chance: {}.",
+                    item.name, item.curse_chance
+                ));
+            }
+            if let Some((min, max)) = item.bundle {
+                if !item.stacks || min == 0 || min > max {
+                    return Err(format!(
+                        "`{}` has an invalid bundle size: {} to {}.",
+                        item.name, min, max
+                    ));
+                }
+            }
+            if let Some(ammo) = &item.ammo {
+                if !templates.items.get(ammo).is_some_and(|ammo| ammo.stacks) {
+                    return Err(format!(
+                        "`{}` fires `{}`, which isn't an item that stacks.",
+                        item.name, ammo
+                    ));
+                }
+            }
+        }
+        for class in templates
+            .items
+            .values()
+            .filter_map(|item| item.appearance.as_ref())
+        {
+            let needed = templates
+                .items
+                .values()
+                .filter(|item| item.appearance.as_ref() == Some(class))
+                .count();
+            let names = templates
+                .appearances
+                .get(class)
+                .map_or(0, |names| names.len());
+            if names < needed {
+                return Err(format!("Not enough `{}` appearances.", class));
+            }
+        }
+
+        for required in [AMULET, KEY] {
+            if !templates.items.contains_key(required) {
+                return Err(format!("The `{}` item is missing.", required));
+            }
+        }
+        if let Some(boss) = &templates.boss {
+            if !templates.monsters.contains_key(boss) {
+                return Err(format!("Unknown boss `{}`.", boss));
+            }
+        }
+        if templates.dungeons.is_empty() {
+            return Err("There must be at least one dungeon.".to_string());
+        }
+        for dungeon in &templates.dungeons {
+            if dungeon.branches.is_empty() {
+                return Err(format!("`{}` must have at least one branch.", dungeon.name));
+            }
+            let mut next_depth = 1;
+            for branch in &dungeon.branches {
+                if branch.depths.0 != next_depth || branch.depths.1 < branch.depths.0 {
+                    return Err(format!(
+                        "Branch `{}` must start at level {}.",
+                        branch.name, next_depth
+                    ));
+                }
+                next_depth = branch.depths.1 + 1;
+            }
+            if let Some(&depth) = dungeon
+                .stories
+                .keys()
+                .find(|&&depth| depth == 0 || depth > dungeon.max_depth())
+            {
+                return Err(format!(
+                    "`{}` has a story for level {}, which it doesn't have.",
+                    dungeon.name, depth
+                ));
+            }
+        }
+        for branch in templates
+            .dungeons
+            .iter()
+            .flat_map(|dungeon| &dungeon.branches)
+        {
+            for (what, (min, max)) in [
+                ("monster", branch.monster_count),
+                ("item", branch.item_count),
+            ] {
+                if min > max {
+                    return Err(format!(
+                        "`{}` has an invalid {} count: {} to {}.",
+                        branch.name, what, min, max
+                    ));
+                }
+            }
+            for (monster, _) in &branch.monsters {
+                if !templates.monsters.contains_key(monster) {
+                    return Err(format!(
+                        "Unknown monster `{}` in `{}`.",
+                        monster, branch.name
+                    ));
+                }
+            }
+            for (item, _) in &branch.items {
+                if !templates.items.contains_key(item) {
+                    return Err(format!("Unknown item `{}` in `{}`.", item, branch.name));
+                }
+            }
+        }
+        Ok(templates)
+    }
+
+    /// The branch level `depth` of `dungeon` belongs to.
+    pub fn branch(&self, dungeon: usize, depth: u32) -> &Branch {
+        self.dungeons[dungeon].branch(depth)
+    }
+
+    /// The deepest level of `dungeon`.
+    pub fn max_depth(&self, dungeon: usize) -> u32 {
+        self.dungeons[dungeon].max_depth()
+    }
+}
+
+fn normal_speed() -> u32 {
+    Speed::NORMAL.0
+}
+
+/// Turn a colour like `#ff8000` into a `Color`.
+pub fn parse_color(hex: &str) -> Result<Color, String> {
+    let digits = hex.trim_start_matches('#');
+    if digits.len() == 6 && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(Color::from_hex(digits))
+    } else {
+        Err(format!("Invalid colour: `{}`", hex))
+    }
+}
+
+/// Pick one of the names in `table` at random. The chance of each entry
+/// is proportional to its weight.
+pub fn pick_weighted<'a>(table: &'a [(String, u32)], rng: &mut StdRng) -> Option<&'a str> {
+    let total: u32 = table.iter().map(|(_, weight)| weight).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rng.gen_range(0, total);
+    for (name, weight) in table {
+        if roll < *weight {
+            return Some(name);
+        }
+        roll -= weight;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn builtin_templates_are_valid() {
+        let templates = Templates::builtin();
+        assert_eq!(templates.branch(MAIN_DUNGEON, 1).name, "Goblin Warrens");
+        assert_eq!(templates.branch(MAIN_DUNGEON, 3).name, "Goblin Warrens");
+        assert_eq!(templates.branch(MAIN_DUNGEON, 4).name, "Orc Barracks");
+        assert_eq!(templates.branch(MAIN_DUNGEON, 100).name, "Troll Caverns");
+        assert_eq!(templates.max_depth(MAIN_DUNGEON), 8);
+        assert!(templates.dungeons.len() > 1);
+    }
+
+    #[test]
+    fn unknown_monsters_are_rejected() {
+        let text = BUILTIN_TEMPLATES.replace("(\"troll\", 4)", "(\"dragon\", 4)");
+        assert_eq!(
+            Templates::parse(&text),
+            Err("Unknown monster `dragon` in `Troll Caverns`.".to_string())
+        );
+    }
+
+    #[test]
+    fn branch_counts_go_from_min_to_max() {
+        let text = BUILTIN_TEMPLATES.replacen("monster_count: (2, 4)", "monster_count: (5, 2)", 1);
+        assert_eq!(
+            Templates::parse(&text),
+            Err("`Goblin Warrens` has an invalid monster count: 5 to 2.".to_string())
+        );
+    }
+
+    #[test]
+    fn curse_chances_must_be_chances() {
+        let text = BUILTIN_TEMPLATES.replacen("curse_chance: 0.", "curse_chance: 1.", 1);
+        assert!(Templates::parse(&text)
+            .unwrap_err()
+            .contains("invalid curse chance"));
+    }
+
+    #[test]
+    fn stories_must_be_for_existing_levels() {
+        let text = BUILTIN_TEMPLATES.replace(
+            "            stories: {\n                1:",
+            "            stories: {\n                9:",
+        );
+        assert_eq!(
+            Templates::parse(&text),
+            Err("`Dungeon of Yendor` has a story for level 9, which it doesn't have.".to_string())
+        );
+    }
+
+    #[test]
+    fn palette_colors_are_replaced_by_hex_codes() {
+        let templates = Templates::builtin();
+        assert_eq!(templates.monsters["orc"].color, "#208020");
+
+        let text = BUILTIN_TEMPLATES.replace("color: \"moss\"", "color: \"mauve\"");
+        assert!(Templates::parse(&text).is_err());
+    }
+
+    #[test]
+    fn overlays_add_and_replace_templates() {
+        let overlay = Overlay::parse(
+            r##"(
+                palette: { "moss": "#00ff00" },
+                monsters: {
+                    "orc": (name: "uruk", glyph: 'U', color: "moss", hp: 9),
+                    "slime": (name: "slime", glyph: 'j', color: "#40c040", hp: 2),
+                },
+            )"##,
+        )
+        .unwrap();
+        let templates = Templates::with_overlays(&[overlay]).unwrap();
+        assert_eq!(templates.monsters["orc"].name, "uruk");
+        assert_eq!(templates.monsters["slime"].hp, 2);
+        // Everything that was called moss changes its colour
+        let builtin = Templates::builtin();
+        for (name, monster) in &templates.monsters {
+            if builtin.monsters.get(name).map(|old| old.color.as_str()) == Some("#208020") {
+                assert_eq!(monster.color, "#00ff00", "{}", name);
+            }
+        }
+        assert_eq!(templates.dungeons, builtin.dungeons);
+
+        let broken = Overlay::parse(r#"(boss: Some("dragon"))"#).unwrap();
+        assert_eq!(
+            Templates::with_overlays(&[broken]),
+            Err("Unknown boss `dragon`.".to_string())
+        );
+    }
+
+    #[test]
+    fn weighted_picks_follow_the_weights() {
+        let table = vec![("common".to_string(), 9), ("rare".to_string(), 1)];
+        let mut rng = StdRng::seed_from_u64(7);
+        let rare = (0..1000)
+            .filter(|_| pick_weighted(&table, &mut rng) == Some("rare"))
+            .count();
+        assert!(rare > 50 && rare < 150, "rare picked {} times", rare);
+
+        let never = vec![("nothing".to_string(), 0)];
+        assert_eq!(pick_weighted(&never, &mut rng), None);
+    }
+}
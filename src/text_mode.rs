@@ -0,0 +1,178 @@
+//! The game as plain text, for screen readers and terminals.
+//!
+//! With `--text` the game prints a frame (see `TextMode::update`) after
+//! every turn: the stories told and the messages logged since the last
+//! one, what the player can see of the map, a status line and the
+//! inventory. On the web it goes into a page element instead. The
+//! `text` binary plays the whole game this way, without a window.
+
+use quicksilver::geom::Vector;
+
+use crate::{geometry::tile_key, locale, world::World};
+
+/// Remembers what's been shown already so every frame only has what's
+/// new.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextMode {
+    /// How many messages had been logged at the last frame.
+    shown: usize,
+    /// How many times the last of those had come up. A repeat bumps the
+    /// count instead of logging a new message.
+    last_count: u32,
+    /// The turn of the last frame.
+    turn: Option<u32>,
+    /// How many of `World::stories` had been told at the last frame.
+    stories: usize,
+}
+
+impl TextMode {
+    /// The frame for the turn just taken, `None` if there's been no new
+    /// turn and nothing new in the log since the last one.
+    pub fn update(&mut self, world: &World) -> Option<String> {
+        let new_turn = self.turn != Some(world.stats.turns);
+        let stories = self.new_stories(world);
+        let messages = self.new_messages(world);
+        if !new_turn && stories.is_empty() && messages.is_empty() {
+            return None;
+        }
+        self.turn = Some(world.stats.turns);
+        let mut lines = stories;
+        lines.extend(messages);
+        lines.push(visible_map(world));
+        lines.push(status(world));
+        lines.push(inventory(world));
+        Some(lines.join("\n"))
+    }
+
+    /// The paragraphs of the stories told since the last call, with a
+    /// blank line after each.
+    pub fn new_stories(&mut self, world: &World) -> Vec<String> {
+        let start = self.stories.min(world.stories.len());
+        self.stories = world.stories.len();
+        world.stories[start..]
+            .iter()
+            .flatten()
+            .map(|paragraph| format!("{}\n", paragraph))
+            .collect()
+    }
+
+    /// The messages logged since the last call, repeats included.
+    pub fn new_messages(&mut self, world: &World) -> Vec<String> {
+        let log = &world.log;
+        let mut start = self.shown.min(log.len());
+        if start > 0 && log[start - 1].count != self.last_count {
+            start -= 1;
+        }
+        let messages = log[start..].iter().map(|message| message.to_string());
+        let messages = messages.collect();
+        self.shown = log.len();
+        self.last_count = log.last().map_or(0, |message| message.count);
+        messages
+    }
+}
+
+/// The map as the player knows it: everything they've explored, with
+/// whoever they can see right now and the items they remember. One line
+/// per row, with the unexplored parts blank and the empty rows above
+/// and below left out.
+pub fn visible_map(world: &World) -> String {
+    let width = world.map.size.x as usize;
+    let height = world.map.size.y as usize;
+    let mut rows = vec![vec![' '; width]; height];
+    for tile in &world.map.tiles {
+        if world.fov.is_explored(tile.pos) {
+            rows[tile.pos.y as usize][tile.pos.x as usize] = tile.glyph;
+        }
+    }
+    for (&(x, y), &(glyph, _)) in &world.fov.remembered_items {
+        if world.map.contains(Vector::new(x, y)) {
+            rows[y as usize][x as usize] = glyph;
+        }
+    }
+    // Whoever blocks the tile goes over what's lying on it
+    let mut seen: Vec<usize> = (0..world.entities.len())
+        .filter(|&id| world.fov.is_visible(world.entities[id].pos) && world.sees_entity(id))
+        .collect();
+    seen.sort_by_key(|&id| (id == world.player_id, world.entities[id].blocks_tile));
+    for id in seen {
+        let (x, y) = tile_key(world.entities[id].pos);
+        rows[y as usize][x as usize] = world.entities[id].glyph;
+    }
+    let lines: Vec<String> = rows
+        .into_iter()
+        .map(|row| row.into_iter().collect::<String>().trim_end().to_string())
+        .collect();
+    // No point reading out the rows the player knows nothing about
+    let first = lines.iter().position(|line| !line.is_empty()).unwrap_or(0);
+    let last = lines.iter().rposition(|line| !line.is_empty()).unwrap_or(0);
+    lines[first..=last].join("\n")
+}
+
+/// The player's health, where they are and their gold.
+pub fn status(world: &World) -> String {
+    let player = world.player();
+    let level = if world.depth == 0 {
+        locale::text("hud.surface")
+    } else {
+        let branch = &world.templates.branch(world.dungeon, world.depth).name;
+        locale::format(
+            "hud.depth",
+            &[
+                ("depth", &world.depth),
+                ("branch", &locale::content(branch)),
+            ],
+        )
+    };
+    locale::format(
+        "text-mode.status",
+        &[
+            ("hp", &player.hp),
+            ("max_hp", &player.max_hp),
+            ("level", &level),
+            ("gold", &player.gold),
+        ],
+    )
+}
+
+/// Everything the player carries, with the letters the HUD shows.
+pub fn inventory(world: &World) -> String {
+    let items: Vec<String> = (b'A'..=b'Z')
+        .zip(world.player().inventory.iter())
+        .map(|(letter, item)| format!("[{}] {}", letter as char, world.inventory_line(item)))
+        .collect();
+    format!("{} {}", locale::text("hud.inventory"), items.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{tests::player_with, Action};
+
+    #[test]
+    fn frames_show_the_map_and_whats_new() {
+        let mut world = player_with(vec![], 5);
+        world.entities[world.player_id].glyph = '@';
+        let mut text_mode = TextMode::default();
+        let frame = text_mode.update(&world).unwrap();
+        assert!(frame.contains("\n#....@."));
+        assert!(frame.ends_with(&format!("{}\n{}", status(&world), inventory(&world))));
+        assert_eq!(text_mode.update(&world), None);
+
+        world.log("You hear something.");
+        world.log("You hear something.");
+        assert_eq!(text_mode.new_messages(&world), ["You hear something. x2"]);
+        world.log("You hear something.");
+        assert_eq!(text_mode.new_messages(&world), ["You hear something. x3"]);
+        assert!(text_mode.new_messages(&world).is_empty());
+
+        world.player_turn(Action::Move(Vector::new(1, 0)));
+        let frame = text_mode.update(&world).unwrap();
+        assert!(frame.contains("\n#.....@."));
+
+        // Stories come first and only once
+        world.stories.push(vec!["Once upon a time.".to_string()]);
+        let frame = text_mode.update(&world).unwrap();
+        assert!(frame.starts_with("Once upon a time.\n\n"));
+        assert_eq!(text_mode.update(&world), None);
+    }
+}
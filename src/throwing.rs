@@ -0,0 +1,261 @@
+//! Throwing things.
+//!
+//! Any item can be thrown. It flies in a straight line towards the tile
+//! the player picked and stops at the first wall or creature in the
+//! way. Creatures take the item's `throw_damage` or get its `effect`.
+//! Items with an effect shatter, the rest land on the floor. Confusion
+//! potions leave a cloud of gas behind, see `hazards`. Low walls
+//! stop thrown things too, and creatures standing next to one can duck
+//! behind it, see `dodges`.
+
+use quicksilver::geom::Vector;
+use rand::Rng;
+
+use crate::{
+    geometry::line,
+    hazards::{self, HazardKind, GAS_RADIUS},
+    identify::identify,
+    locale,
+    map::BLOCKS_PROJECTILES,
+    noise::{self, SHATTER_NOISE},
+    templates::Effect,
+    world::{is_stuck, take_damage, Entity, World},
+};
+
+/// How far (in tiles) things can be thrown.
+pub const THROW_RANGE: usize = 8;
+
+/// The chance of something thrown (or cast) at a creature in cover
+/// missing it.
+pub const COVER_DODGE_CHANCE: f64 = 0.5;
+
+/// The tiles the thrown item passes through on its way from `from` to
+/// `target`, ending where it lands. It stops before walls (low ones
+/// included), on the first creature in the way, at the target or when
+/// it's out of range.
+pub fn trajectory(world: &World, from: Vector, target: Vector) -> Vec<Vector> {
+    flight_path(world, from, target, THROW_RANGE)
+}
+
+/// Like `trajectory`, for anything that flies up to `range` tiles.
+pub fn flight_path(world: &World, from: Vector, target: Vector, range: usize) -> Vec<Vector> {
+    let mut path = vec![];
+    for pos in line(from, target).into_iter().skip(1).take(range) {
+        if world.map.blocks(pos, BLOCKS_PROJECTILES) {
+            break;
+        }
+        path.push(pos);
+        if world.blocker_at(pos).is_some() {
+            break;
+        }
+    }
+    path
+}
+
+/// The player throws the inventory item at `index` towards `target`.
+pub fn throw(world: &mut World, index: usize, target: Vector) {
+    let player_id = world.player_id;
+    if index >= world.player().inventory.len() {
+        return world.log(locale::text("log.no-such-item"));
+    }
+    if is_stuck(&world.player().inventory[index]) {
+        let name = world.item_name(&world.player().inventory[index]);
+        return world.log(locale::message("log.cannot-take-off", &[("item", &name)]));
+    }
+    let start = world.player().pos;
+    let path = trajectory(world, start, target);
+    let landing = match path.last() {
+        Some(&landing) => landing,
+        None => return world.log(locale::text("log.no-room-to-throw")),
+    };
+
+    let mut item = world.take_one(player_id, index);
+    if let Some(item) = &mut item.item {
+        item.equipped = false;
+    }
+    world.stats.items_used += 1;
+    let template = item
+        .item
+        .as_ref()
+        .and_then(|item| world.templates.items.get(&item.template))
+        .cloned();
+    let (damage, effect) = match &template {
+        Some(template) => (template.throw_damage, template.effect),
+        None => (0, None),
+    };
+    let name = world.item_name(&item);
+    world.log(locale::message("log.throw", &[("item", &name)]));
+
+    let hit = world
+        .blocker_at(landing)
+        .filter(|&id| world.entities[id].is_alive())
+        .filter(|&id| !dodges(world, start, id));
+    match (hit, effect) {
+        (Some(id), Some(effect)) => apply_effect(world, &item, id, effect),
+        (Some(id), None) => hit_with(world, &name, id, damage.max(1)),
+        (None, Some(_)) => world.log(locale::message("log.shatters", &[("item", &name)])),
+        (None, None) => {}
+    }
+    if effect.is_some() {
+        noise::make(world, landing, SHATTER_NOISE);
+    }
+    if let Some(Effect::Confuse(_)) = effect {
+        hazards::spawn(world, HazardKind::Gas, landing, GAS_RADIUS);
+    }
+    if effect.is_none() {
+        item.pos = landing;
+        world.entities.push(item);
+        world.rebuild_spatial();
+    }
+}
+
+/// Whether `target` ducks behind cover (see `Map::in_cover`) to get out
+/// of the way of something flying at it from `from`. There's no hiding
+/// from somebody right next to you.
+pub fn dodges(world: &mut World, from: Vector, target: usize) -> bool {
+    let pos = world.entities[target].pos;
+    let offset = pos - from;
+    let adjacent = offset.x.abs() <= 1.0 && offset.y.abs() <= 1.0;
+    if adjacent || !world.map.in_cover(pos) || !world.rng.gen_bool(COVER_DODGE_CHANCE) {
+        return false;
+    }
+    let name = world.entities[target].name.clone();
+    world.log(locale::message(
+        "log.ducks-behind-cover",
+        &[("name", &name)],
+    ));
+    true
+}
+
+/// The player hurt `target` with something called `item`.
+pub fn hit_with(world: &mut World, item: &str, target: usize, damage: i32) {
+    let player_id = world.player_id;
+    let killed = take_damage(world, Some(player_id), target, damage);
+    let name = world.entities[target].name.clone();
+    world.log(locale::message(
+        "log.hits",
+        &[("attacker", &item), ("name", &name)],
+    ));
+    if killed {
+        world.log(locale::message("log.dies", &[("name", &name)]));
+    }
+}
+
+fn apply_effect(world: &mut World, item: &Entity, target: usize, effect: Effect) {
+    let name = world.entities[target].name.clone();
+    let item_name = world.item_name(item);
+    world.log(locale::message(
+        "log.shatters-on",
+        &[("item", &item_name), ("name", &name)],
+    ));
+    match effect {
+        Effect::Heal(amount) => {
+            let entity = &mut world.entities[target];
+            entity.hp = (entity.hp + amount).min(entity.max_hp);
+            world.log(locale::message("log.looks-healthier", &[("name", &name)]));
+        }
+        Effect::Confuse(turns) => {
+            if let Some(ai) = &mut world.entities[target].ai {
+                ai.confused = turns;
+            }
+            world.log(locale::message("log.looks-confused", &[("name", &name)]));
+        }
+        // Reading it is the only way to find out anything
+        Effect::Identify | Effect::RemoveCurse => return,
+    }
+    // Seeing what it did gives it away
+    if let Some(template) = item.item.as_ref().map(|item| item.template.clone()) {
+        identify(world, &template);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{LOW_WALL, WALL},
+        world::{
+            tests::{goblin, player_with},
+            Action, Item,
+        },
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn carried(template: &str) -> Entity {
+        Entity {
+            name: template.to_string(),
+            item: Some(Item {
+                template: template.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn world_with_goblin() -> World {
+        let goblin = Entity {
+            ai: None,
+            ..goblin(10, 3)
+        };
+        let mut world = player_with(vec![goblin], 5);
+        let player_id = world.player_id;
+        world.entities[player_id].inventory = vec![carried("dart"), carried("healing potion")];
+        world
+    }
+
+    #[test]
+    fn thrown_items_stop_at_the_first_creature_and_land() {
+        let mut world = world_with_goblin();
+        world.player_turn(Action::Throw(0, Vector::new(14, 5)));
+        assert_eq!(world.entities[0].hp, 1);
+        let dart = world
+            .item_at(Vector::new(10, 5))
+            .expect("The dart is gone.");
+        assert_eq!(world.entities[dart].name, "dart");
+        assert_eq!(world.player().inventory.len(), 1);
+    }
+
+    #[test]
+    fn potions_shatter_and_apply_their_effect() {
+        let mut world = world_with_goblin();
+        world.entities[0].hp = 1;
+        world.player_turn(Action::Throw(1, Vector::new(10, 5)));
+        assert_eq!(world.entities[0].hp, 3);
+        assert!(world.entities.iter().all(|entity| entity.item.is_none()));
+        assert!(world.hazards.is_empty());
+
+        // Confusion leaves a cloud of gas behind
+        world.entities[1]
+            .inventory
+            .push(carried("confusion potion"));
+        world.player_turn(Action::Throw(1, Vector::new(10, 5)));
+        assert!(hazards::at(&world, Vector::new(9, 5)).is_some());
+    }
+
+    #[test]
+    fn creatures_in_cover_dodge_some_of_the_time() {
+        let mut dodged = 0;
+        for seed in 0..20 {
+            let mut world = world_with_goblin();
+            world.rng = StdRng::seed_from_u64(seed);
+            world.map.set_glyph(Vector::new(10, 4), LOW_WALL);
+            world.player_turn(Action::Throw(0, Vector::new(10, 5)));
+            if world.entities[0].hp == 3 {
+                dodged += 1;
+                assert!(world
+                    .log
+                    .iter()
+                    .any(|message| *message == "The goblin ducks behind cover."));
+            }
+        }
+        assert!(dodged > 0 && dodged < 20, "{} dodges", dodged);
+    }
+
+    #[test]
+    fn walls_stop_thrown_items() {
+        let mut world = world_with_goblin();
+        world.map.set_glyph(Vector::new(8, 5), WALL);
+        let path = trajectory(&world, Vector::new(5, 5), Vector::new(10, 5));
+        assert_eq!(path, [Vector::new(6, 5), Vector::new(7, 5)]);
+    }
+}
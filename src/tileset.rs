@@ -0,0 +1,111 @@
+//! Which glyphs the map needs drawn.
+//!
+//! The tiles are rendered from a font, each one the first time it's
+//! needed, so monsters and items can use any character. The ones the
+//! templates mention are rendered up front. A glyph the font doesn't
+//! have would show up as a blank, so `FALLBACK` is drawn instead.
+
+use rusttype::Font;
+
+use crate::{
+    decoration::{GRASS, MOSS, RUBBLE},
+    map::{
+        DEEP_WATER, DOOR, FLOOR, LAVA, LOCKED_DOOR, LOW_WALL, SHALLOW_WATER, STAIRS_DOWN,
+        STAIRS_UP, WALL,
+    },
+    templates::Templates,
+};
+
+/// Drawn in place of the glyphs the font doesn't have.
+pub const FALLBACK: char = '?';
+
+/// The glyphs of the map tiles and of the entities the game creates on
+/// its own rather than from the templates: the player, corpses, gold,
+/// braziers and chests.
+pub const BUILTIN_GLYPHS: &[char] = &[
+    WALL,
+    FLOOR,
+    DOOR,
+    STAIRS_DOWN,
+    STAIRS_UP,
+    SHALLOW_WATER,
+    DEEP_WATER,
+    LAVA,
+    LOCKED_DOOR,
+    LOW_WALL,
+    RUBBLE,
+    GRASS,
+    MOSS,
+    '@',
+    '%',
+    '$',
+    '&',
+    '(',
+];
+
+/// Every glyph the game can draw on the map, each one once.
+pub fn glyphs(templates: &Templates) -> String {
+    let monsters = templates.monsters.values().map(|monster| monster.glyph);
+    let items = templates.items.values().map(|item| item.glyph);
+    let mut glyphs = String::new();
+    for glyph in BUILTIN_GLYPHS.iter().copied().chain(monsters).chain(items) {
+        if !glyphs.contains(glyph) {
+            glyphs.push(glyph);
+        }
+    }
+    glyphs
+}
+
+/// Which characters a font can draw.
+pub struct FontGlyphs {
+    font: Font<'static>,
+}
+
+impl FontGlyphs {
+    /// `font` is the contents of a TrueType file.
+    pub fn new(font: Vec<u8>) -> Result<Self, String> {
+        let font = Font::from_bytes(font).map_err(|error| error.to_string())?;
+        Ok(FontGlyphs { font })
+    }
+
+    pub fn has(&self, glyph: char) -> bool {
+        self.font.glyph(glyph).id().0 != 0
+    }
+
+    /// The glyph to draw for `glyph`: itself if the font has it,
+    /// `FALLBACK` otherwise.
+    pub fn resolve(&self, glyph: char) -> char {
+        if self.has(glyph) {
+            glyph
+        } else {
+            FALLBACK
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE_FONT: &[u8] = include_bytes!("../static/square.ttf");
+
+    #[test]
+    fn the_templates_add_their_glyphs() {
+        let glyphs = glyphs(&Templates::builtin());
+        assert!(glyphs.starts_with("#.+><"));
+        for glyph in &['g', 'T', '!', '?', '"'] {
+            assert_eq!(glyphs.matches(*glyph).count(), 1);
+        }
+    }
+
+    #[test]
+    fn missing_glyphs_fall_back() {
+        let font = FontGlyphs::new(SQUARE_FONT.to_vec()).unwrap();
+        assert!(glyphs(&Templates::builtin())
+            .chars()
+            .all(|glyph| font.has(glyph)));
+        assert!(font.has(FALLBACK));
+        assert_eq!(font.resolve('g'), 'g');
+        assert_eq!(font.resolve('\u{2603}'), FALLBACK);
+    }
+}
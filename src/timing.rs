@@ -0,0 +1,64 @@
+//! Keeping time in updates rather than frames.
+//!
+//! Quicksilver calls `update` at a fixed rate (catching up when a frame
+//! took too long) while `draw` runs as often as the display allows. So
+//! anything that should take the same time everywhere, on the web and
+//! the desktop, on 60 Hz and 144 Hz monitors, counts updates.
+
+/// How often `update` is called, in milliseconds.
+pub const UPDATE_RATE_MS: f64 = 1000.0 / 60.0;
+
+/// The most updates to run to catch up after a slow frame. Past this the
+/// game slows down instead of skipping ahead.
+pub const MAX_UPDATES: u32 = 5;
+
+/// The number of updates that take `seconds`, at least one.
+pub fn updates(seconds: f64) -> u32 {
+    ((seconds * 1000.0 / UPDATE_RATE_MS).round() as u32).max(1)
+}
+
+/// Fires once every `period` updates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timer {
+    period: u32,
+    elapsed: u32,
+}
+
+impl Timer {
+    pub fn from_seconds(seconds: f64) -> Self {
+        Timer {
+            period: updates(seconds),
+            elapsed: 0,
+        }
+    }
+
+    /// Count one update. Return `true` when the period is up.
+    pub fn tick(&mut self) -> bool {
+        self.elapsed += 1;
+        if self.elapsed >= self.period {
+            self.elapsed = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seconds_are_converted_to_updates() {
+        assert_eq!(updates(1.0), 60);
+        assert_eq!(updates(0.25), 15);
+        assert_eq!(updates(0.0), 1);
+    }
+
+    #[test]
+    fn timers_fire_once_per_period() {
+        let mut timer = Timer::from_seconds(0.05);
+        let fired: Vec<bool> = (0..6).map(|_| timer.tick()).collect();
+        assert_eq!(fired, [false, false, true, false, false, true]);
+    }
+}
@@ -0,0 +1,282 @@
+//! Doing things over several turns without a key press for each one.
+//!
+//! Anything that moves the player this way (running now, mouse travel or
+//! auto-explore later) must stop under the same conditions, see
+//! `travel_interrupted`.
+
+use quicksilver::geom::Vector;
+
+use crate::{
+    map::FLOOR,
+    world::{Action, World},
+};
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// Give up resting after this many turns no matter what.
+pub const MAX_REST_TURNS: u32 = 200;
+
+/// Return `true` if the player has been hurt since `start_hp` or sees a
+/// monster. Nothing should happen on its own in that case.
+pub fn in_danger(world: &World, start_hp: i32) -> bool {
+    world.player().hp < start_hp || !world.visible_monsters().is_empty()
+}
+
+/// Return `true` if the player should stop moving on their own: they're
+/// in danger or they're standing on something worth a look (an item, a
+/// door, stairs).
+pub fn travel_interrupted(world: &World, start_hp: i32) -> bool {
+    let player = world.player();
+    let on_something = world.item_at(player.pos).is_some()
+        || world
+            .map
+            .tile(player.pos)
+            .is_some_and(|tile| tile.glyph != FLOOR);
+    on_something || in_danger(world, start_hp)
+}
+
+/// Something the player keeps doing turn after turn.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Activity {
+    Run(Run),
+    Rest(Rest),
+}
+
+impl Activity {
+    /// The action for this turn or `None` when the activity is over.
+    pub fn next_action(&mut self, world: &World) -> Option<Action> {
+        match self {
+            Activity::Run(run) => run.next_step(world).map(Action::Move),
+            Activity::Rest(rest) => rest.next_action(world),
+        }
+    }
+}
+
+/// Waiting until the player's health is full.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rest {
+    start_hp: i32,
+    turns: u32,
+}
+
+impl Rest {
+    pub fn new(world: &World) -> Self {
+        Rest {
+            start_hp: world.player().hp,
+            turns: 0,
+        }
+    }
+
+    pub fn next_action(&mut self, world: &World) -> Option<Action> {
+        let player = world.player();
+        if player.hp >= player.max_hp
+            || self.turns >= MAX_REST_TURNS
+            || in_danger(world, self.start_hp)
+        {
+            return None;
+        }
+        // Regeneration raises the health so don't treat that as the
+        // baseline for getting hurt
+        self.start_hp = self.start_hp.max(player.hp);
+        self.turns += 1;
+        Some(Action::Wait)
+    }
+}
+
+/// Running in a direction until something interesting happens.
+///
+/// In a corridor the run follows its bends and stops at junctions and
+/// dead ends. In a room it goes straight and stops when the walls to
+/// the side change (e.g. at a doorway or a corner).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Run {
+    pub dir: Vector,
+    start_hp: i32,
+    steps: u32,
+    in_corridor: bool,
+    /// Whether the tiles to the left and right were open on the last step.
+    sides: Option<(bool, bool)>,
+}
+
+impl Run {
+    pub fn new(world: &World, dir: Vector) -> Self {
+        Run {
+            dir,
+            start_hp: world.player().hp,
+            steps: 0,
+            in_corridor: false,
+            sides: None,
+        }
+    }
+
+    /// The offset of the next step or `None` when the run is over.
+    pub fn next_step(&mut self, world: &World) -> Option<Vector> {
+        let pos = world.player().pos;
+        let is_open = |offset: Vector| {
+            let target = pos + offset;
+            world.is_passable(world.player_id, target) && world.blocker_at(target).is_none()
+        };
+
+        if self.steps > 0 {
+            if travel_interrupted(world, self.start_hp) {
+                return None;
+            }
+
+            let open: Vec<Vector> = DIRECTIONS
+                .iter()
+                .map(|&dir| Vector::from(dir))
+                .filter(|&dir| is_open(dir))
+                .collect();
+            if open.len() <= 2 {
+                // A corridor: follow it wherever it goes
+                let exits: Vec<Vector> = open.into_iter().filter(|&dir| dir != -self.dir).collect();
+                if exits.len() != 1 {
+                    return None;
+                }
+                self.dir = exits[0];
+                self.in_corridor = true;
+                self.sides = None;
+            } else if self.in_corridor {
+                // The corridor opens up into a junction or a room
+                return None;
+            } else {
+                let left = Vector::new(self.dir.y, -self.dir.x);
+                let sides = (is_open(left), is_open(-left));
+                if self.sides.is_some_and(|previous| previous != sides) {
+                    return None;
+                }
+                self.sides = Some(sides);
+            }
+        } else if !world.visible_monsters().is_empty() {
+            // Don't run off with a monster right there
+            return None;
+        }
+
+        if !is_open(self.dir) {
+            return None;
+        }
+        self.steps += 1;
+        Some(self.dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{Map, WALL},
+        regeneration::PLAYER_REGENERATION_TURNS,
+        world::Entity,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Build a world from an ASCII picture. `@` is the player, `g` a
+    /// goblin.
+    fn world_from(rows: &[&str]) -> World {
+        let size = Vector::new(rows[0].len() as i32, rows.len() as i32);
+        let mut map = Map::filled(size, WALL);
+        let mut monsters = vec![];
+        let mut player = Entity {
+            name: "player".to_string(),
+            hp: 5,
+            max_hp: 5,
+            blocks_tile: true,
+            regeneration: Some(PLAYER_REGENERATION_TURNS),
+            ..Default::default()
+        };
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let pos = Vector::new(x as i32, y as i32);
+                match c {
+                    '#' => {}
+                    '@' => {
+                        player.pos = pos;
+                        map.set_glyph(pos, FLOOR);
+                    }
+                    'g' => {
+                        monsters.push(Entity {
+                            pos,
+                            name: "goblin".to_string(),
+                            hp: 1,
+                            max_hp: 1,
+                            blocks_tile: true,
+                            ..Default::default()
+                        });
+                        map.set_glyph(pos, FLOOR);
+                    }
+                    _ => map.set_glyph(pos, c),
+                }
+            }
+        }
+        World::from_parts(0, StdRng::seed_from_u64(0), map, monsters, player)
+    }
+
+    fn run(world: &mut World, x: i32, y: i32) -> Vector {
+        let mut run = Run::new(world, Vector::new(x, y));
+        while let Some(step) = run.next_step(world) {
+            world.player_turn(Action::Move(step));
+        }
+        world.player().pos
+    }
+
+    #[test]
+    fn runs_follow_corridors_to_junctions() {
+        let mut world = world_from(&[
+            "#########",
+            "#@....###",
+            "#####.###",
+            "#####....",
+            "#####.###",
+            "#########",
+        ]);
+        assert_eq!(run(&mut world, 1, 0), Vector::new(5, 3));
+    }
+
+    #[test]
+    fn runs_stop_at_dead_ends_and_doors() {
+        let mut world = world_from(&["#######", "#@...+#", "#######"]);
+        assert_eq!(run(&mut world, 1, 0), Vector::new(5, 1));
+
+        let mut world = world_from(&["######", "#@...#", "######"]);
+        assert_eq!(run(&mut world, 1, 0), Vector::new(4, 1));
+    }
+
+    #[test]
+    fn runs_in_rooms_stop_where_the_walls_change() {
+        let mut world = world_from(&[
+            "#########",
+            "#.......#",
+            "#@......#",
+            "####.####",
+            "#########",
+        ]);
+        assert_eq!(run(&mut world, 1, 0), Vector::new(4, 2));
+    }
+
+    #[test]
+    fn resting_heals_the_player() {
+        let mut world = world_from(&["#####", "#@..#", "#####"]);
+        let player_id = world.player_id;
+        world.entities[player_id].hp = 1;
+
+        let mut rest = Rest::new(&world);
+        while let Some(action) = rest.next_action(&world) {
+            world.player_turn(action);
+        }
+        assert_eq!(world.player().hp, world.player().max_hp);
+    }
+
+    #[test]
+    fn resting_stops_when_a_monster_shows_up() {
+        let mut world = world_from(&["######", "#@..g#", "######"]);
+        let player_id = world.player_id;
+        world.entities[player_id].hp = 1;
+        assert_eq!(Rest::new(&world).next_action(&world), None);
+    }
+
+    #[test]
+    fn monsters_in_view_stop_runs() {
+        let mut world = world_from(&["#########", "#@......g", "#########"]);
+        assert_eq!(run(&mut world, 1, 0), Vector::new(1, 1));
+    }
+}
@@ -0,0 +1,837 @@
+//! Helpers for drawing the user interface.
+
+use quicksilver::{
+    geom::{Rectangle, Vector},
+    graphics::Color,
+};
+use rusttype::{point, Font, Scale};
+
+use crate::{
+    locale,
+    map::{WALL_EAST, WALL_NORTH, WALL_SOUTH, WALL_WEST},
+    message::Segment,
+    schedule,
+    skills::{Cooldowns, HOTBAR},
+    world::{capitalize, Action, World},
+};
+
+/// Below this fraction of their health the player blinks red.
+pub const CRITICAL_HEALTH: f32 = 0.25;
+
+/// How often entities that blink switch between their two looks.
+pub const BLINK_SECONDS: f64 = 0.3;
+
+/// The colour of the hotbar skills that aren't ready.
+pub const GREYED_OUT: Color = Color {
+    r: 0.6,
+    g: 0.6,
+    b: 0.6,
+    a: 1.0,
+};
+
+/// The colour of a health bar that's `ratio` (from 0.0 to 1.0) full.
+///
+/// It goes from red when nearly dead through yellow to green at full
+/// health.
+pub fn health_color(ratio: f32) -> Color {
+    let ratio = ratio.clamp(0.0, 1.0);
+    if ratio < 0.5 {
+        Color {
+            r: 1.0,
+            g: ratio * 2.0,
+            b: 0.0,
+            a: 1.0,
+        }
+    } else {
+        Color {
+            r: (1.0 - ratio) * 2.0,
+            g: 1.0,
+            b: 0.0,
+            a: 1.0,
+        }
+    }
+}
+
+/// `color` faded towards the background the darker the tile is.
+pub fn lit_color(color: Color, light: f32) -> Color {
+    color.with_alpha(color.a * (0.4 + 0.6 * light.clamp(0.0, 1.0)))
+}
+
+/// The colour to draw an entity in view with, `None` if it shouldn't be
+/// drawn at all. `blink` flips every `BLINK_SECONDS`.
+///
+/// Invisible monsters only show up (faintly) right next to the player.
+/// Confused ones blink, and so does the player when nearly dead.
+pub fn entity_color(world: &World, id: usize, blink: bool) -> Option<Color> {
+    let entity = &world.entities[id];
+    if !world.sees_entity(id) {
+        return None;
+    }
+    let mut color = lit_color(entity.color, world.light.level(entity.pos));
+    if entity.invisible {
+        color = color.with_alpha(color.a * 0.5);
+    }
+    let confused = entity.ai.as_ref().is_some_and(|ai| ai.confused > 0);
+    if confused && entity.is_alive() && blink {
+        color = color.with_alpha(color.a * 0.2);
+    }
+    let health = entity.hp as f32 / entity.max_hp.max(1) as f32;
+    if id == world.player_id && entity.is_alive() && health <= CRITICAL_HEALTH && blink {
+        color = Color::RED.with_alpha(color.a);
+    }
+    Some(color)
+}
+
+/// Whether the entity gets a health bar of its own on the map: it's a
+/// wounded monster the player can see.
+pub fn shows_health_bar(world: &World, id: usize) -> bool {
+    let entity = &world.entities[id];
+    let wounded = entity.is_alive() && entity.hp < entity.max_hp;
+    id != world.player_id && entity.ai.is_some() && wounded && world.sees_entity(id)
+}
+
+/// The action with how many ticks it takes (see `schedule::cost`), shown
+/// before the player takes it: "Move: 100", "Wade: 200".
+pub fn cost_label(world: &World, action: &Action) -> String {
+    let key = match action {
+        Action::Move(offset) => {
+            let destination = world.player().pos + *offset;
+            let blocker = world.blocker_at(destination).map(|id| &world.entities[id]);
+            let cost = world.map.movement_cost(destination, world.player().swims);
+            match blocker {
+                Some(entity) if entity.shopkeeper => "action.trade",
+                Some(entity) if entity.chest.is_some() => "action.open",
+                Some(_) => "action.attack",
+                None if cost.is_some_and(|cost| cost > 1) => "action.wade",
+                None => "action.move",
+            }
+        }
+        Action::Descend | Action::Ascend => "action.stairs",
+        Action::PickUp => "action.pick-up",
+        Action::Wait => "action.wait",
+        Action::Buy(_) | Action::Sell(_) => "action.trade",
+        Action::Equip(_) => "action.equip",
+        Action::Throw(..) => "action.throw",
+        Action::Fire(_) => "action.fire",
+        Action::Use(_) => "action.use",
+        Action::Sneak => "action.sneak",
+        Action::Take(_) | Action::TakeAll => "action.take",
+        Action::Cheat(_) => "action.cheat",
+        Action::Skill(..) => "",
+    };
+    let name = match action {
+        Action::Skill(skill, _) => capitalize(&skill.name()),
+        _ => locale::text(key),
+    };
+    locale::format(
+        "cost.label",
+        &[("action", &name), ("ticks", &schedule::cost(world, action))],
+    )
+}
+
+/// The health bar of a monster on the `tile`: as wide as the tile, right
+/// above it.
+pub fn monster_bar(tile: Rectangle) -> Rectangle {
+    let height = (tile.size.y / 8.0).round().max(2.0);
+    Rectangle::new(tile.pos - Vector::new(0.0, height), (tile.size.x, height))
+}
+
+/// The grey version of `color` used for tiles that have been seen but
+/// aren't in view.
+pub fn remembered_color(color: Color) -> Color {
+    let luminance = 0.3 * color.r + 0.59 * color.g + 0.11 * color.b;
+    // Keep it light so the remembered parts don't draw the eye
+    let grey = 0.5 + luminance * 0.3;
+    Color {
+        r: grey,
+        g: grey,
+        b: grey,
+        a: color.a,
+    }
+}
+
+/// The rectangles making up a wall tile of `tile_size_px`, relative to
+/// the tile's top-left corner.
+///
+/// Every wall has a block in the middle and a line going to each
+/// neighbouring wall in `mask` (see `Map::wall_mask`). Together they look
+/// like box-drawing characters (`─│┌┐└┘├┤┬┴┼`) which the fonts don't have.
+pub fn wall_sprite(mask: u8, tile_size_px: Vector) -> Vec<Rectangle> {
+    let thickness = (tile_size_px.x.min(tile_size_px.y) / 4.0).round();
+    let center = tile_size_px / 2.0;
+    let half = Vector::new(thickness, thickness) / 2.0;
+
+    let mut parts = vec![Rectangle::new(center - half, (thickness, thickness))];
+    if mask & WALL_NORTH != 0 {
+        parts.push(Rectangle::new(
+            (center.x - half.x, 0.0),
+            (thickness, center.y),
+        ));
+    }
+    if mask & WALL_SOUTH != 0 {
+        parts.push(Rectangle::new(
+            (center.x - half.x, center.y),
+            (thickness, center.y),
+        ));
+    }
+    if mask & WALL_WEST != 0 {
+        parts.push(Rectangle::new(
+            (0.0, center.y - half.y),
+            (center.x, thickness),
+        ));
+    }
+    if mask & WALL_EAST != 0 {
+        parts.push(Rectangle::new(
+            (center.x, center.y - half.y),
+            (center.x, thickness),
+        ));
+    }
+    parts
+}
+
+/// The filled part of a bar (e.g. the health bar) that's `ratio` (from
+/// 0.0 to 1.0) full.
+pub fn bar_fill(area: Rectangle, ratio: f32) -> Rectangle {
+    Rectangle::new(area.pos, (area.size.x * ratio.clamp(0.0, 1.0), area.size.y))
+}
+
+/// The most tiles across and down the map's area on the screen has room
+/// for. Bigger maps scroll with the player.
+pub const MAX_VIEW_TILES: (i32, i32) = (32, 20);
+
+/// How many tiles across and down the map's area on the screen is for
+/// levels of `map_size`.
+pub fn view_tiles(map_size: Vector) -> Vector {
+    Vector::new(
+        map_size.x.min(MAX_VIEW_TILES.0 as f32),
+        map_size.y.min(MAX_VIEW_TILES.1 as f32),
+    )
+}
+
+/// The smallest the map tiles get when zooming out.
+pub const MIN_TILE_PX: f32 = 12.0;
+
+/// The largest the map tiles get when zooming in.
+pub const MAX_TILE_PX: f32 = 48.0;
+
+/// How much one turn of the mouse wheel zooms by.
+pub const ZOOM_STEP_PX: f32 = 4.0;
+
+/// The tile size after zooming in (the mouse wheel rolled forward,
+/// `notches` below zero) or out by `ZOOM_STEP_PX`. It stays between
+/// `MIN_TILE_PX` and `MAX_TILE_PX`.
+pub fn zoom(tile_px: f32, notches: f32) -> f32 {
+    let step = if notches < 0.0 {
+        ZOOM_STEP_PX
+    } else if notches > 0.0 {
+        -ZOOM_STEP_PX
+    } else {
+        0.0
+    };
+    (tile_px + step).clamp(MIN_TILE_PX, MAX_TILE_PX)
+}
+
+/// The part of the map shown in the map's area on the screen.
+///
+/// When the tiles are too big for the whole map to fit, the view
+/// follows `center` (the player) and stops at the edges of the map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapView {
+    /// Where the top-left tile of the view goes on the screen.
+    pub offset_px: Vector,
+    pub tile_size_px: Vector,
+    /// The top-left tile of the view.
+    pub origin: Vector,
+    /// How many tiles fit across and down.
+    pub tiles: Vector,
+}
+
+impl MapView {
+    pub fn new(area: Rectangle, tile_px: f32, map_size: Vector, center: Vector) -> Self {
+        let tiles = Vector::new(
+            (area.size.x / tile_px).floor().min(map_size.x),
+            (area.size.y / tile_px).floor().min(map_size.y),
+        );
+        let axis = |center: f32, tiles: f32, size: f32| {
+            (center - (tiles / 2.0).floor()).clamp(0.0, size - tiles)
+        };
+        MapView {
+            offset_px: area.pos,
+            tile_size_px: Vector::new(tile_px, tile_px),
+            origin: Vector::new(
+                axis(center.x, tiles.x, map_size.x),
+                axis(center.y, tiles.y, map_size.y),
+            ),
+            tiles,
+        }
+    }
+
+    /// Where the tile at `pos` goes on the screen, `None` if it's out of
+    /// view.
+    pub fn tile_px(&self, pos: Vector) -> Option<Vector> {
+        let relative = pos - self.origin;
+        let inside = relative.x >= 0.0
+            && relative.y >= 0.0
+            && relative.x < self.tiles.x
+            && relative.y < self.tiles.y;
+        if inside {
+            Some(self.offset_px + relative.times(self.tile_size_px))
+        } else {
+            None
+        }
+    }
+
+    /// The tile under the point `pos_px` on the screen.
+    pub fn tile_at(&self, pos_px: Vector) -> Vector {
+        let relative = pos_px - self.offset_px;
+        self.origin
+            + Vector::new(
+                (relative.x / self.tile_size_px.x).floor(),
+                (relative.y / self.tile_size_px.y).floor(),
+            )
+    }
+}
+
+/// A box on the screen with lines of text in it.
+///
+/// This only works out where everything goes. The game draws the
+/// `area` filled with the `background`, the `borders`, the title and
+/// then each line at `line_pos`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Panel {
+    pub area: Rectangle,
+    pub title: Option<String>,
+    /// `None` leaves whatever is behind the panel showing.
+    pub background: Option<Color>,
+    pub border: Option<Color>,
+    /// The space between the border and the text.
+    pub padding_px: f32,
+    pub line_height_px: f32,
+}
+
+impl Panel {
+    /// The thickness of the border.
+    pub const BORDER_PX: f32 = 2.0;
+
+    /// A nearly opaque panel with a border, covering whatever is under
+    /// it.
+    pub fn new(area: Rectangle) -> Self {
+        Panel {
+            area,
+            title: None,
+            background: Some(Color::WHITE.with_alpha(0.95)),
+            border: Some(Color::BLACK),
+            padding_px: 10.0,
+            line_height_px: 20.0,
+        }
+    }
+
+    /// Just the text, e.g. for the inventory next to the map.
+    pub fn plain(area: Rectangle) -> Self {
+        Panel {
+            background: None,
+            border: None,
+            padding_px: 0.0,
+            ..Panel::new(area)
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_line_height(mut self, line_height_px: f32) -> Self {
+        self.line_height_px = line_height_px;
+        self
+    }
+
+    /// The four sides of the border, inside the `area`. Empty if the
+    /// panel has no border.
+    pub fn borders(&self) -> Vec<Rectangle> {
+        if self.border.is_none() {
+            return vec![];
+        }
+        let (pos, size) = (self.area.pos, self.area.size);
+        let thickness = Panel::BORDER_PX;
+        vec![
+            Rectangle::new(pos, (size.x, thickness)),
+            Rectangle::new((pos.x, pos.y + size.y - thickness), (size.x, thickness)),
+            Rectangle::new(pos, (thickness, size.y)),
+            Rectangle::new((pos.x + size.x - thickness, pos.y), (thickness, size.y)),
+        ]
+    }
+
+    /// The top-left corner of the title.
+    pub fn title_pos(&self) -> Vector {
+        self.area.pos + Vector::new(self.padding_px, self.padding_px)
+    }
+
+    /// The top-left corner of the line of text at `index`. The lines
+    /// start under the title, if there is one.
+    pub fn line_pos(&self, index: usize) -> Vector {
+        let title_px = if self.title.is_some() {
+            self.line_height_px * 2.0
+        } else {
+            0.0
+        };
+        self.title_pos() + Vector::new(0.0, title_px + index as f32 * self.line_height_px)
+    }
+
+    /// How wide the lines of text can be.
+    pub fn text_width(&self) -> f32 {
+        self.area.size.x - self.padding_px * 2.0
+    }
+
+    /// How many lines of text fit in the panel.
+    pub fn capacity(&self) -> usize {
+        let bottom = self.area.pos.y + self.area.size.y - self.padding_px;
+        let available = bottom - self.line_pos(0).y;
+        (available / self.line_height_px).max(0.0) as usize
+    }
+}
+
+/// A list of choices with one of them selected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Menu<T> {
+    pub entries: Vec<(T, String)>,
+    pub selected: usize,
+}
+
+impl<T: Copy> Menu<T> {
+    pub fn new(entries: Vec<(T, String)>) -> Self {
+        assert!(!entries.is_empty(), "A menu needs at least one entry.");
+        Menu {
+            entries,
+            selected: 0,
+        }
+    }
+
+    pub fn selected(&self) -> T {
+        self.entries[self.selected].0
+    }
+
+    /// Move the selection down, wrapping around at the bottom.
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.entries.len();
+    }
+
+    /// Move the selection up, wrapping around at the top.
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + self.entries.len() - 1) % self.entries.len();
+    }
+
+    /// The entries as text, the selected one marked.
+    pub fn lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, (_, label))| {
+                let marker = if index == self.selected { ">" } else { " " };
+                format!("{} {}", marker, label)
+            })
+            .collect()
+    }
+}
+
+/// Split `lines` into pages of at most `per_page` lines. There's always
+/// at least one page, even if it's empty.
+pub fn pages(lines: &[String], per_page: usize) -> Vec<&[String]> {
+    if lines.is_empty() {
+        return vec![&[]];
+    }
+    lines.chunks(per_page.max(1)).collect()
+}
+
+/// Like `pages` but every page except the last one ends with a "more"
+/// line. That line counts towards the `per_page`.
+pub fn more_pages(lines: &[String], per_page: usize) -> Vec<Vec<String>> {
+    let mut pages: Vec<Vec<String>> = pages(lines, per_page.saturating_sub(1).max(1))
+        .into_iter()
+        .map(|page| page.to_vec())
+        .collect();
+    let last = pages.len() - 1;
+    let more = locale::text("ui.more");
+    for page in &mut pages[..last] {
+        page.push(more.clone());
+    }
+    pages
+}
+
+/// Break `text` into lines no wider than `width_px`, between words when
+/// possible. `measure` gives the width of a piece of text, see
+/// `TextMetrics`. Words too long for a line of their own are split
+/// wherever they need to be.
+pub fn wrap(text: &str, width_px: f32, measure: impl Fn(&str) -> f32) -> Vec<String> {
+    // Keep the spacing of lines that fit, e.g. the columns of the help
+    if measure(text) <= width_px {
+        return vec![text.to_string()];
+    }
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if measure(&candidate) <= width_px {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if measure(&line) > width_px && line.chars().count() > 1 {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Give the `lines` that `wrap` made out of the segments' text the
+/// segments' colours back.
+pub fn split_segments(segments: &[Segment], lines: &[String]) -> Vec<Vec<Segment>> {
+    let mut chars = segments
+        .iter()
+        .flat_map(|segment| segment.text.chars().map(move |c| (c, segment.color)))
+        .peekable();
+    let mut split = vec![];
+    for line in lines {
+        let mut colored: Vec<Segment> = vec![];
+        for c in line.chars() {
+            // Skip the whitespace the wrapping dropped between the lines
+            while chars
+                .peek()
+                .is_some_and(|&(next, _)| next != c && next.is_whitespace())
+            {
+                chars.next();
+            }
+            let color = chars.next().and_then(|(_, color)| color);
+            match colored.last_mut() {
+                Some(last) if last.color == color => last.text.push(c),
+                _ => colored.push(Segment {
+                    text: c.to_string(),
+                    color,
+                }),
+            }
+        }
+        split.push(colored);
+    }
+    split
+}
+
+/// The hotbar as one line: the key and icon of every slot. Skills still
+/// cooling down are greyed out with the turns they have left, e.g.
+/// `3:*(4)`.
+pub fn hotbar(cooldowns: &Cooldowns) -> Vec<Segment> {
+    HOTBAR
+        .iter()
+        .enumerate()
+        .map(|(slot, skill)| {
+            let key = slot + 1;
+            match skill {
+                Some(skill) if cooldowns.is_ready(*skill) => {
+                    Segment::plain(format!("{}:{}  ", key, skill.glyph()))
+                }
+                Some(skill) => Segment {
+                    text: format!(
+                        "{}:{}({})  ",
+                        key,
+                        skill.glyph(),
+                        cooldowns.remaining(*skill)
+                    ),
+                    color: Some(GREYED_OUT),
+                },
+                None => Segment {
+                    text: format!("{}:-  ", key),
+                    color: Some(GREYED_OUT),
+                },
+            }
+        })
+        .collect()
+}
+
+/// The size of text drawn with a font, without drawing it.
+pub struct TextMetrics {
+    font: Font<'static>,
+    scale: Scale,
+}
+
+impl TextMetrics {
+    /// `font` is the contents of a TrueType file, `size` the height the
+    /// text is rendered at.
+    pub fn new(font: Vec<u8>, size: f32) -> Result<Self, String> {
+        let font = Font::from_bytes(font).map_err(|error| error.to_string())?;
+        Ok(TextMetrics {
+            font,
+            scale: Scale::uniform(size),
+        })
+    }
+
+    pub fn width(&self, text: &str) -> f32 {
+        self.font
+            .layout(text, self.scale, point(0.0, 0.0))
+            .last()
+            .map_or(0.0, |glyph| {
+                glyph.position().x + glyph.unpositioned().h_metrics().advance_width
+            })
+    }
+
+    /// `wrap` with this font's measurements.
+    pub fn wrap(&self, text: &str, width_px: f32) -> Vec<String> {
+        wrap(text, width_px, |text| self.width(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::SHALLOW_WATER,
+        message::{Message, HIGHLIGHT},
+        skills::Skill,
+        world::{
+            tests::{goblin, player_with},
+            Entity,
+        },
+    };
+
+    #[test]
+    fn health_goes_from_red_to_green() {
+        assert_eq!(health_color(0.0), Color::RED);
+        assert_eq!(health_color(0.5), Color::YELLOW);
+        assert_eq!(health_color(1.0), Color::GREEN);
+        assert_eq!(health_color(-3.0), Color::RED);
+        assert_eq!(health_color(7.0), Color::GREEN);
+    }
+
+    #[test]
+    fn only_wounded_monsters_get_health_bars() {
+        let mut world = player_with(vec![goblin(7, 2)], 5);
+        let (monster, player) = (0, world.player_id);
+        world.entities[player].hp = 1;
+        assert!(!shows_health_bar(&world, monster));
+        assert!(!shows_health_bar(&world, player));
+        world.entities[monster].hp = 1;
+        assert!(shows_health_bar(&world, monster));
+
+        let bar = monster_bar(Rectangle::new((48, 72), (24, 24)));
+        assert_eq!(bar, Rectangle::new((48, 69), (24, 3)));
+    }
+
+    #[test]
+    fn costs_are_shown_before_acting() {
+        let mut world = player_with(vec![goblin(6, 2)], 5);
+        world.map.set_glyph(Vector::new(5, 6), SHALLOW_WATER);
+        let label = |x, y| cost_label(&world, &Action::Move(Vector::new(x, y)));
+        assert_eq!(label(-1, 0), "Move: 100");
+        assert_eq!(label(1, 0), "Attack: 100");
+        assert_eq!(label(0, 1), "Wade: 200");
+        assert_eq!(cost_label(&world, &Action::Sneak), "Sneak: 0");
+        let firebolt = Action::Skill(Skill::Firebolt, Vector::new(6, 5));
+        assert_eq!(cost_label(&world, &firebolt), "Firebolt: 100");
+    }
+
+    #[test]
+    fn remembered_colors_are_grey() {
+        for &color in &[Color::RED, Color::BLUE, Color::BLACK] {
+            let grey = remembered_color(color);
+            assert_eq!(grey.r, grey.g);
+            assert_eq!(grey.g, grey.b);
+            assert!(grey.r >= 0.5 && grey.r <= 0.8);
+        }
+        assert_eq!(remembered_color(Color::RED.with_alpha(0.5)).a, 0.5);
+    }
+
+    #[test]
+    fn wall_sprites_reach_the_connected_edges() {
+        let size = Vector::new(24, 24);
+        assert_eq!(wall_sprite(0, size).len(), 1);
+
+        let corner = wall_sprite(WALL_EAST | WALL_SOUTH, size);
+        assert_eq!(corner.len(), 3);
+        let right = corner.iter().map(|part| part.pos.x + part.size.x);
+        let bottom = corner.iter().map(|part| part.pos.y + part.size.y);
+        assert_eq!(right.fold(0.0, f32::max), 24.0);
+        assert_eq!(bottom.fold(0.0, f32::max), 24.0);
+        assert!(corner
+            .iter()
+            .all(|part| part.pos.x >= 9.0 && part.pos.y >= 9.0));
+    }
+
+    #[test]
+    fn zooming_stays_within_bounds() {
+        assert_eq!(zoom(24.0, -120.0), 28.0);
+        assert_eq!(zoom(24.0, 3.0), 20.0);
+        assert_eq!(zoom(24.0, 0.0), 24.0);
+        assert_eq!(zoom(MAX_TILE_PX, -1.0), MAX_TILE_PX);
+        assert_eq!(zoom(MIN_TILE_PX, 1.0), MIN_TILE_PX);
+    }
+
+    #[test]
+    fn big_maps_get_a_view_of_their_own() {
+        assert_eq!(view_tiles(Vector::new(20, 15)), Vector::new(20, 15));
+        assert_eq!(view_tiles(Vector::new(80, 50)), Vector::new(32, 20));
+        assert_eq!(view_tiles(Vector::new(25, 60)), Vector::new(25, 20));
+    }
+
+    #[test]
+    fn zoomed_in_views_follow_the_player_to_the_edge() {
+        // Room for a 20x15 map at 24 pixels a tile
+        let area = Rectangle::new((50, 120), (480, 360));
+        let map_size = Vector::new(20, 15);
+        let whole = MapView::new(area, 24.0, map_size, Vector::new(3, 3));
+        assert_eq!(whole.origin, Vector::new(0, 0));
+        assert_eq!(
+            whole.tile_px(Vector::new(19, 14)),
+            Some(Vector::new(506, 456))
+        );
+
+        let view = MapView::new(area, 48.0, map_size, Vector::new(12, 7));
+        assert_eq!(view.tiles, Vector::new(10, 7));
+        assert_eq!(view.origin, Vector::new(7, 4));
+        assert_eq!(
+            view.tile_px(Vector::new(12, 7)),
+            Some(Vector::new(290, 264))
+        );
+        assert_eq!(view.tile_px(Vector::new(3, 3)), None);
+        assert_eq!(view.tile_at(Vector::new(290, 264)), Vector::new(12, 7));
+
+        let corner = MapView::new(area, 48.0, map_size, Vector::new(19, 14));
+        assert_eq!(corner.origin, Vector::new(10, 8));
+    }
+
+    #[test]
+    fn long_pages_say_there_is_more() {
+        let lines: Vec<String> = (0..5).map(|n| n.to_string()).collect();
+        let pages = more_pages(&lines, 3);
+        let more = "-- more --";
+        assert_eq!(
+            pages,
+            vec![vec!["0", "1", more], vec!["2", "3", more], vec!["4"]]
+        );
+        assert_eq!(more_pages(&lines, 10).len(), 1);
+    }
+
+    #[test]
+    fn text_wraps_between_words() {
+        // Every character is 10 pixels wide
+        let measure = |text: &str| text.chars().count() as f32 * 10.0;
+        assert_eq!(
+            wrap("You hit the goblin.", 100.0, measure),
+            vec!["You hit", "the", "goblin."]
+        );
+        assert_eq!(wrap("Aaaaaaaa", 30.0, measure), vec!["Aaa", "aaa", "aa"]);
+        assert_eq!(wrap("", 30.0, measure), vec![""]);
+        assert_eq!(wrap("a   b", 50.0, measure), vec!["a   b"]);
+    }
+
+    #[test]
+    fn wrapped_messages_keep_their_colours() {
+        let message = Message::parse("You hit the *goblin boss*.");
+        let measure = |text: &str| text.chars().count() as f32 * 10.0;
+        let lines = wrap(&message.text(), 150.0, measure);
+        assert_eq!(lines, vec!["You hit the", "goblin boss."]);
+        let split = split_segments(&message.segments, &lines);
+        assert_eq!(split[0], vec![Segment::plain("You hit the")]);
+        assert_eq!(
+            split[1],
+            vec![
+                Segment {
+                    text: "goblin boss".to_string(),
+                    color: Some(HIGHLIGHT),
+                },
+                Segment::plain("."),
+            ]
+        );
+    }
+
+    #[test]
+    fn cooling_skills_are_greyed_out() {
+        let mut cooldowns = Cooldowns::default();
+        cooldowns.start(Skill::Firebolt);
+        let hotbar = hotbar(&cooldowns);
+        assert_eq!(hotbar.len(), HOTBAR.len());
+        assert_eq!(hotbar[0], Segment::plain("1:[  "));
+        assert_eq!(hotbar[2].text, "3:*(10)  ");
+        assert_eq!(hotbar[2].color, Some(GREYED_OUT));
+        assert_eq!(hotbar[4].text, "5:-  ");
+    }
+
+    #[test]
+    fn text_is_measured_with_the_font() {
+        let font = include_bytes!("../static/mononoki-Regular.ttf").to_vec();
+        let metrics = TextMetrics::new(font, 20.0).unwrap();
+        let short = metrics.width("goblin");
+        assert!(short > 0.0);
+        assert!((metrics.width("goblin goblin") - short * 13.0 / 6.0).abs() < 1.0);
+        assert_eq!(metrics.wrap("goblin goblin", short * 1.5).len(), 2);
+    }
+
+    #[test]
+    fn panel_text_goes_under_the_title() {
+        let panel = Panel::new(Rectangle::new((100, 50), (200, 150)));
+        assert_eq!(panel.line_pos(0), Vector::new(110, 60));
+        assert_eq!(panel.line_pos(2), Vector::new(110, 100));
+        assert_eq!(panel.capacity(), 6);
+        assert_eq!(panel.borders().len(), 4);
+
+        let panel = panel.with_title("Inventory");
+        assert_eq!(panel.title_pos(), Vector::new(110, 60));
+        assert_eq!(panel.line_pos(0), Vector::new(110, 100));
+        assert_eq!(panel.capacity(), 4);
+
+        assert!(Panel::plain(Rectangle::new((0, 0), (10, 10)))
+            .borders()
+            .is_empty());
+    }
+
+    #[test]
+    fn menu_selection_wraps_around() {
+        let mut menu = Menu::new(vec![(1, "One".to_string()), (2, "Two".to_string())]);
+        assert_eq!(menu.selected(), 1);
+        menu.select_previous();
+        assert_eq!(menu.selected(), 2);
+        menu.select_next();
+        assert_eq!(menu.selected(), 1);
+        assert_eq!(menu.lines(), vec!["> One", "  Two"]);
+    }
+
+    #[test]
+    fn long_texts_are_split_into_pages() {
+        let lines: Vec<String> = (0..5).map(|n| n.to_string()).collect();
+        let pages = pages(&lines, 2);
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[2], ["4".to_string()]);
+        assert_eq!(super::pages(&[], 2).len(), 1);
+    }
+
+    #[test]
+    fn invisible_monsters_only_show_up_next_to_the_player() {
+        let wraith = Entity {
+            color: Color::BLACK,
+            invisible: true,
+            ..goblin(8, 2)
+        };
+        let mut world = player_with(vec![wraith], 5);
+        let player = &mut world.entities[world.player_id];
+        player.color = Color::BLUE;
+        player.hp = 1;
+        assert_eq!(entity_color(&world, 0, false), None);
+        world.entities[0].pos = Vector::new(6, 6);
+        assert_eq!(entity_color(&world, 0, false).unwrap().a, 0.5);
+
+        // The player is nearly dead
+        let player_id = world.player_id;
+        assert_eq!(entity_color(&world, player_id, false), Some(Color::BLUE));
+        assert_eq!(entity_color(&world, player_id, true), Some(Color::RED));
+    }
+}
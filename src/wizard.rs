@@ -0,0 +1,298 @@
+//! Wizard mode: a debug mode for trying things out without losing the
+//! game. Dying doesn't end it and cheats are allowed.
+
+use quicksilver::geom::Vector;
+
+use crate::{
+    geometry::tile_key,
+    locale, spawn,
+    templates::Behavior,
+    world::{capitalize, with_article, Outcome, World},
+};
+
+/// How the game treats death and cheating.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GameMode {
+    /// Death is permanent and the save file goes with it.
+    #[default]
+    Normal,
+    /// Started with `--wizard`. The player can't die and can cheat.
+    Wizard,
+    /// The daily challenge, see `daily`. Normal rules, with no way into
+    /// wizard mode.
+    Daily,
+}
+
+/// Debug commands only available in wizard mode. They can be typed into
+/// the console and some have keys of their own.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cheat {
+    /// Restore the player's health.
+    Heal,
+    /// Show the whole level.
+    Reveal,
+    /// Go down to the next level wherever the player stands.
+    Descend,
+    /// Put a monster (by its template key) on the tile.
+    Spawn(String, Vector),
+    /// Move the player to the tile.
+    Teleport(Vector),
+}
+
+impl Cheat {
+    /// Read a console command, e.g. `spawn goblin 5 3` or `tp 10 10`.
+    pub fn parse(text: &str) -> Result<Cheat, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let pos = |x: &str, y: &str| -> Result<Vector, String> {
+            match (x.parse::<i32>(), y.parse::<i32>()) {
+                (Ok(x), Ok(y)) => Ok(Vector::new(x, y)),
+                _ => Err(format!("Not a position: `{} {}`", x, y)),
+            }
+        };
+        match words.as_slice() {
+            ["heal"] => Ok(Cheat::Heal),
+            ["reveal"] => Ok(Cheat::Reveal),
+            ["descend"] => Ok(Cheat::Descend),
+            ["tp", x, y] => Ok(Cheat::Teleport(pos(x, y)?)),
+            // Template keys can have spaces in them, e.g. "goblin boss"
+            ["spawn", name @ .., x, y] if !name.is_empty() => {
+                Ok(Cheat::Spawn(name.join(" "), pos(x, y)?))
+            }
+            [] => Err(
+                "Type a command: heal, reveal, descend, spawn <monster> <x> <y> or tp <x> <y>."
+                    .to_string(),
+            ),
+            _ => Err(format!("Unknown command: `{}`", text.trim())),
+        }
+    }
+
+    /// The command as it's typed into the console.
+    pub fn to_text(&self) -> String {
+        match self {
+            Cheat::Heal => "heal".to_string(),
+            Cheat::Reveal => "reveal".to_string(),
+            Cheat::Descend => "descend".to_string(),
+            Cheat::Spawn(name, pos) => format!("spawn {} {} {}", name, pos.x as i32, pos.y as i32),
+            Cheat::Teleport(pos) => format!("tp {} {}", pos.x as i32, pos.y as i32),
+        }
+    }
+}
+
+/// Apply the cheat. It doesn't take a turn.
+pub fn cheat(world: &mut World, cheat: Cheat) {
+    if world.mode != GameMode::Wizard {
+        return world.log(locale::text("log.wizard-only"));
+    }
+    match cheat {
+        Cheat::Heal => {
+            let player_id = world.player_id;
+            let player = &mut world.entities[player_id];
+            player.hp = player.max_hp;
+            world.log(locale::text("log.cheat-healed"));
+        }
+        Cheat::Reveal => {
+            let keys: Vec<(i32, i32)> = world
+                .map
+                .tiles
+                .iter()
+                .map(|tile| tile_key(tile.pos))
+                .collect();
+            world.fov.explored.extend(keys);
+            world.log(locale::text("log.cheat-revealed"));
+        }
+        Cheat::Descend => {
+            if world.depth < world.templates.max_depth(world.dungeon) {
+                world.descend();
+            } else {
+                world.log(locale::text("log.cheat-no-deeper"));
+            }
+        }
+        Cheat::Spawn(name, pos) => {
+            let template = match world.templates.monsters.get(&name) {
+                Some(template) => template,
+                None => {
+                    return world.log(locale::message("log.cheat-no-monster", &[("name", &name)]))
+                }
+            };
+            if world.map.is_blocked(pos) || world.blocker_at(pos).is_some() {
+                return world.log(locale::text("log.cheat-no-room"));
+            }
+            let monster = spawn::monster(&name, template, pos);
+            world.log(locale::message(
+                "log.cheat-appears",
+                &[("name", &capitalize(&with_article(&monster.name)))],
+            ));
+            world.entities.push(monster);
+            world.rebuild_spatial();
+            world.update_fov();
+        }
+        Cheat::Teleport(pos) => {
+            if world.map.is_blocked(pos) || world.blocker_at(pos).is_some() {
+                return world.log(locale::text("log.cheat-no-teleport"));
+            }
+            let player_id = world.player_id;
+            world.move_entity(player_id, pos);
+            world.update_fov();
+            world.log(locale::text("log.cheat-teleport"));
+        }
+    }
+}
+
+/// Everything about the tile at `pos` and the entities on it, for the
+/// inspector.
+pub fn inspect(world: &World, pos: Vector) -> Vec<String> {
+    let (x, y) = tile_key(pos);
+    let tile = match world.map.tile(pos) {
+        Some(tile) => tile,
+        None => return vec![format!("({}, {}) is off the map", x, y)],
+    };
+    let mut lines = vec![
+        format!("({}, {}) '{}'", x, y, tile.glyph),
+        format!("light {:.2}", world.light.level(pos)),
+    ];
+    for &id in world.spatial.entities_at(pos) {
+        let entity = &world.entities[id];
+        lines.push(String::new());
+        lines.push(format!("#{} {} '{}'", id, entity.name, entity.glyph));
+        lines.push(format!(
+            "hp {}/{} {:?}",
+            entity.hp, entity.max_hp, entity.faction
+        ));
+        if entity.blocks_tile {
+            lines.push("blocks the tile".to_string());
+        }
+        if let Some(ai) = &entity.ai {
+            lines.push(format!("ai {:?}", ai.behavior));
+            lines.push(format!("alerted {} provoked {}", ai.alerted, ai.provoked));
+            if ai.asleep {
+                lines.push("asleep".to_string());
+            }
+            if let Some(pos) = ai.last_seen {
+                lines.push(format!(
+                    "searching {} {} for {}",
+                    pos.x, pos.y, ai.search_turns
+                ));
+            }
+            if ai.confused > 0 {
+                lines.push(format!("confused for {}", ai.confused));
+            }
+            if ai.behavior == Behavior::Boss {
+                lines.push(format!("phase {:?}", ai.phase));
+            }
+            if let Some(pack) = ai.pack {
+                lines.push(format!("pack {}", pack));
+            }
+        }
+        if let Some(item) = &entity.item {
+            lines.push(format!("item {}", item.template));
+        }
+        if let Some(radius) = entity.light {
+            lines.push(format!("light radius {}", radius));
+        }
+        if entity.gold > 0 {
+            lines.push(format!("gold {}", entity.gold));
+        }
+        if !entity.inventory.is_empty() {
+            lines.push(format!("carries {} items", entity.inventory.len()));
+        }
+    }
+    lines
+}
+
+/// In wizard mode the player survives what would have killed them. Return
+/// `true` if that just happened.
+pub fn save_from_death(world: &mut World) -> bool {
+    let player_id = world.player_id;
+    if world.mode != GameMode::Wizard || world.entities[player_id].is_alive() {
+        return false;
+    }
+    let player = &mut world.entities[player_id];
+    player.hp = player.max_hp;
+    world.log(locale::text("log.wizard-saves"));
+    true
+}
+
+/// Whether the game has ended with a death that should wipe the save.
+pub fn is_permadeath(world: &World) -> bool {
+    world.mode != GameMode::Wizard && world.outcome == Some(Outcome::Died)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Action;
+
+    #[test]
+    fn wizards_survive_and_can_cheat() {
+        let mut world = World::new(3);
+        let player_id = world.player_id;
+        world.entities[player_id].hp = 3;
+        world.player_turn(Action::Cheat(Cheat::Heal));
+        assert!(world.player().hp < world.player().max_hp);
+
+        world.mode = GameMode::Wizard;
+        world.player_turn(Action::Cheat(Cheat::Heal));
+        assert_eq!(world.player().hp, world.player().max_hp);
+        // From the surface down to the first level
+        world.player_turn(Action::Cheat(Cheat::Descend));
+        assert_eq!(world.depth, 1);
+
+        let player_id = world.player_id;
+        world.entities[player_id].hp = -2;
+        world.player_turn(Action::Wait);
+        assert_eq!(world.outcome, None);
+        assert!(!is_permadeath(&world));
+    }
+
+    #[test]
+    fn the_inspector_shows_the_entities_on_a_tile() {
+        let world = World::new(5);
+        let lines = inspect(&world, world.player().pos);
+        let id = world.player_id;
+        assert!(lines.contains(&format!("#{} player '@'", id)));
+        assert!(lines.contains(&"hp 7/7 Player".to_string()));
+        assert_eq!(inspect(&world, Vector::new(-1, 0)).len(), 1);
+    }
+
+    #[test]
+    fn console_commands_round_trip() {
+        for text in &[
+            "heal",
+            "reveal",
+            "descend",
+            "spawn goblin boss 5 3",
+            "tp 10 -2",
+        ] {
+            assert_eq!(
+                Cheat::parse(text).map(|cheat| cheat.to_text()),
+                Ok(text.to_string())
+            );
+        }
+        assert!(Cheat::parse("spawn 5 3").is_err());
+        assert!(Cheat::parse("tp here").is_err());
+        assert!(Cheat::parse("fly").is_err());
+    }
+
+    #[test]
+    fn spawning_and_teleporting() {
+        let mut world = World::new(5);
+        world.mode = GameMode::Wizard;
+        let start = world.player().pos;
+        let free = world
+            .map
+            .tiles
+            .iter()
+            .map(|tile| tile.pos)
+            .find(|&pos| {
+                pos != start && !world.map.is_blocked(pos) && world.blocker_at(pos).is_none()
+            })
+            .unwrap();
+
+        world.player_turn(Action::Cheat(Cheat::Teleport(free)));
+        assert_eq!(world.player().pos, free);
+        world.player_turn(Action::Cheat(Cheat::Spawn("orc".to_string(), start)));
+        let orc = world.blocker_at(start).expect("No orc spawned.");
+        assert_eq!(world.entities[orc].name, "orc");
+        assert_eq!(world.log.last().unwrap(), "An orc appears.");
+    }
+}
@@ -0,0 +1,1836 @@
+//! The game state and the rules that change it.
+//!
+//! Nothing in here knows about the window, keyboard or fonts so the game
+//! logic can be driven (and tested) without opening a window.
+
+use quicksilver::{geom::Vector, graphics::Color};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use std::{collections::BTreeSet, fmt::Display};
+
+use crate::{
+    ai::Phase,
+    ammo,
+    chest::{self, Chest},
+    config::Config,
+    decoration,
+    events::{self, Event, EventQueue},
+    fov::Fov,
+    geometry::los,
+    hazards::{self, Hazard},
+    identify::{self, Identification},
+    levels::{LevelId, LevelStore, SavedLevel},
+    lighting::LightMap,
+    locale, locks,
+    map::{
+        Map, BLOCKS_SIGHT, DEEP_WATER, LAVA, LOCKED_DOOR, SHALLOW_WATER, STAIRS_DOWN, STAIRS_UP,
+        WALL,
+    },
+    mapgen::generate_level,
+    message::Message,
+    noise::{self, Noise},
+    overworld::{self, DAYLIGHT},
+    packs, prefabs,
+    profile::{Ability, PlayerProfile, KEEN_EYES_RANGE},
+    regeneration,
+    schedule::{self, Speed},
+    shop,
+    skills::{self, Cooldowns, Skill},
+    spatial::SpatialMap,
+    spawn,
+    stats::Stats,
+    story,
+    templates::{Behavior, Faction, Slot, Templates, AMULET, MAIN_DUNGEON},
+    throwing,
+    wizard::{self, Cheat, GameMode},
+};
+
+/// The size of every level in tiles, unless the `Config` says otherwise.
+pub const MAP_WIDTH: i32 = 20;
+pub const MAP_HEIGHT: i32 = 15;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Entity {
+    pub pos: Vector,
+    pub glyph: char,
+    pub color: Color,
+    pub name: String,
+    pub hp: i32,
+    pub max_hp: i32,
+    /// Set for things that can be picked up.
+    pub item: Option<Item>,
+    /// The items carried around.
+    pub inventory: Vec<Entity>,
+    /// Nothing else can stand on the same tile as this entity.
+    pub blocks_tile: bool,
+    pub faction: Faction,
+    /// The player's wallet, or the size of a pile of gold lying on the
+    /// floor.
+    pub gold: u32,
+    /// Bumping into a shopkeeper trades with them instead of attacking.
+    /// Their inventory is what they sell.
+    pub shopkeeper: bool,
+    /// Set for monsters that act on their own.
+    pub ai: Option<Ai>,
+    /// The radius of the light the entity gives off, if any.
+    pub light: Option<f32>,
+    /// Can go through deep water.
+    pub swims: bool,
+    /// Can only be seen from right next to it.
+    pub invisible: bool,
+    /// A line or two of flavour text from the templates.
+    pub description: Option<String>,
+    /// What the entity may leave behind when it dies: item templates
+    /// with the chance of each.
+    pub drops: Vec<(String, f64)>,
+    /// Heals a point of damage every this many turns, see the
+    /// `regeneration` module.
+    pub regeneration: Option<u32>,
+    /// The turn the entity was last hurt on.
+    pub last_hurt: Option<u32>,
+    /// Turns until each skill can be used again.
+    pub cooldowns: Cooldowns,
+    /// Set for chests. Bumping into one opens it and its inventory is
+    /// what's inside.
+    pub chest: Option<Chest>,
+    /// How quickly the entity acts, see the `schedule` module.
+    pub speed: Speed,
+    /// Ticks until the entity's next action.
+    pub wait: u32,
+    /// The key of the monster's entry in `Templates::monsters`.
+    pub template: Option<String>,
+}
+
+impl Entity {
+    /// Living entities block movement and can be attacked. Corpses can
+    /// be walked over.
+    pub fn is_alive(&self) -> bool {
+        self.hp > 0
+    }
+
+    /// Turn the entity into its corpse.
+    pub fn die(&mut self) {
+        self.hp = 0;
+        self.glyph = '%';
+        self.color = Color::PURPLE;
+        self.blocks_tile = false;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    /// The key of the item's entry in `Templates::items`.
+    pub template: String,
+    /// Worn or wielded, for items in an inventory.
+    pub equipped: bool,
+    /// Can't be taken off once equipped.
+    pub cursed: bool,
+    /// Whether the player knows if the item is cursed or not, from
+    /// wearing it or identifying it.
+    pub curse_known: bool,
+    /// How many there are of the item, for the ones that stack (see
+    /// `ItemTemplate::stacks`). Always 1 for the rest.
+    pub count: u32,
+}
+
+impl Default for Item {
+    fn default() -> Self {
+        Item {
+            template: String::new(),
+            equipped: false,
+            cursed: false,
+            curse_known: false,
+            count: 1,
+        }
+    }
+}
+
+/// What a monster's mind is set on. See the `ai` module.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ai {
+    pub behavior: Behavior,
+    pub flees: bool,
+    /// Guards start chasing once they've seen the player.
+    pub alerted: bool,
+    /// The monster stumbles around at random for this many turns.
+    pub confused: u32,
+    /// The player has attacked this monster. Even `Vermin` fight back
+    /// then.
+    pub provoked: bool,
+    /// Monsters spawned together share a pack number. They all go after
+    /// the same target and attacking one provokes the rest.
+    pub pack: Option<usize>,
+    /// Doesn't notice anything until a noise wakes it up.
+    pub asleep: bool,
+    /// Where the monster last saw its target. It goes there once it
+    /// loses sight of them.
+    pub last_seen: Option<Vector>,
+    /// How many more turns the monster looks around `last_seen` before
+    /// it gives up.
+    pub search_turns: u32,
+    /// How far along its fight a `Boss` is.
+    pub phase: Phase,
+    /// The template of the monster a `Boss` calls for help.
+    pub summons: Option<String>,
+    /// Turns until a `Boss` can call for help again.
+    pub summon_cooldown: u32,
+}
+
+/// Somebody got hurt. Kept around so the UI can show it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub target: usize,
+    pub pos: Vector,
+    pub damage: i32,
+}
+
+/// How much damage standing in lava does every turn.
+pub const LAVA_DAMAGE: i32 = 1;
+
+/// How far (in tiles) the player can see.
+pub const SIGHT_RADIUS: f32 = 8.0;
+
+/// The chance that a hit with a shield equipped knocks the target back.
+pub const KNOCKBACK_CHANCE: f64 = 0.5;
+
+/// The extra damage for being knocked into a wall or somebody else.
+pub const SLAM_DAMAGE: i32 = 1;
+
+/// The player can carry one item for each letter of the alphabet.
+pub const INVENTORY_SIZE: usize = 26;
+
+/// How the game ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Died,
+    Won,
+}
+
+/// Everything the player can do on their turn.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Step by the given offset. Bumping into a monster attacks it.
+    Move(Vector),
+    /// Take the stairs down to the next level.
+    Descend,
+    /// Take the stairs up to the previous level (or the surface).
+    Ascend,
+    /// Pick up an item from the floor.
+    PickUp,
+    /// Do nothing for a turn.
+    Wait,
+    /// Buy the item at the index of the open shop's stock.
+    Buy(usize),
+    /// Sell the item at the index of the player's inventory.
+    Sell(usize),
+    /// Equip the item at the index of the player's inventory, or take it
+    /// off if it's already equipped.
+    Equip(usize),
+    /// Throw the item at the index of the player's inventory at the
+    /// tile.
+    Throw(usize, Vector),
+    /// Fire the equipped ranged weapon at the tile, see `ammo`.
+    Fire(Vector),
+    /// Drink or read the item at the index of the player's inventory.
+    Use(usize),
+    /// Use the skill in the direction or at the tile, see `Skill::aim`.
+    Skill(Skill, Vector),
+    /// Start or stop sneaking. Doesn't take a turn.
+    Sneak,
+    /// Take the item at the index of the open chest's inventory.
+    Take(usize),
+    /// Take everything out of the open chest.
+    TakeAll,
+    /// Only allowed in wizard mode.
+    Cheat(Cheat),
+}
+
+pub struct World {
+    pub map: Map,
+    pub entities: Vec<Entity>,
+    pub player_id: usize,
+    /// The current level of `dungeon`, starting at 1. The surface is 0.
+    pub depth: u32,
+    /// Which of `Templates::dungeons` the player is in, or last came out
+    /// of when they're on the surface.
+    pub dungeon: usize,
+    /// The way down into each dungeon on the surface.
+    pub entrances: Vec<Vector>,
+    /// Every level but the current one the player has been to.
+    pub levels: LevelStore,
+    pub templates: Templates,
+    /// The seed the world was created with. The same seed and the same
+    /// player actions always produce the same game.
+    pub seed: u64,
+    /// All the randomness in the game must come from here, otherwise
+    /// replays won't be deterministic.
+    pub rng: StdRng,
+    /// Every hit since the UI last took them out.
+    pub hits: Vec<Hit>,
+    /// What's happened this turn that hasn't been dealt with yet, see
+    /// `events`.
+    pub events: EventQueue,
+    /// Every noise since the monsters last listened, see `noise`.
+    pub noises: Vec<Noise>,
+    /// The player's steps are quieter, see `noise::step_volume`.
+    pub sneaking: bool,
+    /// Turns the player has left stumbling around, see
+    /// `hazards::stumble`.
+    pub confused: u32,
+    /// The gas and fire on the level.
+    pub hazards: Vec<Hazard>,
+    /// The messages shown to the player, oldest first.
+    pub log: Vec<Message>,
+    /// The paragraphs of every story told so far, oldest first, see
+    /// `story`.
+    pub stories: Vec<Vec<String>>,
+    /// The items whose story has been told.
+    pub told: BTreeSet<String>,
+    pub stats: Stats,
+    /// What the potions and scrolls of this run look like.
+    pub identification: Identification,
+    pub mode: GameMode,
+    /// Set once the game is over.
+    pub outcome: Option<Outcome>,
+    /// Where everybody is. All position queries should go through this.
+    /// It's rebuilt at the start of every turn and kept up to date
+    /// during it.
+    pub spatial: SpatialMap,
+    /// The shopkeeper the player is trading with.
+    pub shop: Option<usize>,
+    /// The chest the player has open.
+    pub chest: Option<usize>,
+    /// Updated at the end of every turn.
+    pub fov: Fov,
+    /// Updated along with `fov`.
+    pub light: LightMap,
+    /// The class the player picked.
+    pub profile: PlayerProfile,
+    /// The map size and difficulty the game was started with.
+    pub config: Config,
+}
+
+impl World {
+    pub fn new(seed: u64) -> Self {
+        Self::with_profile(seed, PlayerProfile::default())
+    }
+
+    /// Start a new game as the player described by `profile`.
+    pub fn with_profile(seed: u64, profile: PlayerProfile) -> Self {
+        Self::with_config(seed, profile, Config::default())
+    }
+
+    /// Start a new game as `profile` with levels of the size and
+    /// difficulty in `config`.
+    pub fn with_config(seed: u64, profile: PlayerProfile, config: Config) -> Self {
+        let map = Map::filled(config.map_size, WALL);
+        let rng = StdRng::seed_from_u64(seed);
+        let mut world = Self::from_parts(seed, rng, map, vec![], Entity::default());
+        world.config = config;
+        world.entities[world.player_id] = spawn::player(&profile, &world.templates);
+        world.identification = Identification::new(&world.templates, &mut world.rng);
+        // The player knows what they packed
+        for (item, _) in profile.class.starting_items() {
+            world.identification.identify(item);
+        }
+        if profile.has(Ability::Lore) {
+            for (name, item) in &world.templates.items {
+                if item.appearance.is_some() {
+                    world.identification.identify(name);
+                }
+            }
+        }
+        world.profile = profile;
+        let surface = overworld::generate(config.map_size, &world.templates, &mut world.rng);
+        world.entrances = surface.entrances;
+        world.depth = 0;
+        let level = SavedLevel {
+            map: surface.map,
+            entities: surface.entities,
+            explored: Default::default(),
+            remembered_items: Default::default(),
+            hazards: vec![],
+        };
+        let player = world.entities[world.player_id].clone();
+        world.return_to(level, player, surface.start, DAYLIGHT);
+        world.log(locale::text("log.arrive-in-town"));
+        story::intro(&mut world);
+        world
+    }
+
+    /// Put a world together from an already generated map and entities.
+    pub fn from_parts(
+        seed: u64,
+        rng: StdRng,
+        map: Map,
+        mut entities: Vec<Entity>,
+        player: Entity,
+    ) -> Self {
+        let player_id = entities.len();
+        entities.push(player);
+        let mut world = World {
+            map,
+            entities,
+            player_id,
+            depth: 1,
+            dungeon: MAIN_DUNGEON,
+            entrances: vec![],
+            levels: LevelStore::default(),
+            templates: packs::current().templates.clone(),
+            seed,
+            rng,
+            hits: vec![],
+            events: EventQueue::default(),
+            noises: vec![],
+            sneaking: false,
+            confused: 0,
+            hazards: vec![],
+            log: vec![],
+            stories: vec![],
+            told: BTreeSet::new(),
+            stats: Stats {
+                deepest_level: 1,
+                ..Default::default()
+            },
+            // Hand-made worlds have everything identified
+            identification: Identification::default(),
+            mode: GameMode::Normal,
+            outcome: None,
+            spatial: SpatialMap::default(),
+            shop: None,
+            chest: None,
+            fov: Fov::default(),
+            // Hand-made worlds (e.g. in tests) are fully lit
+            light: LightMap::uniform(1.0),
+            profile: PlayerProfile::default(),
+            config: Config::default(),
+        };
+        world.rebuild_spatial();
+        world.update_fov();
+        world
+    }
+
+    pub fn player(&self) -> &Entity {
+        &self.entities[self.player_id]
+    }
+
+    /// The name the player knows the entity by. Unidentified items go by
+    /// their appearance.
+    pub fn item_name(&self, entity: &Entity) -> String {
+        match &entity.item {
+            Some(item) => {
+                let name = self.identification.name(&self.templates, &item.template);
+                if item.cursed && item.curse_known {
+                    locale::format("item.cursed", &[("item", &name)])
+                } else {
+                    name
+                }
+            }
+            None => entity.name.clone(),
+        }
+    }
+
+    /// Return the entity blocking the tile at `pos`, if any.
+    pub fn blocker_at(&self, pos: Vector) -> Option<usize> {
+        self.spatial
+            .entities_at(pos)
+            .iter()
+            .copied()
+            .find(|&id| self.entities[id].blocks_tile)
+    }
+
+    /// Return the first item lying at `pos`, if any.
+    pub fn item_at(&self, pos: Vector) -> Option<usize> {
+        self.spatial
+            .entities_at(pos)
+            .iter()
+            .copied()
+            .find(|&id| self.entities[id].item.is_some())
+    }
+
+    /// Return `true` if the player can see the tile at `pos`: it's lit
+    /// and in sight.
+    pub fn is_visible(&self, pos: Vector) -> bool {
+        self.light.is_lit(pos) && self.can_see(self.player().pos, pos)
+    }
+
+    /// Return `true` if the entity can step onto `pos`. It doesn't matter
+    /// whether somebody's standing there.
+    pub fn is_passable(&self, id: usize, pos: Vector) -> bool {
+        self.map
+            .movement_cost(pos, self.entities[id].swims)
+            .is_some()
+    }
+
+    /// Return `true` if somebody standing at `from` can see `to`.
+    pub fn can_see(&self, from: Vector, to: Vector) -> bool {
+        (to - from).len() <= SIGHT_RADIUS
+            && los(from, to, |tile| self.map.blocks(tile, BLOCKS_SIGHT))
+    }
+
+    /// The living monsters the player can see.
+    pub fn visible_monsters(&self) -> Vec<usize> {
+        (0..self.entities.len())
+            .filter(|&id| id != self.player_id && self.entities[id].is_alive())
+            .filter(|&id| self.sees_entity(id))
+            .collect()
+    }
+
+    /// The boss, if it's alive and in view.
+    pub fn visible_boss(&self) -> Option<usize> {
+        self.visible_monsters().into_iter().find(|&id| {
+            let ai = self.entities[id].ai.as_ref();
+            ai.is_some_and(|ai| ai.behavior == Behavior::Boss)
+        })
+    }
+
+    /// Return `true` if the player can see the entity: it's on a tile
+    /// in view and, if it's invisible, right next to them (or a bit
+    /// further with `Ability::KeenEyes`).
+    pub fn sees_entity(&self, id: usize) -> bool {
+        let entity = &self.entities[id];
+        let offset = entity.pos - self.player().pos;
+        let close = if self.profile.has(Ability::KeenEyes) {
+            offset.len() <= KEEN_EYES_RANGE
+        } else {
+            offset.x.abs() <= 1.0 && offset.y.abs() <= 1.0
+        };
+        self.is_visible(entity.pos) && (!entity.invisible || close)
+    }
+
+    /// Work out the lighting and what the player sees in it.
+    pub fn update_fov(&mut self) {
+        self.light = LightMap::new(self, self.light.ambient);
+        self.fov = Fov::update(self);
+    }
+
+    pub fn rebuild_spatial(&mut self) {
+        self.spatial.rebuild(&self.entities);
+    }
+
+    /// Put the entity at `pos`, keeping `spatial` up to date.
+    pub fn move_entity(&mut self, id: usize, pos: Vector) {
+        let from = self.entities[id].pos;
+        self.entities[id].pos = pos;
+        self.spatial.move_entity(id, from, pos);
+    }
+
+    /// Add a message to the log. Saying the same thing again just bumps
+    /// the count of the last message.
+    pub fn log(&mut self, message: impl Into<Message>) {
+        let message = message.into();
+        match self.log.last_mut() {
+            Some(last) if last.is_repeat_of(&message) => last.count += 1,
+            _ => self.log.push(message),
+        }
+    }
+
+    /// Take an entity out of the world, keeping `player_id` valid.
+    pub fn remove_entity(&mut self, id: usize) -> Entity {
+        assert_ne!(id, self.player_id, "The player can't be removed.");
+        if id < self.player_id {
+            self.player_id -= 1;
+        }
+        let entity = self.entities.remove(id);
+        self.rebuild_spatial();
+        entity
+    }
+
+    pub fn has_amulet(&self) -> bool {
+        self.player()
+            .inventory
+            .iter()
+            .any(|item| item.item.as_ref().map(|item| item.template.as_str()) == Some(AMULET))
+    }
+
+    /// Where the item goes when equipped, if it can be.
+    pub fn slot(&self, item: &Entity) -> Option<Slot> {
+        let item = item.item.as_ref()?;
+        self.templates.items.get(&item.template)?.slot
+    }
+
+    /// Whether `item` fits in the entity's inventory: there's a free
+    /// slot or a stack for it to go on.
+    pub fn has_room(&self, id: usize, item: &Entity) -> bool {
+        let inventory = &self.entities[id].inventory;
+        inventory.len() < INVENTORY_SIZE || stack_index(inventory, item, &self.templates).is_some()
+    }
+
+    /// Put `item` in the entity's inventory, see `stack`.
+    pub fn give(&mut self, id: usize, item: Entity) -> usize {
+        stack(&mut self.entities[id].inventory, item, &self.templates)
+    }
+
+    /// Take one of the item at `index` out of the entity's inventory,
+    /// leaving the rest of its stack.
+    pub fn take_one(&mut self, id: usize, index: usize) -> Entity {
+        let inventory = &mut self.entities[id].inventory;
+        match &mut inventory[index].item {
+            Some(item) if item.count > 1 => {
+                item.count -= 1;
+                let mut one = inventory[index].clone();
+                if let Some(item) = &mut one.item {
+                    item.count = 1;
+                    item.equipped = false;
+                }
+                one
+            }
+            _ => inventory.remove(index),
+        }
+    }
+
+    /// The item's name with how many of it there are, if there's more
+    /// than one.
+    pub fn stack_name(&self, entity: &Entity) -> String {
+        let name = self.item_name(entity);
+        match &entity.item {
+            Some(item) if item.count > 1 => {
+                locale::format("item.count", &[("item", &name), ("count", &item.count)])
+            }
+            _ => name,
+        }
+    }
+
+    /// How an item the player carries is listed: its `stack_name`,
+    /// whether it's equipped and, for a ranged weapon, how much ammo is
+    /// left for it.
+    pub fn inventory_line(&self, entity: &Entity) -> String {
+        let name = self.stack_name(entity);
+        let item = match &entity.item {
+            Some(item) if item.equipped => item,
+            _ => return name,
+        };
+        let ammo = self
+            .templates
+            .items
+            .get(&item.template)
+            .and_then(|template| template.ammo.as_ref());
+        match ammo {
+            Some(ammo) => {
+                let count = ammo::carried(self, ammo);
+                locale::format("hud.equipped-ammo", &[("item", &name), ("count", &count)])
+            }
+            None => locale::format("hud.equipped", &[("item", &name)]),
+        }
+    }
+
+    /// The item the entity has equipped in the slot, if any.
+    pub fn equipped(&self, id: usize, slot: Slot) -> Option<&Entity> {
+        self.entities[id].inventory.iter().find(|entity| {
+            let equipped = entity.item.as_ref().is_some_and(|item| item.equipped);
+            equipped && self.slot(entity) == Some(slot)
+        })
+    }
+
+    fn player_tile(&self) -> Option<char> {
+        self.map.tile(self.player().pos).map(|tile| tile.glyph)
+    }
+
+    /// Resolve one player turn.
+    pub fn player_turn(&mut self, action: Action) {
+        if self.outcome.is_some() {
+            return;
+        }
+        match action {
+            Action::Buy(index) => return shop::buy(self, index),
+            Action::Sell(index) => return shop::sell(self, index),
+            Action::Cheat(cheat) => return wizard::cheat(self, cheat),
+            Action::Sneak => return self.toggle_sneaking(),
+            Action::Take(index) => return chest::take(self, index),
+            Action::TakeAll => return chest::take_all(self),
+            _ => {
+                self.shop = None;
+                self.chest = None;
+            }
+        }
+        self.stats.turns += 1;
+        for entity in &mut self.entities {
+            entity.cooldowns.tick();
+        }
+        self.rebuild_spatial();
+        let depth = self.depth;
+        let start = self.player().pos;
+        let action = match action {
+            Action::Move(offset) => Action::Move(hazards::stumble(self, offset)),
+            action => action,
+        };
+        let duration = schedule::cost(self, &action);
+        match action {
+            Action::Move(offset) => move_or_attack(self, self.player_id, offset),
+            Action::Descend => {
+                if self.player_tile() == Some(STAIRS_DOWN) {
+                    self.descend();
+                } else {
+                    self.log(locale::text("log.no-stairs-down"));
+                }
+            }
+            Action::Ascend => {
+                if self.player_tile() != Some(STAIRS_UP) {
+                    self.log(locale::text("log.no-stairs-up"));
+                } else if self.depth > 1 {
+                    self.enter_level(self.dungeon, self.depth - 1, STAIRS_DOWN);
+                } else if self.depth == 1 {
+                    self.climb_out();
+                }
+            }
+            Action::PickUp => pick_up(self),
+            Action::Equip(index) => equip(self, index),
+            Action::Throw(index, target) => throwing::throw(self, index, target),
+            Action::Fire(target) => ammo::fire(self, target),
+            Action::Use(index) => identify::use_item(self, index),
+            Action::Skill(skill, aim) => skills::use_skill(self, skill, aim),
+            Action::Wait
+            | Action::Buy(_)
+            | Action::Sell(_)
+            | Action::Sneak
+            | Action::Take(_)
+            | Action::TakeAll
+            | Action::Cheat(_) => {}
+        }
+        events::process(self);
+        let moved = self.depth == depth && self.player().pos != start;
+        if moved {
+            self.stats.steps += 1;
+            let pos = self.player().pos;
+            noise::make(self, pos, noise::step_volume(self.sneaking));
+        }
+        // Monsters on a level the player has just entered don't get to
+        // act before the player does
+        if self.depth == depth && self.outcome.is_none() {
+            schedule::run(self, duration);
+        }
+        self.end_turn();
+    }
+
+    fn toggle_sneaking(&mut self) {
+        self.sneaking = !self.sneaking;
+        if self.sneaking {
+            self.log(locale::text("log.start-sneaking"));
+        } else {
+            self.log(locale::text("log.stop-sneaking"));
+        }
+    }
+
+    /// Everything that happens after the player's action.
+    fn end_turn(&mut self) {
+        for id in 0..self.entities.len() {
+            let on_lava = self.map.tile(self.entities[id].pos).map(|tile| tile.glyph) == Some(LAVA);
+            if on_lava && self.entities[id].is_alive() {
+                burn(self, id);
+            }
+        }
+        hazards::update(self);
+        events::process(self);
+        regeneration::regenerate(self);
+        if !self.player().is_alive() && !wizard::save_from_death(self) {
+            self.log(locale::text("log.you-die"));
+            self.outcome = Some(Outcome::Died);
+        }
+        self.update_fov();
+    }
+
+    /// Leave the current level and generate the next one. From the
+    /// surface that's the first level of the dungeon whose entrance the
+    /// player stands on (or the main one if they aren't on any).
+    pub fn descend(&mut self) {
+        if self.depth == 0 {
+            let pos = self.player().pos;
+            let dungeon = self
+                .entrances
+                .iter()
+                .position(|&entrance| entrance == pos)
+                .unwrap_or(MAIN_DUNGEON);
+            self.enter_level(dungeon, 1, STAIRS_UP);
+        } else {
+            self.enter_level(self.dungeon, self.depth + 1, STAIRS_UP);
+        }
+    }
+
+    /// Climb from the first level of the dungeon back to its entrance
+    /// on the surface. With the amulet that wins the game.
+    fn climb_out(&mut self) {
+        let entrance = self.entrances.get(self.dungeon).copied();
+        let arrival = entrance.or_else(|| {
+            let surface = self.levels.get(LevelId::SURFACE)?;
+            spawn::random_free_tile(&surface.map, &surface.entities, &mut self.rng)
+        });
+        let player = self.entities[self.player_id].clone();
+        self.leave_level();
+        self.depth = 0;
+        let surface = self
+            .levels
+            .take(LevelId::SURFACE)
+            .expect("There's no surface.");
+        let arrival = arrival.expect("The surface has no room for the player.");
+        self.return_to(surface, player, arrival, DAYLIGHT);
+        if self.has_amulet() {
+            self.log(locale::text("log.escape"));
+            self.outcome = Some(Outcome::Won);
+        } else {
+            let dungeon = &self.templates.dungeons[self.dungeon].name;
+            self.log(locale::message(
+                "log.climb-out",
+                &[("dungeon", &locale::content(dungeon))],
+            ));
+        }
+    }
+
+    pub fn level_id(&self) -> LevelId {
+        LevelId::new(self.dungeon, self.depth)
+    }
+
+    /// Store the current level with everybody but the player for when
+    /// they come back.
+    fn leave_level(&mut self) {
+        let mut entities = std::mem::take(&mut self.entities);
+        entities.remove(self.player_id);
+        let level = SavedLevel {
+            map: self.map.clone(),
+            entities,
+            explored: std::mem::take(&mut self.fov.explored),
+            remembered_items: std::mem::take(&mut self.fov.remembered_items),
+            hazards: std::mem::take(&mut self.hazards),
+        };
+        self.levels.store(self.level_id(), level);
+    }
+
+    /// Put the player back on a level they've been to before, at
+    /// `arrival`. Whoever is standing there makes room.
+    fn return_to(&mut self, level: SavedLevel, mut player: Entity, arrival: Vector, ambient: f32) {
+        let mut entities = level.entities;
+        player.pos = arrival;
+        let in_the_way = entities
+            .iter()
+            .position(|entity| entity.blocks_tile && entity.pos == arrival);
+        if let Some(id) = in_the_way {
+            let others = [&entities[..], std::slice::from_ref(&player)].concat();
+            if let Some(pos) = spawn::random_free_tile(&level.map, &others, &mut self.rng) {
+                entities[id].pos = pos;
+            }
+        }
+        self.map = level.map;
+        self.settle(entities, player, ambient);
+        self.hazards = level.hazards;
+        self.fov.explored.extend(level.explored);
+        for (key, item) in level.remembered_items {
+            self.fov.remembered_items.entry(key).or_insert(item);
+        }
+    }
+
+    /// Put the player on the current map with `entities` and start
+    /// seeing it under `ambient` light.
+    fn settle(&mut self, mut entities: Vec<Entity>, player: Entity, ambient: f32) {
+        self.player_id = entities.len();
+        entities.push(player);
+        self.entities = entities;
+        // Nobody here heard what happened on the previous level
+        self.noises.clear();
+        self.hazards.clear();
+        self.rebuild_spatial();
+        self.fov = Fov::default();
+        self.light = LightMap::uniform(ambient);
+        self.update_fov();
+    }
+
+    /// Go to level `depth` of `dungeon` and put the player on its
+    /// `arrive_on` stairs. The level is generated the first time, after
+    /// that it's restored from `levels`. Only the player comes along.
+    fn enter_level(&mut self, dungeon: usize, depth: u32, arrive_on: char) {
+        let previous_branch = if self.depth > 0 && self.depth != depth {
+            Some(self.templates.branch(self.dungeon, self.depth).name.clone())
+        } else {
+            None
+        };
+        let has_amulet = self.has_amulet();
+        let player = self.entities[self.player_id].clone();
+        self.leave_level();
+        let descending = depth > self.depth;
+        self.dungeon = dungeon;
+        self.depth = depth;
+        self.stats.deepest_level = self.stats.deepest_level.max(depth);
+
+        let light = self.templates.branch(dungeon, depth).light;
+        match self.levels.take(LevelId::new(dungeon, depth)) {
+            Some(level) => {
+                let stairs = level.map.tiles.iter().find(|tile| tile.glyph == arrive_on);
+                let arrival = match stairs {
+                    Some(tile) => tile.pos,
+                    None => spawn::random_free_tile(&level.map, &level.entities, &mut self.rng)
+                        .expect("The map has no room for the player."),
+                };
+                self.return_to(level, player, arrival, light);
+            }
+            None => self.generate(player, arrive_on, has_amulet, light),
+        }
+
+        let branch = self.templates.branch(dungeon, depth).name.clone();
+        let args: &[(&str, &dyn Display)] =
+            &[("branch", &locale::content(&branch)), ("depth", &depth)];
+        let message = match previous_branch {
+            None => locale::message("log.enter-branch", args),
+            Some(_) if !descending => locale::message("log.climb-back", args),
+            Some(previous) if previous == branch => locale::message("log.descend-deeper", args),
+            Some(_) => locale::message("log.descend-into", args),
+        };
+        self.log(message);
+    }
+
+    /// Generate the current level for the first time.
+    fn generate(&mut self, mut player: Entity, arrive_on: char, has_amulet: bool, light: f32) {
+        let (dungeon, depth) = (self.dungeon, self.depth);
+        self.map = generate_level(self.config.map_size, &mut self.rng);
+        let mut stairs = vec![STAIRS_UP];
+        if depth < self.templates.max_depth(dungeon) {
+            stairs.push(STAIRS_DOWN);
+        }
+        let mut arrival = None;
+        for glyph in stairs {
+            if let Some(pos) = spawn::random_free_tile(&self.map, &[], &mut self.rng) {
+                self.map.set_glyph(pos, glyph);
+                if glyph == arrive_on {
+                    arrival = Some(pos);
+                }
+            }
+        }
+
+        let prefab = prefabs::add_prefab(
+            &mut self.map,
+            &self.templates,
+            dungeon,
+            depth,
+            &mut self.rng,
+        );
+
+        let mut entities = spawn::level_entities(
+            &self.map,
+            &self.templates,
+            &self.config,
+            dungeon,
+            depth,
+            &mut self.rng,
+        );
+        // The prefab's own things take precedence over the random ones
+        entities.retain(|entity| prefab.iter().all(|fixture| fixture.pos != entity.pos));
+        entities.extend(prefab);
+        let deepest = depth == self.templates.max_depth(dungeon);
+        if dungeon == MAIN_DUNGEON && deepest && !has_amulet {
+            if let Some(pos) = spawn::random_free_tile(&self.map, &entities, &mut self.rng) {
+                let template = &self.templates.items[AMULET];
+                entities.push(spawn::item(AMULET, template, pos));
+            }
+        }
+        player.pos = arrival
+            .or_else(|| spawn::random_free_tile(&self.map, &entities, &mut self.rng))
+            .expect("The map has no room for the player.");
+        // Nobody may stand on the stairs the player arrives on
+        entities.retain(|entity| entity.pos != player.pos);
+        locks::lock_doors(
+            &mut self.map,
+            &mut entities,
+            &self.templates,
+            player.pos,
+            &mut self.rng,
+        );
+        // The looks get a generator of their own so they don't change
+        // anything else about the level
+        let mut looks = StdRng::seed_from_u64(self.rng.gen());
+        decoration::decorate(&mut self.map, &mut looks);
+        self.settle(entities, player, light);
+        story::level(self);
+    }
+}
+
+/// Move the first item lying under the player into their inventory.
+fn pick_up(world: &mut World) {
+    let player_id = world.player_id;
+    match world.item_at(world.player().pos) {
+        None => world.log(locale::text("log.nothing-to-pick-up")),
+        Some(index) if !world.has_room(player_id, &world.entities[index]) => {
+            world.log(locale::text("log.inventory-full"))
+        }
+        Some(index) => {
+            let item = world.remove_entity(index);
+            let player_id = world.player_id;
+            let index = world.give(player_id, item);
+            world.events.push(Event::PickedUp {
+                id: player_id,
+                index,
+                chest: None,
+            });
+        }
+    }
+}
+
+/// Put `item` in `inventory`, on the stack of the same items if it
+/// stacks and there is one. Returns where it went.
+pub fn stack(inventory: &mut Vec<Entity>, item: Entity, templates: &Templates) -> usize {
+    let count = item.item.as_ref().map_or(1, |item| item.count);
+    match stack_index(inventory, &item, templates) {
+        Some(index) => {
+            if let Some(item) = &mut inventory[index].item {
+                item.count += count;
+            }
+            index
+        }
+        None => {
+            inventory.push(item);
+            inventory.len() - 1
+        }
+    }
+}
+
+/// Where the stack `item` would go on is in `inventory`, if there is one.
+fn stack_index(inventory: &[Entity], item: &Entity, templates: &Templates) -> Option<usize> {
+    let item = item.item.as_ref()?;
+    if !templates.items.get(&item.template)?.stacks {
+        return None;
+    }
+    inventory.iter().position(|other| {
+        let other = other.item.as_ref();
+        other.is_some_and(|other| other.template == item.template && other.cursed == item.cursed)
+    })
+}
+
+/// Equip the player's item at `index`, taking off whatever was in its
+/// slot before. Equipping an item that's already equipped takes it off.
+fn equip(world: &mut World, index: usize) {
+    let player_id = world.player_id;
+    let item = match world.player().inventory.get(index) {
+        Some(item) if item.item.is_some() => item,
+        _ => return world.log(locale::text("log.no-such-item")),
+    };
+    let name = world.item_name(item);
+    let equipped = item.item.as_ref().is_some_and(|item| item.equipped);
+    let slot = match world.slot(item) {
+        Some(slot) => slot,
+        None => {
+            return world.log(locale::message(
+                "log.cannot-equip",
+                &[("item", &with_article(&name))],
+            ))
+        }
+    };
+    if equipped {
+        if is_stuck(item) {
+            return world.log(locale::message("log.cannot-take-off", &[("item", &name)]));
+        }
+        set_equipped(world, index, false);
+        return world.log(locale::message("log.take-off", &[("item", &name)]));
+    }
+    if let Some(previous) = world
+        .equipped(player_id, slot)
+        .filter(|item| is_stuck(item))
+    {
+        let previous = world.item_name(previous);
+        return world.log(locale::message(
+            "log.cannot-take-off",
+            &[("item", &previous)],
+        ));
+    }
+    let cursed = item.item.as_ref().is_some_and(|item| item.cursed);
+    let previous = world
+        .equipped(player_id, slot)
+        .map(|item| world.item_name(item));
+    for other in 0..world.player().inventory.len() {
+        if world.slot(&world.player().inventory[other]) == Some(slot) {
+            set_equipped(world, other, false);
+        }
+    }
+    set_equipped(world, index, true);
+    match previous {
+        Some(previous) => world.log(locale::message(
+            "log.swap",
+            &[("previous", &previous), ("item", &name)],
+        )),
+        None => world.log(locale::message("log.equip", &[("item", &name)])),
+    }
+    // Putting it on is a sure way to find out
+    if let Some(item) = &mut world.entities[player_id].inventory[index].item {
+        item.curse_known = true;
+    }
+    if cursed {
+        world.log(locale::message("log.stuck-with-curse", &[("item", &name)]));
+    }
+}
+
+/// Return `true` for equipped items that are cursed and can't be taken
+/// off, thrown away or sold.
+pub fn is_stuck(item: &Entity) -> bool {
+    item.item
+        .as_ref()
+        .is_some_and(|item| item.equipped && item.cursed)
+}
+
+fn set_equipped(world: &mut World, index: usize, equipped: bool) {
+    let player_id = world.player_id;
+    if let Some(item) = &mut world.entities[player_id].inventory[index].item {
+        item.equipped = equipped;
+    }
+}
+
+/// Gold goes straight into the player's wallet, no need to pick it up.
+fn pick_up_gold(world: &mut World, pos: Vector) {
+    let pile = world.spatial.entities_at(pos).iter().copied().find(|&id| {
+        let entity = &world.entities[id];
+        id != world.player_id && entity.gold > 0 && !entity.blocks_tile
+    });
+    if let Some(pile) = pile {
+        let pile = world.remove_entity(pile);
+        let player_id = world.player_id;
+        world.entities[player_id].gold += pile.gold;
+        world.log(locale::message(
+            "log.pick-up-gold",
+            &[("amount", &pile.gold)],
+        ));
+    }
+}
+
+/// "dart" becomes "a dart", "Amulet of Yendor" is "the Amulet of Yendor".
+/// Languages without articles can leave the name as it is.
+pub fn with_article(name: &str) -> String {
+    let key = match name.chars().next() {
+        Some(c) if c.is_uppercase() => "article.the",
+        Some('a') | Some('e') | Some('i') | Some('o') | Some('u') => "article.an",
+        _ => "article.a",
+    };
+    locale::format(key, &[("name", &name)])
+}
+
+/// "a dart" becomes "A dart".
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Move the entity by `offset` unless the destination is a wall or
+/// taken. If there's somebody alive standing there, attack them instead.
+pub fn move_or_attack(world: &mut World, id: usize, offset: Vector) {
+    let destination = world.entities[id].pos + offset;
+    if let Some(target) = world.blocker_at(destination) {
+        if id == world.player_id && world.entities[target].shopkeeper {
+            world.shop = Some(target);
+            world.log(locale::text("log.shop-welcome"));
+        } else if id == world.player_id && world.entities[target].chest.is_some() {
+            chest::open(world, target);
+        } else if world.entities[target].is_alive() {
+            world.events.push(Event::WantsToAttack {
+                attacker: id,
+                target,
+            });
+        }
+    } else if id == world.player_id
+        && world.map.tile(destination).map(|tile| tile.glyph) == Some(DEEP_WATER)
+    {
+        world.log(locale::text("log.deep-water"));
+    } else if id == world.player_id
+        && world.map.tile(destination).map(|tile| tile.glyph) == Some(LOCKED_DOOR)
+    {
+        locks::open_door(world, destination);
+    } else if world.is_passable(id, destination) {
+        world.move_entity(id, destination);
+        if id == world.player_id {
+            pick_up_gold(world, destination);
+            if let Some(message) = describe_floor(world, destination) {
+                world.log(message);
+            }
+        }
+    }
+}
+
+/// Tell the player about anything interesting lying at `pos`.
+fn describe_floor(world: &World, pos: Vector) -> Option<Message> {
+    if let Some(item) = world.item_at(pos) {
+        let name = world.item_name(&world.entities[item]);
+        return Some(locale::message(
+            "log.see-item",
+            &[("item", &with_article(&name))],
+        ));
+    }
+    match world.map.tile(pos).map(|tile| tile.glyph) {
+        Some(STAIRS_DOWN) if world.depth == 0 => {
+            let dungeon = world
+                .entrances
+                .iter()
+                .position(|&entrance| entrance == pos)?;
+            let name = &world.templates.dungeons[dungeon].name;
+            Some(locale::message(
+                "log.entrance-here",
+                &[("dungeon", &locale::content(name))],
+            ))
+        }
+        Some(STAIRS_DOWN) => Some(locale::text("log.stairs-down-here").into()),
+        Some(STAIRS_UP) => Some(locale::text("log.stairs-up-here").into()),
+        Some(SHALLOW_WATER) => Some(locale::text("log.wade").into()),
+        _ => None,
+    }
+}
+
+/// Return `true` if `id` would attack `other` given the chance.
+/// Monsters and the player are always at war, other factions keep to
+/// themselves unless the player provokes them. Shopkeepers are nobody's
+/// enemy.
+pub fn is_hostile(world: &World, id: usize, other: usize) -> bool {
+    let provoked = |id: usize| {
+        let entity = &world.entities[id];
+        entity.ai.as_ref().is_some_and(|ai| ai.provoked)
+    };
+    let (a, b) = (&world.entities[id], &world.entities[other]);
+    if a.shopkeeper || b.shopkeeper {
+        return false;
+    }
+    match (a.faction, b.faction) {
+        (Faction::Player, Faction::Monsters) | (Faction::Monsters, Faction::Player) => true,
+        (Faction::Player, _) => provoked(other),
+        (_, Faction::Player) => provoked(id),
+        _ => false,
+    }
+}
+
+/// The player attacked `id`: it fights back, and so does the rest of
+/// its pack.
+pub fn provoke(world: &mut World, id: usize) {
+    noise::wake_up(world, id);
+    let pack = match &mut world.entities[id].ai {
+        Some(ai) => {
+            ai.provoked = true;
+            ai.pack
+        }
+        None => return,
+    };
+    if pack.is_none() {
+        return;
+    }
+    for entity in &mut world.entities {
+        if let Some(ai) = entity.ai.as_mut().filter(|ai| ai.pack == pack) {
+            ai.provoked = true;
+        }
+    }
+}
+
+/// Hurt `target`, because of `attacker` if it was anybody. Return
+/// `true` if that killed them. A dead entity leaves a corpse behind,
+/// the loot ends up on it once the `Died` event is handled. In wizard
+/// mode the player can't die.
+pub fn take_damage(world: &mut World, attacker: Option<usize>, target: usize, damage: i32) -> bool {
+    world.events.push(Event::Damaged {
+        attacker,
+        target,
+        amount: damage,
+    });
+    let turn = world.stats.turns;
+    let entity = &mut world.entities[target];
+    entity.hp -= damage;
+    entity.last_hurt = Some(turn);
+    world.hits.push(Hit {
+        target,
+        pos: entity.pos,
+        damage,
+    });
+    if target == world.player_id && wizard::save_from_death(world) {
+        return false;
+    }
+    let entity = &mut world.entities[target];
+    let killed = !entity.is_alive();
+    if killed {
+        entity.die();
+        world.events.push(Event::Died {
+            attacker,
+            id: target,
+        });
+    }
+    killed
+}
+
+/// Whoever stands in lava gets burned.
+fn burn(world: &mut World, id: usize) {
+    let killed = take_damage(world, None, id, LAVA_DAMAGE);
+    let name = world.entities[id].name.clone();
+    if id == world.player_id {
+        world.log(locale::text("log.lava-burns-you"));
+    } else if world.is_visible(world.entities[id].pos) {
+        if killed {
+            world.log(locale::message("log.burns-to-death", &[("name", &name)]));
+        } else {
+            world.log(locale::message("log.burned", &[("name", &name)]));
+        }
+    }
+}
+
+/// Deal a point of damage to `target`. With a shield equipped the
+/// attacker may knock a survivor back as well.
+pub fn attack(world: &mut World, attacker: usize, target: usize) {
+    let killed = strike(world, attacker, target);
+    let mastery = attacker == world.player_id && world.profile.has(Ability::ShieldMastery);
+    if !killed
+        && world.equipped(attacker, Slot::Shield).is_some()
+        && (mastery || world.rng.gen_bool(KNOCKBACK_CHANCE))
+    {
+        let direction = world.entities[target].pos - world.entities[attacker].pos;
+        knock_back(world, attacker, target, direction);
+    }
+}
+
+/// Deal a point of damage to `target` and say so, without any
+/// knockback. Return `true` if that killed them.
+pub fn strike(world: &mut World, attacker: usize, target: usize) -> bool {
+    let killed = take_damage(world, Some(attacker), target, 1);
+    let pos = world.entities[target].pos;
+    noise::make(world, pos, noise::FIGHT_NOISE);
+
+    let name = world.entities[target].name.clone();
+    let attacker_name = world.entities[attacker].name.clone();
+    if attacker == world.player_id {
+        world.log(locale::message("log.you-hit", &[("name", &name)]));
+    } else if target == world.player_id {
+        world.log(locale::message("log.hits-you", &[("name", &attacker_name)]));
+    } else if world.is_visible(world.entities[target].pos) {
+        world.log(locale::message(
+            "log.hits",
+            &[("attacker", &attacker_name), ("name", &name)],
+        ));
+    }
+    if killed && target != world.player_id && world.is_visible(world.entities[target].pos) {
+        world.log(locale::message("log.dies", &[("name", &name)]));
+    }
+    killed
+}
+
+/// Push `target` one tile in `direction`. If there's a wall or somebody
+/// standing there, they slam into it and get hurt instead. Nothing
+/// stops them being pushed into lava or water they can't swim in.
+pub fn knock_back(world: &mut World, attacker: usize, target: usize, direction: Vector) {
+    let destination = world.entities[target].pos + direction;
+    let name = world.entities[target].name.clone();
+    let obstacle = match world.blocker_at(destination) {
+        Some(other) => Some(locale::format(
+            "log.obstacle",
+            &[("name", &world.entities[other].name)],
+        )),
+        None if world.map.is_blocked(destination) => Some(locale::text("log.the-wall")),
+        None => None,
+    };
+    let visible = world.is_visible(world.entities[target].pos);
+    match obstacle {
+        Some(obstacle) => {
+            let killed = take_damage(world, Some(attacker), target, SLAM_DAMAGE);
+            if target == world.player_id {
+                world.log(locale::message(
+                    "log.knocked-into",
+                    &[("obstacle", &obstacle)],
+                ));
+            } else if visible {
+                world.log(locale::message(
+                    "log.slams-into",
+                    &[("name", &name), ("obstacle", &obstacle)],
+                ));
+                if killed {
+                    world.log(locale::message("log.dies", &[("name", &name)]));
+                }
+            }
+        }
+        None => {
+            world.move_entity(target, destination);
+            if target == world.player_id {
+                world.log(locale::text("log.you-are-knocked-back"));
+            } else if visible {
+                world.log(locale::message("log.knocked-back", &[("name", &name)]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{map::generate_map, profile::Class};
+
+    /// A goblin at (`x`, 5) that fights back, for the other modules'
+    /// tests.
+    pub(crate) fn goblin(x: i32, hp: i32) -> Entity {
+        Entity {
+            ai: Some(Ai::default()),
+            ..entity(x, 5, 'g', hp)
+        }
+    }
+
+    /// The player with `hp` health at (5, 5) of an empty room with
+    /// `monsters` in it.
+    pub(crate) fn player_with(monsters: Vec<Entity>, hp: i32) -> World {
+        let player = Entity {
+            pos: Vector::new(5, 5),
+            name: "player".to_string(),
+            hp,
+            max_hp: hp,
+            blocks_tile: true,
+            faction: Faction::Player,
+            ..Default::default()
+        };
+        World::from_parts(
+            0,
+            StdRng::seed_from_u64(0),
+            generate_map(Vector::new(20, 15)),
+            monsters,
+            player,
+        )
+    }
+
+    fn entity(x: i32, y: i32, glyph: char, hp: i32) -> Entity {
+        Entity {
+            pos: Vector::new(x, y),
+            glyph,
+            name: "goblin".to_string(),
+            hp,
+            max_hp: hp,
+            blocks_tile: hp > 0,
+            ..Default::default()
+        }
+    }
+
+    /// An empty room with two goblins, a corpse and the player at (5, 3).
+    fn test_world() -> World {
+        World::from_parts(
+            0,
+            StdRng::seed_from_u64(0),
+            generate_map(Vector::new(20, 15)),
+            vec![
+                entity(9, 6, 'g', 1),
+                entity(2, 4, 'g', 1),
+                entity(7, 5, '%', 0),
+            ],
+            entity(5, 3, '@', 3),
+        )
+    }
+
+    fn step(world: &mut World, x: i32, y: i32) {
+        world.player_turn(Action::Move(Vector::new(x, y)));
+    }
+
+    #[test]
+    fn player_walks_around() {
+        let mut world = test_world();
+        step(&mut world, 1, 0);
+        step(&mut world, 0, 1);
+        step(&mut world, 0, 1);
+        assert_eq!(world.player().pos, Vector::new(6, 5));
+    }
+
+    #[test]
+    fn walls_stop_the_player() {
+        let mut world = test_world();
+        for _ in 0..10 {
+            step(&mut world, 0, -1);
+        }
+        assert_eq!(world.player().pos, Vector::new(5, 1));
+    }
+
+    #[test]
+    fn bumping_into_a_monster_kills_it() {
+        let mut world = test_world();
+        // The goblin at (2, 4)
+        let goblin = world.blocker_at(Vector::new(2, 4)).unwrap();
+        step(&mut world, 0, 1);
+        step(&mut world, -1, 0);
+        step(&mut world, -1, 0);
+        assert_eq!(world.player().pos, Vector::new(3, 4));
+
+        step(&mut world, -1, 0);
+        assert_eq!(world.player().pos, Vector::new(3, 4));
+        assert!(!world.entities[goblin].is_alive());
+        assert_eq!(world.entities[goblin].glyph, '%');
+        assert_eq!(
+            world.hits,
+            vec![Hit {
+                target: goblin,
+                pos: Vector::new(2, 4),
+                damage: 1
+            }]
+        );
+        assert_eq!(world.log, vec!["You hit the goblin.", "The goblin dies."]);
+
+        // The corpse doesn't get in the way any more
+        assert_eq!(world.blocker_at(Vector::new(2, 4)), None);
+        step(&mut world, -1, 0);
+        assert_eq!(world.player().pos, Vector::new(2, 4));
+        assert_eq!(world.blocker_at(Vector::new(2, 4)), Some(world.player_id));
+        assert_eq!(world.blocker_at(Vector::new(3, 4)), None);
+    }
+
+    #[test]
+    fn monsters_drop_their_loot_when_they_die() {
+        let mut world = test_world();
+        // Bring the goblin at (2, 4) right next to the player
+        let goblin = world.blocker_at(Vector::new(2, 4)).unwrap();
+        world.move_entity(goblin, Vector::new(5, 4));
+        world.entities[goblin].drops = vec![("dart".to_string(), 1.0), ("sword".to_string(), 0.0)];
+
+        step(&mut world, 0, 1);
+        assert!(!world.entities[goblin].is_alive());
+        let loot = world.item_at(Vector::new(5, 4)).unwrap();
+        assert_eq!(world.entities[loot].name, "dart");
+        assert_eq!(world.spatial.entities_at(Vector::new(5, 4)).len(), 2);
+    }
+
+    #[test]
+    fn blocking_entities_that_are_not_alive_stop_movement() {
+        let mut world = test_world();
+        let statue = Entity {
+            pos: Vector::new(5, 4),
+            glyph: '&',
+            blocks_tile: true,
+            ..Default::default()
+        };
+        world.entities.insert(0, statue);
+        world.player_id += 1;
+        world.rebuild_spatial();
+
+        step(&mut world, 0, 1);
+        assert_eq!(world.player().pos, Vector::new(5, 3));
+        assert!(world.hits.is_empty());
+    }
+
+    #[test]
+    fn classes_start_differently() {
+        let fighter = World::new(2);
+        assert_eq!(fighter.player().max_hp, 7);
+        assert!(fighter.equipped(fighter.player_id, Slot::Shield).is_some());
+
+        let mage = World::with_profile(2, PlayerProfile::new(Class::Mage));
+        assert_eq!(mage.player().inventory.len(), 3);
+        assert!(mage.identification.is_known("scroll of remove curse"));
+        assert!(!fighter.identification.is_known("scroll of remove curse"));
+
+        // A wraith two steps from the player
+        let mut world = test_world();
+        world.entities[0].pos = Vector::new(7, 3);
+        world.entities[0].invisible = true;
+        assert!(!world.sees_entity(0));
+        world.profile = PlayerProfile::new(Class::Rogue);
+        assert!(world.sees_entity(0));
+    }
+
+    #[test]
+    fn new_worlds_place_everybody_on_separate_floor_tiles() {
+        for seed in 0..10 {
+            let world = World::new(seed);
+            assert_eq!(world.player().name, "player");
+            for (index, entity) in world.entities.iter().enumerate() {
+                assert!(!world.map.is_blocked(entity.pos));
+                // Except for the loot lying on corpses
+                let on_corpse = world.entities.iter().any(|other| {
+                    other.ai.is_some() && !other.is_alive() && other.pos == entity.pos
+                });
+                let others = &world.entities[index + 1..];
+                assert!(others
+                    .iter()
+                    .all(|other| other.pos != entity.pos || (on_corpse && other.item.is_some())));
+            }
+        }
+    }
+
+    #[test]
+    fn stairs_lead_to_the_next_branch() {
+        let mut world = World::new(1);
+        assert_eq!(world.depth, 0);
+        assert_eq!(
+            world.log,
+            vec!["You stand in the town above the dungeons. Find a way down."]
+        );
+
+        world.player_turn(Action::Descend);
+        assert_eq!(world.depth, 0);
+
+        let player_id = world.player_id;
+        world.entities[player_id].pos = world.entrances[MAIN_DUNGEON];
+        world.player_turn(Action::Descend);
+        assert_eq!(world.depth, 1);
+        assert_eq!(world.log.last().unwrap(), "You enter the Goblin Warrens.");
+
+        let player = world.player().clone();
+        for depth in 2..=4 {
+            let stairs = world
+                .map
+                .tiles
+                .iter()
+                .find(|tile| tile.glyph == STAIRS_DOWN);
+            world.entities[world.player_id].pos = stairs.unwrap().pos;
+            world.player_turn(Action::Descend);
+            assert_eq!(world.depth, depth);
+            assert_eq!(world.player().hp, player.hp);
+        }
+        assert_eq!(
+            &world.log[world.log.len() - 2..],
+            [
+                // Levels 2 and 3 both say so
+                "You descend deeper into the Goblin Warrens. x2",
+                "You descend into the Orc Barracks."
+            ]
+        );
+    }
+
+    fn stand_on(world: &mut World, glyph: char) {
+        let tile = world.map.tiles.iter().find(|tile| tile.glyph == glyph);
+        let pos = tile.expect("The glyph is not on the map.").pos;
+        let player_id = world.player_id;
+        world.entities[player_id].pos = pos;
+    }
+
+    #[test]
+    fn bringing_the_amulet_to_the_surface_wins() {
+        let mut world = World::new(2);
+        while world.depth < world.templates.max_depth(MAIN_DUNGEON) {
+            world.descend();
+        }
+        // The deepest level has no way down, only the amulet
+        assert!(world.map.tiles.iter().all(|tile| tile.glyph != STAIRS_DOWN));
+        let amulet = world
+            .entities
+            .iter()
+            .position(|entity| entity.name == "Amulet of Yendor")
+            .unwrap();
+        let player_id = world.player_id;
+        world.entities[player_id].pos = world.entities[amulet].pos;
+        world.player_turn(Action::PickUp);
+        assert!(world.has_amulet());
+
+        while world.depth > 1 {
+            stand_on(&mut world, STAIRS_UP);
+            let depth = world.depth;
+            world.player_turn(Action::Ascend);
+            assert_eq!(world.depth, depth - 1);
+            assert_eq!(world.player_tile(), Some(STAIRS_DOWN));
+        }
+        stand_on(&mut world, STAIRS_UP);
+        world.player_turn(Action::Ascend);
+        assert_eq!(world.depth, 0);
+        assert_eq!(world.outcome, Some(Outcome::Won));
+        assert_eq!(
+            world.stats.deepest_level,
+            world.templates.max_depth(MAIN_DUNGEON)
+        );
+    }
+
+    #[test]
+    fn going_back_up_restores_the_level() {
+        let mut world = World::new(3);
+        world.descend();
+        let map = world.map.clone();
+        let others = |world: &World| {
+            let mut entities = world.entities.clone();
+            entities.remove(world.player_id);
+            entities
+        };
+        let entities = others(&world);
+        let explored = world.fov.explored.clone();
+        assert!(world.levels.contains(LevelId::SURFACE));
+
+        stand_on(&mut world, STAIRS_DOWN);
+        world.player_turn(Action::Descend);
+        assert_eq!(world.depth, 2);
+        assert!(world.levels.contains(LevelId::new(MAIN_DUNGEON, 1)));
+        stand_on(&mut world, STAIRS_UP);
+        world.player_turn(Action::Ascend);
+        assert_eq!(world.depth, 1);
+        assert_eq!(world.map, map);
+        assert_eq!(world.player_tile(), Some(STAIRS_DOWN));
+        assert!(world.fov.explored.is_superset(&explored));
+        // The monsters got one turn after the player arrived
+        let now = others(&world);
+        assert_eq!(now.len(), entities.len());
+        for (now, then) in now.iter().zip(&entities) {
+            assert_eq!(now.name, then.name);
+            assert_eq!(now.hp, then.hp);
+        }
+        assert_eq!(world.levels.len(), 2);
+    }
+
+    #[test]
+    fn each_entrance_leads_to_its_own_dungeon_and_back() {
+        let mut world = World::new(4);
+        let town = world.map.clone();
+        let shopkeeper = world.entities.iter().position(|entity| entity.shopkeeper);
+        assert!(shopkeeper.is_some());
+
+        let player_id = world.player_id;
+        world.entities[player_id].pos = world.entrances[1];
+        world.player_turn(Action::Descend);
+        assert_eq!((world.dungeon, world.depth), (1, 1));
+        assert_eq!(world.log.last().unwrap(), "You enter the Sunken Crypt.");
+        assert_eq!(world.player_tile(), Some(STAIRS_UP));
+
+        // Without the amulet the surface is just the surface
+        world.player_turn(Action::Ascend);
+        assert_eq!(world.outcome, None);
+        assert_eq!(world.depth, 0);
+        assert_eq!(world.map, town);
+        assert_eq!(world.player().pos, world.entrances[1]);
+        assert!(world.entities.iter().any(|entity| entity.shopkeeper));
+        assert!(world
+            .log
+            .iter()
+            .any(|message| message == "You climb out of the Sunken Crypt into the daylight."));
+        world.player_turn(Action::Ascend);
+        assert_eq!(world.log.last().unwrap(), "There are no stairs up here.");
+    }
+
+    #[test]
+    fn items_on_the_entrance_are_still_there_on_the_way_back() {
+        let mut world = World::new(4);
+        let entrance = world.entrances[MAIN_DUNGEON];
+        let dart = spawn::item("dart", &world.templates.items["dart"], entrance);
+        world.entities.insert(0, dart);
+        world.player_id += 1;
+        let player_id = world.player_id;
+        world.entities[player_id].pos = entrance;
+        world.rebuild_spatial();
+
+        world.player_turn(Action::Descend);
+        world.player_turn(Action::Ascend);
+        assert_eq!(world.depth, 0);
+        assert_eq!(world.player().pos, entrance);
+        world.player_turn(Action::PickUp);
+        assert_eq!(world.log.last().unwrap(), "You pick up a dart.");
+    }
+
+    #[test]
+    fn picking_up_items() {
+        let mut world = test_world();
+        let dart = Entity {
+            pos: Vector::new(5, 4),
+            glyph: ')',
+            name: "dart".to_string(),
+            item: Some(Item {
+                template: "dart".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        world.entities.insert(0, dart);
+        world.player_id += 1;
+        world.rebuild_spatial();
+
+        world.player_turn(Action::PickUp);
+        assert_eq!(
+            world.log.last().unwrap(),
+            "There is nothing here to pick up."
+        );
+        step(&mut world, 0, 1);
+        assert_eq!(world.log.last().unwrap(), "You see a dart here.");
+        world.player_turn(Action::PickUp);
+        assert_eq!(world.log.last().unwrap(), "You pick up a dart.");
+        assert_eq!(world.player().glyph, '@');
+        assert_eq!(world.player().inventory.len(), 1);
+        assert!(world.entities.iter().all(|entity| entity.item.is_none()));
+    }
+
+    #[test]
+    fn walking_over_gold_picks_it_up() {
+        let mut world = test_world();
+        let gold = Entity {
+            pos: Vector::new(5, 4),
+            glyph: '$',
+            name: "gold".to_string(),
+            gold: 12,
+            ..Default::default()
+        };
+        world.entities.insert(0, gold);
+        world.player_id += 1;
+        world.rebuild_spatial();
+
+        step(&mut world, 0, 1);
+        assert_eq!(world.player().gold, 12);
+        assert_eq!(world.log.last().unwrap(), "You pick up 12 gold.");
+        assert!(world.entities.iter().all(|entity| entity.name != "gold"));
+    }
+
+    #[test]
+    fn lava_burns_and_deep_water_stops_the_player() {
+        let mut world = test_world();
+        world.map.set_glyph(Vector::new(6, 3), LAVA);
+        world.map.set_glyph(Vector::new(4, 3), DEEP_WATER);
+
+        step(&mut world, -1, 0);
+        assert_eq!(world.player().pos, Vector::new(5, 3));
+        assert_eq!(
+            world.log.last().unwrap(),
+            "The water is too deep. You can't swim."
+        );
+
+        step(&mut world, 1, 0);
+        assert_eq!(world.player().hp, 2);
+        assert_eq!(world.log.last().unwrap(), "The lava burns you!");
+    }
+
+    fn carried(template: &str) -> Entity {
+        Entity {
+            name: template.to_string(),
+            item: Some(Item {
+                template: template.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn equipping_swaps_items_in_the_same_slot() {
+        let mut world = test_world();
+        let player_id = world.player_id;
+        let inventory = &mut world.entities[player_id].inventory;
+        inventory.extend(vec![carried("shield"), carried("dart"), carried("shield")]);
+
+        world.player_turn(Action::Equip(1));
+        assert_eq!(world.log.last().unwrap(), "You can't equip a dart.");
+        world.player_turn(Action::Equip(0));
+        world.player_turn(Action::Equip(2));
+        assert_eq!(
+            world.log.last().unwrap(),
+            "You swap the shield for the shield."
+        );
+        let equipped: Vec<bool> = world
+            .player()
+            .inventory
+            .iter()
+            .map(|item| item.item.as_ref().unwrap().equipped)
+            .collect();
+        assert_eq!(equipped, [false, false, true]);
+
+        world.player_turn(Action::Equip(2));
+        assert_eq!(world.equipped(player_id, Slot::Shield), None);
+    }
+
+    #[test]
+    fn knocking_back_into_walls_hurts() {
+        let mut world = test_world();
+        let player_id = world.player_id;
+        world.entities[0].pos = Vector::new(6, 3);
+        world.entities[0].hp = 3;
+        world.rebuild_spatial();
+
+        knock_back(&mut world, player_id, 0, Vector::new(1, 0));
+        assert_eq!(world.entities[0].pos, Vector::new(7, 3));
+        assert_eq!(world.entities[0].hp, 3);
+
+        world.map.set_glyph(Vector::new(8, 3), WALL);
+        knock_back(&mut world, player_id, 0, Vector::new(1, 0));
+        assert_eq!(world.entities[0].pos, Vector::new(7, 3));
+        assert_eq!(world.entities[0].hp, 2);
+        assert_eq!(world.log.last().unwrap(), "The goblin slams into the wall.");
+    }
+
+    #[test]
+    fn monsters_knocked_into_lava_burn() {
+        let mut world = test_world();
+        let player_id = world.player_id;
+        world.map.set_glyph(Vector::new(7, 3), LAVA);
+        world.entities[0].pos = Vector::new(6, 3);
+        world.entities[0].hp = 3;
+        world.rebuild_spatial();
+
+        knock_back(&mut world, player_id, 0, Vector::new(1, 0));
+        world.player_turn(Action::Wait);
+        assert_eq!(world.entities[0].hp, 2);
+    }
+}